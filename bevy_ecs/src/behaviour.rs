@@ -0,0 +1,800 @@
+// -----------------------------------------------------------------------------
+// Behaviour trees
+// -----------------------------------------------------------------------------
+//
+// Decides what non-playable characters do. A tree is ticked from its root once a frame:
+// composites run their children in turn, decorators change how their one child runs, and leaves
+// check the world or act on it by walking the character around and attacking. Every node reports
+// whether it succeeded, failed or is still running.
+//
+// Trees are written in RON (see assets/behaviours) and shared between characters. Each character
+// keeps its own place in the tree and a blackboard of what it knows, such as who it is after.
+// Nothing but `Time`, the character components and the factions is needed, so trees also run in a
+// headless app built on `MinimalPlugins`.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::character::{Character, CharacterState, CharacterSystems};
+use crate::faction::{see_characters, AttackEvent, CharacterFaction, FactionRelations, Seen};
+
+// Needs the character and faction plugins.
+pub struct BehaviourPlugin;
+
+impl Plugin for BehaviourPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            run_behaviours
+                .in_set(BehaviourSystems)
+                .before(CharacterSystems),
+        );
+    }
+}
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BehaviourSystems;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Status {
+    Success,
+    Failure,
+    Running,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ParallelPolicy {
+    RequireAll, // Succeeds once every child has, fails as soon as one does.
+    RequireOne, // Succeeds as soon as one child does, fails once every child has.
+}
+
+// A node of a behaviour tree, as written in RON. Distances are in pixels, speeds in pixels per
+// second and times in seconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Node {
+    // Composites.
+    Sequence(Vec<Node>), // Runs the children in order until one fails, picking up where it was.
+    Selector(Vec<Node>), // Runs the first child that does not fail, trying from the first each tick.
+    Parallel {
+        policy: ParallelPolicy,
+        children: Vec<Node>, // Run side by side; a finished child waits for the others.
+    },
+
+    // Decorators.
+    Invert(Box<Node>),  // Swaps success and failure.
+    Succeed(Box<Node>), // Turns failure into success.
+    Repeat {
+        times: Option<u32>, // Forever when `None`.
+        child: Box<Node>,   // Repeats after every success; a failure ends it.
+    },
+    Cooldown {
+        seconds: f32, // Fails for this long after the child finishes.
+        child: Box<Node>,
+    },
+    Timeout {
+        seconds: f32, // Fails the child when it runs for longer than this.
+        child: Box<Node>,
+    },
+
+    // Conditions.
+    HasTarget,         // The target is still around.
+    TargetWithin(f32), // The target is within this distance.
+    Grounded,          // The character stands on something.
+    Flag(String),      // The flag is set on the blackboard.
+
+    // Actions.
+    SetFlag(String, bool),
+    // Targets the nearest hostile character in range.
+    FindEnemy {
+        range: f32,
+    },
+    // Stands still for a while.
+    Idle(f32),
+    // Walks to a random point near home.
+    Wander {
+        radius: f32,
+        speed: f32,
+    },
+    // Walks up to the target.
+    Chase {
+        speed: f32,
+        reach: f32,
+    },
+    // Runs from the target until far enough away.
+    Flee {
+        speed: f32,
+        distance: f32,
+    },
+    // Attacks the target if hostile and in reach, at most once per cooldown.
+    Attack {
+        reach: f32,
+        cooldown: f32,
+    },
+}
+
+impl Node {
+    fn children(&self) -> &[Node] {
+        match self {
+            Node::Sequence(children)
+            | Node::Selector(children)
+            | Node::Parallel { children, .. } => children,
+            Node::Invert(child)
+            | Node::Succeed(child)
+            | Node::Repeat { child, .. }
+            | Node::Cooldown { child, .. }
+            | Node::Timeout { child, .. } => std::slice::from_ref(child),
+            _ => &[],
+        }
+    }
+}
+
+// A tree ready to run. Nodes are numbered depth first, so a node's descendants follow it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BehaviourTree {
+    root: Node,
+    sizes: Vec<usize>, // Nodes in the subtree of each node, itself included.
+}
+
+impl BehaviourTree {
+    pub fn new(root: Node) -> Self {
+        fn measure(node: &Node, sizes: &mut Vec<usize>) -> usize {
+            let id = sizes.len();
+            sizes.push(0);
+            let size = 1 + node
+                .children()
+                .iter()
+                .map(|child| measure(child, sizes))
+                .sum::<usize>();
+            sizes[id] = size;
+            size
+        }
+        let mut sizes = Vec::new();
+        measure(&root, &mut sizes);
+        Self { root, sizes }
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(text).map(Self::new)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, BehaviourError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| BehaviourError::Io(path.to_path_buf(), error))?;
+        Self::from_ron(&text).map_err(|error| BehaviourError::Parse(path.to_path_buf(), error))
+    }
+
+    pub fn root(&self) -> &Node {
+        &self.root
+    }
+}
+
+#[derive(Debug)]
+pub enum BehaviourError {
+    Io(PathBuf, std::io::Error),              // The file could not be read.
+    Parse(PathBuf, ron::error::SpannedError), // The file is not a valid tree.
+}
+
+impl std::fmt::Display for BehaviourError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BehaviourError::Io(path, error) => {
+                write!(f, "failed to read {}: {error}", path.display())
+            }
+            BehaviourError::Parse(path, error) => {
+                write!(f, "failed to parse {}: {error}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for BehaviourError {}
+
+// What a character knows, shared by the nodes of its tree.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Blackboard {
+    pub target: Option<Entity>, // Who the character is after, or running from.
+    pub home: Option<Vec2>,     // Where it wanders around; where it started unless set.
+    pub flags: HashSet<String>,
+}
+
+// Runs a behaviour tree on a character.
+#[derive(Component)]
+pub struct Behaviour {
+    tree: Arc<BehaviourTree>,
+    pub blackboard: Blackboard,
+    memory: Vec<Memory>,
+    tick: u64,
+    clock: f32, // Seconds the tree has been running.
+    seed: u32,  // Random state for wandering.
+}
+
+impl Behaviour {
+    pub fn new(tree: Arc<BehaviourTree>) -> Self {
+        Self {
+            memory: vec![Memory::default(); tree.sizes.len()],
+            tree,
+            blackboard: Blackboard::default(),
+            tick: 0,
+            clock: 0.0,
+            seed: 0,
+        }
+    }
+}
+
+// A node's progress, for one character.
+#[derive(Debug, Default, Clone)]
+struct Memory {
+    last_tick: u64,     // A gap since the last tick means the node was interrupted.
+    child: usize,       // Child a sequence is on.
+    count: u32,         // Successes of a repeat's child.
+    elapsed: f32,       // Seconds the node has been running.
+    goal: Option<Vec2>, // Where a wander is heading.
+    ready_at: f32,      // When a cooldown or attack is next allowed; survives restarts.
+    // How each child of a parallel ended, if it has.
+    finished: Vec<Option<Status>>,
+}
+
+impl Memory {
+    fn restart(&mut self) {
+        *self = Memory {
+            last_tick: self.last_tick,
+            ready_at: self.ready_at,
+            ..Memory::default()
+        };
+    }
+}
+
+// Everything one character's tree works with during a tick.
+struct Runner<'a> {
+    tree: &'a BehaviourTree,
+    memory: &'a mut [Memory],
+    blackboard: &'a mut Blackboard,
+    tick: u64,
+    clock: f32,
+    dt: f32,
+    seed: &'a mut u32,
+    entity: Entity,
+    position: Vec2,
+    faction: Option<CharacterFaction>,
+    grounded: bool,
+    walk: f32, // Horizontal velocity the actions ask for.
+    seen: &'a [Seen],
+    relations: &'a FactionRelations,
+    attacks: &'a mut Vec<AttackEvent>,
+}
+
+impl Runner<'_> {
+    fn tick(&mut self, node: &Node, id: usize) -> Status {
+        let memory = &mut self.memory[id];
+        if memory.last_tick + 1 != self.tick {
+            memory.restart();
+        }
+        memory.last_tick = self.tick;
+
+        let status = self.run(node, id);
+        // A finished node starts its subtree over next time, including children it cut short.
+        if status != Status::Running {
+            for memory in &mut self.memory[id..id + self.tree.sizes[id]] {
+                memory.restart();
+            }
+        }
+        status
+    }
+
+    fn run(&mut self, node: &Node, id: usize) -> Status {
+        let first_child = id + 1;
+        match node {
+            Node::Sequence(children) => {
+                let ids = self.child_ids(id);
+                for index in self.memory[id].child..children.len() {
+                    match self.tick(&children[index], ids[index]) {
+                        Status::Success => continue,
+                        Status::Running => {
+                            self.memory[id].child = index;
+                            return Status::Running;
+                        }
+                        Status::Failure => return Status::Failure,
+                    }
+                }
+                Status::Success
+            }
+            Node::Selector(children) => {
+                let ids = self.child_ids(id);
+                for (child, child_id) in children.iter().zip(ids) {
+                    match self.tick(child, child_id) {
+                        Status::Failure => continue,
+                        status => return status,
+                    }
+                }
+                Status::Failure
+            }
+            Node::Parallel { policy, children } => {
+                let ids = self.child_ids(id);
+                self.memory[id].finished.resize(children.len(), None);
+                // Children that have finished keep their result until the parallel does.
+                for (index, (child, child_id)) in children.iter().zip(ids).enumerate() {
+                    if self.memory[id].finished[index].is_some() {
+                        continue;
+                    }
+                    let status = self.tick(child, child_id);
+                    if status != Status::Running {
+                        self.memory[id].finished[index] = Some(status);
+                    }
+                }
+                let finished = &self.memory[id].finished;
+                let count = |status| finished.iter().filter(|&&s| s == Some(status)).count();
+                let (succeeded, failed) = (count(Status::Success), count(Status::Failure));
+                match policy {
+                    ParallelPolicy::RequireAll if failed > 0 => Status::Failure,
+                    ParallelPolicy::RequireAll if succeeded == children.len() => Status::Success,
+                    ParallelPolicy::RequireOne if succeeded > 0 => Status::Success,
+                    ParallelPolicy::RequireOne if failed == children.len() => Status::Failure,
+                    _ => Status::Running,
+                }
+            }
+
+            Node::Invert(child) => match self.tick(child, first_child) {
+                Status::Success => Status::Failure,
+                Status::Failure => Status::Success,
+                Status::Running => Status::Running,
+            },
+            Node::Succeed(child) => match self.tick(child, first_child) {
+                Status::Running => Status::Running,
+                _ => Status::Success,
+            },
+            Node::Repeat { times, child } => match self.tick(child, first_child) {
+                Status::Success => {
+                    let memory = &mut self.memory[id];
+                    memory.count += 1;
+                    if times.is_some_and(|times| memory.count >= times) {
+                        Status::Success
+                    } else {
+                        Status::Running
+                    }
+                }
+                status => status,
+            },
+            Node::Cooldown { seconds, child } => {
+                if self.clock < self.memory[id].ready_at {
+                    return Status::Failure;
+                }
+                let status = self.tick(child, first_child);
+                if status != Status::Running {
+                    self.memory[id].ready_at = self.clock + seconds;
+                }
+                status
+            }
+            Node::Timeout { seconds, child } => {
+                self.memory[id].elapsed += self.dt;
+                if self.memory[id].elapsed > *seconds {
+                    return Status::Failure;
+                }
+                self.tick(child, first_child)
+            }
+
+            Node::HasTarget => status(self.target().is_some()),
+            Node::TargetWithin(distance) => status(
+                self.target()
+                    .is_some_and(|target| self.position.distance(target.position) <= *distance),
+            ),
+            Node::Grounded => status(self.grounded),
+            Node::Flag(name) => status(self.blackboard.flags.contains(name)),
+
+            Node::SetFlag(name, true) => {
+                self.blackboard.flags.insert(name.clone());
+                Status::Success
+            }
+            Node::SetFlag(name, false) => {
+                self.blackboard.flags.remove(name);
+                Status::Success
+            }
+            Node::FindEnemy { range } => {
+                let enemy = self
+                    .seen
+                    .iter()
+                    .filter(|other| other.entity != self.entity && self.is_hostile(other))
+                    .map(|other| (other.entity, self.position.distance(other.position)))
+                    .filter(|&(_, distance)| distance <= *range)
+                    .min_by(|a, b| a.1.total_cmp(&b.1));
+                self.blackboard.target = enemy.map(|(entity, _)| entity);
+                status(enemy.is_some())
+            }
+            Node::Idle(seconds) => {
+                self.memory[id].elapsed += self.dt;
+                status_after(self.memory[id].elapsed >= *seconds)
+            }
+            Node::Wander { radius, speed } => {
+                let home = *self.blackboard.home.get_or_insert(self.position);
+                let goal = match self.memory[id].goal {
+                    Some(goal) => goal,
+                    None => {
+                        let offset = (self.random() * 2.0 - 1.0) * radius;
+                        let goal = home + Vec2::new(offset, 0.0);
+                        self.memory[id].goal = Some(goal);
+                        goal
+                    }
+                };
+                status_after(self.walk_to(goal.x, *speed))
+            }
+            Node::Chase { speed, reach } => {
+                let Some(target) = self.target().map(|target| target.position) else {
+                    return Status::Failure;
+                };
+                if self.position.distance(target) <= *reach {
+                    return Status::Success;
+                }
+                self.walk_to(target.x, *speed);
+                Status::Running
+            }
+            Node::Flee { speed, distance } => {
+                let Some(target) = self.target().map(|target| target.position) else {
+                    return Status::Failure;
+                };
+                if self.position.distance(target) >= *distance {
+                    return Status::Success;
+                }
+                let away = if self.position.x < target.x {
+                    -1.0
+                } else {
+                    1.0
+                };
+                self.walk = away * speed;
+                Status::Running
+            }
+            Node::Attack { reach, cooldown } => {
+                let Some(target) = self.target() else {
+                    return Status::Failure;
+                };
+                if !self.is_hostile(target) || self.position.distance(target.position) > *reach {
+                    return Status::Failure;
+                }
+                if self.clock < self.memory[id].ready_at {
+                    return Status::Running;
+                }
+                let target = target.entity;
+                self.attacks.push(AttackEvent {
+                    attacker: self.entity,
+                    target,
+                });
+                self.memory[id].ready_at = self.clock + cooldown;
+                Status::Success
+            }
+        }
+    }
+
+    fn child_ids(&self, id: usize) -> Vec<usize> {
+        let mut ids = Vec::new();
+        let mut child = id + 1;
+        while child < id + self.tree.sizes[id] {
+            ids.push(child);
+            child += self.tree.sizes[child];
+        }
+        ids
+    }
+
+    fn target(&self) -> Option<&Seen> {
+        let target = self.blackboard.target?;
+        self.seen.iter().find(|other| other.entity == target)
+    }
+
+    fn is_hostile(&self, other: &Seen) -> bool {
+        self.faction
+            .is_some_and(|faction| self.relations.are_hostile(faction, other.faction))
+    }
+
+    // Walks towards `x`, returning true once there.
+    fn walk_to(&mut self, x: f32, speed: f32) -> bool {
+        let offset = x - self.position.x;
+        if offset.abs() <= (speed * self.dt).max(1.0) {
+            return true;
+        }
+        self.walk = offset.signum() * speed;
+        false
+    }
+
+    // A number in [0, 1).
+    fn random(&mut self) -> f32 {
+        crate::random(self.seed)
+    }
+}
+
+fn status(condition: bool) -> Status {
+    if condition {
+        Status::Success
+    } else {
+        Status::Failure
+    }
+}
+
+fn status_after(done: bool) -> Status {
+    if done {
+        Status::Success
+    } else {
+        Status::Running
+    }
+}
+
+// Ticks every tree and hands the results to the characters: the walking speed goes into the
+// character's velocity and attacks go out as events.
+fn run_behaviours(
+    time: Res<Time>,
+    relations: Res<FactionRelations>,
+    characters: Query<(Entity, &Transform, &CharacterFaction)>,
+    mut behaviours: Query<(
+        Entity,
+        &mut Behaviour,
+        &mut Character,
+        &CharacterState,
+        &Transform,
+    )>,
+    mut events: EventWriter<AttackEvent>,
+) {
+    let dt = time.delta().as_secs_f32();
+    let seen = see_characters(&characters);
+    let mut attacks = Vec::new();
+    for (entity, mut behaviour, mut character, state, transform) in &mut behaviours {
+        let behaviour = &mut *behaviour;
+        behaviour.tick += 1;
+        behaviour.clock += dt;
+        if behaviour.seed == 0 {
+            behaviour.seed = crate::random_seed(entity);
+        }
+        let mut runner = Runner {
+            tree: &behaviour.tree,
+            memory: &mut behaviour.memory,
+            blackboard: &mut behaviour.blackboard,
+            tick: behaviour.tick,
+            clock: behaviour.clock,
+            dt,
+            seed: &mut behaviour.seed,
+            entity,
+            position: transform.translation.truncate(),
+            faction: seen
+                .iter()
+                .find(|other| other.entity == entity)
+                .map(|other| other.faction),
+            grounded: state.grounded,
+            walk: 0.0,
+            seen: &seen,
+            relations: &relations,
+            attacks: &mut attacks,
+        };
+        runner.tick(behaviour.tree.root(), 0);
+        character.velocity.x = runner.walk;
+    }
+    events.send_batch(attacks);
+}
+
+#[cfg(test)]
+mod tests {
+    use colliding_bodies::{CharacterController, Shape};
+
+    use super::*;
+    use crate::faction::FactionPlugin;
+    use crate::testing::{self, TENTH_OF_A_SECOND};
+
+    // The tree the game gives its enemies.
+    const ENEMY_TREE: &str = include_str!("../assets/behaviours/enemy.ron");
+
+    fn app() -> App {
+        testing::app(TENTH_OF_A_SECOND, (FactionPlugin, BehaviourPlugin))
+    }
+
+    fn spawn(app: &mut App, faction: CharacterFaction, x: f32, tree: Option<&str>) -> Entity {
+        let mut entity = app.world_mut().spawn((
+            Character::new(CharacterController::new(Shape::aabb(20.0, 20.0))),
+            CharacterState {
+                grounded: true,
+                ..default()
+            },
+            Transform::from_xyz(x, 0.0, 0.0),
+            faction,
+        ));
+        if let Some(tree) = tree {
+            let tree = BehaviourTree::from_ron(tree).expect("a valid tree");
+            entity.insert(Behaviour::new(Arc::new(tree)));
+        }
+        entity.id()
+    }
+
+    fn flags(app: &App, entity: Entity) -> Vec<&str> {
+        let behaviour = app.world().get::<Behaviour>(entity).expect("a behaviour");
+        let mut flags: Vec<_> = behaviour
+            .blackboard
+            .flags
+            .iter()
+            .map(String::as_str)
+            .collect();
+        flags.sort_unstable();
+        flags
+    }
+
+    // Runs `updates` frames, returning the attacks sent during them.
+    fn attacks(app: &mut App, updates: usize) -> Vec<AttackEvent> {
+        let mut cursor = app
+            .world()
+            .resource::<Events<AttackEvent>>()
+            .get_cursor_current();
+        let mut attacks = Vec::new();
+        for _ in 0..updates {
+            app.update();
+            let events = app.world().resource::<Events<AttackEvent>>();
+            attacks.extend(cursor.read(events).copied());
+        }
+        attacks
+    }
+
+    #[test]
+    fn sequence_stops_at_a_failure() {
+        let mut app = app();
+        let npc = spawn(
+            &mut app,
+            CharacterFaction::Neutral,
+            0.0,
+            Some(r#"Sequence([SetFlag("first", true), Flag("missing"), SetFlag("last", true)])"#),
+        );
+        app.update();
+        assert_eq!(flags(&app, npc), ["first"]);
+    }
+
+    #[test]
+    fn sequence_picks_up_where_it_was() {
+        let mut app = app();
+        // Rerunning the first child would fail, as it sees its own flag.
+        let npc = spawn(
+            &mut app,
+            CharacterFaction::Neutral,
+            0.0,
+            Some(
+                r#"Sequence([
+                    Invert(Flag("started")),
+                    SetFlag("started", true),
+                    Idle(0.25),
+                    SetFlag("done", true),
+                ])"#,
+            ),
+        );
+        app.update();
+        assert_eq!(flags(&app, npc), ["started"]);
+        for _ in 0..5 {
+            app.update();
+        }
+        assert_eq!(flags(&app, npc), ["done", "started"]);
+    }
+
+    #[test]
+    fn selector_runs_the_first_child_that_does_not_fail() {
+        let mut app = app();
+        let npc = spawn(
+            &mut app,
+            CharacterFaction::Neutral,
+            0.0,
+            Some(
+                r#"Selector([
+                    Flag("missing"),
+                    Sequence([SetFlag("second", true), Flag("missing")]),
+                    SetFlag("third", true),
+                    SetFlag("fourth", true),
+                ])"#,
+            ),
+        );
+        app.update();
+        assert_eq!(flags(&app, npc), ["second", "third"]);
+    }
+
+    #[test]
+    fn parallel_fails_when_a_required_child_does() {
+        let mut app = app();
+        let npc = spawn(
+            &mut app,
+            CharacterFaction::Neutral,
+            0.0,
+            Some(
+                r#"Selector([
+                    Parallel(policy: RequireAll, children: [
+                        Flag("missing"),
+                        SetFlag("ran", true),
+                    ]),
+                    SetFlag("fallback", true),
+                ])"#,
+            ),
+        );
+        app.update();
+        assert_eq!(flags(&app, npc), ["fallback", "ran"]);
+    }
+
+    #[test]
+    fn parallel_succeeds_once_one_child_does() {
+        let mut app = app();
+        let npc = spawn(
+            &mut app,
+            CharacterFaction::Neutral,
+            0.0,
+            Some(
+                r#"Sequence([
+                    Parallel(policy: RequireOne, children: [Idle(10.0), Idle(0.25)]),
+                    SetFlag("done", true),
+                ])"#,
+            ),
+        );
+        app.update();
+        assert!(flags(&app, npc).is_empty());
+        for _ in 0..5 {
+            app.update();
+        }
+        assert_eq!(flags(&app, npc), ["done"]);
+    }
+
+    #[test]
+    fn parallel_runs_finished_children_again_only_once_it_finishes() {
+        let mut app = app();
+        let red = spawn(
+            &mut app,
+            CharacterFaction::Red,
+            0.0,
+            Some(
+                r#"Sequence([
+                    FindEnemy(range: 100.0),
+                    Parallel(policy: RequireAll, children: [
+                        Attack(reach: 40.0, cooldown: 0.0),
+                        Idle(0.35),
+                    ]),
+                ])"#,
+            ),
+        );
+        let blue = spawn(&mut app, CharacterFaction::Blue, 30.0, None);
+
+        // The attack succeeds on the first tick and is not repeated while the idle runs on.
+        let attack = AttackEvent {
+            attacker: red,
+            target: blue,
+        };
+        assert_eq!(attacks(&mut app, 3), [attack]);
+
+        // Once the idle ends the parallel succeeds and starts over, attacking again.
+        assert_eq!(attacks(&mut app, 5), [attack]);
+    }
+
+    #[test]
+    fn enemies_chase_then_attack() {
+        let mut app = app();
+        let red = spawn(&mut app, CharacterFaction::Red, 0.0, Some(ENEMY_TREE));
+        let blue = spawn(&mut app, CharacterFaction::Blue, 200.0, None);
+
+        assert!(attacks(&mut app, 1).is_empty());
+        let character = app.world().get::<Character>(red).expect("a character");
+        assert_eq!(character.velocity.x, 120.0);
+
+        app.world_mut()
+            .get_mut::<Transform>(blue)
+            .expect("a transform")
+            .translation
+            .x = 30.0;
+        let attacks = attacks(&mut app, 1);
+        assert_eq!(
+            attacks,
+            [AttackEvent {
+                attacker: red,
+                target: blue,
+            }]
+        );
+        let character = app.world().get::<Character>(red).expect("a character");
+        assert_eq!(character.velocity.x, 0.0);
+    }
+
+    #[test]
+    fn neutrals_are_left_alone() {
+        let mut app = app();
+        let red = spawn(&mut app, CharacterFaction::Red, 0.0, Some(ENEMY_TREE));
+        spawn(&mut app, CharacterFaction::Neutral, 30.0, None);
+
+        assert!(attacks(&mut app, 3).is_empty());
+        let behaviour = app.world().get::<Behaviour>(red).expect("a behaviour");
+        assert_eq!(behaviour.blackboard.target, None);
+    }
+}
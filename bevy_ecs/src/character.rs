@@ -0,0 +1,373 @@
+// -----------------------------------------------------------------------------
+// Character movement
+// -----------------------------------------------------------------------------
+//
+// Moves characters with the kinematic character controller from the colliding_bodies crate.
+// Characters slide along walls, walk up steps and slopes and stand on the level's colliders,
+// instead of having their transforms written directly.
+//
+// Game systems set a character's velocity and read its state; the plugin adds gravity, moves the
+// character and reports whether it is on the ground and what it ran into.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use colliding_bodies as physics;
+use colliding_bodies::{BodyHandle, CharacterController, Grid, RigidBody};
+
+pub struct CharacterPlugin;
+
+impl Plugin for CharacterPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Physics>()
+            .init_resource::<CharacterSettings>()
+            .add_systems(
+                Update,
+                (add_bodies, remove_bodies, sync_colliders, move_characters)
+                    .chain()
+                    .in_set(CharacterSystems),
+            );
+    }
+}
+
+// Systems that set character velocities should run before this set.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CharacterSystems;
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct CharacterSettings {
+    pub gravity: Vec2,        // Pixels per second squared.
+    pub knockback_decay: f32, // How fast knockback dies down, in pixels per second squared.
+}
+
+impl Default for CharacterSettings {
+    fn default() -> Self {
+        Self {
+            gravity: Vec2::new(0.0, -980.0),
+            knockback_decay: 1500.0,
+        }
+    }
+}
+
+// The physics world characters move through: a body for every collider and character. It is
+// only queried, never stepped.
+#[derive(Resource, Default)]
+struct Physics {
+    world: physics::World,
+    entities: HashMap<BodyHandle, Entity>,
+}
+
+// Solid level geometry that characters cannot walk through, placed by the entity's transform.
+#[derive(Component)]
+pub struct Collider(pub physics::Shape);
+
+// Solid tiles: the blocked cells of a grid, placed by the grid's own origin and cell size.
+#[derive(Component)]
+pub struct TileCollider(pub Grid);
+
+// An entity moved by a character controller.
+#[derive(Component)]
+pub struct Character {
+    pub controller: CharacterController,
+    pub velocity: Vec2, // Pixels per second, including gravity.
+    pub knockback: f32, // Sideways speed that overrides walking until it dies down.
+}
+
+impl Character {
+    pub fn new(controller: CharacterController) -> Self {
+        Self {
+            controller,
+            velocity: Vec2::ZERO,
+            knockback: 0.0,
+        }
+    }
+}
+
+// How a character's last move went.
+#[derive(Component, Debug, Default, Clone, PartialEq)]
+pub struct CharacterState {
+    pub grounded: bool,
+    pub ground: Option<Entity>, // What the character is standing on.
+    pub ground_normal: Vec2,    // Zero when in the air.
+    pub contacts: Vec<Entity>,  // Everything the character ran into, in order.
+}
+
+// The body standing in for an entity in the physics world.
+#[derive(Component)]
+struct PhysicsBody(BodyHandle);
+
+fn add_bodies(
+    mut commands: Commands,
+    mut physics: ResMut<Physics>,
+    colliders: Query<(Entity, &Collider, &Transform), Without<PhysicsBody>>,
+    tiles: Query<(Entity, &TileCollider), Added<TileCollider>>,
+    characters: Query<(Entity, &Character, &Transform), Without<PhysicsBody>>,
+) {
+    for (entity, collider, transform) in &colliders {
+        let body = RigidBody::fixed(collider.0.clone())
+            .with_position(to_physics(transform.translation.truncate()))
+            .with_angle(angle(transform));
+        let handle = physics.world.add_body(body);
+        physics.entities.insert(handle, entity);
+        commands.entity(entity).insert(PhysicsBody(handle));
+    }
+    // A box for every run of blocked cells along a row, so characters do not catch on the seams
+    // between tiles. Grids are only looked at when added, as one with no blocked cells gets no
+    // boxes, and so no `PhysicsBody`, to say it was done.
+    for (entity, tiles) in &tiles {
+        let grid = &tiles.0;
+        let mut handles = Vec::new();
+        for y in 0..grid.height() as i32 {
+            let mut x = 0;
+            while x < grid.width() as i32 {
+                if grid.is_open((x, y)) {
+                    x += 1;
+                    continue;
+                }
+                let start = x;
+                while x < grid.width() as i32 && !grid.is_open((x, y)) {
+                    x += 1;
+                }
+                let (first, last) = (grid.cell_center((start, y)), grid.cell_center((x - 1, y)));
+                let width = (x - start) as f32 * grid.cell_size;
+                let body = RigidBody::fixed(physics::Shape::aabb(width, grid.cell_size))
+                    .with_position((first + last) * 0.5);
+                handles.push(physics.world.add_body(body));
+            }
+        }
+        for &handle in &handles {
+            physics.entities.insert(handle, entity);
+        }
+        // Removing the component removes every box, as they all map back to the entity.
+        if let Some(&handle) = handles.first() {
+            commands.entity(entity).insert(PhysicsBody(handle));
+        }
+    }
+    // Characters are kinematic bodies, so they block each other.
+    for (entity, character, transform) in &characters {
+        let body = RigidBody::kinematic(character.controller.shape.clone())
+            .with_position(to_physics(transform.translation.truncate()));
+        let handle = physics.world.add_body(body);
+        physics.entities.insert(handle, entity);
+        commands
+            .entity(entity)
+            .insert((PhysicsBody(handle), CharacterState::default()));
+    }
+}
+
+fn remove_bodies(mut physics: ResMut<Physics>, mut removed: RemovedComponents<PhysicsBody>) {
+    for entity in removed.read() {
+        let handles: Vec<BodyHandle> = physics
+            .entities
+            .iter()
+            .filter(|&(_, &e)| e == entity)
+            .map(|(&handle, _)| handle)
+            .collect();
+        for handle in handles {
+            physics.world.remove_body(handle);
+            physics.entities.remove(&handle);
+        }
+    }
+}
+
+// Moved colliders, such as platforms, carry their bodies along.
+fn sync_colliders(
+    mut physics: ResMut<Physics>,
+    colliders: Query<(&PhysicsBody, Ref<Transform>), With<Collider>>,
+) {
+    for (body, transform) in &colliders {
+        if !transform.is_changed() {
+            continue;
+        }
+        if let Some(body_mut) = physics.world.body_mut(body.0) {
+            body_mut.position = to_physics(transform.translation.truncate());
+            body_mut.angle = angle(&transform);
+        }
+    }
+    physics.world.update_broad_phase();
+}
+
+fn move_characters(
+    time: Res<Time>,
+    settings: Res<CharacterSettings>,
+    mut physics: ResMut<Physics>,
+    mut characters: Query<(
+        &mut Character,
+        &mut CharacterState,
+        &mut Transform,
+        &PhysicsBody,
+    )>,
+) {
+    let dt = time.delta().as_secs_f32();
+    let physics = &mut *physics;
+    for (mut character, mut state, mut transform, body) in &mut characters {
+        // Characters whose transforms were set by hand, say by loading a game, are put there.
+        let position = to_physics(transform.translation.truncate());
+        if let Some(body_mut) = physics.world.body_mut(body.0) {
+            if body_mut.position != position {
+                body_mut.position = position;
+                physics.world.update_broad_phase();
+            }
+        }
+        if character.knockback != 0.0 {
+            character.velocity.x = character.knockback;
+            let decay = settings.knockback_decay * dt;
+            character.knockback -= character.knockback.clamp(-decay, decay);
+        }
+        character.velocity += settings.gravity * dt;
+        let translation = to_physics(character.velocity * dt);
+        let Some(result) = character
+            .controller
+            .move_body(&mut physics.world, body.0, translation)
+        else {
+            continue;
+        };
+        physics.world.update_broad_phase();
+        transform.translation.x = result.position.x;
+        transform.translation.y = result.position.y;
+
+        // Stop falling once on the ground, and stop moving into walls and ceilings, so speed does
+        // not build up against them.
+        if result.grounded {
+            character.velocity.y = character.velocity.y.max(0.0);
+        }
+        for contact in &result.contacts {
+            if character.controller.is_walkable(contact.normal) {
+                continue;
+            }
+            let normal = from_physics(contact.normal);
+            let into = character.velocity.dot(normal);
+            if into < 0.0 {
+                character.velocity -= normal * into;
+            }
+        }
+
+        *state = CharacterState {
+            grounded: result.grounded,
+            ground: result
+                .ground
+                .and_then(|ground| physics.entities.get(&ground.body).copied()),
+            ground_normal: result
+                .ground
+                .map_or(Vec2::ZERO, |ground| from_physics(ground.normal)),
+            contacts: result
+                .contacts
+                .iter()
+                .filter_map(|contact| physics.entities.get(&contact.body).copied())
+                .collect(),
+        };
+    }
+}
+
+fn angle(transform: &Transform) -> f32 {
+    transform.rotation.to_euler(EulerRot::ZYX).0
+}
+
+fn to_physics(v: Vec2) -> physics::Vec2 {
+    physics::Vec2::new(v.x, v.y)
+}
+
+fn from_physics(v: physics::Vec2) -> Vec2 {
+    Vec2::new(v.x, v.y)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::character_controller;
+    use crate::testing;
+
+    const STANDING: f32 = 10.5; // Where a character standing on the floor is, skin included.
+
+    // Moves characters a hundred times a second over a floor whose top is at 0.
+    fn app() -> (App, Entity) {
+        let mut app = testing::app(Duration::from_millis(10), CharacterPlugin);
+        let floor = app
+            .world_mut()
+            .spawn((
+                Collider(physics::Shape::aabb(400.0, 20.0)),
+                Transform::from_xyz(0.0, -10.0, 0.0),
+            ))
+            .id();
+        (app, floor)
+    }
+
+    fn character(app: &mut App, x: f32, y: f32) -> Entity {
+        app.world_mut()
+            .spawn((
+                Character::new(character_controller()),
+                Transform::from_xyz(x, y, 0.0),
+            ))
+            .id()
+    }
+
+    fn run(app: &mut App, updates: usize) {
+        for _ in 0..updates {
+            app.update();
+        }
+    }
+
+    #[test]
+    fn characters_fall_onto_colliders() {
+        let (mut app, floor) = app();
+        let falling = character(&mut app, 0.0, 50.0);
+        run(&mut app, 2);
+        assert!(!app.world().get::<CharacterState>(falling).unwrap().grounded);
+
+        run(&mut app, 100);
+        let world = app.world();
+        let state = world.get::<CharacterState>(falling).unwrap();
+        assert!(state.grounded);
+        assert_eq!(state.ground, Some(floor));
+        assert_eq!(state.ground_normal, Vec2::Y);
+        let y = world.get::<Transform>(falling).unwrap().translation.y;
+        assert!((y - STANDING).abs() < 0.5, "stands at {y}");
+        assert_eq!(world.get::<Character>(falling).unwrap().velocity.y, 0.0);
+    }
+
+    #[test]
+    fn walls_stop_characters() {
+        let (mut app, _) = app();
+        // Its left face is at x = 40.
+        let wall = app
+            .world_mut()
+            .spawn((
+                Collider(physics::Shape::aabb(20.0, 100.0)),
+                Transform::from_xyz(50.0, 50.0, 0.0),
+            ))
+            .id();
+        let walker = character(&mut app, 0.0, STANDING);
+        // Walking right every frame, as a game would.
+        for _ in 0..50 {
+            app.world_mut()
+                .get_mut::<Character>(walker)
+                .unwrap()
+                .velocity
+                .x = 200.0;
+            app.update();
+        }
+
+        let world = app.world();
+        let x = world.get::<Transform>(walker).unwrap().translation.x;
+        assert!(x > 28.0 && x < 30.0, "stopped at {x}");
+        assert_eq!(world.get::<Character>(walker).unwrap().velocity.x, 0.0);
+        let state = world.get::<CharacterState>(walker).unwrap();
+        assert!(state.contacts.contains(&wall), "{:?}", state.contacts);
+    }
+
+    #[test]
+    fn despawning_removes_the_body() {
+        let (mut app, _) = app();
+        let gone = character(&mut app, 0.0, STANDING);
+        run(&mut app, 1);
+        assert_eq!(app.world().resource::<Physics>().world.body_count(), 2);
+
+        app.world_mut().despawn(gone);
+        run(&mut app, 1);
+        let physics = app.world().resource::<Physics>();
+        assert_eq!(physics.world.body_count(), 1);
+        assert!(!physics.entities.values().any(|&entity| entity == gone));
+    }
+}
@@ -0,0 +1,452 @@
+// -----------------------------------------------------------------------------
+// Combat
+// -----------------------------------------------------------------------------
+//
+// Health, damage and death. Entities get hurt two ways: by an `AttackEvent`, when the attacker has
+// a `Damage` to deal, and by touching a hitbox with their hurtbox. Allies never hurt each other,
+// whichever way the hit comes.
+//
+// A hit takes off the damage left after the target's armor, knocks the target away from whatever
+// hit it and leaves it invulnerable for a moment, so a hitbox that stays in contact does not hurt
+// every frame. Every hit goes out as a `DamageEvent`; entities that run out of health go out as a
+// `DeathEvent` and are despawned.
+
+use bevy::math::bounding::{Aabb2d, IntersectsVolume};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::character::{Character, CharacterSystems};
+use crate::faction::{AttackEvent, Factions};
+use crate::steering::Velocity;
+
+// Needs the faction plugin.
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageEvent>()
+            .add_event::<DeathEvent>()
+            .add_systems(
+                Update,
+                (wear_off_invulnerability, deal_damage, die)
+                    .chain()
+                    .in_set(CombatSystems)
+                    .before(CharacterSystems),
+            );
+    }
+}
+
+// Systems that attack should run before this set to have their hits land the same frame.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CombatSystems;
+
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+    pub invulnerability: f32, // Seconds a hit leaves the entity unhurtable for.
+    #[serde(skip)]
+    invulnerable_for: f32,
+    #[serde(skip)]
+    last_attacker: Option<Entity>, // Who gets the credit for a death.
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self {
+            current: max,
+            max,
+            invulnerability: 0.5,
+            invulnerable_for: 0.0,
+            last_attacker: None,
+        }
+    }
+
+    pub fn with_invulnerability(mut self, seconds: f32) -> Self {
+        self.invulnerability = seconds;
+        self
+    }
+
+    pub fn is_invulnerable(&self) -> bool {
+        self.invulnerable_for > 0.0
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+}
+
+// What an entity's attacks, or its hitbox, deal.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Damage {
+    pub amount: f32,
+    pub knockback: f32, // Speed the target is knocked away at, in pixels per second.
+}
+
+// Takes the edge off the hits an entity takes: first `flat` is taken off, then `resistance`, a
+// fraction between 0 and 1, of what is left.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Armor {
+    pub flat: f32,
+    pub resistance: f32,
+}
+
+impl Armor {
+    pub fn reduce(&self, amount: f32) -> f32 {
+        (amount - self.flat).max(0.0) * (1.0 - self.resistance.clamp(0.0, 1.0))
+    }
+}
+
+// A box that hurts the hurtboxes it touches with the entity's `Damage`.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Hitbox {
+    pub size: Vec2,
+    pub offset: Vec2,          // From the entity's position.
+    pub owner: Option<Entity>, // Who is attacking, if not the entity itself.
+}
+
+impl Hitbox {
+    pub fn new(size: Vec2) -> Self {
+        Self {
+            size,
+            offset: Vec2::ZERO,
+            owner: None,
+        }
+    }
+}
+
+// The box an entity with `Health` can be hit in.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Hurtbox {
+    pub size: Vec2,
+    pub offset: Vec2, // From the entity's position.
+}
+
+impl Hurtbox {
+    pub fn new(size: Vec2) -> Self {
+        Self {
+            size,
+            offset: Vec2::ZERO,
+        }
+    }
+}
+
+// Sent for every hit that does damage.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct DamageEvent {
+    pub attacker: Entity,
+    pub target: Entity,
+    pub amount: f32, // After armor.
+}
+
+// Sent when an entity runs out of health, just before it is despawned.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct DeathEvent {
+    pub entity: Entity,
+    pub killer: Option<Entity>, // Whoever hit it last.
+}
+
+fn world_box(position: Vec2, offset: Vec2, size: Vec2) -> Aabb2d {
+    Aabb2d::new(position + offset, size / 2.0)
+}
+
+fn wear_off_invulnerability(time: Res<Time>, mut healths: Query<&mut Health>) {
+    let dt = time.delta().as_secs_f32();
+    for mut health in &mut healths {
+        if health.invulnerable_for > 0.0 {
+            health.invulnerable_for = (health.invulnerable_for - dt).max(0.0);
+        }
+    }
+}
+
+// A hit about to land: who is attacking whom, with what, and from where.
+struct Hit {
+    attacker: Entity,
+    target: Entity,
+    damage: Damage,
+    from: Vec2,
+}
+
+// Everything a hit changes about its target.
+type Victim = (
+    &'static mut Health,
+    Option<&'static Armor>,
+    &'static Transform,
+    Option<&'static mut Character>,
+    Option<&'static mut Velocity>,
+);
+
+fn deal_damage(
+    mut attacks: EventReader<AttackEvent>,
+    factions: Factions,
+    attackers: Query<(&Damage, &Transform)>,
+    hitboxes: Query<(Entity, &Hitbox, &Damage, &Transform)>,
+    hurtboxes: Query<(Entity, &Hurtbox, &Transform)>,
+    mut targets: Query<Victim>,
+    mut damaged: EventWriter<DamageEvent>,
+) {
+    let mut hits = Vec::new();
+    for attack in attacks.read() {
+        if let Ok((&damage, transform)) = attackers.get(attack.attacker) {
+            hits.push(Hit {
+                attacker: attack.attacker,
+                target: attack.target,
+                damage,
+                from: transform.translation.truncate(),
+            });
+        }
+    }
+    for (entity, hitbox, &damage, transform) in &hitboxes {
+        let position = transform.translation.truncate();
+        let reach = world_box(position, hitbox.offset, hitbox.size);
+        let attacker = hitbox.owner.unwrap_or(entity);
+        for (target, hurtbox, target_transform) in &hurtboxes {
+            if target == entity || target == attacker {
+                continue;
+            }
+            let target_position = target_transform.translation.truncate();
+            if reach.intersects(&world_box(target_position, hurtbox.offset, hurtbox.size)) {
+                hits.push(Hit {
+                    attacker,
+                    target,
+                    damage,
+                    from: position + hitbox.offset,
+                });
+            }
+        }
+    }
+
+    for hit in hits {
+        if factions.are_allied(hit.attacker, hit.target) {
+            continue;
+        }
+        let Ok((mut health, armor, transform, character, velocity)) = targets.get_mut(hit.target)
+        else {
+            continue;
+        };
+        if health.is_dead() || health.is_invulnerable() {
+            continue;
+        }
+        let amount = armor.map_or(hit.damage.amount, |armor| armor.reduce(hit.damage.amount));
+        if amount <= 0.0 {
+            continue;
+        }
+        health.current -= amount;
+        health.invulnerable_for = health.invulnerability;
+        health.last_attacker = Some(hit.attacker);
+
+        // Away from the hit and a little up, so characters get thrown off their feet.
+        let away = (transform.translation.truncate() - hit.from).normalize_or(Vec2::X);
+        let knockback = (away + Vec2::Y * 0.5).normalize() * hit.damage.knockback;
+        if let Some(mut character) = character {
+            character.knockback = knockback.x;
+            character.velocity.y = character.velocity.y.max(knockback.y);
+        } else if let Some(mut velocity) = velocity {
+            velocity.0 += knockback;
+        }
+        damaged.send(DamageEvent {
+            attacker: hit.attacker,
+            target: hit.target,
+            amount,
+        });
+    }
+}
+
+fn die(
+    mut commands: Commands,
+    healths: Query<(Entity, &Health)>,
+    mut deaths: EventWriter<DeathEvent>,
+) {
+    for (entity, health) in &healths {
+        if health.is_dead() {
+            deaths.send(DeathEvent {
+                entity,
+                killer: health.last_attacker,
+            });
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use colliding_bodies::{CharacterController, Shape};
+
+    use super::*;
+    use crate::faction::{CharacterFaction, FactionPlugin};
+    use crate::testing::{self, TENTH_OF_A_SECOND};
+
+    fn app() -> App {
+        testing::app(TENTH_OF_A_SECOND, (FactionPlugin, CombatPlugin))
+    }
+
+    fn fighter(app: &mut App, faction: CharacterFaction, x: f32) -> Entity {
+        app.world_mut()
+            .spawn((
+                Transform::from_xyz(x, 0.0, 0.0),
+                faction,
+                Health::new(100.0),
+                Hurtbox::new(Vec2::splat(20.0)),
+                Damage {
+                    amount: 10.0,
+                    knockback: 200.0,
+                },
+            ))
+            .id()
+    }
+
+    fn health(app: &App, entity: Entity) -> f32 {
+        app.world().get::<Health>(entity).expect("a health").current
+    }
+
+    fn attack(app: &mut App, attacker: Entity, target: Entity) {
+        app.world_mut().send_event(AttackEvent { attacker, target });
+    }
+
+    // Runs `updates` frames, returning the events of type `E` sent during them.
+    fn run<E: Event + Copy>(app: &mut App, updates: usize) -> Vec<E> {
+        let mut cursor = app.world().resource::<Events<E>>().get_cursor_current();
+        let mut sent = Vec::new();
+        for _ in 0..updates {
+            app.update();
+            sent.extend(cursor.read(app.world().resource::<Events<E>>()).copied());
+        }
+        sent
+    }
+
+    #[test]
+    fn armor_takes_off_a_flat_amount_then_a_fraction() {
+        let armor = Armor {
+            flat: 2.0,
+            resistance: 0.25,
+        };
+        assert_eq!(armor.reduce(10.0), 6.0);
+        assert_eq!(armor.reduce(1.0), 0.0);
+        let full = Armor {
+            flat: 0.0,
+            resistance: 1.5,
+        };
+        assert_eq!(full.reduce(10.0), 0.0);
+
+        let mut app = app();
+        let red = fighter(&mut app, CharacterFaction::Red, 0.0);
+        let blue = fighter(&mut app, CharacterFaction::Blue, 30.0);
+        app.world_mut().entity_mut(blue).insert(armor);
+        attack(&mut app, red, blue);
+        let hits = run::<DamageEvent>(&mut app, 1);
+        assert_eq!(
+            hits,
+            [DamageEvent {
+                attacker: red,
+                target: blue,
+                amount: 6.0,
+            }]
+        );
+        assert_eq!(health(&app, blue), 94.0);
+
+        // Hits that armor soaks up entirely do not count as hits.
+        app.world_mut().entity_mut(blue).insert(full);
+        for _ in 0..10 {
+            app.update();
+        }
+        attack(&mut app, red, blue);
+        assert!(run::<DamageEvent>(&mut app, 1).is_empty());
+        assert_eq!(health(&app, blue), 94.0);
+    }
+
+    #[test]
+    fn invulnerability_blocks_repeat_hits() {
+        let mut app = app();
+        let red = fighter(&mut app, CharacterFaction::Red, 0.0);
+        let blue = fighter(&mut app, CharacterFaction::Blue, 30.0);
+
+        // Only the first of two attacks in a frame lands.
+        attack(&mut app, red, blue);
+        attack(&mut app, red, blue);
+        assert_eq!(run::<DamageEvent>(&mut app, 1).len(), 1);
+        assert_eq!(health(&app, blue), 90.0);
+        assert!(app.world().get::<Health>(blue).unwrap().is_invulnerable());
+
+        // A hitbox left touching the target hurts it again about once every half second.
+        app.world_mut()
+            .entity_mut(red)
+            .insert(Hitbox::new(Vec2::splat(60.0)));
+        assert!(run::<DamageEvent>(&mut app, 4).is_empty());
+        assert_eq!(run::<DamageEvent>(&mut app, 2).len(), 1);
+        assert!(run::<DamageEvent>(&mut app, 4).is_empty());
+        assert_eq!(run::<DamageEvent>(&mut app, 2).len(), 1);
+        assert_eq!(health(&app, blue), 70.0);
+    }
+
+    #[test]
+    fn allies_do_not_hurt_each_other() {
+        let mut app = app();
+        let red = fighter(&mut app, CharacterFaction::Red, 0.0);
+        let ally = fighter(&mut app, CharacterFaction::Red, 30.0);
+        attack(&mut app, red, ally);
+        assert!(run::<DamageEvent>(&mut app, 1).is_empty());
+
+        // Nor through a hitbox of their own, or one they own, such as a thrown weapon.
+        let mut hitbox = Hitbox::new(Vec2::splat(60.0));
+        app.world_mut().entity_mut(red).insert(hitbox);
+        hitbox.owner = Some(ally);
+        app.world_mut().spawn((
+            Transform::from_xyz(0.0, 0.0, 0.0),
+            hitbox,
+            Damage {
+                amount: 50.0,
+                knockback: 0.0,
+            },
+        ));
+        assert!(run::<DamageEvent>(&mut app, 3).is_empty());
+        assert_eq!(health(&app, red), 100.0);
+        assert_eq!(health(&app, ally), 100.0);
+
+        // Anyone else in reach is fair game, neutrals included.
+        let neutral = fighter(&mut app, CharacterFaction::Neutral, -30.0);
+        let hits = run::<DamageEvent>(&mut app, 1);
+        assert_eq!(hits.len(), 1, "{hits:?}");
+        assert_eq!(hits[0].target, neutral);
+    }
+
+    #[test]
+    fn hits_knock_characters_away() {
+        let mut app = app();
+        let red = fighter(&mut app, CharacterFaction::Red, 0.0);
+        let blue = fighter(&mut app, CharacterFaction::Blue, 30.0);
+        app.world_mut()
+            .entity_mut(blue)
+            .insert(Character::new(CharacterController::new(Shape::aabb(
+                20.0, 20.0,
+            ))));
+        attack(&mut app, red, blue);
+        app.update();
+        let character = app.world().get::<Character>(blue).expect("a character");
+        assert!(character.knockback > 0.0 && character.velocity.y > 0.0);
+    }
+
+    #[test]
+    fn running_out_of_health_kills() {
+        let mut app = app();
+        let red = fighter(&mut app, CharacterFaction::Red, 0.0);
+        let blue = fighter(&mut app, CharacterFaction::Blue, 30.0);
+        app.world_mut()
+            .entity_mut(blue)
+            .insert(Health::new(15.0).with_invulnerability(0.0));
+
+        attack(&mut app, red, blue);
+        assert!(run::<DeathEvent>(&mut app, 1).is_empty());
+        assert_eq!(health(&app, blue), 5.0);
+
+        attack(&mut app, red, blue);
+        let deaths = run::<DeathEvent>(&mut app, 1);
+        assert_eq!(
+            deaths,
+            [DeathEvent {
+                entity: blue,
+                killer: Some(red),
+            }]
+        );
+        assert!(app.world().get_entity(blue).is_err());
+    }
+}
@@ -0,0 +1,504 @@
+// -----------------------------------------------------------------------------
+// Controls
+// -----------------------------------------------------------------------------
+//
+// Turns keys, mouse buttons and gamepads into the actions players take, so game systems ask
+// whether a player wants to jump rather than whether W is down. Every player has their own
+// bindings, and an action can be bound to any number of inputs: keys, mouse buttons, gamepad
+// buttons and sticks, two of those making an axis, and chords that need all their inputs held.
+// Each player reads at most one gamepad, so several players can share the keyboard or have a
+// gamepad each.
+//
+// Bindings live in `ControlSettings`, which is written back to its RON file whenever it changes,
+// so actions rebound while the game runs stay rebound next time.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const PRESS_THRESHOLD: f32 = 0.5; // How far an axis has to go for its action to count as pressed.
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ControlSettings>().add_systems(
+            PreUpdate,
+            (read_actions, rebind, save_settings)
+                .chain()
+                .in_set(ControlsSystems)
+                .after(InputSystem),
+        );
+    }
+}
+
+// Actions are read before `Update`, so every game system sees this frame's.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ControlsSystems;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Move, // From -1 (left) to 1 (right).
+    Jump,
+    Attack,
+    Rebind, // Rebinds jumping to whatever is pressed next.
+    QuickSave,
+    QuickLoad,
+}
+
+// An input an action can be bound to. Buttons read from 0 to 1 and axes from -1 to 1.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Button(GamepadButton), // Analog for triggers.
+    Stick {
+        axis: GamepadAxis,
+        dead_zone: f32, // Anything closer to the middle reads as zero.
+    },
+    // Two bindings pulling in opposite directions, such as a pair of keys.
+    Axis {
+        negative: Box<Binding>,
+        positive: Box<Binding>,
+    },
+    Chord(Vec<Binding>), // Reads as the least held of its bindings, so all must be held.
+}
+
+// The inputs one player reads.
+struct Devices<'a> {
+    keys: &'a ButtonInput<KeyCode>,
+    mouse: &'a ButtonInput<MouseButton>,
+    gamepad: Option<&'a Gamepad>,
+}
+
+impl Binding {
+    fn value(&self, devices: &Devices) -> f32 {
+        let held = |pressed: bool| if pressed { 1.0 } else { 0.0 };
+        match self {
+            Binding::Key(key) => held(devices.keys.pressed(*key)),
+            Binding::Mouse(button) => held(devices.mouse.pressed(*button)),
+            Binding::Button(button) => devices.gamepad.map_or(0.0, |gamepad| {
+                gamepad
+                    .get(*button)
+                    .unwrap_or_else(|| held(gamepad.pressed(*button)))
+            }),
+            Binding::Stick { axis, dead_zone } => {
+                let value = devices
+                    .gamepad
+                    .and_then(|gamepad| gamepad.get(*axis))
+                    .unwrap_or(0.0);
+                // Scaled so that the stick still goes smoothly from zero just past the dead zone.
+                let dead_zone = dead_zone.clamp(0.0, 0.99);
+                let past = (value.abs() - dead_zone).max(0.0) / (1.0 - dead_zone);
+                past.min(1.0) * value.signum()
+            }
+            Binding::Axis { negative, positive } => {
+                (positive.value(devices).abs() - negative.value(devices).abs()).clamp(-1.0, 1.0)
+            }
+            Binding::Chord(bindings) => bindings
+                .iter()
+                .map(|binding| binding.value(devices).abs())
+                .reduce(f32::min)
+                .unwrap_or(0.0),
+        }
+    }
+}
+
+// One player's bindings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlayerBindings {
+    pub gamepad: Option<usize>, // Which connected gamepad the player uses, counting from 0.
+    pub actions: BTreeMap<Action, Vec<Binding>>,
+}
+
+impl PlayerBindings {
+    pub fn with_gamepad(mut self, gamepad: usize) -> Self {
+        self.gamepad = Some(gamepad);
+        self
+    }
+
+    pub fn with(mut self, action: Action, binding: Binding) -> Self {
+        self.actions.entry(action).or_default().push(binding);
+        self
+    }
+}
+
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ControlSettings {
+    pub players: Vec<PlayerBindings>, // Player 1 first.
+    #[serde(skip)]
+    path: Option<PathBuf>, // Where changes are saved to; nowhere when `None`.
+}
+
+// Player 1 on the keyboard and mouse, player 2 on the first gamepad.
+impl Default for ControlSettings {
+    fn default() -> Self {
+        let keys = |negative, positive| Binding::Axis {
+            negative: Box::new(Binding::Key(negative)),
+            positive: Box::new(Binding::Key(positive)),
+        };
+        let buttons = |negative, positive| Binding::Axis {
+            negative: Box::new(Binding::Button(negative)),
+            positive: Box::new(Binding::Button(positive)),
+        };
+        let player1 = PlayerBindings::default()
+            .with(Action::Move, keys(KeyCode::KeyA, KeyCode::KeyD))
+            .with(Action::Move, keys(KeyCode::ArrowLeft, KeyCode::ArrowRight))
+            .with(Action::Jump, Binding::Key(KeyCode::KeyW))
+            .with(Action::Jump, Binding::Key(KeyCode::Space))
+            .with(Action::Attack, Binding::Key(KeyCode::KeyF))
+            .with(Action::Attack, Binding::Mouse(MouseButton::Left))
+            .with(
+                Action::Rebind,
+                Binding::Chord(vec![
+                    Binding::Key(KeyCode::ControlLeft),
+                    Binding::Key(KeyCode::KeyR),
+                ]),
+            )
+            .with(Action::QuickSave, Binding::Key(KeyCode::F5))
+            .with(Action::QuickLoad, Binding::Key(KeyCode::F9));
+        let player2 = PlayerBindings::default()
+            .with_gamepad(0)
+            .with(
+                Action::Move,
+                Binding::Stick {
+                    axis: GamepadAxis::LeftStickX,
+                    dead_zone: 0.2,
+                },
+            )
+            .with(
+                Action::Move,
+                buttons(GamepadButton::DPadLeft, GamepadButton::DPadRight),
+            )
+            .with(Action::Jump, Binding::Button(GamepadButton::South))
+            .with(Action::Attack, Binding::Button(GamepadButton::West))
+            .with(
+                Action::Attack,
+                Binding::Button(GamepadButton::RightTrigger2),
+            )
+            .with(
+                Action::Rebind,
+                Binding::Chord(vec![
+                    Binding::Button(GamepadButton::Select),
+                    Binding::Button(GamepadButton::North),
+                ]),
+            );
+        Self {
+            players: vec![player1, player2],
+            path: None,
+        }
+    }
+}
+
+impl ControlSettings {
+    // Reads the settings saved at `path`, or starts from the defaults if nothing has been saved
+    // there yet. Either way, changes are saved back to `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ControlsError> {
+        let path = path.as_ref();
+        let mut settings = match std::fs::read_to_string(path) {
+            Ok(text) => ron::from_str(&text)
+                .map_err(|error| ControlsError::Parse(path.to_path_buf(), error))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(error) => return Err(ControlsError::Io(path.to_path_buf(), error)),
+        };
+        settings.path = Some(path.to_path_buf());
+        Ok(settings)
+    }
+
+    pub fn save(&self) -> Result<(), ControlsError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|error| ControlsError::Serialize(path.clone(), error))?;
+        std::fs::write(path, text).map_err(|error| ControlsError::Io(path.clone(), error))
+    }
+
+    // Replaces everything bound to a player's action.
+    pub fn rebind(&mut self, player: usize, action: Action, bindings: Vec<Binding>) {
+        if self.players.len() <= player {
+            self.players.resize(player + 1, PlayerBindings::default());
+        }
+        self.players[player].actions.insert(action, bindings);
+    }
+}
+
+#[derive(Debug)]
+pub enum ControlsError {
+    Io(PathBuf, std::io::Error), // The file could not be read or written.
+    Parse(PathBuf, ron::error::SpannedError), // The file is not valid settings.
+    Serialize(PathBuf, ron::Error), // The settings could not be written out.
+}
+
+impl std::fmt::Display for ControlsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ControlsError::Io(path, error) => {
+                write!(f, "failed to access {}: {error}", path.display())
+            }
+            ControlsError::Parse(path, error) => {
+                write!(f, "failed to parse {}: {error}", path.display())
+            }
+            ControlsError::Serialize(path, error) => {
+                write!(f, "failed to write settings to {}: {error}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ControlsError {}
+
+// An entity controlled by a player, counting from 0 for player 1.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[require(ActionState)]
+pub struct Player(pub usize);
+
+// What a player is doing this frame.
+#[derive(Component, Debug, Default, Clone, PartialEq)]
+pub struct ActionState {
+    values: BTreeMap<Action, f32>,
+    previous: BTreeMap<Action, f32>, // Last frame's, to tell when a button went down.
+}
+
+impl ActionState {
+    pub fn value(&self, action: Action) -> f32 {
+        self.values.get(&action).copied().unwrap_or(0.0)
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.value(action).abs() >= PRESS_THRESHOLD
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        let before = self.previous.get(&action).copied().unwrap_or(0.0);
+        self.pressed(action) && before.abs() < PRESS_THRESHOLD
+    }
+}
+
+// Waits for the player to press something, then binds the action to it alone. Insert it to start
+// rebinding; it is removed once done.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct Rebinding {
+    pub player: usize,
+    pub action: Action,
+}
+
+// Gamepads in the order they were found, which is the order players are given them.
+fn connected_gamepads<'a>(gamepads: &'a Query<(Entity, &Gamepad)>) -> Vec<&'a Gamepad> {
+    let mut gamepads: Vec<_> = gamepads.iter().collect();
+    gamepads.sort_by_key(|&(entity, _)| entity);
+    gamepads.into_iter().map(|(_, gamepad)| gamepad).collect()
+}
+
+fn read_actions(
+    settings: Res<ControlSettings>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    gamepads: Query<(Entity, &Gamepad)>,
+    mut players: Query<(&Player, &mut ActionState)>,
+) {
+    let gamepads = connected_gamepads(&gamepads);
+    for (player, mut state) in &mut players {
+        let state = &mut *state;
+        state.previous = std::mem::take(&mut state.values);
+        let Some(bindings) = settings.players.get(player.0) else {
+            continue;
+        };
+        let devices = Devices {
+            keys: &keys,
+            mouse: &mouse,
+            gamepad: bindings
+                .gamepad
+                .and_then(|index| gamepads.get(index).copied()),
+        };
+        for (&action, bindings) in &bindings.actions {
+            // The binding pushed furthest wins, so keys and sticks can be used side by side.
+            let value = bindings.iter().map(|binding| binding.value(&devices)).fold(
+                0.0,
+                |best: f32, value| {
+                    if value.abs() > best.abs() {
+                        value
+                    } else {
+                        best
+                    }
+                },
+            );
+            state.values.insert(action, value);
+        }
+    }
+}
+
+fn rebind(
+    mut commands: Commands,
+    rebinding: Option<Res<Rebinding>>,
+    mut settings: ResMut<ControlSettings>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    gamepads: Query<(Entity, &Gamepad)>,
+) {
+    let Some(rebinding) = rebinding else {
+        return;
+    };
+    // Only the player's own gamepad counts, so another player cannot rebind it by accident.
+    let gamepads = connected_gamepads(&gamepads);
+    let gamepad = settings
+        .players
+        .get(rebinding.player)
+        .and_then(|bindings| bindings.gamepad)
+        .and_then(|index| gamepads.get(index).copied());
+    let pressed = keys
+        .get_just_pressed()
+        .next()
+        .map(|&key| Binding::Key(key))
+        .or_else(|| {
+            mouse
+                .get_just_pressed()
+                .next()
+                .map(|&button| Binding::Mouse(button))
+        })
+        .or_else(|| {
+            gamepad
+                .and_then(|gamepad| gamepad.get_just_pressed().next())
+                .map(|&button| Binding::Button(button))
+        });
+    if let Some(binding) = pressed {
+        info!(
+            "Player {} {:?} bound to {binding:?}",
+            rebinding.player + 1,
+            rebinding.action
+        );
+        settings.rebind(rebinding.player, rebinding.action, vec![binding]);
+        commands.remove_resource::<Rebinding>();
+    }
+}
+
+fn save_settings(settings: Res<ControlSettings>) {
+    if !settings.is_changed() || settings.is_added() {
+        return;
+    }
+    if let Err(error) = settings.save() {
+        warn!("{error}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(settings: ControlSettings) -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, ControlsPlugin))
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<ButtonInput<MouseButton>>()
+            .insert_resource(settings);
+        app
+    }
+
+    fn value(binding: &Binding, keys: &ButtonInput<KeyCode>, gamepad: Option<&Gamepad>) -> f32 {
+        let devices = Devices {
+            keys,
+            mouse: &ButtonInput::default(),
+            gamepad,
+        };
+        binding.value(&devices)
+    }
+
+    #[test]
+    fn sticks_read_zero_inside_the_dead_zone_and_scale_past_it() {
+        let stick = Binding::Stick {
+            axis: GamepadAxis::LeftStickX,
+            dead_zone: 0.2,
+        };
+        let keys = ButtonInput::default();
+        let mut gamepad = Gamepad::default();
+        let mut read = |position: f32| {
+            gamepad.analog_mut().set(GamepadAxis::LeftStickX, position);
+            value(&stick, &keys, Some(&gamepad))
+        };
+        assert_eq!(read(0.0), 0.0);
+        assert_eq!(read(0.2), 0.0);
+        assert_eq!(read(-0.15), 0.0);
+        assert!((read(0.6) - 0.5).abs() < 1e-6);
+        assert!((read(-0.6) + 0.5).abs() < 1e-6);
+        assert_eq!(read(1.0), 1.0);
+        assert_eq!(read(-1.0), -1.0);
+        // Without a gamepad the stick stays in the middle.
+        assert_eq!(value(&stick, &keys, None), 0.0);
+    }
+
+    #[test]
+    fn chords_need_every_input_held() {
+        let chord = Binding::Chord(vec![
+            Binding::Key(KeyCode::ControlLeft),
+            Binding::Key(KeyCode::KeyR),
+        ]);
+        let mut keys = ButtonInput::default();
+        assert_eq!(value(&chord, &keys, None), 0.0);
+        keys.press(KeyCode::KeyR);
+        assert_eq!(value(&chord, &keys, None), 0.0);
+        keys.press(KeyCode::ControlLeft);
+        assert_eq!(value(&chord, &keys, None), 1.0);
+        assert_eq!(value(&Binding::Chord(Vec::new()), &keys, None), 0.0);
+
+        // Through a player, the chord is pressed only on the frame both are down.
+        let mut app = app(ControlSettings::default());
+        let player = app.world_mut().spawn(Player(0)).id();
+        let press = |app: &mut App, key| {
+            let mut keys = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+            keys.press(key);
+        };
+        press(&mut app, KeyCode::KeyR);
+        app.update();
+        assert!(!app
+            .world()
+            .get::<ActionState>(player)
+            .unwrap()
+            .pressed(Action::Rebind));
+        press(&mut app, KeyCode::ControlLeft);
+        app.update();
+        let state = app.world().get::<ActionState>(player).unwrap();
+        assert!(state.just_pressed(Action::Rebind));
+        app.update();
+        let state = app.world().get::<ActionState>(player).unwrap();
+        assert!(state.pressed(Action::Rebind) && !state.just_pressed(Action::Rebind));
+    }
+
+    #[test]
+    fn rebound_actions_are_saved_and_reloaded() {
+        let directory =
+            std::env::temp_dir().join(format!("bevy_ecs-controls-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("controls.ron");
+
+        // Nothing saved yet, so the defaults are used.
+        let settings = ControlSettings::load(&path).unwrap();
+        assert_eq!(settings.players, ControlSettings::default().players);
+        let mut app = app(settings);
+        app.update();
+        assert!(!path.exists(), "unchanged settings are not written");
+
+        app.insert_resource(Rebinding {
+            player: 0,
+            action: Action::Jump,
+        });
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::KeyJ);
+        app.update();
+        assert!(app.world().get_resource::<Rebinding>().is_none());
+
+        let reloaded = ControlSettings::load(&path).unwrap();
+        assert_eq!(
+            reloaded.players[0].actions[&Action::Jump],
+            vec![Binding::Key(KeyCode::KeyJ)]
+        );
+        assert_eq!(
+            reloaded.players,
+            app.world().resource::<ControlSettings>().players
+        );
+    }
+}
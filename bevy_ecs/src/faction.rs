@@ -0,0 +1,388 @@
+// -----------------------------------------------------------------------------
+// Factions
+// -----------------------------------------------------------------------------
+//
+// How the factions feel about each other. Each faction holds a reputation for every faction,
+// itself included, from -100 (sworn enemies) to 100 (close allies), which decides whether it
+// treats that faction as allied, neutral or hostile. Attacks lower the reputation of the attacker's
+// faction with the victim's faction and its allies.
+//
+// Game systems send an `AttackEvent` when one character attacks another, and ask `Factions`
+// whether two characters are hostile.
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub struct FactionPlugin;
+
+impl Plugin for FactionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FactionRelations>()
+            .add_event::<AttackEvent>()
+            .add_event::<RelationChanged>()
+            .add_systems(Update, update_relations);
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CharacterFaction {
+    Red,
+    Blue,
+    Neutral,
+}
+
+impl CharacterFaction {
+    pub const ALL: [CharacterFaction; 3] = [
+        CharacterFaction::Red,
+        CharacterFaction::Blue,
+        CharacterFaction::Neutral,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Relation {
+    Allied,
+    Neutral,
+    Hostile,
+}
+
+pub const MIN_REPUTATION: i32 = -100;
+pub const MAX_REPUTATION: i32 = 100;
+
+// What every faction thinks of every other. Reputations need not be mutual: a faction that was
+// attacked thinks less of the attacker than the attacker does of it.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FactionRelations {
+    reputation: [[i32; CharacterFaction::ALL.len()]; CharacterFaction::ALL.len()],
+    pub allied_at: i32, // Reputation at which a faction counts another as an ally.
+    pub hostile_at: i32, // Reputation at which a faction counts another as an enemy.
+    pub attack_penalty: i32, // Reputation lost with the victim's faction per attack.
+    pub ally_penalty: i32, // Reputation lost with the victim's allies per attack.
+}
+
+impl Default for FactionRelations {
+    // Every faction is allied with itself, red and blue are at war and the neutrals are neutral.
+    fn default() -> Self {
+        use CharacterFaction::*;
+        let mut relations = Self {
+            reputation: [[0; CharacterFaction::ALL.len()]; CharacterFaction::ALL.len()],
+            allied_at: 50,
+            hostile_at: -50,
+            attack_penalty: 30,
+            ally_penalty: 10,
+        };
+        for faction in CharacterFaction::ALL {
+            relations.set_reputation(faction, faction, MAX_REPUTATION);
+        }
+        relations.set_mutual_reputation(Red, Blue, -75);
+        relations
+    }
+}
+
+impl FactionRelations {
+    // What `of` thinks of `towards`.
+    pub fn reputation(&self, of: CharacterFaction, towards: CharacterFaction) -> i32 {
+        self.reputation[of.index()][towards.index()]
+    }
+
+    pub fn set_reputation(&mut self, of: CharacterFaction, towards: CharacterFaction, value: i32) {
+        self.reputation[of.index()][towards.index()] = value.clamp(MIN_REPUTATION, MAX_REPUTATION);
+    }
+
+    pub fn set_mutual_reputation(&mut self, a: CharacterFaction, b: CharacterFaction, value: i32) {
+        self.set_reputation(a, b, value);
+        self.set_reputation(b, a, value);
+    }
+
+    // Adds `change` to what `of` thinks of `towards`, returning the new relation.
+    pub fn change_reputation(
+        &mut self,
+        of: CharacterFaction,
+        towards: CharacterFaction,
+        change: i32,
+    ) -> Relation {
+        let value = self.reputation(of, towards).saturating_add(change);
+        self.set_reputation(of, towards, value);
+        self.relation(of, towards)
+    }
+
+    // How `of` treats `towards`.
+    pub fn relation(&self, of: CharacterFaction, towards: CharacterFaction) -> Relation {
+        let reputation = self.reputation(of, towards);
+        if reputation >= self.allied_at {
+            Relation::Allied
+        } else if reputation <= self.hostile_at {
+            Relation::Hostile
+        } else {
+            Relation::Neutral
+        }
+    }
+
+    // True if either faction treats the other as an enemy: one side attacking is enough for a
+    // fight.
+    pub fn are_hostile(&self, a: CharacterFaction, b: CharacterFaction) -> bool {
+        self.relation(a, b) == Relation::Hostile || self.relation(b, a) == Relation::Hostile
+    }
+
+    // True if both factions treat each other as allies.
+    pub fn are_allied(&self, a: CharacterFaction, b: CharacterFaction) -> bool {
+        self.relation(a, b) == Relation::Allied && self.relation(b, a) == Relation::Allied
+    }
+}
+
+// Sent by whatever makes one character attack another.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttackEvent {
+    pub attacker: Entity,
+    pub target: Entity,
+}
+
+// Sent when an attack changes how one faction treats another.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RelationChanged {
+    pub of: CharacterFaction,
+    pub towards: CharacterFaction,
+    pub from: Relation,
+    pub to: Relation,
+}
+
+// Another character and its faction, as AI sees it during a frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Seen {
+    pub entity: Entity,
+    pub position: Vec2,
+    pub faction: CharacterFaction,
+}
+
+// Takes a snapshot of every character with a faction, for systems that look at all of them while
+// changing their own characters.
+pub fn see_characters<'a>(
+    characters: impl IntoIterator<Item = (Entity, &'a Transform, &'a CharacterFaction)>,
+) -> Vec<Seen> {
+    characters
+        .into_iter()
+        .map(|(entity, transform, &faction)| Seen {
+            entity,
+            position: transform.translation.truncate(),
+            faction,
+        })
+        .collect()
+}
+
+// Faction lookups for characters, for systems that deal in entities.
+#[derive(SystemParam)]
+pub struct Factions<'w, 's> {
+    relations: Res<'w, FactionRelations>,
+    factions: Query<'w, 's, &'static CharacterFaction>,
+}
+
+impl Factions<'_, '_> {
+    pub fn faction(&self, entity: Entity) -> Option<CharacterFaction> {
+        self.factions.get(entity).ok().copied()
+    }
+
+    // True if the characters' factions are hostile; characters without a faction never are.
+    pub fn are_hostile(&self, a: Entity, b: Entity) -> bool {
+        match (self.faction(a), self.faction(b)) {
+            (Some(a), Some(b)) => self.relations.are_hostile(a, b),
+            _ => false,
+        }
+    }
+
+    // True if the characters' factions are allied, as every faction is with itself; characters
+    // without a faction never are.
+    pub fn are_allied(&self, a: Entity, b: Entity) -> bool {
+        match (self.faction(a), self.faction(b)) {
+            (Some(a), Some(b)) => self.relations.are_allied(a, b),
+            _ => false,
+        }
+    }
+}
+
+// Attacks sour the victim's faction, and its allies, on the attacker's faction. Fights within a
+// faction are left alone.
+fn update_relations(
+    mut attacks: EventReader<AttackEvent>,
+    factions: Query<&CharacterFaction>,
+    mut relations: ResMut<FactionRelations>,
+    mut changes: EventWriter<RelationChanged>,
+) {
+    for attack in attacks.read() {
+        let (Ok(&attacker), Ok(&victim)) =
+            (factions.get(attack.attacker), factions.get(attack.target))
+        else {
+            continue;
+        };
+        if attacker == victim {
+            continue;
+        }
+        for faction in CharacterFaction::ALL {
+            let penalty = if faction == victim {
+                relations.attack_penalty
+            } else if faction != attacker && relations.are_allied(faction, victim) {
+                relations.ally_penalty
+            } else {
+                continue;
+            };
+            let from = relations.relation(faction, attacker);
+            let to = relations.change_reputation(faction, attacker, -penalty);
+            if from != to {
+                changes.send(RelationChanged {
+                    of: faction,
+                    towards: attacker,
+                    from,
+                    to,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use CharacterFaction::*;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, FactionPlugin));
+        app
+    }
+
+    // Sends one attack and runs a frame, returning the relation changes it caused.
+    fn attack(app: &mut App, attacker: Entity, target: Entity) -> Vec<RelationChanged> {
+        let mut cursor = app
+            .world()
+            .resource::<Events<RelationChanged>>()
+            .get_cursor_current();
+        app.world_mut().send_event(AttackEvent { attacker, target });
+        app.update();
+        let events = app.world().resource::<Events<RelationChanged>>();
+        cursor.read(events).copied().collect()
+    }
+
+    fn relations(app: &App) -> &FactionRelations {
+        app.world().resource::<FactionRelations>()
+    }
+
+    #[test]
+    fn default_relations() {
+        let relations = FactionRelations::default();
+        for faction in CharacterFaction::ALL {
+            assert!(relations.are_allied(faction, faction));
+        }
+        assert!(relations.are_hostile(Red, Blue) && relations.are_hostile(Blue, Red));
+        assert!(!relations.are_hostile(Red, Neutral) && !relations.are_allied(Red, Neutral));
+        assert_eq!(relations.relation(Neutral, Blue), Relation::Neutral);
+    }
+
+    #[test]
+    fn thresholds_are_inclusive_and_reputations_clamped() {
+        let mut relations = FactionRelations::default();
+        relations.set_reputation(Neutral, Red, relations.hostile_at + 1);
+        assert_eq!(relations.relation(Neutral, Red), Relation::Neutral);
+        assert_eq!(
+            relations.change_reputation(Neutral, Red, -1),
+            Relation::Hostile
+        );
+        // One side being hostile is enough for a fight, but not for an alliance.
+        assert!(relations.are_hostile(Red, Neutral));
+        relations.set_reputation(Neutral, Blue, relations.allied_at);
+        assert_eq!(relations.relation(Neutral, Blue), Relation::Allied);
+        assert!(!relations.are_allied(Neutral, Blue));
+
+        relations.change_reputation(Neutral, Red, -1000);
+        assert_eq!(relations.reputation(Neutral, Red), MIN_REPUTATION);
+        relations.change_reputation(Neutral, Red, i32::MAX);
+        assert_eq!(relations.reputation(Neutral, Red), MAX_REPUTATION);
+    }
+
+    #[test]
+    fn repeated_attacks_turn_the_victims_faction_hostile() {
+        let mut app = app();
+        let red = app.world_mut().spawn(Red).id();
+        let villager = app.world_mut().spawn(Neutral).id();
+        let penalty = relations(&app).attack_penalty;
+
+        // 0 -> -30: still neutral.
+        assert!(attack(&mut app, red, villager).is_empty());
+        assert_eq!(relations(&app).reputation(Neutral, Red), -penalty);
+        assert!(!relations(&app).are_hostile(Neutral, Red));
+
+        // -30 -> -60: past the -50 threshold.
+        assert_eq!(
+            attack(&mut app, red, villager),
+            [RelationChanged {
+                of: Neutral,
+                towards: Red,
+                from: Relation::Neutral,
+                to: Relation::Hostile,
+            }]
+        );
+        assert!(relations(&app).are_hostile(Neutral, Red));
+        // Only the victim's side changed its mind.
+        assert_eq!(relations(&app).reputation(Red, Neutral), 0);
+    }
+
+    #[test]
+    fn allies_of_the_victim_take_offence_too() {
+        let mut app = app();
+        app.world_mut()
+            .resource_mut::<FactionRelations>()
+            .set_mutual_reputation(Neutral, Blue, 80);
+        let red = app.world_mut().spawn(Red).id();
+        let villager = app.world_mut().spawn(Neutral).id();
+
+        let before = relations(&app).reputation(Blue, Red);
+        attack(&mut app, red, villager);
+        let relations = relations(&app);
+        assert_eq!(
+            relations.reputation(Blue, Red),
+            before - relations.ally_penalty
+        );
+        assert_eq!(relations.reputation(Red, Red), MAX_REPUTATION);
+    }
+
+    #[test]
+    fn fights_within_a_faction_and_without_one_are_ignored() {
+        let mut app = app();
+        let red = app.world_mut().spawn(Red).id();
+        let other_red = app.world_mut().spawn(Red).id();
+        let nobody = app.world_mut().spawn_empty().id();
+        let before = relations(&app).clone();
+
+        assert!(attack(&mut app, red, other_red).is_empty());
+        assert!(attack(&mut app, nobody, red).is_empty());
+        assert!(attack(&mut app, red, nobody).is_empty());
+        assert_eq!(*relations(&app), before);
+    }
+
+    #[test]
+    fn factions_answer_for_entities() {
+        let mut app = app();
+        let red = app.world_mut().spawn(Red).id();
+        let blue = app.world_mut().spawn(Blue).id();
+        let other_red = app.world_mut().spawn(Red).id();
+        let nobody = app.world_mut().spawn_empty().id();
+
+        let answers = app
+            .world_mut()
+            .run_system_once(move |factions: Factions| {
+                (
+                    factions.are_hostile(red, blue),
+                    factions.are_allied(red, other_red),
+                    factions.are_hostile(red, nobody) || factions.are_allied(nobody, nobody),
+                    factions.faction(nobody),
+                )
+            })
+            .expect("the system runs");
+        assert_eq!(answers, (true, true, false, None));
+    }
+}
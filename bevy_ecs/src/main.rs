@@ -1,12 +1,117 @@
+mod behaviour;
+mod character;
+mod combat;
+mod controls;
+mod faction;
+mod navigation;
+mod planner;
+mod platformer;
+mod prefab;
+mod save;
+mod steering;
+#[cfg(test)]
+mod testing;
+
+use std::path::Path;
+
+use behaviour::BehaviourPlugin;
 use bevy::prelude::*;
+use character::{Character, CharacterPlugin, Collider, TileCollider};
+use colliding_bodies::{Aabb, CharacterController, Grid, NavMesh, Polygon, Shape};
+use combat::{CombatPlugin, Damage, DamageEvent, DeathEvent, Health, Hitbox};
+use controls::{Action, ActionState, ControlSettings, ControlsPlugin, Player, Rebinding};
+use faction::{
+    AttackEvent, CharacterFaction, FactionPlugin, FactionRelations, Factions, RelationChanged,
+};
+use navigation::{NavigationPlugin, NavigationSystems, PathFollower};
+use planner::{Creature, MateEvent, PlannerPlugin};
+use platformer::{PlatformerInput, PlatformerPlugin, PlatformerSystems};
+use prefab::{load_spawns, PrefabComponent, PrefabInstance, PrefabPlugin, Prefabs, SpawnPrefab};
+use save::{LoadGame, SaveApp, SaveGame, SavePlugin, SaveSettings, SaveSlot, SaveSystems};
+use serde::{Deserialize, Serialize};
+use steering::{
+    Avoidance, Steering, SteeringBehaviour, SteeringPlugin, SteeringSystems, Target, Velocity,
+};
+
+const REACH: f32 = 40.0;
+const MAX_CRITTERS: usize = 8;
+const DRONE_REPATH: f32 = 0.5; // Seconds between fresh paths to the player.
+const FLOCK_SIZE: usize = 1000;
+const WISPS: usize = 6;
+const SKY_MIN: Vec2 = Vec2::new(-500.0, 150.0); // The birds' corner of the screen.
+const SKY_MAX: Vec2 = Vec2::new(500.0, 350.0);
 
-const SPEED: f32 = 200.0;
+// Floating platforms over the middle of the level, 20 pixel tiles with the bottom row at y = 60.
+const PLATFORMS: &str = "
+    ####......####
+    ..............
+    ......##......
+";
 
 fn main() {
+    // Broken settings are left alone, rather than overwritten with the defaults.
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("controls.ron");
+    let controls = ControlSettings::load(path).unwrap_or_else(|error| {
+        warn!("{error}");
+        ControlSettings::default()
+    });
+    let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+    // Without prefabs the game still starts, just without the characters made from them.
+    let prefabs = Prefabs::load(&assets).unwrap_or_else(|error| {
+        warn!("{error}");
+        Prefabs::default()
+    });
+
     App::new()
-        .add_plugins(DefaultPlugins)
-        .add_systems(Startup, (setup, add_characters))
-        .add_systems(Update, (move_player, keep_above_ground))
+        .insert_resource(controls)
+        .insert_resource(prefabs)
+        .insert_resource(SaveSettings {
+            directory: Path::new(env!("CARGO_MANIFEST_DIR")).join("saves"),
+            ..default()
+        })
+        .add_plugins((
+            DefaultPlugins,
+            ControlsPlugin,
+            CharacterPlugin,
+            FactionPlugin,
+            BehaviourPlugin,
+            PlannerPlugin,
+            NavigationPlugin,
+            SteeringPlugin,
+            CombatPlugin,
+            PlatformerPlugin,
+            SavePlugin,
+            PrefabPlugin,
+        ))
+        .save_component::<Name>("name")
+        .save_component::<Transform>("transform")
+        .save_component::<CharacterFaction>("faction")
+        .save_component::<Health>("health")
+        .save_respawn::<PrefabInstance>("prefab")
+        .save_resource::<FactionRelations>("factions")
+        .add_systems(
+            Startup,
+            (
+                setup,
+                add_level,
+                add_characters,
+                add_swarms.after(add_characters),
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                move_player.before(PlatformerSystems),
+                start_rebinding,
+                quick_save.before(SaveSystems),
+                player_attack,
+                report_relations.after(player_attack),
+                report_combat,
+                breed_critters,
+                chase_player_in_the_air.before(NavigationSystems),
+                wrap_birds.after(SteeringSystems),
+            ),
+        )
         .run();
 }
 
@@ -14,65 +119,420 @@ fn setup(mut commands: Commands) {
     commands.spawn(Camera2d);
 }
 
-fn add_characters(
+// A 20 by 20 character that climbs 8 pixel steps and slopes up to 45 degrees.
+fn character_controller() -> CharacterController {
+    CharacterController::new(Shape::aabb(20.0, 20.0))
+        .with_step_height(8.0)
+        .with_snap_distance(6.0)
+        .with_skin_width(0.5)
+}
+
+// Converts a Bevy vector for the physics crate, and back.
+fn to_physics(v: Vec2) -> colliding_bodies::Vec2 {
+    colliding_bodies::Vec2::new(v.x, v.y)
+}
+
+fn from_physics(v: colliding_bodies::Vec2) -> Vec2 {
+    Vec2::new(v.x, v.y)
+}
+
+// Where an entity's random numbers start; never zero, which xorshift would never leave.
+fn random_seed(entity: Entity) -> u32 {
+    entity.index().wrapping_mul(0x9e37_79b9) | 1
+}
+
+// A number in [0, 1), moving `seed` on.
+fn random(seed: &mut u32) -> f32 {
+    // Xorshift.
+    let mut x = *seed;
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    *seed = x;
+    (x >> 8) as f32 / (1 << 24) as f32
+}
+
+// The ground, with a wall, a step, a ramp and some platforms to get around on, and the navigation
+// mesh of the air above them.
+fn add_level(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    let player1 = (
-        Object,
-        PlayableCharacter,
-        CharacterFaction::BlueFaction,
-        Name("Player 1".to_string()),
-        Mesh2d(meshes.add(Rectangle::new(20.0, 20.0))),
-        MeshMaterial2d(materials.add(Color::rgb(0.0, 0.0, 1.0))),
-        Transform::from_xyz(0.0, 0.0, 0.0),
+    let material = materials.add(Color::srgb(0.4, 0.4, 0.4));
+    let mut obstacles = Vec::new();
+    let blocks = [
+        (Vec2::new(0.0, -30.0), Vec2::new(1000.0, 40.0)), // Ground, its top at y = -10.
+        (Vec2::new(-250.0, 40.0), Vec2::new(20.0, 100.0)), // Wall.
+        (Vec2::new(150.0, -6.0), Vec2::new(60.0, 8.0)),   // Step.
+    ];
+    for (position, size) in blocks {
+        commands.spawn((
+            Collider(Shape::aabb(size.x, size.y)),
+            Mesh2d(meshes.add(Rectangle::new(size.x, size.y))),
+            MeshMaterial2d(material.clone()),
+            Transform::from_xyz(position.x, position.y, 0.0),
+        ));
+        let (min, max) = (position - size / 2.0, position + size / 2.0);
+        let outline = [min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)];
+        obstacles.push(outline.map(to_physics).to_vec());
+    }
+
+    // A 30 degree ramp up to the right.
+    let corners = [
+        Vec2::new(250.0, -10.0),
+        Vec2::new(350.0, -10.0),
+        Vec2::new(350.0, -10.0 + 100.0 * 30_f32.to_radians().tan()),
+    ];
+    let centroid = (corners[0] + corners[1] + corners[2]) / 3.0;
+    let [a, b, c] = corners.map(|corner| corner - centroid);
+    let outline = [a, b, c].map(to_physics);
+    let polygon = Polygon::new(&outline).expect("the ramp has area");
+    commands.spawn((
+        Collider(Shape::Polygon(polygon)),
+        Mesh2d(meshes.add(Triangle2d::new(a, b, c))),
+        MeshMaterial2d(material.clone()),
+        Transform::from_xyz(centroid.x, centroid.y, 0.0),
+    ));
+    obstacles.push(corners.map(to_physics).to_vec());
+
+    let tiles = Grid::from_ascii(PLATFORMS)
+        .with_origin(to_physics(Vec2::new(-140.0, 60.0)))
+        .with_cell_size(20.0);
+    let tile_mesh = meshes.add(Rectangle::new(tiles.cell_size, tiles.cell_size));
+    let half = Vec2::splat(tiles.cell_size / 2.0);
+    for x in 0..tiles.width() as i32 {
+        for y in 0..tiles.height() as i32 {
+            if tiles.is_open((x, y)) {
+                continue;
+            }
+            let center = from_physics(tiles.cell_center((x, y)));
+            commands.spawn((
+                Mesh2d(tile_mesh.clone()),
+                MeshMaterial2d(material.clone()),
+                Transform::from_xyz(center.x, center.y, 0.0),
+            ));
+            let (min, max) = (center - half, center + half);
+            let outline = [min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)];
+            obstacles.push(outline.map(to_physics).to_vec());
+        }
+    }
+    commands.spawn(TileCollider(tiles));
+
+    // A patch of spikes past the ramp, which hurt whoever steps on them.
+    let spikes = Vec2::new(60.0, 6.0);
+    commands.spawn((
+        Hitbox::new(spikes),
+        Damage {
+            amount: 15.0,
+            knockback: 350.0,
+        },
+        Mesh2d(meshes.add(Rectangle::new(spikes.x, spikes.y))),
+        MeshMaterial2d(materials.add(Color::srgb(0.7, 0.1, 0.1))),
+        Transform::from_xyz(420.0, -10.0 + spikes.y / 2.0, 0.0),
+    ));
+
+    let bounds = Aabb::new(
+        to_physics(Vec2::new(-500.0, -10.0)),
+        to_physics(Vec2::new(500.0, 400.0)),
     );
-    let enemy1 = (
+    let mesh = NavMesh::new(bounds, &obstacles, 12.0)
+        .unwrap_or_else(|error| panic!("the level's obstacles make no navigation mesh: {error}"));
+    commands.insert_resource(LevelNavMesh(mesh));
+}
+
+// Spawns whoever assets/level.ron says is in the level. A broken level is left empty.
+fn add_characters(mut commands: Commands) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/level.ron");
+    let spawns = load_spawns(path).unwrap_or_else(|error| {
+        error!("{error}");
+        Vec::new()
+    });
+    for spawn in spawns {
+        commands.spawn_prefab(&spawn.prefab, spawn.overrides);
+    }
+}
+
+// A flock of birds in the sky, chased by a hawk and startled by the drone, and a swarm of wisps
+// that crowd round the player without piling up on each other.
+fn add_swarms(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    players: Query<(Entity, &Player)>,
+    drones: Query<Entity, With<PathFollower>>,
+) {
+    let hawk = commands.spawn_empty().id();
+    let mesh = meshes.add(Circle::new(2.0));
+    let material = materials.add(Color::srgb(0.9, 0.9, 0.9));
+    let middle = (SKY_MIN + SKY_MAX) / 2.0;
+    let mut birds = Vec::with_capacity(FLOCK_SIZE);
+    for i in 0..FLOCK_SIZE {
+        // Spread out on a spiral, heading every which way.
+        let angle = i as f32 * 2.4;
+        let position = middle + Vec2::from_angle(angle) * (i as f32).sqrt() * 3.0;
+        let mut steering = Steering::new(120.0, 300.0)
+            .with_flock(1)
+            .with(SteeringBehaviour::Separation { radius: 10.0 }, 2.0)
+            .with(SteeringBehaviour::Alignment { radius: 25.0 }, 1.0)
+            .with(SteeringBehaviour::Cohesion { radius: 25.0 }, 1.0)
+            .with(
+                SteeringBehaviour::Wander {
+                    radius: 10.0,
+                    distance: 30.0,
+                    jitter: 6.0,
+                },
+                0.5,
+            )
+            .with(SteeringBehaviour::Seek(Target::Point(middle)), 0.1)
+            .with(
+                SteeringBehaviour::Evade {
+                    from: hawk,
+                    distance: 60.0,
+                },
+                3.0,
+            );
+        for drone in &drones {
+            let flee = SteeringBehaviour::Flee {
+                from: Target::Entity(drone),
+                distance: 50.0,
+            };
+            steering = steering.with(flee, 3.0);
+        }
+        let bird = commands.spawn((
+            Object,
+            Bird,
+            steering,
+            Velocity(Vec2::from_angle(angle) * 60.0),
+            Mesh2d(mesh.clone()),
+            MeshMaterial2d(material.clone()),
+            Transform::from_xyz(position.x, position.y, -1.0),
+        ));
+        birds.push(bird.id());
+    }
+
+    // The hawk is a little faster than the birds, but turns more slowly.
+    commands.entity(hawk).insert((
         Object,
-        NonPlayableCharacter,
-        CharacterFaction::RedFaction,
-        Name("Enemy 1".to_string()),
-        Mesh2d(meshes.add(Rectangle::new(20.0, 20.0))),
-        MeshMaterial2d(materials.add(Color::rgb(1.0, 0.0, 0.0))),
-        Transform::from_xyz(0.0, 0.0, 0.0),
-    );
+        Bird,
+        Name("Hawk".to_string()),
+        Steering::new(140.0, 150.0)
+            .with_flock(3)
+            .with(SteeringBehaviour::Pursue(birds[0]), 1.0),
+        Mesh2d(meshes.add(Circle::new(5.0))),
+        MeshMaterial2d(materials.add(Color::srgb(0.5, 0.3, 0.1))),
+        Transform::from_xyz(SKY_MIN.x, SKY_MAX.y, -1.0),
+    ));
+
+    let Some((player, _)) = players.iter().find(|(_, player)| player.0 == 0) else {
+        return;
+    };
+    let mesh = meshes.add(Circle::new(6.0));
+    let material = materials.add(Color::srgb(0.5, 0.9, 1.0));
+    for i in 0..WISPS {
+        let steering = Steering::new(160.0, 400.0)
+            .with_flock(2)
+            .with(
+                SteeringBehaviour::Arrive {
+                    target: Target::Entity(player),
+                    slowing_radius: 80.0,
+                },
+                1.0,
+            )
+            .with(SteeringBehaviour::Separation { radius: 20.0 }, 1.0);
+        commands.spawn((
+            Object,
+            Name(format!("Wisp {}", i + 1)),
+            steering,
+            Avoidance::new(8.0).with_time_horizon(0.5),
+            Mesh2d(mesh.clone()),
+            MeshMaterial2d(material.clone()),
+            Transform::from_xyz(400.0 - i as f32 * 15.0, 120.0 + i as f32 * 10.0, 0.0),
+        ));
+    }
+}
 
-    commands.spawn(player1);
-    commands.spawn(enemy1);
+// Birds that fly off one side of the sky come back on the other.
+fn wrap_birds(mut birds: Query<&mut Transform, With<Bird>>) {
+    let size = SKY_MAX - SKY_MIN;
+    for mut transform in &mut birds {
+        let position = transform.translation.truncate();
+        let wrapped = SKY_MIN + (position - SKY_MIN).rem_euclid(size);
+        if wrapped != position {
+            transform.translation.x = wrapped.x;
+            transform.translation.y = wrapped.y;
+        }
+    }
 }
 
-fn move_player(
-    time: Res<Time>,
-    input: Res<ButtonInput<KeyCode>>, // Get input resource to check for key presses
-    mut query: Query<&mut Transform, With<PlayableCharacter>>,
+// Critters that mate have a young one beside them, as long as there is room.
+fn breed_critters(
+    mut commands: Commands,
+    mut matings: EventReader<MateEvent>,
+    critters: Query<&Transform, With<Creature>>,
+) {
+    let mut count = critters.iter().count();
+    for mating in matings.read() {
+        let Ok(transform) = critters.get(mating.parent) else {
+            continue;
+        };
+        if count >= MAX_CRITTERS {
+            continue;
+        }
+        count += 1;
+        let position = transform.translation.truncate() + Vec2::new(0.0, 30.0);
+        commands.spawn_prefab(
+            "critter",
+            vec![PrefabComponent::Position(position.x, position.y)],
+        );
+    }
+}
+
+// Walks the players left and right, and jumps; the platformer plugin does the rest.
+fn move_player(mut query: Query<(&ActionState, &mut PlatformerInput), With<PlayableCharacter>>) {
+    for (actions, mut platformer) in &mut query {
+        *platformer = PlatformerInput {
+            direction: actions.value(Action::Move),
+            jump_pressed: actions.just_pressed(Action::Jump),
+            jump_held: actions.pressed(Action::Jump),
+        };
+    }
+}
+
+// Rebinds a player's jump to the next thing they press.
+fn start_rebinding(mut commands: Commands, players: Query<(&Player, &ActionState)>) {
+    for (player, actions) in &players {
+        if actions.just_pressed(Action::Rebind) {
+            info!("Player {}: press something to jump with", player.0 + 1);
+            commands.insert_resource(Rebinding {
+                player: player.0,
+                action: Action::Jump,
+            });
+        }
+    }
+}
+
+// Player 1 saves to and loads from the first slot.
+fn quick_save(
+    players: Query<(&Player, &ActionState)>,
+    mut saves: EventWriter<SaveGame>,
+    mut loads: EventWriter<LoadGame>,
+) {
+    let slot = SaveSlot::Manual(1);
+    for (player, actions) in &players {
+        if player.0 != 0 {
+            continue;
+        }
+        if actions.just_pressed(Action::QuickSave) {
+            saves.send(SaveGame { slot });
+        }
+        if actions.just_pressed(Action::QuickLoad) {
+            loads.send(LoadGame { slot });
+        }
+    }
+}
+
+// Attacks every non-playable character within reach.
+fn player_attack(
+    players: Query<(Entity, &Transform, &ActionState), With<PlayableCharacter>>,
+    npcs: Query<(Entity, &Transform), With<NonPlayableCharacter>>,
+    mut attacks: EventWriter<AttackEvent>,
 ) {
-    for mut transform in &mut query {
-        // Move the player based on key presses
-        if input.pressed(KeyCode::KeyW) {
-            transform.translation.y += SPEED * time.delta().as_secs_f32(); // Move up
+    for (attacker, from, actions) in &players {
+        if !actions.just_pressed(Action::Attack) {
+            continue;
         }
-        if input.pressed(KeyCode::KeyS) {
-            transform.translation.y -= SPEED * time.delta().as_secs_f32(); // Move down
+        for (target, to) in &npcs {
+            if from.translation.distance(to.translation) <= REACH {
+                attacks.send(AttackEvent { attacker, target });
+            }
         }
-        if input.pressed(KeyCode::KeyA) {
-            transform.translation.x -= SPEED * time.delta().as_secs_f32(); // Move left
+    }
+}
+
+// Every so often, and whenever it arrives, finds the drone a fresh way through the air to just
+// above player 1.
+fn chase_player_in_the_air(
+    time: Res<Time>,
+    mut since_repath: Local<f32>,
+    nav_mesh: Res<LevelNavMesh>,
+    players: Query<(&Transform, &Player)>,
+    mut drones: Query<(&Transform, &mut PathFollower), Without<Character>>,
+) {
+    *since_repath += time.delta().as_secs_f32();
+    let due = *since_repath >= DRONE_REPATH;
+    if due {
+        *since_repath = 0.0;
+    }
+    let Some((player, _)) = players.iter().find(|(_, player)| player.0 == 0) else {
+        return;
+    };
+    let goal = player.translation.truncate() + Vec2::new(0.0, 40.0);
+    for (transform, mut follower) in &mut drones {
+        // Drones that got where they were going look again straight away.
+        if !due && !follower.is_finished() {
+            continue;
         }
-        if input.pressed(KeyCode::KeyD) {
-            transform.translation.x += SPEED * time.delta().as_secs_f32(); // Move right
+        let start = transform.translation.truncate();
+        if let Some(path) = nav_mesh.0.find_path(to_physics(start), to_physics(goal)) {
+            follower.follow(path.into_iter().skip(1).map(from_physics));
         }
     }
 }
 
-fn keep_above_ground(mut query: Query<&mut Transform, With<Object>>) {
-    for mut transform in &mut query {
-        if transform.translation.y < 0.0 {
-            transform.translation.y = 0.0;
+fn report_combat(
+    mut damaged: EventReader<DamageEvent>,
+    mut deaths: EventReader<DeathEvent>,
+    names: Query<&Name>,
+    healths: Query<&Health>,
+) {
+    let name = |entity| {
+        names
+            .get(entity)
+            .map_or("Something", |name| name.0.as_str())
+    };
+    for damage in damaged.read() {
+        if let Ok(health) = healths.get(damage.target) {
+            info!(
+                "{} hit {} for {:.1} ({:.1}/{:.1} left)",
+                name(damage.attacker),
+                name(damage.target),
+                damage.amount,
+                health.current,
+                health.max
+            );
+        }
+    }
+    for death in deaths.read() {
+        match death.killer {
+            Some(killer) => info!("{} was killed by {}", name(death.entity), name(killer)),
+            None => info!("{} died", name(death.entity)),
         }
     }
 }
 
-// Main and other components setup would go here
+fn report_relations(
+    mut changes: EventReader<RelationChanged>,
+    factions: Factions,
+    players: Query<Entity, With<PlayableCharacter>>,
+    npcs: Query<(Entity, &Name), With<NonPlayableCharacter>>,
+) {
+    for change in changes.read() {
+        info!(
+            "{:?} now treats {:?} as {:?} (was {:?})",
+            change.of, change.towards, change.to, change.from
+        );
+        for player in &players {
+            for (npc, name) in &npcs {
+                info!(
+                    "{} hostile to the player: {}",
+                    name.0,
+                    factions.are_hostile(npc, player)
+                );
+            }
+        }
+    }
+}
 
 // Characters.
 #[derive(Component)]
@@ -84,20 +544,13 @@ struct PlayableCharacter;
 #[derive(Component)]
 struct NonPlayableCharacter;
 
-// Relations.
-#[derive(Component)]
-struct AlliedRelation;
-
 #[derive(Component)]
-enum CharacterFaction {
-    RedFaction,
-    BlueFaction,
-    NeutralFaction,
-}
-
-#[derive(Component)]
-struct NeutralRelation;
+struct Bird;
 
 // Properties.
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize)]
 struct Name(String);
+
+// Where flying things can go.
+#[derive(Resource)]
+struct LevelNavMesh(NavMesh);
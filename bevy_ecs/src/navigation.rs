@@ -0,0 +1,250 @@
+// -----------------------------------------------------------------------------
+// Path following
+// -----------------------------------------------------------------------------
+//
+// Moves entities along paths, such as those found on the grids and navigation meshes of the
+// colliding_bodies crate, by pointing their velocity at each waypoint in turn. Characters only
+// follow a path sideways, as the steering plugin only hands them the horizontal part of their
+// velocity; anything else flies straight from waypoint to waypoint.
+
+use bevy::prelude::*;
+
+use crate::character::Character;
+use crate::steering::{SteeringSystems, Velocity};
+
+const STOP_DISTANCE: f32 = 0.5; // How close counts as reaching the end of a path.
+
+// Needs the steering plugin.
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            follow_paths
+                .in_set(NavigationSystems)
+                .before(SteeringSystems),
+        );
+    }
+}
+
+// Systems that hand out new paths should run before this set.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NavigationSystems;
+
+// Steers an entity along a list of waypoints.
+#[derive(Component, Debug, Clone, PartialEq)]
+#[require(Velocity)]
+pub struct PathFollower {
+    pub speed: f32,           // Pixels per second.
+    pub arrive_distance: f32, // How close counts as passing a waypoint.
+    waypoints: Vec<Vec2>,
+    next: usize,
+}
+
+impl PathFollower {
+    pub fn new(speed: f32) -> Self {
+        Self {
+            speed,
+            arrive_distance: 4.0,
+            waypoints: Vec::new(),
+            next: 0,
+        }
+    }
+
+    // Drops the current path and starts on a new one.
+    pub fn follow(&mut self, waypoints: impl IntoIterator<Item = Vec2>) {
+        self.waypoints = waypoints.into_iter().collect();
+        self.next = 0;
+    }
+
+    pub fn next_waypoint(&self) -> Option<Vec2> {
+        self.waypoints.get(self.next).copied()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.waypoints.len()
+    }
+}
+
+// Points each follower's velocity at its next waypoint, moving on to the one after whenever it
+// gets close enough.
+fn follow_paths(
+    time: Res<Time>,
+    mut followers: Query<(&mut PathFollower, &mut Velocity, &Transform, Has<Character>)>,
+) {
+    let dt = time.delta().as_secs_f32();
+    for (mut follower, mut velocity, transform, walking) in &mut followers {
+        let position = transform.translation.truncate();
+        let mut offset = Vec2::ZERO;
+        while let Some(waypoint) = follower.next_waypoint() {
+            offset = waypoint - position;
+            if walking {
+                offset.y = 0.0;
+            }
+            // Pass close to corners, but stop right on the end of the path.
+            let last = follower.next + 1 == follower.waypoints.len();
+            let reach = if last {
+                follower.arrive_distance.min(STOP_DISTANCE)
+            } else {
+                follower.arrive_distance
+            };
+            if offset.length() > reach {
+                break;
+            }
+            offset = Vec2::ZERO;
+            follower.next += 1;
+        }
+        // Slow down so as to stop on the waypoint rather than overshoot it.
+        let speed = if dt > 0.0 {
+            follower.speed.min(offset.length() / dt)
+        } else {
+            follower.speed
+        };
+        velocity.0 = offset.normalize_or_zero() * speed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use colliding_bodies::{CharacterController, Shape};
+
+    use super::*;
+    use crate::steering::SteeringPlugin;
+    use crate::testing::{self, TENTH_OF_A_SECOND};
+
+    fn app() -> App {
+        testing::app(TENTH_OF_A_SECOND, (SteeringPlugin, NavigationPlugin))
+    }
+
+    fn follower(speed: f32, waypoints: &[Vec2]) -> PathFollower {
+        let mut follower = PathFollower::new(speed);
+        follower.follow(waypoints.iter().copied());
+        follower
+    }
+
+    fn position(app: &App, entity: Entity) -> Vec2 {
+        app.world()
+            .get::<Transform>(entity)
+            .expect("a transform")
+            .translation
+            .truncate()
+    }
+
+    #[test]
+    fn flies_through_every_waypoint_and_stops_on_the_last() {
+        let mut app = app();
+        let corner = Vec2::new(50.0, 0.0);
+        let end = Vec2::new(50.0, 50.0);
+        let flyer = app
+            .world_mut()
+            .spawn((Transform::default(), follower(100.0, &[corner, end])))
+            .id();
+
+        let mut turned_at = None;
+        for _ in 0..30 {
+            let before = position(&app, flyer);
+            app.update();
+            let follower = app.world().get::<PathFollower>(flyer).expect("a follower");
+            if turned_at.is_none() && follower.next_waypoint() == Some(end) {
+                turned_at = Some(before);
+            }
+            assert!(
+                position(&app, flyer).x <= corner.x + 1e-3,
+                "overshot the corner"
+            );
+        }
+
+        // The corner was cut by at most the arrive distance, but the end was reached exactly.
+        let turned_at = turned_at.expect("the follower turned the corner");
+        assert!(turned_at.distance(corner) <= 4.0 + 1e-3, "{turned_at:?}");
+        assert!(position(&app, flyer).distance(end) <= STOP_DISTANCE);
+        let follower = app.world().get::<PathFollower>(flyer).expect("a follower");
+        assert!(follower.is_finished());
+        assert_eq!(
+            app.world().get::<Velocity>(flyer),
+            Some(&Velocity(Vec2::ZERO))
+        );
+    }
+
+    #[test]
+    fn keeps_to_its_speed() {
+        let mut app = app();
+        let flyer = app
+            .world_mut()
+            .spawn((
+                Transform::default(),
+                follower(100.0, &[Vec2::new(300.0, 400.0)]),
+            ))
+            .id();
+        app.update();
+        app.update();
+        let velocity = app.world().get::<Velocity>(flyer).expect("a velocity").0;
+        assert!(
+            (velocity - Vec2::new(60.0, 80.0)).length() < 1e-3,
+            "{velocity:?}"
+        );
+    }
+
+    #[test]
+    fn characters_only_follow_sideways() {
+        let mut app = app();
+        let walker = app
+            .world_mut()
+            .spawn((
+                Transform::default(),
+                Character::new(CharacterController::new(Shape::aabb(20.0, 20.0))),
+                follower(100.0, &[Vec2::new(30.0, 200.0)]),
+            ))
+            .id();
+        app.update();
+        assert_eq!(
+            app.world().get::<Velocity>(walker),
+            Some(&Velocity(Vec2::new(100.0, 0.0)))
+        );
+        let character = app.world().get::<Character>(walker).expect("a character");
+        assert_eq!(character.velocity.x, 100.0);
+
+        // Standing below the waypoint is as close as a character can get.
+        app.world_mut()
+            .get_mut::<Transform>(walker)
+            .expect("a transform")
+            .translation
+            .x = 30.0;
+        app.update();
+        let follower = app.world().get::<PathFollower>(walker).expect("a follower");
+        assert!(follower.is_finished());
+    }
+
+    #[test]
+    fn a_new_path_replaces_the_old_one() {
+        let mut app = app();
+        let flyer = app
+            .world_mut()
+            .spawn((
+                Transform::default(),
+                follower(100.0, &[Vec2::new(100.0, 0.0)]),
+            ))
+            .id();
+        app.update();
+        app.update();
+        let mut follower = app
+            .world_mut()
+            .get_mut::<PathFollower>(flyer)
+            .expect("a follower");
+        follower.follow([Vec2::new(0.0, -100.0)]);
+        app.update();
+        let velocity = app.world().get::<Velocity>(flyer).expect("a velocity").0;
+        assert!(velocity.y < 0.0 && velocity.x < 0.0, "{velocity:?}");
+
+        app.world_mut()
+            .get_mut::<PathFollower>(flyer)
+            .expect("a follower")
+            .follow([]);
+        app.update();
+        assert_eq!(
+            app.world().get::<Velocity>(flyer),
+            Some(&Velocity(Vec2::ZERO))
+        );
+    }
+}
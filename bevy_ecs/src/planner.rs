@@ -0,0 +1,674 @@
+// -----------------------------------------------------------------------------
+// Goal-oriented action planning
+// -----------------------------------------------------------------------------
+//
+// A second kind of brain for non-playable characters. Instead of following a fixed tree, a
+// creature picks the most important goal it can reach and searches, with A*, for the cheapest
+// chain of actions that gets it there, each action needing some facts about the world and
+// changing others. Whenever the world stops looking the way the plan expected, the creature
+// plans again.
+//
+// Plans are carried out one step at a time as `CreatureAction`s: moving, attacking, mating and
+// dying. Attacks go out as `AttackEvent`s and matings as `MateEvent`s for the game to act on, and
+// creatures that die are despawned.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::Arc;
+
+use bevy::prelude::*;
+
+use crate::character::{Character, CharacterSystems};
+use crate::faction::{see_characters, AttackEvent, CharacterFaction, FactionRelations, Seen};
+
+const MAX_EXPANSIONS: usize = 1000; // States the planner looks at before giving up on a goal.
+
+// Needs the character and faction plugins.
+pub struct PlannerPlugin;
+
+impl Plugin for PlannerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<MateEvent>().add_systems(
+            Update,
+            run_planners.in_set(PlannerSystems).before(CharacterSystems),
+        );
+    }
+}
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PlannerSystems;
+
+// Something that is either true or false for a creature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fact {
+    SeesEnemy,   // A hostile character is in sight.
+    NearEnemy,   // A hostile character is in reach.
+    EnemyHurt,   // The creature has hit an enemy.
+    SeesMate,    // A creature of the same faction that is ready to mate is in sight.
+    NearMate,    // Such a creature is in reach.
+    ReadyToMate, // The creature is old enough and has not mated lately.
+    Mated,       // The creature has mated.
+    Old,         // The creature has outlived its lifespan.
+    Dead,        // The creature has died.
+}
+
+impl Fact {
+    fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+// The facts that hold for a creature.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WorldState(u32);
+
+impl WorldState {
+    pub fn with(self, fact: Fact, holds: bool) -> Self {
+        if holds {
+            Self(self.0 | fact.bit())
+        } else {
+            Self(self.0 & !fact.bit())
+        }
+    }
+}
+
+// Some facts, each required to hold or not. Used for what an action needs, what it changes and
+// what a goal wants; facts that are not mentioned do not matter, or are left alone.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Conditions {
+    mentioned: u32,
+    holds: u32,
+}
+
+impl Conditions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(self, fact: Fact, holds: bool) -> Self {
+        Self {
+            mentioned: self.mentioned | fact.bit(),
+            holds: WorldState(self.holds).with(fact, holds).0,
+        }
+    }
+
+    pub fn met_by(self, state: WorldState) -> bool {
+        self.unmet(state) == 0
+    }
+
+    // Makes the conditions hold in `state`.
+    pub fn apply(self, state: WorldState) -> WorldState {
+        WorldState(state.0 & !self.mentioned | self.holds)
+    }
+
+    // How many of the conditions `state` does not meet.
+    fn unmet(self, state: WorldState) -> u32 {
+        ((state.0 ^ self.holds) & self.mentioned).count_ones()
+    }
+}
+
+// Who an approach heads for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Target {
+    Enemy,
+    Mate,
+}
+
+// What an action does, before it is pointed at anyone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Act {
+    Approach(Target),
+    Attack,
+    Mate,
+    Die,
+}
+
+// One step of a plan, pointed at someone.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CreatureAction {
+    Move { x: f32 }, // Walk towards a point along the ground; gravity sees to the height.
+    Attack(Entity),
+    Die,
+    Mate(Entity),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Action {
+    pub name: &'static str,
+    pub act: Act,
+    pub preconditions: Conditions,
+    pub effects: Conditions,
+    pub cost: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Goal {
+    pub name: &'static str,
+    pub when: Conditions,    // The goal only counts while these hold.
+    pub desired: Conditions, // Reached once these hold.
+    pub priority: f32,       // Goals that count are planned for highest first.
+}
+
+// The actions every creature starts with: walking up to enemies and mates, attacking, mating and
+// dying.
+pub fn creature_actions() -> Vec<Action> {
+    use Fact::*;
+    vec![
+        Action {
+            name: "approach enemy",
+            act: Act::Approach(Target::Enemy),
+            preconditions: Conditions::new().with(SeesEnemy, true),
+            effects: Conditions::new().with(NearEnemy, true),
+            cost: 2.0,
+        },
+        Action {
+            name: "attack",
+            act: Act::Attack,
+            preconditions: Conditions::new().with(NearEnemy, true),
+            effects: Conditions::new().with(EnemyHurt, true),
+            cost: 1.0,
+        },
+        Action {
+            name: "approach mate",
+            act: Act::Approach(Target::Mate),
+            preconditions: Conditions::new().with(SeesMate, true),
+            effects: Conditions::new().with(NearMate, true),
+            cost: 2.0,
+        },
+        Action {
+            name: "mate",
+            act: Act::Mate,
+            preconditions: Conditions::new()
+                .with(NearMate, true)
+                .with(ReadyToMate, true),
+            effects: Conditions::new().with(Mated, true),
+            cost: 1.0,
+        },
+        Action {
+            name: "die",
+            act: Act::Die,
+            preconditions: Conditions::new().with(Old, true),
+            effects: Conditions::new().with(Dead, true),
+            cost: 1.0,
+        },
+    ]
+}
+
+// The goals every creature starts with: dying of old age comes first, then fighting enemies it
+// sees, then breeding.
+pub fn creature_goals() -> Vec<Goal> {
+    use Fact::*;
+    vec![
+        Goal {
+            name: "die of old age",
+            when: Conditions::new().with(Old, true),
+            desired: Conditions::new().with(Dead, true),
+            priority: 3.0,
+        },
+        Goal {
+            name: "fight",
+            when: Conditions::new().with(SeesEnemy, true),
+            desired: Conditions::new().with(EnemyHurt, true),
+            priority: 2.0,
+        },
+        Goal {
+            name: "breed",
+            when: Conditions::new().with(ReadyToMate, true),
+            desired: Conditions::new().with(Mated, true),
+            priority: 1.0,
+        },
+    ]
+}
+
+// Finds the cheapest actions, in order, that take `start` to a state meeting `goal`.
+pub fn plan(start: WorldState, goal: Conditions, actions: &[Action]) -> Option<Vec<usize>> {
+    // Every unmet fact takes at least one action, so this never overestimates.
+    let cheapest = actions.iter().map(|a| a.cost).fold(f32::INFINITY, f32::min);
+    let estimate = |state: WorldState| goal.unmet(state) as f32 * cheapest;
+
+    let mut open = BinaryHeap::from([Open {
+        estimate: estimate(start),
+        state: start,
+    }]);
+    let mut costs = HashMap::from([(start, 0.0)]);
+    let mut came_from: HashMap<WorldState, (WorldState, usize)> = HashMap::new();
+    let mut expansions = 0;
+    while let Some(Open { state, .. }) = open.pop() {
+        if goal.met_by(state) {
+            let mut steps = Vec::new();
+            let mut state = state;
+            while let Some(&(previous, action)) = came_from.get(&state) {
+                steps.push(action);
+                state = previous;
+            }
+            steps.reverse();
+            return Some(steps);
+        }
+        expansions += 1;
+        if expansions > MAX_EXPANSIONS {
+            return None;
+        }
+        let cost = costs[&state];
+        for (index, action) in actions.iter().enumerate() {
+            if !action.preconditions.met_by(state) {
+                continue;
+            }
+            let next = action.effects.apply(state);
+            let next_cost = cost + action.cost;
+            if costs.get(&next).is_some_and(|&known| known <= next_cost) {
+                continue;
+            }
+            costs.insert(next, next_cost);
+            came_from.insert(next, (state, index));
+            open.push(Open {
+                estimate: next_cost + estimate(next),
+                state: next,
+            });
+        }
+    }
+    None
+}
+
+// A state waiting to be looked at, cheapest estimate first.
+struct Open {
+    estimate: f32,
+    state: WorldState,
+}
+
+impl PartialEq for Open {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Open {}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+// Sent when a creature mates with another. Both creatures have mated; what comes of it is up to
+// the game.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MateEvent {
+    pub parent: Entity,
+    pub partner: Entity,
+}
+
+// A character that plans what to do.
+#[derive(Component)]
+pub struct Creature {
+    pub actions: Arc<[Action]>,
+    pub goals: Vec<Goal>,
+    pub sight: f32,           // How far away, in pixels, it notices enemies and mates.
+    pub reach: f32,           // How close it has to be to attack or mate.
+    pub speed: f32,           // Walking speed, in pixels per second.
+    pub attack_cooldown: f32, // Seconds between attacks.
+    pub mate_cooldown: f32,   // Seconds between matings, and before the first.
+    pub lifespan: f32,        // Seconds until it is old.
+    age: f32,
+    next_attack: f32,   // Age at which it may attack again.
+    next_mate: f32,     // Age at which it may mate again.
+    memory: WorldState, // Facts its own actions made true, for the current plan.
+    plan: Option<Plan>,
+}
+
+#[derive(Debug, Clone)]
+struct Plan {
+    steps: Vec<usize>,    // Actions to take, in order.
+    next: usize,          // Step being taken.
+    expected: WorldState, // How the world should look while taking it.
+}
+
+impl Creature {
+    pub fn new(actions: Arc<[Action]>, goals: Vec<Goal>) -> Self {
+        let mate_cooldown = 15.0;
+        Self {
+            actions,
+            goals,
+            sight: 200.0,
+            reach: 30.0,
+            speed: 80.0,
+            attack_cooldown: 1.0,
+            mate_cooldown,
+            lifespan: 60.0,
+            age: 0.0,
+            next_attack: 0.0,
+            next_mate: mate_cooldown,
+            memory: WorldState::default(),
+            plan: None,
+        }
+    }
+
+    pub fn with_lifespan(mut self, lifespan: f32) -> Self {
+        self.lifespan = lifespan;
+        self
+    }
+
+    // Plans for the most important goal that counts, is not met yet and can be reached.
+    fn replan(&mut self, state: WorldState) {
+        let mut goals: Vec<&Goal> = self
+            .goals
+            .iter()
+            .filter(|goal| goal.when.met_by(state) && !goal.desired.met_by(state))
+            .collect();
+        goals.sort_by(|a, b| b.priority.total_cmp(&a.priority));
+        self.plan = goals.into_iter().find_map(|goal| {
+            let steps = plan(state, goal.desired, &self.actions)?;
+            debug!(
+                "planned to {}: {:?}",
+                goal.name,
+                steps
+                    .iter()
+                    .map(|&step| self.actions[step].name)
+                    .collect::<Vec<_>>()
+            );
+            Some(Plan {
+                steps,
+                next: 0,
+                expected: state,
+            })
+        });
+        if self.plan.is_none() {
+            self.memory = WorldState::default();
+        }
+    }
+
+    // Marks the current step done, applying its effects.
+    fn complete_step(&mut self) {
+        let Some(plan) = &mut self.plan else {
+            return;
+        };
+        let effects = self.actions[plan.steps[plan.next]].effects;
+        self.memory = effects.apply(self.memory);
+        plan.expected = effects.apply(plan.expected);
+        plan.next += 1;
+        if plan.next == plan.steps.len() {
+            // The goal is reached; what the creature did for it is forgotten.
+            self.plan = None;
+            self.memory = WorldState::default();
+        }
+    }
+}
+
+// Senses the world for every creature, plans when the world is not as expected and carries out
+// the next step of each plan.
+fn run_planners(
+    mut commands: Commands,
+    time: Res<Time>,
+    relations: Res<FactionRelations>,
+    characters: Query<(Entity, &Transform, &CharacterFaction)>,
+    mut creatures: Query<(Entity, &mut Creature, &mut Character, &Transform)>,
+    mut attacks: EventWriter<AttackEvent>,
+    mut matings: EventWriter<MateEvent>,
+) {
+    let dt = time.delta().as_secs_f32();
+    let seen = see_characters(&characters);
+    let ready: Vec<Entity> = creatures
+        .iter()
+        .filter(|(_, creature, ..)| creature.age >= creature.next_mate)
+        .map(|(entity, ..)| entity)
+        .collect();
+
+    let mut mated = Vec::new();
+    for (entity, mut creature, mut character, transform) in &mut creatures {
+        let creature = &mut *creature;
+        creature.age += dt;
+        character.velocity.x = 0.0;
+        let position = transform.translation.truncate();
+        let faction = seen.iter().find(|s| s.entity == entity).map(|s| s.faction);
+
+        // Sense.
+        let nearest = |accept: &dyn Fn(&Seen) -> bool| {
+            seen.iter()
+                .filter(|other| other.entity != entity && accept(other))
+                .map(|other| (other, position.distance(other.position)))
+                .filter(|&(_, distance)| distance <= creature.sight)
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+        };
+        let enemy = nearest(&|other| {
+            faction.is_some_and(|faction| relations.are_hostile(faction, other.faction))
+        });
+        let mate = nearest(&|other| {
+            ready.contains(&other.entity)
+                && Some(other.faction) == faction
+                && !mated.contains(&other.entity)
+        });
+        let ready_to_mate = creature.age >= creature.next_mate && !mated.contains(&entity);
+        let within_reach = |target: Option<(&Seen, f32)>| {
+            target.is_some_and(|(_, distance)| distance <= creature.reach)
+        };
+        let state = creature
+            .memory
+            .with(Fact::SeesEnemy, enemy.is_some())
+            .with(Fact::NearEnemy, within_reach(enemy))
+            .with(Fact::SeesMate, mate.is_some())
+            .with(Fact::NearMate, within_reach(mate))
+            .with(Fact::ReadyToMate, ready_to_mate)
+            .with(Fact::Old, creature.age >= creature.lifespan);
+
+        // Walking steps are done once the world shows their effects.
+        if let Some(plan) = &creature.plan {
+            let step = &creature.actions[plan.steps[plan.next]];
+            if matches!(step.act, Act::Approach(_)) && step.effects.met_by(state) {
+                creature.complete_step();
+            }
+        }
+        // Plan again whenever the world is not as the plan expects.
+        if creature
+            .plan
+            .as_ref()
+            .is_none_or(|plan| plan.expected != state)
+        {
+            creature.replan(state);
+        }
+        let Some(plan) = &creature.plan else {
+            continue;
+        };
+
+        // Point the next step at its target.
+        let step = &creature.actions[plan.steps[plan.next]];
+        let action = match step.act {
+            Act::Approach(Target::Enemy) => enemy.map(|(enemy, _)| CreatureAction::Move {
+                x: enemy.position.x,
+            }),
+            Act::Approach(Target::Mate) => {
+                mate.map(|(mate, _)| CreatureAction::Move { x: mate.position.x })
+            }
+            Act::Attack => enemy.map(|(enemy, _)| CreatureAction::Attack(enemy.entity)),
+            Act::Mate => mate.map(|(mate, _)| CreatureAction::Mate(mate.entity)),
+            Act::Die => Some(CreatureAction::Die),
+        };
+        let Some(action) = action else {
+            creature.plan = None;
+            continue;
+        };
+
+        match action {
+            CreatureAction::Move { x } => {
+                let offset = x - position.x;
+                if offset.abs() > 1.0 {
+                    character.velocity.x = offset.signum() * creature.speed;
+                }
+            }
+            CreatureAction::Attack(target) => {
+                if creature.age >= creature.next_attack {
+                    attacks.send(AttackEvent {
+                        attacker: entity,
+                        target,
+                    });
+                    creature.next_attack = creature.age + creature.attack_cooldown;
+                    creature.complete_step();
+                }
+            }
+            CreatureAction::Mate(partner) => {
+                matings.send(MateEvent {
+                    parent: entity,
+                    partner,
+                });
+                creature.next_mate = creature.age + creature.mate_cooldown;
+                mated.extend([entity, partner]);
+                creature.complete_step();
+            }
+            CreatureAction::Die => {
+                commands.entity(entity).despawn_recursive();
+                creature.complete_step();
+            }
+        }
+    }
+
+    // Partners mate too, and wait as long before mating again.
+    for partner in mated {
+        if let Ok((_, mut creature, ..)) = creatures.get_mut(partner) {
+            if creature.age < creature.next_mate {
+                continue;
+            }
+            creature.next_mate = creature.age + creature.mate_cooldown;
+            creature.plan = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::event::EventCursor;
+    use colliding_bodies::{CharacterController, Shape};
+
+    use super::*;
+    use crate::faction::FactionPlugin;
+    use crate::testing::{self, TENTH_OF_A_SECOND};
+    use Fact::*;
+
+    // Characters do not move, as there is no physics.
+    fn app() -> App {
+        testing::app(TENTH_OF_A_SECOND, (FactionPlugin, PlannerPlugin))
+    }
+
+    fn spawn(app: &mut App, faction: CharacterFaction, x: f32) -> Entity {
+        app.world_mut()
+            .spawn((
+                Character::new(CharacterController::new(Shape::aabb(20.0, 20.0))),
+                Transform::from_xyz(x, 0.0, 0.0),
+                faction,
+            ))
+            .id()
+    }
+
+    fn names(steps: &[usize], actions: &[Action]) -> Vec<&'static str> {
+        steps.iter().map(|&step| actions[step].name).collect()
+    }
+
+    #[test]
+    fn plans_take_the_cheapest_way() {
+        let mut actions = creature_actions();
+        actions.push(Action {
+            name: "teleport",
+            act: Act::Approach(Target::Enemy),
+            preconditions: Conditions::new(),
+            effects: Conditions::new().with(NearEnemy, true),
+            cost: 5.0,
+        });
+        let goal = Conditions::new().with(EnemyHurt, true);
+
+        let sees_enemy = WorldState::default().with(SeesEnemy, true);
+        let steps = plan(sees_enemy, goal, &actions).unwrap();
+        assert_eq!(names(&steps, &actions), ["approach enemy", "attack"]);
+        // Teleporting costs more, but is the only way to an enemy out of sight.
+        let steps = plan(WorldState::default(), goal, &actions).unwrap();
+        assert_eq!(names(&steps, &actions), ["teleport", "attack"]);
+        // A goal already met needs nothing done.
+        let hurt = sees_enemy.with(EnemyHurt, true);
+        assert_eq!(plan(hurt, goal, &actions), Some(Vec::new()));
+    }
+
+    #[test]
+    fn unreachable_goals_have_no_plan() {
+        let actions = creature_actions();
+        let mated = Conditions::new().with(Mated, true);
+        assert_eq!(plan(WorldState::default(), mated, &actions), None);
+        let sees_mate = WorldState::default().with(SeesMate, true);
+        assert_eq!(plan(sees_mate, mated, &actions), None, "not ready to mate");
+        // Nothing makes a creature old.
+        let dead = Conditions::new().with(Dead, true);
+        assert_eq!(plan(WorldState::default(), dead, &actions), None);
+    }
+
+    #[test]
+    fn creatures_replan_when_the_world_changes() {
+        let mut app = app();
+        let creature = spawn(&mut app, CharacterFaction::Red, 0.0);
+        let actions: Arc<[Action]> = creature_actions().into();
+        let brain = Creature::new(actions.clone(), creature_goals()).with_lifespan(0.45);
+        let hat = app.world_mut().spawn_empty().id();
+        app.world_mut()
+            .entity_mut(creature)
+            .insert(brain)
+            .add_child(hat);
+        spawn(&mut app, CharacterFaction::Blue, 100.0);
+
+        // Off to fight the enemy in sight.
+        app.update();
+        let world = app.world();
+        let plan = world
+            .get::<Creature>(creature)
+            .unwrap()
+            .plan
+            .clone()
+            .unwrap();
+        assert_eq!(names(&plan.steps, &actions), ["approach enemy", "attack"]);
+        assert_eq!(plan.next, 0);
+        assert!(world.get::<Character>(creature).unwrap().velocity.x > 0.0);
+
+        // Growing old halfway there, the creature gives up the fight to die.
+        for _ in 0..10 {
+            app.update();
+        }
+        assert!(app.world().get_entity(creature).is_err());
+        assert!(
+            app.world().get_entity(hat).is_err(),
+            "the hat outlived its creature"
+        );
+        assert!(app.world().resource::<Events<AttackEvent>>().is_empty());
+    }
+
+    #[test]
+    fn partners_both_wait_to_mate_again() {
+        let mut app = app();
+        let mut pair = Vec::new();
+        for x in [0.0, 10.0] {
+            let creature = spawn(&mut app, CharacterFaction::Red, x);
+            let mut brain = Creature::new(creature_actions().into(), creature_goals());
+            brain.mate_cooldown = 0.5;
+            brain.next_mate = 0.0;
+            app.world_mut().entity_mut(creature).insert(brain);
+            pair.push(creature);
+        }
+
+        // Side by side and ready, one mates with the other straight away.
+        app.update();
+        let mut cursor = EventCursor::<MateEvent>::default();
+        let events = app.world().resource::<Events<MateEvent>>();
+        let matings: Vec<MateEvent> = cursor.read(events).copied().collect();
+        assert_eq!(matings.len(), 1, "{matings:?}");
+        let mut mated = [matings[0].parent, matings[0].partner];
+        mated.sort();
+        assert_eq!(mated[..], pair[..]);
+
+        // Neither is ready again until the cooldown is over.
+        for creature in pair {
+            let creature = app.world().get::<Creature>(creature).unwrap();
+            assert_eq!(creature.next_mate, creature.age + 0.5);
+        }
+        app.update();
+        let events = app.world().resource::<Events<MateEvent>>();
+        assert_eq!(cursor.read(events).count(), 0);
+    }
+}
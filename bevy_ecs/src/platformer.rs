@@ -0,0 +1,327 @@
+// -----------------------------------------------------------------------------
+// Platformer movement
+// -----------------------------------------------------------------------------
+//
+// Turns what a player asks for into how a platformer character moves: speeding up and slowing
+// down instead of starting and stopping dead, jumps that go higher the longer the button is held,
+// and the usual forgiveness, coyote time to jump just after running off a ledge, and a jump
+// buffer to jump on landing when the button was pressed just before.
+//
+// Game systems fill in each character's `PlatformerInput`; `PlatformerSettings` tunes the feel.
+// Gravity and ground detection come from the character plugin.
+
+use bevy::prelude::*;
+
+use crate::character::{Character, CharacterSettings, CharacterState, CharacterSystems};
+
+// Needs the character plugin.
+pub struct PlatformerPlugin;
+
+impl Plugin for PlatformerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlatformerSettings>().add_systems(
+            Update,
+            move_platformers
+                .in_set(PlatformerSystems)
+                .before(CharacterSystems),
+        );
+    }
+}
+
+// Systems that fill in platformer input should run before this set.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PlatformerSystems;
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct PlatformerSettings {
+    pub max_speed: f32,         // Pixels per second.
+    pub acceleration: f32,      // Pixels per second squared, speeding up on the ground.
+    pub deceleration: f32,      // Slowing down on the ground with nothing held.
+    pub turn_deceleration: f32, // Slowing down on the ground while heading the other way.
+    pub air_control: f32,       // Fraction of the above that applies in the air.
+    pub jump_speed: f32,        // Upward speed a jump starts with.
+    pub jump_cut: f32,          // Fraction of upward speed kept on letting go of jump early.
+    pub fall_gravity: f32,      // Gravity multiplier while falling, for snappier jumps.
+    pub max_fall_speed: f32,    // Pixels per second.
+    pub coyote_time: f32,       // Seconds after leaving the ground a jump is still allowed.
+    pub jump_buffer: f32,       // Seconds a jump pressed in the air waits for the ground.
+}
+
+impl Default for PlatformerSettings {
+    fn default() -> Self {
+        Self {
+            max_speed: 200.0,
+            acceleration: 1800.0,
+            deceleration: 2200.0,
+            turn_deceleration: 3500.0,
+            air_control: 0.6,
+            jump_speed: 450.0,
+            jump_cut: 0.45,
+            fall_gravity: 1.6,
+            max_fall_speed: 700.0,
+            coyote_time: 0.1,
+            jump_buffer: 0.12,
+        }
+    }
+}
+
+// What the player asks a platformer character to do this frame.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+pub struct PlatformerInput {
+    pub direction: f32,     // From -1 (left) to 1 (right).
+    pub jump_pressed: bool, // The jump button went down this frame.
+    pub jump_held: bool,
+}
+
+// A character moved like a platformer hero.
+#[derive(Component, Debug, Clone, PartialEq)]
+#[require(PlatformerInput)]
+pub struct Platformer {
+    since_grounded: f32,     // Seconds since the character last stood on something.
+    since_jump_pressed: f32, // Seconds since jump was last pressed.
+    rising: bool,            // In a jump that letting go of the button can still cut short.
+    jumped: bool,            // Has jumped since last on the ground, so coyote time is spent.
+}
+
+impl Default for Platformer {
+    fn default() -> Self {
+        Self {
+            since_grounded: f32::INFINITY,
+            since_jump_pressed: f32::INFINITY,
+            rising: false,
+            jumped: false,
+        }
+    }
+}
+
+// Moves `value` towards `target` by at most `step`.
+fn approach(value: f32, target: f32, step: f32) -> f32 {
+    value + (target - value).clamp(-step, step)
+}
+
+fn move_platformers(
+    time: Res<Time>,
+    settings: Res<PlatformerSettings>,
+    character_settings: Res<CharacterSettings>,
+    mut platformers: Query<(
+        &mut Platformer,
+        &PlatformerInput,
+        &mut Character,
+        &CharacterState,
+    )>,
+) {
+    let dt = time.delta().as_secs_f32();
+    for (mut platformer, input, mut character, state) in &mut platformers {
+        // Sideways: accelerate towards the speed asked for, braking harder when turning round.
+        let target = input.direction.clamp(-1.0, 1.0) * settings.max_speed;
+        let speed = character.velocity.x;
+        let mut rate = if target == 0.0 {
+            settings.deceleration
+        } else if speed != 0.0 && speed.signum() != target.signum() {
+            settings.turn_deceleration
+        } else if speed.abs() > target.abs() {
+            settings.deceleration
+        } else {
+            settings.acceleration
+        };
+        if !state.grounded {
+            rate *= settings.air_control;
+        }
+        character.velocity.x = approach(speed, target, rate * dt);
+
+        // Jumping.
+        if state.grounded {
+            platformer.since_grounded = 0.0;
+            platformer.jumped = false;
+        } else {
+            platformer.since_grounded += dt;
+        }
+        if input.jump_pressed {
+            platformer.since_jump_pressed = 0.0;
+        } else {
+            platformer.since_jump_pressed += dt;
+        }
+        let can_jump = !platformer.jumped && platformer.since_grounded <= settings.coyote_time;
+        if can_jump && platformer.since_jump_pressed <= settings.jump_buffer {
+            character.velocity.y = settings.jump_speed;
+            platformer.jumped = true;
+            platformer.rising = true;
+            platformer.since_jump_pressed = f32::INFINITY;
+        }
+
+        // Letting go early cuts the jump short; once past the top, fall faster than it rose.
+        if platformer.rising && (character.velocity.y <= 0.0 || !input.jump_held) {
+            if character.velocity.y > 0.0 {
+                character.velocity.y *= settings.jump_cut;
+            }
+            platformer.rising = false;
+        }
+        if character.velocity.y < 0.0 {
+            let extra = character_settings.gravity.y * (settings.fall_gravity - 1.0) * dt;
+            character.velocity.y = (character.velocity.y + extra).max(-settings.max_fall_speed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use colliding_bodies::Shape;
+
+    use super::*;
+    use crate::character::{CharacterPlugin, Collider};
+    use crate::character_controller;
+    use crate::testing;
+
+    const FLOOR_END: f32 = 100.0; // The floor runs from -100 to 100, with its top at 0.
+    const STANDING: f32 = 10.5; // Where a character standing on the floor is.
+
+    // Moves everything a hundred times a second.
+    fn app() -> App {
+        let mut app = testing::app(
+            Duration::from_millis(10),
+            (CharacterPlugin, PlatformerPlugin),
+        );
+        app.world_mut().spawn((
+            Collider(Shape::aabb(2.0 * FLOOR_END, 20.0)),
+            Transform::from_xyz(0.0, -10.0, 0.0),
+        ));
+        app
+    }
+
+    fn spawn(app: &mut App, x: f32, y: f32) -> Entity {
+        app.world_mut()
+            .spawn((
+                Character::new(character_controller()),
+                Platformer::default(),
+                Transform::from_xyz(x, y, 0.0),
+            ))
+            .id()
+    }
+
+    // Runs one frame with the given input.
+    fn frame(app: &mut App, hero: Entity, input: PlatformerInput) {
+        *app.world_mut().get_mut::<PlatformerInput>(hero).unwrap() = input;
+        app.update();
+    }
+
+    fn height(app: &App, hero: Entity) -> f32 {
+        app.world().get::<Transform>(hero).unwrap().translation.y
+    }
+
+    fn grounded(app: &App, hero: Entity) -> bool {
+        app.world().get::<CharacterState>(hero).unwrap().grounded
+    }
+
+    fn rising(app: &App, hero: Entity) -> bool {
+        app.world().get::<Character>(hero).unwrap().velocity.y > 0.0
+    }
+
+    fn jump(held: bool) -> PlatformerInput {
+        PlatformerInput {
+            jump_pressed: true,
+            jump_held: held,
+            ..default()
+        }
+    }
+
+    fn hold() -> PlatformerInput {
+        PlatformerInput {
+            jump_held: true,
+            ..default()
+        }
+    }
+
+    // Runs right off the end of the floor, then waits `frames` frames before pressing jump.
+    // Returns whether the jump happened.
+    fn jump_after_leaving_the_ledge(frames: usize) -> bool {
+        let mut app = app();
+        let hero = spawn(&mut app, FLOOR_END - 20.0, STANDING);
+        let run = PlatformerInput {
+            direction: 1.0,
+            ..default()
+        };
+        for _ in 0..100 {
+            frame(&mut app, hero, run);
+            if app.world().get::<Transform>(hero).unwrap().translation.x > FLOOR_END
+                && !grounded(&app, hero)
+            {
+                break;
+            }
+        }
+        assert!(!grounded(&app, hero), "ran off the ledge");
+        for _ in 0..frames {
+            frame(&mut app, hero, run);
+        }
+        assert!(!rising(&app, hero));
+        frame(&mut app, hero, jump(true));
+        rising(&app, hero)
+    }
+
+    #[test]
+    fn coyote_time_allows_a_late_jump() {
+        assert!(jump_after_leaving_the_ledge(5));
+        assert!(!jump_after_leaving_the_ledge(15));
+    }
+
+    // Drops from high up and presses jump once the character is `above` pixels off the floor.
+    // Returns whether it jumped on landing, or soon after.
+    fn jump_before_landing(above: f32) -> bool {
+        let mut app = app();
+        let hero = spawn(&mut app, 0.0, 200.0);
+        while height(&app, hero) > STANDING + above {
+            frame(&mut app, hero, PlatformerInput::default());
+        }
+        assert!(!grounded(&app, hero));
+        frame(&mut app, hero, jump(true));
+        while !grounded(&app, hero) {
+            frame(&mut app, hero, hold());
+        }
+        for _ in 0..10 {
+            frame(&mut app, hero, hold());
+        }
+        height(&app, hero) > STANDING + 20.0
+    }
+
+    #[test]
+    fn jump_buffer_allows_an_early_jump() {
+        assert!(jump_before_landing(10.0));
+        assert!(!jump_before_landing(150.0));
+    }
+
+    // How high a jump goes when jump is let go of after `held` frames.
+    fn peak(held: usize) -> f32 {
+        let mut app = app();
+        let hero = spawn(&mut app, 0.0, STANDING);
+        for _ in 0..10 {
+            frame(&mut app, hero, PlatformerInput::default());
+        }
+        assert!(grounded(&app, hero));
+        frame(&mut app, hero, jump(true));
+        let mut peak = height(&app, hero);
+        for i in 0..150 {
+            let input = if i < held {
+                hold()
+            } else {
+                PlatformerInput::default()
+            };
+            frame(&mut app, hero, input);
+            peak = peak.max(height(&app, hero));
+        }
+        assert!(grounded(&app, hero), "landed again");
+        peak
+    }
+
+    #[test]
+    fn letting_go_early_cuts_the_jump_short() {
+        let full = peak(usize::MAX);
+        let short = peak(5);
+        assert!(full > STANDING + 80.0, "{full}");
+        assert!(
+            short < STANDING + (full - STANDING) * 0.5,
+            "{short} vs {full}"
+        );
+        assert!(short > STANDING, "{short}");
+    }
+}
@@ -0,0 +1,834 @@
+// -----------------------------------------------------------------------------
+// Prefabs
+// -----------------------------------------------------------------------------
+//
+// Entities described in RON files (see assets/prefabs) rather than built in code, so new kinds of
+// enemies and critters need no changes to the game. A prefab lists the components its entity gets
+// and the child entities it has, and may extend another prefab, overriding some of its
+// components. Overriding replaces the component of the same kind, or adds it if there was none.
+//
+// Spawn prefabs with `Commands::spawn_prefab`, which takes overrides of its own, typically a
+// position. The prefab files are watched while the game runs: when one changes, every entity
+// spawned from it, or from a prefab extending it, gets the components that changed. Components that
+// did not change are left alone, so a character keeps its place and health when only its colour
+// is edited. Saving `PrefabInstance` lets a save bring back a prefab's entity after it is gone.
+
+use std::collections::HashMap;
+use std::mem::discriminant;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::behaviour::{Behaviour, BehaviourTree};
+use crate::character::Character;
+use crate::combat::{Armor, Damage, Health, Hitbox, Hurtbox};
+use crate::controls::Player;
+use crate::faction::CharacterFaction;
+use crate::navigation::PathFollower;
+use crate::planner::{creature_actions, creature_goals, Creature};
+use crate::platformer::Platformer;
+use crate::save::{Respawn, Saved};
+use crate::{character_controller, Name, NonPlayableCharacter, Object, PlayableCharacter};
+
+const RELOAD_INTERVAL: f32 = 0.5; // Seconds between looking for edited prefab files.
+const MAX_DEPTH: usize = 16; // How long a chain of prefabs extending each other can be.
+
+pub struct PrefabPlugin;
+
+impl Plugin for PrefabPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Prefabs>()
+            .add_systems(Update, reload_prefabs.in_set(PrefabSystems));
+    }
+}
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PrefabSystems;
+
+// A component of a prefab, as written in RON. Distances are in pixels, speeds in pixels per
+// second and times in seconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PrefabComponent {
+    Name(String),
+    Saved(String),      // The key the entity is saved under.
+    Position(f32, f32), // From the parent, for children.
+    Faction(CharacterFaction),
+    Player(usize), // Controlled by player 1 (0) onwards.
+    NonPlayable,
+    Character, // Moved by a 20 by 20 character controller.
+    Platformer,
+    Behaviour(String), // The behaviour tree in assets/behaviours.
+    Creature {
+        lifespan: f32,
+    },
+    PathFollower {
+        speed: f32,
+    },
+    Health {
+        max: f32,
+        #[serde(default)]
+        invulnerability: Option<f32>, // Seconds; half a second when `None`.
+    },
+    Armor {
+        flat: f32,
+        resistance: f32,
+    },
+    Damage {
+        amount: f32,
+        knockback: f32,
+    },
+    Hurtbox {
+        width: f32,
+        height: f32,
+    },
+    Hitbox {
+        width: f32,
+        height: f32,
+    },
+
+    // Looks.
+    Rectangle {
+        width: f32,
+        height: f32,
+    },
+    Circle(f32),
+    Color(f32, f32, f32), // Red, green and blue, from 0 to 1.
+}
+
+impl PrefabComponent {
+    fn insert(&self, world: &mut World, entity: Entity, trees: &mut BehaviourTrees) {
+        let mut entity = world.entity_mut(entity);
+        match self {
+            PrefabComponent::Name(name) => {
+                entity.insert(Name(name.clone()));
+            }
+            PrefabComponent::Saved(key) => {
+                entity.insert(Saved(key.clone()));
+            }
+            PrefabComponent::Position(x, y) => {
+                entity.insert(Transform::from_xyz(*x, *y, 0.0));
+            }
+            PrefabComponent::Faction(faction) => {
+                entity.insert(*faction);
+            }
+            PrefabComponent::Player(index) => {
+                entity.insert((PlayableCharacter, Player(*index)));
+            }
+            PrefabComponent::NonPlayable => {
+                entity.insert(NonPlayableCharacter);
+            }
+            PrefabComponent::Character => {
+                entity.insert(Character::new(character_controller()));
+            }
+            PrefabComponent::Platformer => {
+                entity.insert(Platformer::default());
+            }
+            PrefabComponent::Behaviour(name) => match trees.get(name) {
+                Ok(tree) => {
+                    entity.insert(Behaviour::new(tree));
+                }
+                Err(error) => warn!("{error}"),
+            },
+            PrefabComponent::Creature { lifespan } => {
+                let creature = Creature::new(creature_actions().into(), creature_goals());
+                entity.insert(creature.with_lifespan(*lifespan));
+            }
+            PrefabComponent::PathFollower { speed } => {
+                entity.insert(PathFollower::new(*speed));
+            }
+            PrefabComponent::Health {
+                max,
+                invulnerability,
+            } => {
+                let health = Health::new(*max);
+                entity.insert(match invulnerability {
+                    Some(seconds) => health.with_invulnerability(*seconds),
+                    None => health,
+                });
+            }
+            PrefabComponent::Armor { flat, resistance } => {
+                entity.insert(Armor {
+                    flat: *flat,
+                    resistance: *resistance,
+                });
+            }
+            PrefabComponent::Damage { amount, knockback } => {
+                entity.insert(Damage {
+                    amount: *amount,
+                    knockback: *knockback,
+                });
+            }
+            PrefabComponent::Hurtbox { width, height } => {
+                entity.insert(Hurtbox::new(Vec2::new(*width, *height)));
+            }
+            PrefabComponent::Hitbox { width, height } => {
+                entity.insert(Hitbox::new(Vec2::new(*width, *height)));
+            }
+            PrefabComponent::Rectangle { width, height } => {
+                let mesh = entity.world_scope(|world| {
+                    world
+                        .resource_mut::<Assets<Mesh>>()
+                        .add(Rectangle::new(*width, *height))
+                });
+                entity.insert(Mesh2d(mesh));
+            }
+            PrefabComponent::Circle(radius) => {
+                let mesh = entity.world_scope(|world| {
+                    world
+                        .resource_mut::<Assets<Mesh>>()
+                        .add(Circle::new(*radius))
+                });
+                entity.insert(Mesh2d(mesh));
+            }
+            PrefabComponent::Color(red, green, blue) => {
+                let material = entity.world_scope(|world| {
+                    world
+                        .resource_mut::<Assets<ColorMaterial>>()
+                        .add(Color::srgb(*red, *green, *blue))
+                });
+                entity.insert(MeshMaterial2d(material));
+            }
+        }
+    }
+
+    // Takes off what `insert` put on. Entities keep their place when their position goes.
+    fn remove(&self, world: &mut World, entity: Entity) {
+        let mut entity = world.entity_mut(entity);
+        match self {
+            PrefabComponent::Name(_) => {
+                entity.remove::<Name>();
+            }
+            PrefabComponent::Saved(_) => {
+                entity.remove::<Saved>();
+            }
+            PrefabComponent::Position(..) => {}
+            PrefabComponent::Faction(_) => {
+                entity.remove::<CharacterFaction>();
+            }
+            PrefabComponent::Player(_) => {
+                entity.remove::<(PlayableCharacter, Player)>();
+            }
+            PrefabComponent::NonPlayable => {
+                entity.remove::<NonPlayableCharacter>();
+            }
+            PrefabComponent::Character => {
+                entity.remove::<Character>();
+            }
+            PrefabComponent::Platformer => {
+                entity.remove::<Platformer>();
+            }
+            PrefabComponent::Behaviour(_) => {
+                entity.remove::<Behaviour>();
+            }
+            PrefabComponent::Creature { .. } => {
+                entity.remove::<Creature>();
+            }
+            PrefabComponent::PathFollower { .. } => {
+                entity.remove::<PathFollower>();
+            }
+            PrefabComponent::Health { .. } => {
+                entity.remove::<Health>();
+            }
+            PrefabComponent::Armor { .. } => {
+                entity.remove::<Armor>();
+            }
+            PrefabComponent::Damage { .. } => {
+                entity.remove::<Damage>();
+            }
+            PrefabComponent::Hurtbox { .. } => {
+                entity.remove::<Hurtbox>();
+            }
+            PrefabComponent::Hitbox { .. } => {
+                entity.remove::<Hitbox>();
+            }
+            PrefabComponent::Rectangle { .. } | PrefabComponent::Circle(_) => {
+                entity.remove::<Mesh2d>();
+            }
+            PrefabComponent::Color(..) => {
+                entity.remove::<MeshMaterial2d<ColorMaterial>>();
+            }
+        }
+    }
+}
+
+// An entity as written in RON: its components and children, on top of those of the prefab it
+// extends, if any.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Prefab {
+    #[serde(default)]
+    pub extends: Option<String>,
+    #[serde(default)]
+    pub components: Vec<PrefabComponent>,
+    #[serde(default)]
+    pub children: Vec<Prefab>,
+}
+
+impl Prefab {
+    pub fn from_ron(text: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(text)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PrefabError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| PrefabError::Io(path.to_path_buf(), error))?;
+        Self::from_ron(&text).map_err(|error| PrefabError::Parse(path.to_path_buf(), error))
+    }
+
+    // Replaces components of the same kind and adds the rest.
+    fn override_with(&mut self, overrides: &[PrefabComponent]) {
+        for component in overrides {
+            let kind = discriminant(component);
+            match self
+                .components
+                .iter_mut()
+                .find(|existing| discriminant(*existing) == kind)
+            {
+                Some(existing) => *existing = component.clone(),
+                None => self.components.push(component.clone()),
+            }
+        }
+    }
+}
+
+// A prefab to spawn and what to override, as written in a level file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrefabSpawn {
+    pub prefab: String,
+    #[serde(default)]
+    pub overrides: Vec<PrefabComponent>,
+}
+
+// Reads a RON list of prefabs to spawn.
+pub fn load_spawns(path: impl AsRef<Path>) -> Result<Vec<PrefabSpawn>, PrefabError> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path)
+        .map_err(|error| PrefabError::Io(path.to_path_buf(), error))?;
+    ron::from_str(&text).map_err(|error| PrefabError::Parse(path.to_path_buf(), error))
+}
+
+#[derive(Debug)]
+pub enum PrefabError {
+    Io(PathBuf, std::io::Error),              // The file could not be read.
+    Parse(PathBuf, ron::error::SpannedError), // The file is not a valid prefab.
+    Unknown(String),                          // There is no prefab by that name.
+    TooDeep(String),                          // Extends itself, or nearly so.
+}
+
+impl std::fmt::Display for PrefabError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PrefabError::Io(path, error) => {
+                write!(f, "failed to read {}: {error}", path.display())
+            }
+            PrefabError::Parse(path, error) => {
+                write!(f, "failed to parse {}: {error}", path.display())
+            }
+            PrefabError::Unknown(name) => write!(f, "there is no prefab called {name}"),
+            PrefabError::TooDeep(name) => {
+                write!(f, "prefab {name} extends too many prefabs, maybe itself")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PrefabError {}
+
+// Behaviour trees by name, each loaded the first time a prefab needs it.
+#[derive(Default)]
+struct BehaviourTrees {
+    directory: PathBuf,
+    trees: HashMap<String, Arc<BehaviourTree>>,
+}
+
+impl BehaviourTrees {
+    fn get(&mut self, name: &str) -> Result<Arc<BehaviourTree>, crate::behaviour::BehaviourError> {
+        if let Some(tree) = self.trees.get(name) {
+            return Ok(tree.clone());
+        }
+        let path = self.directory.join(name).with_extension("ron");
+        let tree = Arc::new(BehaviourTree::load(path)?);
+        self.trees.insert(name.to_string(), tree.clone());
+        Ok(tree)
+    }
+}
+
+// Every prefab in assets/prefabs, by file name.
+#[derive(Resource, Default)]
+pub struct Prefabs {
+    directory: PathBuf,
+    prefabs: HashMap<String, Prefab>,
+    modified: HashMap<String, SystemTime>, // When each file was last read.
+    trees: BehaviourTrees,
+}
+
+impl Prefabs {
+    // Reads the prefabs in <assets>/prefabs; their behaviour trees come from <assets>/behaviours.
+    // Files that fail to load are skipped with a warning, as when reloading, and read again once
+    // they change. Fails only if the directory cannot be read.
+    pub fn load(assets: impl AsRef<Path>) -> Result<Self, PrefabError> {
+        let assets = assets.as_ref();
+        let mut prefabs = Self {
+            directory: assets.join("prefabs"),
+            trees: BehaviourTrees {
+                directory: assets.join("behaviours"),
+                ..default()
+            },
+            ..default()
+        };
+        for (name, path, modified) in prefabs.files()? {
+            prefabs.modified.insert(name.clone(), modified);
+            match Prefab::load(&path) {
+                Ok(prefab) => {
+                    prefabs.prefabs.insert(name, prefab);
+                }
+                Err(error) => warn!("{error}"),
+            }
+        }
+        Ok(prefabs)
+    }
+
+    // Every prefab file, with its name and when it was last modified.
+    fn files(&self) -> Result<Vec<(String, PathBuf, SystemTime)>, PrefabError> {
+        let io = |error| PrefabError::Io(self.directory.clone(), error);
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(&self.directory).map_err(io)? {
+            let path = entry.map_err(io)?.path();
+            if path.extension().is_none_or(|extension| extension != "ron") {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let modified = std::fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .map_err(|error| PrefabError::Io(path.clone(), error))?;
+            files.push((name.to_string(), path, modified));
+        }
+        Ok(files)
+    }
+
+    // Reads the prefab files that are new or have changed since last read, keeping the old
+    // version of any that fail to load. Returns whether anything changed.
+    fn reload(&mut self) -> bool {
+        let files = match self.files() {
+            Ok(files) => files,
+            Err(error) => {
+                warn!("{error}");
+                return false;
+            }
+        };
+        let mut changed = false;
+        for (name, path, modified) in files {
+            if self.modified.get(&name) == Some(&modified) {
+                continue;
+            }
+            self.modified.insert(name.clone(), modified);
+            match Prefab::load(&path) {
+                Ok(prefab) => {
+                    info!("Reloaded prefab {name}");
+                    self.prefabs.insert(name, prefab);
+                    changed = true;
+                }
+                Err(error) => warn!("{error}"),
+            }
+        }
+        changed
+    }
+
+    // The prefab with everything it extends filled in, children included.
+    fn resolve(&self, name: &str) -> Result<Prefab, PrefabError> {
+        let prefab = self
+            .prefabs
+            .get(name)
+            .ok_or_else(|| PrefabError::Unknown(name.to_string()))?;
+        self.flatten(prefab, name, 0)
+    }
+
+    fn flatten(&self, prefab: &Prefab, name: &str, depth: usize) -> Result<Prefab, PrefabError> {
+        if depth > MAX_DEPTH {
+            return Err(PrefabError::TooDeep(name.to_string()));
+        }
+        let mut flat = match &prefab.extends {
+            Some(base) => {
+                let base_prefab = self
+                    .prefabs
+                    .get(base)
+                    .ok_or_else(|| PrefabError::Unknown(base.clone()))?;
+                self.flatten(base_prefab, name, depth + 1)?
+            }
+            None => Prefab::default(),
+        };
+        flat.override_with(&prefab.components);
+        for child in &prefab.children {
+            flat.children.push(self.flatten(child, name, depth + 1)?);
+        }
+        Ok(flat)
+    }
+}
+
+// An entity spawned from a prefab, and what it was given, to update it when the prefab changes.
+// Only the prefab and overrides are saved, which is enough to spawn the entity again.
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrefabInstance {
+    pub prefab: String,
+    overrides: Vec<PrefabComponent>,
+    #[serde(skip)]
+    applied: Prefab,
+}
+
+impl Respawn for PrefabInstance {
+    fn respawn(self, world: &mut World, entity: Entity) {
+        build_prefab(world, entity, self.prefab, self.overrides);
+    }
+}
+
+// A child entity a prefab spawned, replaced whenever the prefab's children change.
+#[derive(Component)]
+struct PrefabChild;
+
+pub trait SpawnPrefab {
+    // Spawns the named prefab with `overrides` replacing its components of the same kind. The
+    // entity is filled in when commands are applied; unknown prefabs are despawned with a warning.
+    fn spawn_prefab(&mut self, name: &str, overrides: Vec<PrefabComponent>) -> Entity;
+}
+
+impl SpawnPrefab for Commands<'_, '_> {
+    fn spawn_prefab(&mut self, name: &str, overrides: Vec<PrefabComponent>) -> Entity {
+        let entity = self.spawn_empty().id();
+        let name = name.to_string();
+        self.queue(move |world: &mut World| build_prefab(world, entity, name, overrides));
+        entity
+    }
+}
+
+// Makes an empty entity into the named prefab, or despawns it with a warning if it is unknown.
+fn build_prefab(world: &mut World, entity: Entity, name: String, overrides: Vec<PrefabComponent>) {
+    world.resource_scope(|world, mut prefabs: Mut<Prefabs>| {
+        let prefab = prefabs.resolve(&name).map(|mut prefab| {
+            prefab.override_with(&overrides);
+            prefab
+        });
+        match prefab {
+            Ok(prefab) => {
+                apply(
+                    world,
+                    entity,
+                    &Prefab::default(),
+                    &prefab,
+                    &mut prefabs.trees,
+                );
+                world.entity_mut(entity).insert((
+                    Object,
+                    PrefabInstance {
+                        prefab: name,
+                        overrides,
+                        applied: prefab,
+                    },
+                ));
+            }
+            Err(error) => {
+                warn!("{error}");
+                world.entity_mut(entity).despawn();
+            }
+        }
+    });
+}
+
+// Brings an entity made from `old` in line with `new`, only touching what differs.
+fn apply(
+    world: &mut World,
+    entity: Entity,
+    old: &Prefab,
+    new: &Prefab,
+    trees: &mut BehaviourTrees,
+) {
+    for component in &old.components {
+        let kind = discriminant(component);
+        if !new.components.iter().any(|c| discriminant(c) == kind) {
+            component.remove(world, entity);
+        }
+    }
+    for component in &new.components {
+        if !old.components.contains(component) {
+            component.insert(world, entity, trees);
+        }
+    }
+    if old.children == new.children {
+        return;
+    }
+    let children: Vec<Entity> = world
+        .get::<Children>(entity)
+        .map(|children| children.iter().copied().collect())
+        .unwrap_or_default();
+    for child in children {
+        if world.get::<PrefabChild>(child).is_some() {
+            world.entity_mut(child).despawn_recursive();
+        }
+    }
+    for prefab in &new.children {
+        let child = world.spawn((PrefabChild, Transform::default())).id();
+        world.entity_mut(entity).add_child(child);
+        apply(world, child, &Prefab::default(), prefab, trees);
+    }
+}
+
+// Every so often, rereads edited prefab files and updates the entities spawned from them.
+fn reload_prefabs(world: &mut World, mut since_check: Local<f32>) {
+    *since_check += world.resource::<Time>().delta().as_secs_f32();
+    if *since_check < RELOAD_INTERVAL {
+        return;
+    }
+    *since_check = 0.0;
+    world.resource_scope(|world, mut prefabs: Mut<Prefabs>| {
+        if prefabs.directory.as_os_str().is_empty() || !prefabs.reload() {
+            return;
+        }
+        let instances: Vec<(Entity, PrefabInstance)> = world
+            .query::<(Entity, &PrefabInstance)>()
+            .iter(world)
+            .map(|(entity, instance)| (entity, instance.clone()))
+            .collect();
+        for (entity, mut instance) in instances {
+            let mut prefab = match prefabs.resolve(&instance.prefab) {
+                Ok(prefab) => prefab,
+                Err(error) => {
+                    warn!("{error}");
+                    continue;
+                }
+            };
+            prefab.override_with(&instance.overrides);
+            if prefab == instance.applied {
+                continue;
+            }
+            apply(
+                world,
+                entity,
+                &instance.applied,
+                &prefab,
+                &mut prefabs.trees,
+            );
+            instance.applied = prefab;
+            world.entity_mut(entity).insert(instance);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    fn prefab(extends: Option<&str>, components: Vec<PrefabComponent>) -> Prefab {
+        Prefab {
+            extends: extends.map(str::to_string),
+            components,
+            children: Vec::new(),
+        }
+    }
+
+    fn health(max: f32) -> PrefabComponent {
+        PrefabComponent::Health {
+            max,
+            invulnerability: None,
+        }
+    }
+
+    fn prefabs(prefabs: impl IntoIterator<Item = (&'static str, Prefab)>) -> Prefabs {
+        Prefabs {
+            prefabs: prefabs
+                .into_iter()
+                .map(|(name, prefab)| (name.to_string(), prefab))
+                .collect(),
+            ..default()
+        }
+    }
+
+    fn app(prefabs: Prefabs) -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, PrefabPlugin))
+            .insert_resource(prefabs);
+        app
+    }
+
+    fn name(world: &World, entity: Entity) -> Option<&str> {
+        world.get::<Name>(entity).map(|name| name.0.as_str())
+    }
+
+    #[test]
+    fn overrides_replace_components_of_the_same_kind() {
+        let mut prefab = prefab(
+            None,
+            vec![PrefabComponent::Name("base".into()), health(10.0)],
+        );
+        prefab.override_with(&[health(20.0), PrefabComponent::NonPlayable]);
+        assert_eq!(
+            prefab.components,
+            vec![
+                PrefabComponent::Name("base".into()),
+                health(20.0),
+                PrefabComponent::NonPlayable
+            ]
+        );
+    }
+
+    #[test]
+    fn resolving_fills_in_what_a_prefab_extends() {
+        let mut base = prefab(
+            None,
+            vec![PrefabComponent::Name("base".into()), health(10.0)],
+        );
+        base.children
+            .push(prefab(None, vec![PrefabComponent::Position(0.0, 5.0)]));
+        let middle = prefab(
+            Some("base"),
+            vec![
+                health(20.0),
+                PrefabComponent::Faction(CharacterFaction::Red),
+            ],
+        );
+        let top = prefab(Some("middle"), vec![PrefabComponent::Name("top".into())]);
+        let prefabs = prefabs([("base", base), ("middle", middle), ("top", top)]);
+
+        let flat = prefabs.resolve("top").unwrap();
+        assert_eq!(flat.extends, None);
+        assert_eq!(
+            flat.components,
+            vec![
+                PrefabComponent::Name("top".into()),
+                health(20.0),
+                PrefabComponent::Faction(CharacterFaction::Red)
+            ]
+        );
+        assert_eq!(flat.children.len(), 1);
+        assert_eq!(
+            flat.children[0].components,
+            vec![PrefabComponent::Position(0.0, 5.0)]
+        );
+    }
+
+    #[test]
+    fn unknown_and_circular_prefabs_are_refused() {
+        let prefabs = prefabs([
+            ("orphan", prefab(Some("missing"), Vec::new())),
+            ("chicken", prefab(Some("egg"), Vec::new())),
+            ("egg", prefab(Some("chicken"), Vec::new())),
+        ]);
+        assert!(
+            matches!(prefabs.resolve("nothing"), Err(PrefabError::Unknown(name)) if name == "nothing")
+        );
+        assert!(
+            matches!(prefabs.resolve("orphan"), Err(PrefabError::Unknown(name)) if name == "missing")
+        );
+        assert!(
+            matches!(prefabs.resolve("chicken"), Err(PrefabError::TooDeep(name)) if name == "chicken")
+        );
+    }
+
+    #[test]
+    fn applying_only_touches_what_changed() {
+        let mut world = World::new();
+        let mut trees = BehaviourTrees::default();
+        let entity = world.spawn_empty().id();
+        let mut old = prefab(
+            None,
+            vec![
+                PrefabComponent::Name("goblin".into()),
+                health(10.0),
+                PrefabComponent::Faction(CharacterFaction::Red),
+            ],
+        );
+        old.children
+            .push(prefab(None, vec![PrefabComponent::NonPlayable]));
+        apply(&mut world, entity, &Prefab::default(), &old, &mut trees);
+        let child = world.get::<Children>(entity).unwrap()[0];
+        assert!(world.get::<NonPlayableCharacter>(child).is_some());
+
+        // A hurt goblin stays hurt when only its faction changes and its name goes.
+        world.get_mut::<Health>(entity).unwrap().current = 4.0;
+        let mut new = old.clone();
+        new.components = vec![
+            health(10.0),
+            PrefabComponent::Faction(CharacterFaction::Blue),
+        ];
+        apply(&mut world, entity, &old, &new, &mut trees);
+        assert_eq!(name(&world, entity), None);
+        assert_eq!(world.get::<Health>(entity).unwrap().current, 4.0);
+        assert_eq!(
+            world.get::<CharacterFaction>(entity),
+            Some(&CharacterFaction::Blue)
+        );
+        assert_eq!(world.get::<Children>(entity).unwrap()[..], [child]);
+
+        // Changed children are replaced.
+        let mut newer = new.clone();
+        newer.children = vec![prefab(None, vec![PrefabComponent::Name("hat".into())])];
+        apply(&mut world, entity, &new, &newer, &mut trees);
+        assert!(world.get_entity(child).is_err());
+        let hat = world.get::<Children>(entity).unwrap()[0];
+        assert_eq!(name(&world, hat), Some("hat"));
+        assert!(world.get::<PrefabChild>(hat).is_some());
+    }
+
+    #[test]
+    fn spawning_applies_overrides_and_drops_unknown_prefabs() {
+        let goblin = prefab(
+            None,
+            vec![PrefabComponent::Name("goblin".into()), health(10.0)],
+        );
+        let mut app = app(prefabs([("goblin", goblin)]));
+        let (spawned, unknown) = app
+            .world_mut()
+            .run_system_once(|mut commands: Commands| {
+                (
+                    commands.spawn_prefab("goblin", vec![health(30.0)]),
+                    commands.spawn_prefab("troll", Vec::new()),
+                )
+            })
+            .unwrap();
+
+        let world = app.world();
+        assert_eq!(name(world, spawned), Some("goblin"));
+        assert_eq!(world.get::<Health>(spawned).unwrap().max, 30.0);
+        assert!(world.get::<Object>(spawned).is_some());
+        assert_eq!(
+            world.get::<PrefabInstance>(spawned).unwrap().prefab,
+            "goblin"
+        );
+        assert!(world.get_entity(unknown).is_err());
+    }
+
+    #[test]
+    fn saved_instances_respawn_their_prefab() {
+        let goblin = prefab(
+            None,
+            vec![PrefabComponent::Name("goblin".into()), health(10.0)],
+        );
+        let mut app = app(prefabs([("goblin", goblin)]));
+        let spawned = app
+            .world_mut()
+            .run_system_once(|mut commands: Commands| {
+                commands.spawn_prefab("goblin", vec![health(30.0)])
+            })
+            .unwrap();
+        let instance = app.world().get::<PrefabInstance>(spawned).unwrap();
+        let saved = serde_json::to_value(instance).unwrap();
+        let applied = instance.applied.clone();
+
+        let world = app.world_mut();
+        world.entity_mut(spawned).despawn();
+        let respawned = world.spawn_empty().id();
+        let instance: PrefabInstance = serde_json::from_value(saved).unwrap();
+        instance.respawn(world, respawned);
+        assert_eq!(name(world, respawned), Some("goblin"));
+        assert_eq!(world.get::<Health>(respawned).unwrap().max, 30.0);
+        assert_eq!(
+            world.get::<PrefabInstance>(respawned).unwrap().applied,
+            applied
+        );
+    }
+}
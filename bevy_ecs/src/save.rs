@@ -0,0 +1,723 @@
+// -----------------------------------------------------------------------------
+// Saving and loading
+// -----------------------------------------------------------------------------
+//
+// Writes the game to save files and reads it back. Only entities marked `Saved` are saved, and of
+// those only the components registered with `App::save_component`; resources are registered with
+// `App::save_resource`. Loading puts the saved components back on the entities with the same
+// keys, taking off registered components the save does not have, and despawns keyed entities the
+// save does not have. Keyed entities that have gone since saving are spawned again; a component
+// registered with `App::save_respawn`, such as the prefab an entity came from, builds the rest of
+// such an entity before its saved components go on.
+//
+// A save file starts with a line giving its version, format and checksum, followed by the saved
+// game in RON, JSON or compressed JSON. Files whose checksum does not match are refused rather
+// than half loaded. Saves from older versions are brought up to date by `SaveSettings::migrations`
+// before loading, so adding a migration is how the version goes up.
+//
+// Send `SaveGame` or `LoadGame` to save or load a slot; the game is also saved to its own slot
+// every so often.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use bevy::ecs::event::EventCursor;
+use bevy::prelude::*;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const MAGIC: &str = "bevy_ecs-save"; // What every save file starts with.
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SaveSettings>()
+            .init_resource::<SaveRegistry>()
+            .add_event::<SaveGame>()
+            .add_event::<LoadGame>()
+            .add_systems(
+                Update,
+                (autosave, save_and_load).chain().in_set(SaveSystems),
+            );
+    }
+}
+
+// Systems that ask for a save or load should run before this set to have it done the same frame.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SaveSystems;
+
+// Brings a save up to date by one version.
+pub type Migration = fn(&mut Value);
+
+#[derive(Resource, Debug, Clone)]
+pub struct SaveSettings {
+    pub directory: PathBuf,
+    pub format: SaveFormat, // What new saves are written in; any format can be loaded.
+    pub autosave: Option<f32>, // Seconds between autosaves, or `None` for none.
+    pub migrations: Vec<Migration>, // The first upgrades version 1 saves to version 2, and so on.
+}
+
+impl Default for SaveSettings {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("saves"),
+            format: SaveFormat::Ron,
+            autosave: Some(60.0),
+            migrations: Vec::new(),
+        }
+    }
+}
+
+impl SaveSettings {
+    // The version saves are written with.
+    pub fn version(&self) -> u32 {
+        self.migrations.len() as u32 + 1
+    }
+
+    pub fn path(&self, slot: SaveSlot) -> PathBuf {
+        match slot {
+            SaveSlot::Manual(number) => self.directory.join(format!("slot{number}.sav")),
+            SaveSlot::Autosave => self.directory.join("autosave.sav"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SaveFormat {
+    Ron,
+    Json,
+    Binary, // JSON compressed with deflate: the smallest, but not readable.
+}
+
+impl SaveFormat {
+    fn name(self) -> &'static str {
+        match self {
+            SaveFormat::Ron => "ron",
+            SaveFormat::Json => "json",
+            SaveFormat::Binary => "binary",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        [SaveFormat::Ron, SaveFormat::Json, SaveFormat::Binary]
+            .into_iter()
+            .find(|format| format.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SaveSlot {
+    Manual(u32),
+    Autosave,
+}
+
+// Saves the game to a slot, replacing whatever was there.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct SaveGame {
+    pub slot: SaveSlot,
+}
+
+// Loads the game from a slot.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct LoadGame {
+    pub slot: SaveSlot,
+}
+
+// An entity that is saved, known by a key that stays the same from one run to the next.
+#[derive(Component, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Saved(pub String);
+
+// What is in a save file, once read and brought up to date.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SaveData {
+    entities: Vec<SavedEntity>,
+    resources: serde_json::Map<String, Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedEntity {
+    key: String,
+    components: serde_json::Map<String, Value>,
+}
+
+// How to save and load each registered component and resource, under the name it is saved as.
+#[derive(Resource, Default)]
+struct SaveRegistry {
+    components: Vec<(&'static str, SaveComponent, LoadComponent, RemoveComponent)>,
+    respawns: Vec<(&'static str, SaveComponent, LoadComponent)>,
+    resources: Vec<(&'static str, SaveResource, LoadResource)>,
+}
+
+type SaveComponent = fn(&World, Entity) -> Option<serde_json::Result<Value>>;
+type LoadComponent = fn(&mut World, Entity, Value) -> serde_json::Result<()>;
+type RemoveComponent = fn(&mut World, Entity);
+type SaveResource = fn(&World) -> Option<serde_json::Result<Value>>;
+type LoadResource = fn(&mut World, Value) -> serde_json::Result<()>;
+
+fn save_component<C: Component + Serialize>(
+    world: &World,
+    entity: Entity,
+) -> Option<serde_json::Result<Value>> {
+    world.get::<C>(entity).map(serde_json::to_value)
+}
+
+fn load_component<C: Component + DeserializeOwned>(
+    world: &mut World,
+    entity: Entity,
+    value: Value,
+) -> serde_json::Result<()> {
+    let component: C = serde_json::from_value(value)?;
+    world.entity_mut(entity).insert(component);
+    Ok(())
+}
+
+fn remove_component<C: Component>(world: &mut World, entity: Entity) {
+    world.entity_mut(entity).remove::<C>();
+}
+
+fn respawn_component<C: Respawn>(
+    world: &mut World,
+    entity: Entity,
+    value: Value,
+) -> serde_json::Result<()> {
+    let component: C = serde_json::from_value(value)?;
+    component.respawn(world, entity);
+    Ok(())
+}
+
+fn save_resource<R: Resource + Serialize>(world: &World) -> Option<serde_json::Result<Value>> {
+    world.get_resource::<R>().map(serde_json::to_value)
+}
+
+fn load_resource<R: Resource + DeserializeOwned>(
+    world: &mut World,
+    value: Value,
+) -> serde_json::Result<()> {
+    let resource: R = serde_json::from_value(value)?;
+    world.insert_resource(resource);
+    Ok(())
+}
+
+// A saved component that can build the rest of its entity, for bringing back saved entities that
+// were despawned after saving. `respawn` is given the new entity, which has only its `Saved` key,
+// and may despawn it if it cannot be built.
+pub trait Respawn: Component + Serialize + DeserializeOwned {
+    fn respawn(self, world: &mut World, entity: Entity);
+}
+
+// Registers what is saved. Names are what components and resources are saved as, so they must
+// stay the same for old saves to load.
+pub trait SaveApp {
+    fn save_component<C: Component + Serialize + DeserializeOwned>(
+        &mut self,
+        name: &'static str,
+    ) -> &mut Self;
+
+    // Saves a component that is only loaded when its entity has to be spawned again.
+    fn save_respawn<C: Respawn>(&mut self, name: &'static str) -> &mut Self;
+
+    fn save_resource<R: Resource + Serialize + DeserializeOwned>(
+        &mut self,
+        name: &'static str,
+    ) -> &mut Self;
+}
+
+impl SaveApp for App {
+    fn save_component<C: Component + Serialize + DeserializeOwned>(
+        &mut self,
+        name: &'static str,
+    ) -> &mut Self {
+        self.init_resource::<SaveRegistry>();
+        let mut registry = self.world_mut().resource_mut::<SaveRegistry>();
+        registry.components.push((
+            name,
+            save_component::<C>,
+            load_component::<C>,
+            remove_component::<C>,
+        ));
+        self
+    }
+
+    fn save_respawn<C: Respawn>(&mut self, name: &'static str) -> &mut Self {
+        self.init_resource::<SaveRegistry>();
+        let mut registry = self.world_mut().resource_mut::<SaveRegistry>();
+        registry
+            .respawns
+            .push((name, save_component::<C>, respawn_component::<C>));
+        self
+    }
+
+    fn save_resource<R: Resource + Serialize + DeserializeOwned>(
+        &mut self,
+        name: &'static str,
+    ) -> &mut Self {
+        self.init_resource::<SaveRegistry>();
+        let mut registry = self.world_mut().resource_mut::<SaveRegistry>();
+        registry
+            .resources
+            .push((name, save_resource::<R>, load_resource::<R>));
+        self
+    }
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(PathBuf, std::io::Error), // The file could not be read or written.
+    NotASave(PathBuf),           // The file does not start like a save file.
+    Corrupt(PathBuf),            // The checksum does not match.
+    TooNew(PathBuf, u32),        // Saved by a later version of the game.
+    Ron(PathBuf, ron::Error),    // Not valid RON, or not a valid save.
+    Json(PathBuf, serde_json::Error), // Not valid JSON, or not a valid save.
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveError::Io(path, error) => {
+                write!(f, "failed to access {}: {error}", path.display())
+            }
+            SaveError::NotASave(path) => write!(f, "{} is not a save file", path.display()),
+            SaveError::Corrupt(path) => write!(f, "{} is corrupt", path.display()),
+            SaveError::TooNew(path, version) => write!(
+                f,
+                "{} is from a later version of the game ({version})",
+                path.display()
+            ),
+            SaveError::Ron(path, error) => write!(f, "failed to read {}: {error}", path.display()),
+            SaveError::Json(path, error) => {
+                write!(f, "failed to read {}: {error}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+// Writes the saved game out as a save file.
+fn write_save(
+    path: &Path,
+    data: &SaveData,
+    format: SaveFormat,
+    version: u32,
+) -> Result<(), SaveError> {
+    let json = |error| SaveError::Json(path.to_path_buf(), error);
+    let io = |error| SaveError::Io(path.to_path_buf(), error);
+    // Everything goes through a JSON value first, which any of the formats can read back.
+    let value = serde_json::to_value(data).map_err(json)?;
+    let payload = match format {
+        SaveFormat::Ron => ron::ser::to_string_pretty(&value, ron::ser::PrettyConfig::default())
+            .map_err(|error| SaveError::Ron(path.to_path_buf(), error))?
+            .into_bytes(),
+        SaveFormat::Json => serde_json::to_vec_pretty(&value).map_err(json)?,
+        SaveFormat::Binary => {
+            let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+            serde_json::to_writer(&mut encoder, &value).map_err(json)?;
+            encoder.finish().map_err(io)?
+        }
+    };
+    let checksum = crc32fast::hash(&payload);
+    let mut bytes = format!("{MAGIC} {version} {} {checksum:08x}\n", format.name()).into_bytes();
+    bytes.extend_from_slice(&payload);
+
+    // Written next to the slot and then moved over it, so a crash never leaves half a save.
+    std::fs::create_dir_all(path.parent().unwrap_or(Path::new("."))).map_err(io)?;
+    let temporary = path.with_extension("tmp");
+    std::fs::write(&temporary, bytes).map_err(io)?;
+    std::fs::rename(&temporary, path).map_err(io)
+}
+
+// Reads a save file, bringing it up to date.
+fn read_save(path: &Path, migrations: &[Migration]) -> Result<SaveData, SaveError> {
+    let json = |error| SaveError::Json(path.to_path_buf(), error);
+    let bytes = std::fs::read(path).map_err(|error| SaveError::Io(path.to_path_buf(), error))?;
+    let not_a_save = || SaveError::NotASave(path.to_path_buf());
+    let end = bytes
+        .iter()
+        .position(|&b| b == b'\n')
+        .ok_or_else(not_a_save)?;
+    let header = std::str::from_utf8(&bytes[..end]).map_err(|_| not_a_save())?;
+    let payload = &bytes[end + 1..];
+    let [magic, version, format, checksum] = header
+        .split(' ')
+        .collect::<Vec<_>>()
+        .try_into()
+        .map_err(|_| not_a_save())?;
+    let version: u32 = version.parse().map_err(|_| not_a_save())?;
+    let format = SaveFormat::from_name(format).ok_or_else(not_a_save)?;
+    let checksum = u32::from_str_radix(checksum, 16).map_err(|_| not_a_save())?;
+    if magic != MAGIC || version == 0 {
+        return Err(not_a_save());
+    }
+    if crc32fast::hash(payload) != checksum {
+        return Err(SaveError::Corrupt(path.to_path_buf()));
+    }
+    let current = migrations.len() as u32 + 1;
+    if version > current {
+        return Err(SaveError::TooNew(path.to_path_buf(), version));
+    }
+
+    let mut value: Value = match format {
+        SaveFormat::Ron => {
+            let text = std::str::from_utf8(payload).map_err(|_| not_a_save())?;
+            ron::from_str(text).map_err(|error| SaveError::Ron(path.to_path_buf(), error.code))?
+        }
+        SaveFormat::Json => serde_json::from_slice(payload).map_err(json)?,
+        SaveFormat::Binary => {
+            let mut text = Vec::new();
+            DeflateDecoder::new(payload)
+                .read_to_end(&mut text)
+                .map_err(|_| SaveError::Corrupt(path.to_path_buf()))?;
+            serde_json::from_slice(&text).map_err(json)?
+        }
+    };
+    for migrate in &migrations[version as usize - 1..] {
+        migrate(&mut value);
+    }
+    serde_json::from_value(value).map_err(json)
+}
+
+fn save_world(world: &mut World, registry: &SaveRegistry) -> SaveData {
+    let mut saved: Vec<(Entity, String)> = world
+        .query::<(Entity, &Saved)>()
+        .iter(world)
+        .map(|(entity, saved)| (entity, saved.0.clone()))
+        .collect();
+    saved.sort_by(|a, b| a.1.cmp(&b.1));
+
+    let mut data = SaveData::default();
+    for (entity, key) in saved {
+        let mut components = serde_json::Map::new();
+        let components_and_respawns = registry
+            .components
+            .iter()
+            .map(|&(name, save, ..)| (name, save))
+            .chain(
+                registry
+                    .respawns
+                    .iter()
+                    .map(|&(name, save, _)| (name, save)),
+            );
+        for (name, save) in components_and_respawns {
+            match save(world, entity) {
+                Some(Ok(value)) => {
+                    components.insert(name.to_string(), value);
+                }
+                Some(Err(error)) => warn!("{key} {name} not saved: {error}"),
+                None => {}
+            }
+        }
+        data.entities.push(SavedEntity { key, components });
+    }
+    for &(name, save, _) in &registry.resources {
+        match save(world) {
+            Some(Ok(value)) => {
+                data.resources.insert(name.to_string(), value);
+            }
+            Some(Err(error)) => warn!("{name} not saved: {error}"),
+            None => {}
+        }
+    }
+    data
+}
+
+fn load_world(world: &mut World, registry: &SaveRegistry, mut data: SaveData) {
+    for &(name, _, load) in &registry.resources {
+        if let Some(value) = data.resources.remove(name) {
+            if let Err(error) = load(world, value) {
+                warn!("{name} not loaded: {error}");
+            }
+        }
+    }
+
+    let live: Vec<(Entity, String)> = world
+        .query::<(Entity, &Saved)>()
+        .iter(world)
+        .map(|(entity, saved)| (entity, saved.0.clone()))
+        .collect();
+    for (entity, key) in &live {
+        if !data.entities.iter().any(|saved| &saved.key == key) {
+            world.entity_mut(*entity).despawn_recursive();
+        }
+    }
+    for mut saved in data.entities {
+        let entity = match live.iter().find(|(_, key)| key == &saved.key) {
+            Some(&(entity, _)) => entity,
+            None => {
+                let entity = world.spawn(Saved(saved.key.clone())).id();
+                for &(name, _, respawn) in &registry.respawns {
+                    if let Some(value) = saved.components.remove(name) {
+                        if let Err(error) = respawn(world, entity, value) {
+                            warn!("{} {name} not respawned: {error}", saved.key);
+                        }
+                    }
+                }
+                if world.get_entity(entity).is_err() {
+                    warn!("{} could not be spawned again", saved.key);
+                    continue;
+                }
+                entity
+            }
+        };
+        for &(name, _, load, remove) in &registry.components {
+            match saved.components.remove(name) {
+                Some(value) => {
+                    if let Err(error) = load(world, entity, value) {
+                        warn!("{} {name} not loaded: {error}", saved.key);
+                    }
+                }
+                None => remove(world, entity),
+            }
+        }
+    }
+}
+
+fn autosave(
+    time: Res<Time>,
+    settings: Res<SaveSettings>,
+    mut since_save: Local<f32>,
+    mut saves: EventWriter<SaveGame>,
+) {
+    let Some(interval) = settings.autosave else {
+        return;
+    };
+    *since_save += time.delta().as_secs_f32();
+    if *since_save >= interval {
+        *since_save = 0.0;
+        saves.send(SaveGame {
+            slot: SaveSlot::Autosave,
+        });
+    }
+}
+
+// Needs the whole world, as any component or resource might be saved.
+fn save_and_load(
+    world: &mut World,
+    mut saves: Local<EventCursor<SaveGame>>,
+    mut loads: Local<EventCursor<LoadGame>>,
+) {
+    let saves: Vec<SaveGame> = saves
+        .read(world.resource::<Events<SaveGame>>())
+        .copied()
+        .collect();
+    let loads: Vec<LoadGame> = loads
+        .read(world.resource::<Events<LoadGame>>())
+        .copied()
+        .collect();
+    if saves.is_empty() && loads.is_empty() {
+        return;
+    }
+    let settings = world.resource::<SaveSettings>().clone();
+    world.resource_scope(|world, registry: Mut<SaveRegistry>| {
+        for save in saves {
+            let path = settings.path(save.slot);
+            let data = save_world(world, &registry);
+            match write_save(&path, &data, settings.format, settings.version()) {
+                Ok(()) => info!("Saved to {}", path.display()),
+                Err(error) => warn!("{error}"),
+            }
+        }
+        // Only the last load matters, as it replaces whatever came before.
+        if let Some(load) = loads.last() {
+            let path = settings.path(load.slot);
+            match read_save(&path, &settings.migrations) {
+                Ok(data) => {
+                    load_world(world, &registry, data);
+                    info!("Loaded {}", path.display());
+                }
+                Err(error) => warn!("{error}"),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Health(i32);
+
+    #[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Score(u32);
+
+    // Gives a respawned entity the armour its kind wears, which is not saved.
+    #[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Kind(String);
+
+    #[derive(Component, Debug, Clone, PartialEq)]
+    struct Armour(i32);
+
+    impl Respawn for Kind {
+        fn respawn(self, world: &mut World, entity: Entity) {
+            let armour = if self.0 == "knight" { 5 } else { 0 };
+            world.entity_mut(entity).insert((self, Armour(armour)));
+        }
+    }
+
+    // A directory of its own for each test, emptied first.
+    fn directory(test: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("bevy_ecs-save-{}-{test}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        directory
+    }
+
+    fn app(directory: PathBuf, format: SaveFormat) -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, SavePlugin))
+            .insert_resource(SaveSettings {
+                directory,
+                format,
+                autosave: None,
+                migrations: Vec::new(),
+            })
+            .save_component::<Health>("health")
+            .save_respawn::<Kind>("kind")
+            .save_resource::<Score>("score")
+            .insert_resource(Score(0));
+        app
+    }
+
+    fn save(app: &mut App, slot: SaveSlot) {
+        app.world_mut().send_event(SaveGame { slot });
+        app.update();
+    }
+
+    fn load(app: &mut App, slot: SaveSlot) {
+        app.world_mut().send_event(LoadGame { slot });
+        app.update();
+    }
+
+    #[test]
+    fn saves_round_trip_in_every_format() {
+        for format in [SaveFormat::Ron, SaveFormat::Json, SaveFormat::Binary] {
+            let mut app = app(directory(format.name()), format);
+            let hero = app
+                .world_mut()
+                .spawn((Saved("hero".into()), Health(7)))
+                .id();
+            let unsaved = app.world_mut().spawn(Health(3)).id();
+            app.insert_resource(Score(42));
+            save(&mut app, SaveSlot::Manual(1));
+
+            app.world_mut().entity_mut(hero).insert(Health(1));
+            app.world_mut().entity_mut(unsaved).insert(Health(1));
+            app.insert_resource(Score(0));
+            let extra = app
+                .world_mut()
+                .spawn((Saved("extra".into()), Health(5)))
+                .id();
+            load(&mut app, SaveSlot::Manual(1));
+
+            let world = app.world();
+            assert_eq!(world.get::<Health>(hero), Some(&Health(7)), "{format:?}");
+            assert_eq!(world.get::<Health>(unsaved), Some(&Health(1)), "{format:?}");
+            assert_eq!(world.resource::<Score>(), &Score(42), "{format:?}");
+            assert!(world.get_entity(extra).is_err(), "{format:?}");
+        }
+    }
+
+    #[test]
+    fn despawned_entities_come_back() {
+        let mut app = app(directory("respawn"), SaveFormat::Ron);
+        let hero = app
+            .world_mut()
+            .spawn((Saved("hero".into()), Health(7), Kind("knight".into())))
+            .id();
+        save(&mut app, SaveSlot::Manual(1));
+
+        app.world_mut().entity_mut(hero).despawn();
+        load(&mut app, SaveSlot::Manual(1));
+
+        let world = app.world_mut();
+        let (entity, _) = world
+            .query::<(Entity, &Saved)>()
+            .iter(world)
+            .find(|(_, saved)| saved.0 == "hero")
+            .expect("the hero is back");
+        assert_eq!(world.get::<Health>(entity), Some(&Health(7)));
+        assert_eq!(world.get::<Kind>(entity), Some(&Kind("knight".into())));
+        assert_eq!(world.get::<Armour>(entity), Some(&Armour(5)));
+    }
+
+    #[test]
+    fn components_the_save_does_not_have_are_removed() {
+        let mut app = app(directory("remove"), SaveFormat::Json);
+        let hero = app.world_mut().spawn(Saved("hero".into())).id();
+        save(&mut app, SaveSlot::Manual(1));
+
+        // Only registered components go; the kind is only loaded when respawning.
+        app.world_mut()
+            .entity_mut(hero)
+            .insert((Health(3), Kind("knight".into()), Armour(5)));
+        load(&mut app, SaveSlot::Manual(1));
+
+        let world = app.world();
+        assert_eq!(world.get::<Health>(hero), None);
+        assert_eq!(world.get::<Kind>(hero), Some(&Kind("knight".into())));
+        assert_eq!(world.get::<Armour>(hero), Some(&Armour(5)));
+    }
+
+    #[test]
+    fn older_saves_are_migrated() {
+        let directory = directory("migrate");
+        let path = directory.join("old.sav");
+        // Version 1 kept health under another name.
+        let old = SaveData {
+            entities: vec![SavedEntity {
+                key: "hero".into(),
+                components: serde_json::Map::from_iter([("hp".to_string(), Value::from(9))]),
+            }],
+            resources: serde_json::Map::new(),
+        };
+        write_save(&path, &old, SaveFormat::Json, 1).unwrap();
+
+        fn rename_hp(save: &mut Value) {
+            for entity in save["entities"].as_array_mut().unwrap() {
+                let components = entity["components"].as_object_mut().unwrap();
+                if let Some(hp) = components.remove("hp") {
+                    components.insert("health".into(), hp);
+                }
+            }
+        }
+        let data = read_save(&path, &[rename_hp]).unwrap();
+        assert_eq!(data.entities[0].components["health"], Value::from(9));
+        assert!(!data.entities[0].components.contains_key("hp"));
+
+        // Saves written by the current version are not migrated again.
+        write_save(&path, &data, SaveFormat::Json, 2).unwrap();
+        let again = read_save(&path, &[rename_hp]).unwrap();
+        assert_eq!(again.entities[0].components["health"], Value::from(9));
+        assert!(matches!(
+            read_save(&path, &[]),
+            Err(SaveError::TooNew(_, 2))
+        ));
+    }
+
+    #[test]
+    fn a_flipped_byte_is_refused() {
+        let directory = directory("corrupt");
+        let mut app = app(directory.clone(), SaveFormat::Ron);
+        let hero = app
+            .world_mut()
+            .spawn((Saved("hero".into()), Health(7)))
+            .id();
+        save(&mut app, SaveSlot::Autosave);
+
+        let path = directory.join("autosave.sav");
+        let mut bytes = std::fs::read(&path).unwrap();
+        let last = bytes.len() - 2;
+        bytes[last] ^= 0x01;
+        std::fs::write(&path, bytes).unwrap();
+        assert!(matches!(read_save(&path, &[]), Err(SaveError::Corrupt(_))));
+
+        // Nothing is half loaded.
+        app.world_mut().entity_mut(hero).insert(Health(1));
+        load(&mut app, SaveSlot::Autosave);
+        assert_eq!(app.world().get::<Health>(hero), Some(&Health(1)));
+    }
+}
@@ -0,0 +1,989 @@
+// -----------------------------------------------------------------------------
+// Steering and crowd avoidance
+// -----------------------------------------------------------------------------
+//
+// Moves agents by velocity instead of writing their transforms. Steering behaviours (seeking,
+// fleeing, arriving, pursuing, evading, wandering and the separation, cohesion and alignment of
+// flocks) each ask for a velocity; their weighted pulls accelerate the agent, limited by its
+// maximum force and speed.
+//
+// Agents that avoid each other then pick, with optimal reciprocal collision avoidance (ORCA, a
+// form of RVO), the velocity closest to the one they steered towards that will not run into
+// another agent soon, trusting the others to take their half of the effort.
+//
+// Flying agents are moved by their velocity; characters only take its horizontal part and leave
+// the rest to the character controller. Neighbours are found through a spatial hash, and agents
+// steer in parallel, so flocks of thousands keep up.
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use colliding_bodies as physics;
+use colliding_bodies::{BroadPhase, SpatialHash};
+
+use crate::character::{Character, CharacterSystems};
+use crate::{random, random_seed};
+
+const MAX_NEIGHBOURS: usize = 10; // Closest agents each agent avoids.
+const EPSILON: f32 = 1e-5;
+
+pub struct SteeringPlugin;
+
+impl Plugin for SteeringPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (steer, avoid_collisions, move_agents)
+                .chain()
+                .in_set(SteeringSystems)
+                .before(CharacterSystems),
+        );
+    }
+}
+
+// Systems that set velocities or change steering should run before this set.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SteeringSystems;
+
+// How fast an agent is moving, in pixels per second.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+pub struct Velocity(pub Vec2);
+
+// Where a steering behaviour is heading, or what it keeps away from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    Point(Vec2),
+    Entity(Entity), // Where the entity is now; targets that are gone are ignored.
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SteeringBehaviour {
+    // Heads straight for the target at full speed.
+    Seek(Target),
+    // Heads straight away from the target while it is within `distance`.
+    Flee {
+        from: Target,
+        distance: f32,
+    },
+    // Seeks the target, slowing down within `slowing_radius` to stop on it.
+    Arrive {
+        target: Target,
+        slowing_radius: f32,
+    },
+    // Seeks where a moving entity is going to be.
+    Pursue(Entity),
+    // Flees from where a moving entity is going to be, while it is within `distance`.
+    Evade {
+        from: Entity,
+        distance: f32,
+    },
+    // Seeks a point on a circle `distance` ahead, which drifts by up to `jitter` radians a second.
+    Wander {
+        radius: f32,
+        distance: f32,
+        jitter: f32,
+    },
+    // Keeps away from flock mates within `radius`, the more the closer they are.
+    Separation {
+        radius: f32,
+    },
+    // Heads for the middle of the flock mates within `radius`.
+    Cohesion {
+        radius: f32,
+    },
+    // Heads the same way as the flock mates within `radius`.
+    Alignment {
+        radius: f32,
+    },
+}
+
+// Steers an agent with a weighted mix of behaviours.
+#[derive(Component, Debug, Clone, PartialEq)]
+#[require(Velocity)]
+pub struct Steering {
+    pub behaviours: Vec<(SteeringBehaviour, f32)>, // Each with its weight.
+    pub max_speed: f32,                            // Pixels per second.
+    pub max_force: f32,                            // Pixels per second squared.
+    pub flock: u32, // Flocking behaviours only look at agents of the same flock.
+    wander_angle: f32,
+    seed: u32, // Random state for wandering.
+}
+
+impl Steering {
+    pub fn new(max_speed: f32, max_force: f32) -> Self {
+        Self {
+            behaviours: Vec::new(),
+            max_speed,
+            max_force,
+            flock: 0,
+            wander_angle: 0.0,
+            seed: 0,
+        }
+    }
+
+    pub fn with(mut self, behaviour: SteeringBehaviour, weight: f32) -> Self {
+        self.behaviours.push((behaviour, weight));
+        self
+    }
+
+    pub fn with_flock(mut self, flock: u32) -> Self {
+        self.flock = flock;
+        self
+    }
+
+    // How far away the flocking behaviours look; zero if there are none.
+    fn flocking_radius(&self) -> f32 {
+        self.behaviours
+            .iter()
+            .filter_map(|(behaviour, _)| match *behaviour {
+                SteeringBehaviour::Separation { radius }
+                | SteeringBehaviour::Cohesion { radius }
+                | SteeringBehaviour::Alignment { radius } => Some(radius),
+                _ => None,
+            })
+            .fold(0.0, f32::max)
+    }
+}
+
+// Keeps an agent from running into other avoiding agents.
+#[derive(Component, Debug, Clone, PartialEq)]
+#[require(Velocity)]
+pub struct Avoidance {
+    pub radius: f32,             // Of the circle the agent takes up.
+    pub time_horizon: f32,       // Seconds ahead collisions are looked for.
+    pub neighbour_distance: f32, // Agents further away are not considered.
+    velocity: Vec2,              // What the agent chose last frame, as the others saw it.
+}
+
+impl Avoidance {
+    pub fn new(radius: f32) -> Self {
+        Self {
+            radius,
+            time_horizon: 1.0,
+            neighbour_distance: radius * 10.0,
+            velocity: Vec2::ZERO,
+        }
+    }
+
+    pub fn with_time_horizon(mut self, time_horizon: f32) -> Self {
+        self.time_horizon = time_horizon;
+        self
+    }
+}
+
+// A spatial hash holding each agent as a point under its index, for finding the agents near a
+// point.
+fn agent_hash(cell_size: f32, positions: impl Iterator<Item = Vec2>) -> SpatialHash {
+    let mut hash = SpatialHash::new(cell_size.max(1.0));
+    for (index, position) in positions.enumerate() {
+        hash.insert(index, square(position, 0.0));
+    }
+    hash
+}
+
+// Every agent in the square around `position`; some may be further than `radius` away.
+fn near(hash: &SpatialHash, position: Vec2, radius: f32) -> Vec<usize> {
+    hash.query_region(&square(position, radius))
+}
+
+fn square(center: Vec2, half_size: f32) -> physics::Aabb {
+    physics::Aabb::from_center(
+        physics::Vec2::new(center.x, center.y),
+        physics::Vec2::new(half_size, half_size),
+    )
+}
+
+// An agent as seen by the others this frame.
+struct Agent {
+    entity: Entity,
+    position: Vec2,
+    velocity: Vec2,
+    flock: Option<u32>, // None for things that are not steering.
+}
+
+// Everything a steering agent looks at during a frame.
+struct Surroundings<'a> {
+    agents: &'a [Agent],
+    indices: &'a HashMap<Entity, usize>,
+    hash: &'a SpatialHash,
+}
+
+impl Surroundings<'_> {
+    fn agent(&self, entity: Entity) -> Option<&Agent> {
+        self.indices.get(&entity).map(|&index| &self.agents[index])
+    }
+
+    fn position(&self, target: Target) -> Option<Vec2> {
+        match target {
+            Target::Point(point) => Some(point),
+            Target::Entity(entity) => self.agent(entity).map(|agent| agent.position),
+        }
+    }
+
+    // Where an entity will be by the time an agent at `position` could get there.
+    fn predict(&self, entity: Entity, position: Vec2, max_speed: f32) -> Option<Vec2> {
+        let target = self.agent(entity)?;
+        let time = target.position.distance(position) / max_speed.max(EPSILON);
+        Some(target.position + target.velocity * time)
+    }
+
+    // The other agents of a flock within `radius` of `me`, with their squared distances.
+    fn flock_mates(&self, me: &Agent, radius: f32) -> Vec<(f32, &Agent)> {
+        near(self.hash, me.position, radius)
+            .into_iter()
+            .map(|index| &self.agents[index])
+            .filter(|other| other.entity != me.entity && other.flock == me.flock)
+            .map(|other| (other.position.distance_squared(me.position), other))
+            .filter(|&(distance_squared, _)| distance_squared <= radius * radius)
+            .collect()
+    }
+}
+
+// The flock mates within `radius`.
+fn within<'a>(mates: &'a [(f32, &'a Agent)], radius: f32) -> impl Iterator<Item = &'a Agent> {
+    mates
+        .iter()
+        .filter(move |&&(distance_squared, _)| distance_squared <= radius * radius)
+        .map(|&(_, agent)| agent)
+}
+
+// The change of velocity that turns `velocity` into `desired`.
+fn towards(desired: Vec2, velocity: Vec2) -> Vec2 {
+    desired - velocity
+}
+
+fn seek(position: Vec2, target: Vec2, velocity: Vec2, max_speed: f32) -> Vec2 {
+    towards(
+        (target - position).normalize_or_zero() * max_speed,
+        velocity,
+    )
+}
+
+fn flee(position: Vec2, from: Vec2, distance: f32, velocity: Vec2, max_speed: f32) -> Vec2 {
+    if position.distance_squared(from) > distance * distance {
+        return Vec2::ZERO;
+    }
+    towards((position - from).normalize_or_zero() * max_speed, velocity)
+}
+
+// The pull of one behaviour on an agent: the change of velocity it asks for.
+fn steering_force(
+    behaviour: SteeringBehaviour,
+    me: &Agent,
+    mates: &[(f32, &Agent)],
+    steering: &mut Steering,
+    surroundings: &Surroundings,
+    dt: f32,
+) -> Vec2 {
+    let (position, velocity, max_speed) = (me.position, me.velocity, steering.max_speed);
+    match behaviour {
+        SteeringBehaviour::Seek(target) => {
+            surroundings.position(target).map_or(Vec2::ZERO, |target| {
+                seek(position, target, velocity, max_speed)
+            })
+        }
+        SteeringBehaviour::Flee { from, distance } => {
+            surroundings.position(from).map_or(Vec2::ZERO, |from| {
+                flee(position, from, distance, velocity, max_speed)
+            })
+        }
+        SteeringBehaviour::Arrive {
+            target,
+            slowing_radius,
+        } => surroundings.position(target).map_or(Vec2::ZERO, |target| {
+            let offset = target - position;
+            let distance = offset.length();
+            if distance < EPSILON {
+                return towards(Vec2::ZERO, velocity);
+            }
+            let speed = max_speed * (distance / slowing_radius.max(EPSILON)).min(1.0);
+            towards(offset / distance * speed, velocity)
+        }),
+        SteeringBehaviour::Pursue(entity) => surroundings
+            .predict(entity, position, max_speed)
+            .map_or(Vec2::ZERO, |target| {
+                seek(position, target, velocity, max_speed)
+            }),
+        SteeringBehaviour::Evade { from, distance } => {
+            let Some(now) = surroundings.position(Target::Entity(from)) else {
+                return Vec2::ZERO;
+            };
+            if position.distance_squared(now) > distance * distance {
+                return Vec2::ZERO;
+            }
+            let future = surroundings
+                .predict(from, position, max_speed)
+                .unwrap_or(now);
+            towards(
+                (position - future).normalize_or_zero() * max_speed,
+                velocity,
+            )
+        }
+        SteeringBehaviour::Wander {
+            radius,
+            distance,
+            jitter,
+        } => {
+            steering.wander_angle += (random(&mut steering.seed) * 2.0 - 1.0) * jitter * dt;
+            let heading = velocity.try_normalize().unwrap_or(Vec2::X);
+            let target = position
+                + heading * distance
+                + Vec2::from_angle(steering.wander_angle).rotate(heading) * radius;
+            seek(position, target, velocity, max_speed)
+        }
+        SteeringBehaviour::Separation { radius } => {
+            let push: Vec2 = within(mates, radius)
+                .map(|other| {
+                    let away = position - other.position;
+                    away / away.length_squared().max(EPSILON)
+                })
+                .sum();
+            if push == Vec2::ZERO {
+                return Vec2::ZERO;
+            }
+            towards(push.normalize_or_zero() * max_speed, velocity)
+        }
+        SteeringBehaviour::Cohesion { radius } => {
+            let (sum, count) = within(mates, radius)
+                .fold((Vec2::ZERO, 0), |(sum, count), other| {
+                    (sum + other.position, count + 1)
+                });
+            if count == 0 {
+                return Vec2::ZERO;
+            }
+            seek(position, sum / count as f32, velocity, max_speed)
+        }
+        SteeringBehaviour::Alignment { radius } => {
+            let (sum, count) = within(mates, radius)
+                .fold((Vec2::ZERO, 0), |(sum, count), other| {
+                    (sum + other.velocity, count + 1)
+                });
+            if count == 0 {
+                return Vec2::ZERO;
+            }
+            towards(
+                (sum / count as f32).normalize_or_zero() * max_speed,
+                velocity,
+            )
+        }
+    }
+}
+
+// Anything a steering agent might look at.
+type Seen = (
+    Entity,
+    &'static Transform,
+    Option<&'static Velocity>,
+    Option<&'static Character>,
+    Option<&'static Steering>,
+);
+
+type Steered = (
+    Entity,
+    &'static Transform,
+    &'static mut Steering,
+    &'static mut Velocity,
+);
+
+// Accelerates every steering agent by the weighted pull of its behaviours.
+fn steer(time: Res<Time>, mut queries: ParamSet<(Query<Seen>, Query<Steered>)>) {
+    let dt = time.delta().as_secs_f32();
+    let others = queries.p0();
+    let snapshot: Vec<Agent> = others
+        .iter()
+        .map(|(entity, transform, velocity, character, steering)| Agent {
+            entity,
+            position: transform.translation.truncate(),
+            velocity: velocity
+                .map(|velocity| velocity.0)
+                .or(character.map(|character| character.velocity))
+                .unwrap_or(Vec2::ZERO),
+            flock: steering.map(|steering| steering.flock),
+        })
+        .collect();
+    let indices: HashMap<Entity, usize> = snapshot
+        .iter()
+        .enumerate()
+        .map(|(index, agent)| (agent.entity, index))
+        .collect();
+    let cell_size = others
+        .iter()
+        .filter_map(|(_, _, _, _, steering)| steering)
+        .map(Steering::flocking_radius)
+        .fold(0.0, f32::max);
+    let hash = agent_hash(cell_size, snapshot.iter().map(|agent| agent.position));
+    let surroundings = Surroundings {
+        agents: &snapshot,
+        indices: &indices,
+        hash: &hash,
+    };
+
+    queries
+        .p1()
+        .par_iter_mut()
+        .for_each(|(entity, transform, mut steering, mut velocity)| {
+            let steering = &mut *steering;
+            if steering.seed == 0 {
+                steering.seed = random_seed(entity);
+            }
+            let me = Agent {
+                entity,
+                position: transform.translation.truncate(),
+                velocity: velocity.0,
+                flock: Some(steering.flock),
+            };
+            let radius = steering.flocking_radius();
+            let mates = if radius > 0.0 {
+                surroundings.flock_mates(&me, radius)
+            } else {
+                Vec::new()
+            };
+            let mut change = Vec2::ZERO;
+            for index in 0..steering.behaviours.len() {
+                let (behaviour, weight) = steering.behaviours[index];
+                let pull = steering_force(behaviour, &me, &mates, steering, &surroundings, dt);
+                change += pull * weight;
+            }
+            // Make the change within the frame, if the agent is strong enough.
+            let change = change.clamp_length_max(steering.max_force * dt);
+            velocity.0 = (velocity.0 + change).clamp_length_max(steering.max_speed);
+        });
+}
+
+// A half-plane of allowed velocities: those on the left of the line through `point` heading
+// along `direction`.
+#[derive(Debug, Clone, Copy)]
+struct Line {
+    point: Vec2,
+    direction: Vec2,
+}
+
+// An avoiding agent, as seen by the others this frame.
+struct Avoider {
+    position: Vec2,
+    velocity: Vec2,
+    radius: f32,
+}
+
+// The velocities that keep `me` from hitting `other` within `time_horizon` seconds, assuming the
+// other agent takes half the effort of avoiding the collision.
+fn orca_line(me: &Avoider, other: &Avoider, time_horizon: f32, dt: f32) -> Line {
+    let relative_position = other.position - me.position;
+    let relative_velocity = me.velocity - other.velocity;
+    let distance_squared = relative_position.length_squared();
+    let combined_radius = me.radius + other.radius;
+    let combined_radius_squared = combined_radius * combined_radius;
+
+    let (direction, u) = if distance_squared > combined_radius_squared {
+        // Not touching: keep out of the cone of velocities that collide before the horizon.
+        let w = relative_velocity - relative_position / time_horizon;
+        let w_length_squared = w.length_squared();
+        let dot = w.dot(relative_position);
+        if dot < 0.0 && dot * dot > combined_radius_squared * w_length_squared {
+            // Closest to the cut-off circle at the cone's tip.
+            let w_length = w_length_squared.sqrt();
+            let unit_w = w / w_length;
+            let direction = Vec2::new(unit_w.y, -unit_w.x);
+            (
+                direction,
+                unit_w * (combined_radius / time_horizon - w_length),
+            )
+        } else {
+            // Closest to one of the cone's legs.
+            let leg = (distance_squared - combined_radius_squared).sqrt();
+            let (x, y) = (relative_position.x, relative_position.y);
+            let direction = if relative_position.perp_dot(w) > 0.0 {
+                Vec2::new(x * leg - y * combined_radius, x * combined_radius + y * leg)
+                    / distance_squared
+            } else {
+                -Vec2::new(
+                    x * leg + y * combined_radius,
+                    -x * combined_radius + y * leg,
+                ) / distance_squared
+            };
+            let u = direction * relative_velocity.dot(direction) - relative_velocity;
+            (direction, u)
+        }
+    } else {
+        // Already overlapping: get apart within the frame.
+        let w = relative_velocity - relative_position / dt;
+        let w_length = w.length();
+        let unit_w = w / w_length.max(EPSILON);
+        let direction = Vec2::new(unit_w.y, -unit_w.x);
+        (direction, unit_w * (combined_radius / dt - w_length))
+    };
+    Line {
+        point: me.velocity + u * 0.5,
+        direction,
+    }
+}
+
+// The velocity on line `index` within `radius` that is closest to `preferred`, or furthest along
+// `preferred` if `direction_only`, keeping to the earlier lines. None if there is none.
+fn linear_program_1(
+    lines: &[Line],
+    index: usize,
+    radius: f32,
+    preferred: Vec2,
+    direction_only: bool,
+) -> Option<Vec2> {
+    let line = lines[index];
+    let dot = line.point.dot(line.direction);
+    let discriminant = dot * dot + radius * radius - line.point.length_squared();
+    if discriminant < 0.0 {
+        return None; // The line misses the circle of allowed speeds.
+    }
+    let root = discriminant.sqrt();
+    let (mut left, mut right) = (-dot - root, -dot + root);
+    for earlier in &lines[..index] {
+        let denominator = line.direction.perp_dot(earlier.direction);
+        let numerator = earlier.direction.perp_dot(line.point - earlier.point);
+        if denominator.abs() <= EPSILON {
+            // Parallel lines.
+            if numerator < 0.0 {
+                return None;
+            }
+            continue;
+        }
+        let t = numerator / denominator;
+        if denominator >= 0.0 {
+            right = right.min(t);
+        } else {
+            left = left.max(t);
+        }
+        if left > right {
+            return None;
+        }
+    }
+    let t = if direction_only {
+        if preferred.dot(line.direction) > 0.0 {
+            right
+        } else {
+            left
+        }
+    } else {
+        line.direction
+            .dot(preferred - line.point)
+            .clamp(left, right)
+    };
+    Some(line.point + line.direction * t)
+}
+
+// The velocity within `radius` that keeps to every line and is closest to `preferred`, or
+// furthest along it if `direction_only`. If the lines cannot all be kept, returns the index of
+// the first one that could not and the best velocity before it.
+fn linear_program_2(
+    lines: &[Line],
+    radius: f32,
+    preferred: Vec2,
+    direction_only: bool,
+) -> Result<Vec2, (usize, Vec2)> {
+    let mut result = if direction_only {
+        preferred * radius
+    } else {
+        preferred.clamp_length_max(radius)
+    };
+    for (index, line) in lines.iter().enumerate() {
+        if line.direction.perp_dot(line.point - result) > 0.0 {
+            match linear_program_1(lines, index, radius, preferred, direction_only) {
+                Some(velocity) => result = velocity,
+                None => return Err((index, result)),
+            }
+        }
+    }
+    Ok(result)
+}
+
+// When the lines cannot all be kept, the velocity that breaks the worst of them by the least.
+fn linear_program_3(lines: &[Line], first_failure: usize, radius: f32, mut result: Vec2) -> Vec2 {
+    let mut distance = 0.0;
+    for (index, line) in lines.iter().enumerate().skip(first_failure) {
+        if line.direction.perp_dot(line.point - result) <= distance {
+            continue;
+        }
+        let mut projected = Vec::with_capacity(index);
+        for earlier in &lines[..index] {
+            let determinant = line.direction.perp_dot(earlier.direction);
+            let point = if determinant.abs() <= EPSILON {
+                if line.direction.dot(earlier.direction) > 0.0 {
+                    continue; // Same direction.
+                }
+                (line.point + earlier.point) * 0.5
+            } else {
+                line.point
+                    + line.direction
+                        * (earlier.direction.perp_dot(line.point - earlier.point) / determinant)
+            };
+            projected.push(Line {
+                point,
+                direction: (earlier.direction - line.direction).normalize_or_zero(),
+            });
+        }
+        let away = Vec2::new(-line.direction.y, line.direction.x);
+        if let Ok(velocity) = linear_program_2(&projected, radius, away, true) {
+            result = velocity;
+        }
+        distance = line.direction.perp_dot(line.point - result);
+    }
+    result
+}
+
+// Turns the velocity each avoiding agent steered towards into the closest one that keeps clear
+// of its neighbours.
+fn avoid_collisions(
+    time: Res<Time>,
+    mut agents: Query<(
+        Entity,
+        &Transform,
+        &mut Avoidance,
+        &mut Velocity,
+        Option<&Steering>,
+    )>,
+) {
+    let dt = time.delta().as_secs_f32();
+    if dt <= 0.0 {
+        return;
+    }
+    let snapshot: Vec<(Entity, Avoider)> = agents
+        .iter()
+        .map(|(entity, transform, avoidance, _, _)| {
+            let avoider = Avoider {
+                position: transform.translation.truncate(),
+                velocity: avoidance.velocity,
+                radius: avoidance.radius,
+            };
+            (entity, avoider)
+        })
+        .collect();
+    let cell_size = snapshot
+        .iter()
+        .map(|(_, avoider)| avoider.radius * 2.0)
+        .fold(0.0, f32::max);
+    let hash = agent_hash(
+        cell_size,
+        snapshot.iter().map(|(_, avoider)| avoider.position),
+    );
+
+    agents.par_iter_mut().for_each(
+        |(entity, transform, mut avoidance, mut velocity, steering)| {
+            let me = Avoider {
+                position: transform.translation.truncate(),
+                velocity: avoidance.velocity,
+                radius: avoidance.radius,
+            };
+            let reach = avoidance.neighbour_distance;
+            let mut neighbours: Vec<(f32, &Avoider)> = near(&hash, me.position, reach)
+                .into_iter()
+                .map(|index| &snapshot[index])
+                .filter(|(other, _)| *other != entity)
+                .map(|(_, other)| (other.position.distance_squared(me.position), other))
+                .filter(|&(distance_squared, _)| distance_squared <= reach * reach)
+                .collect();
+            neighbours.sort_by(|a, b| a.0.total_cmp(&b.0));
+            neighbours.truncate(MAX_NEIGHBOURS);
+
+            let lines: Vec<Line> = neighbours
+                .iter()
+                .map(|(_, other)| orca_line(&me, other, avoidance.time_horizon, dt))
+                .collect();
+            let preferred = velocity.0;
+            let max_speed = steering.map_or(preferred.length(), |steering| steering.max_speed);
+            let chosen = match linear_program_2(&lines, max_speed, preferred, false) {
+                Ok(chosen) => chosen,
+                Err((failure, best)) => linear_program_3(&lines, failure, max_speed, best),
+            };
+            velocity.0 = chosen;
+            avoidance.velocity = chosen;
+        },
+    );
+}
+
+// Moves flying agents by their velocity, and hands the horizontal part to characters.
+fn move_agents(
+    time: Res<Time>,
+    mut agents: Query<(&Velocity, &mut Transform, Option<&mut Character>)>,
+) {
+    let dt = time.delta().as_secs_f32();
+    for (velocity, mut transform, character) in &mut agents {
+        match character {
+            Some(mut character) => character.velocity.x = velocity.0.x,
+            None => transform.translation += velocity.0.extend(0.0) * dt,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOLERANCE: f32 = 1e-3;
+
+    // What `avoid_collisions` picks: every line kept if it can be, the least broken otherwise.
+    fn solve(lines: &[Line], max_speed: f32, preferred: Vec2) -> Vec2 {
+        match linear_program_2(lines, max_speed, preferred, false) {
+            Ok(velocity) => velocity,
+            Err((failure, best)) => linear_program_3(lines, failure, max_speed, best),
+        }
+    }
+
+    // How far `velocity` is on the wrong side of `line`; zero or less if it keeps to it.
+    fn violation(line: &Line, velocity: Vec2) -> f32 {
+        line.direction.perp_dot(line.point - velocity)
+    }
+
+    // The closest two agents come within `time` seconds, moving as they are.
+    fn closest_approach(a: &Avoider, b: &Avoider, time: f32) -> f32 {
+        let position = b.position - a.position;
+        let velocity = b.velocity - a.velocity;
+        let t = (-position.dot(velocity) / velocity.length_squared().max(EPSILON)).clamp(0.0, time);
+        (position + velocity * t).length()
+    }
+
+    // Both agents pick a new velocity avoiding the other, as `avoid_collisions` does.
+    fn avoid(a: &Avoider, b: &Avoider, max_speed: f32, time_horizon: f32, dt: f32) -> [Avoider; 2] {
+        [(a, b), (b, a)].map(|(me, other)| {
+            let line = orca_line(me, other, time_horizon, dt);
+            let velocity = solve(&[line], max_speed, me.velocity);
+            assert!(violation(&line, velocity) <= TOLERANCE);
+            assert!(velocity.length() <= max_speed + TOLERANCE);
+            Avoider {
+                position: me.position,
+                velocity,
+                radius: me.radius,
+            }
+        })
+    }
+
+    #[test]
+    fn linear_program_1_keeps_to_the_circle_and_earlier_lines() {
+        let above = Line {
+            point: Vec2::new(0.0, 1.0),
+            direction: Vec2::X, // Allows y >= 1.
+        };
+        let velocity = linear_program_1(&[above], 0, 2.0, Vec2::new(3.0, 0.0), false).unwrap();
+        assert!(velocity.distance(Vec2::new(3.0_f32.sqrt(), 1.0)) < TOLERANCE);
+
+        let left = Line {
+            point: Vec2::new(1.0, 0.0),
+            direction: Vec2::Y, // Allows x <= 1.
+        };
+        let velocity = linear_program_1(&[left, above], 1, 2.0, Vec2::new(3.0, 0.0), false);
+        assert!(velocity.unwrap().distance(Vec2::new(1.0, 1.0)) < TOLERANCE);
+
+        let far = Line {
+            point: Vec2::new(0.0, 3.0),
+            direction: Vec2::X,
+        };
+        assert_eq!(linear_program_1(&[far], 0, 2.0, Vec2::ZERO, false), None);
+    }
+
+    #[test]
+    fn linear_program_2_moves_the_preferred_velocity_only_as_far_as_needed() {
+        let above = Line {
+            point: Vec2::new(0.0, 1.0),
+            direction: Vec2::X,
+        };
+        let velocity = Vec2::new(0.5, 1.5);
+        assert_eq!(
+            linear_program_2(&[above], 2.0, velocity, false),
+            Ok(velocity)
+        );
+        let velocity = linear_program_2(&[above], 2.0, Vec2::new(0.5, -1.0), false).unwrap();
+        assert!(velocity.distance(Vec2::new(0.5, 1.0)) < TOLERANCE);
+    }
+
+    #[test]
+    fn linear_program_3_breaks_conflicting_lines_evenly() {
+        let above = Line {
+            point: Vec2::new(0.0, 1.0),
+            direction: Vec2::X, // Allows y >= 1.
+        };
+        let below = Line {
+            point: Vec2::new(0.0, -1.0),
+            direction: -Vec2::X, // Allows y <= -1.
+        };
+        let lines = [above, below];
+        let Err((failure, best)) = linear_program_2(&lines, 5.0, Vec2::new(1.0, 0.5), false) else {
+            panic!("the lines cannot both be kept");
+        };
+        assert_eq!(failure, 1);
+        let velocity = linear_program_3(&lines, failure, 5.0, best);
+        assert!(velocity.y.abs() < TOLERANCE, "{velocity:?}");
+        assert!(velocity.length() <= 5.0 + TOLERANCE);
+    }
+
+    #[test]
+    fn head_on_agents_pass_each_other() {
+        let a = Avoider {
+            position: Vec2::ZERO,
+            velocity: Vec2::new(1.0, 0.0),
+            radius: 0.5,
+        };
+        let b = Avoider {
+            position: Vec2::new(5.0, 0.0),
+            velocity: Vec2::new(-1.0, 0.0),
+            radius: 0.5,
+        };
+        assert!(closest_approach(&a, &b, 10.0) < 1.0);
+
+        let [a, b] = avoid(&a, &b, 1.0, 10.0, 0.1);
+        assert!(closest_approach(&a, &b, 10.0) >= 1.0 - TOLERANCE);
+        // Each sidesteps its own way, and both keep heading on.
+        assert!(
+            a.velocity.y * b.velocity.y < 0.0,
+            "{:?} {:?}",
+            a.velocity,
+            b.velocity
+        );
+        assert!(a.velocity.x > 0.0 && b.velocity.x < 0.0);
+    }
+
+    #[test]
+    fn overlapping_agents_get_apart_within_the_frame() {
+        let dt = 0.1;
+        let a = Avoider {
+            position: Vec2::ZERO,
+            velocity: Vec2::ZERO,
+            radius: 0.5,
+        };
+        let b = Avoider {
+            position: Vec2::new(0.6, 0.0),
+            velocity: Vec2::ZERO,
+            radius: 0.5,
+        };
+        let [a, b] = avoid(&a, &b, 5.0, 2.0, dt);
+        assert!(a.velocity.x < 0.0 && b.velocity.x > 0.0);
+        let apart = (b.position + b.velocity * dt).distance(a.position + a.velocity * dt);
+        assert!(apart >= 1.0 - TOLERANCE, "{apart}");
+    }
+
+    const MAX_SPEED: f32 = 100.0;
+
+    // An agent of flock 0 at `position`, moving at `velocity`.
+    fn agent(index: u32, position: Vec2, velocity: Vec2) -> Agent {
+        Agent {
+            entity: Entity::from_raw(index),
+            position,
+            velocity,
+            flock: Some(0),
+        }
+    }
+
+    // The pull of `behaviour` on the first of `agents`, with the others around it.
+    fn force(behaviour: SteeringBehaviour, agents: &[Agent]) -> Vec2 {
+        let indices: HashMap<Entity, usize> = agents
+            .iter()
+            .enumerate()
+            .map(|(index, agent)| (agent.entity, index))
+            .collect();
+        let hash = agent_hash(50.0, agents.iter().map(|agent| agent.position));
+        let surroundings = Surroundings {
+            agents,
+            indices: &indices,
+            hash: &hash,
+        };
+        let mut steering = Steering::new(MAX_SPEED, 1000.0).with(behaviour, 1.0);
+        let mates = surroundings.flock_mates(&agents[0], steering.flocking_radius());
+        steering_force(
+            behaviour,
+            &agents[0],
+            &mates,
+            &mut steering,
+            &surroundings,
+            0.1,
+        )
+    }
+
+    fn assert_near(force: Vec2, expected: Vec2) {
+        assert!(
+            force.distance(expected) < TOLERANCE,
+            "{force:?} instead of {expected:?}"
+        );
+    }
+
+    #[test]
+    fn arrive_slows_down_to_stop_on_the_target() {
+        let at_rest = [agent(0, Vec2::ZERO, Vec2::ZERO)];
+        let arrive = |target| SteeringBehaviour::Arrive {
+            target: Target::Point(target),
+            slowing_radius: 100.0,
+        };
+        assert_near(
+            force(arrive(Vec2::new(300.0, 0.0)), &at_rest),
+            Vec2::X * MAX_SPEED,
+        );
+        assert_near(
+            force(arrive(Vec2::new(0.0, 50.0)), &at_rest),
+            Vec2::Y * MAX_SPEED / 2.0,
+        );
+        // On the target it only asks to cancel what velocity is left.
+        let moving = [agent(0, Vec2::ZERO, Vec2::new(10.0, -5.0))];
+        assert_near(force(arrive(Vec2::ZERO), &moving), Vec2::new(-10.0, 5.0));
+    }
+
+    #[test]
+    fn flee_and_evade_ignore_what_is_far_away() {
+        for from in [Vec2::new(150.0, 0.0), Vec2::new(50.0, 0.0)] {
+            let agents = [agent(0, Vec2::ZERO, Vec2::ZERO), agent(1, from, Vec2::ZERO)];
+            let flee = SteeringBehaviour::Flee {
+                from: Target::Point(from),
+                distance: 100.0,
+            };
+            let evade = SteeringBehaviour::Evade {
+                from: agents[1].entity,
+                distance: 100.0,
+            };
+            let expected = if from.x > 100.0 {
+                Vec2::ZERO
+            } else {
+                -Vec2::X * MAX_SPEED
+            };
+            assert_near(force(flee, &agents), expected);
+            assert_near(force(evade, &agents), expected);
+        }
+    }
+
+    #[test]
+    fn pursue_aims_where_the_target_is_going() {
+        // A second away at full speed, in which it moves 50 pixels up.
+        let agents = [
+            agent(0, Vec2::ZERO, Vec2::ZERO),
+            agent(1, Vec2::new(100.0, 0.0), Vec2::new(0.0, 50.0)),
+        ];
+        let expected = Vec2::new(100.0, 50.0).normalize() * MAX_SPEED;
+        assert_near(
+            force(SteeringBehaviour::Pursue(agents[1].entity), &agents),
+            expected,
+        );
+    }
+
+    #[test]
+    fn flocks_keep_apart_gather_and_line_up() {
+        let agents = [
+            agent(0, Vec2::ZERO, Vec2::ZERO),
+            agent(1, Vec2::new(20.0, 0.0), Vec2::new(30.0, 0.0)),
+            agent(2, Vec2::new(20.0, 20.0), Vec2::new(0.0, 30.0)),
+            agent(3, Vec2::new(-500.0, 0.0), Vec2::new(-30.0, 0.0)), // Out of reach.
+        ];
+        let separation = force(SteeringBehaviour::Separation { radius: 50.0 }, &agents);
+        // Away from both, but more from the closer one.
+        let expected = (Vec2::new(-20.0, 0.0) / 400.0 + Vec2::new(-20.0, -20.0) / 800.0)
+            .normalize()
+            * MAX_SPEED;
+        assert_near(separation, expected);
+
+        let centre = Vec2::new(20.0, 10.0);
+        let cohesion = force(SteeringBehaviour::Cohesion { radius: 50.0 }, &agents);
+        assert_near(cohesion, centre.normalize() * MAX_SPEED);
+
+        let alignment = force(SteeringBehaviour::Alignment { radius: 50.0 }, &agents);
+        assert_near(alignment, Vec2::ONE.normalize() * MAX_SPEED);
+
+        // Mates of another flock are left out.
+        let mut strangers = agents;
+        strangers[0].flock = Some(1);
+        assert_eq!(
+            force(SteeringBehaviour::Cohesion { radius: 50.0 }, &strangers),
+            Vec2::ZERO
+        );
+    }
+}
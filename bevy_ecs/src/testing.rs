@@ -0,0 +1,24 @@
+// -----------------------------------------------------------------------------
+// Test helpers
+// -----------------------------------------------------------------------------
+//
+// What the unit tests of the game's plugins share.
+
+use std::time::Duration;
+
+use bevy::app::Plugins;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+
+// Ten updates a second, enough for AI that thinks between physics steps.
+pub const TENTH_OF_A_SECOND: Duration = Duration::from_millis(100);
+
+// A headless app with `plugins` whose clock moves on by `step` every update, whatever the wall
+// clock does.
+pub fn app<M>(step: Duration, plugins: impl Plugins<M>) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins(plugins)
+        .insert_resource(TimeUpdateStrategy::ManualDuration(step));
+    app
+}
@@ -0,0 +1,285 @@
+// -----------------------------------------------------------------------------
+// Broad-phase benchmarks
+// -----------------------------------------------------------------------------
+//
+// Compares the spatial hash and the dynamic AABB tree (and a brute-force list as a baseline) on
+// synthetic scenes. Run with `cargo bench`; pass a scene name to run only that scene, e.g.
+// `cargo bench -- bullets`. Every structure's answers are checked against the brute force.
+
+use std::time::{Duration, Instant};
+
+use colliding_bodies::{Aabb, BroadPhase, DynamicAabbTree, SpatialHash, Vec2};
+
+// Small deterministic random numbers, so every run measures the same scenes.
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> f32 {
+        // xorshift64*
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next()
+    }
+}
+
+struct Scene {
+    name: &'static str,
+    boxes: Vec<Aabb>,
+    velocities: Vec<Vec2>,
+    regions: Vec<Aabb>,
+    rays: Vec<(Vec2, Vec2, f32)>,
+    cell_size: f32,
+}
+
+fn scene(
+    name: &'static str,
+    count: usize,
+    area: f32,
+    size: impl Fn(&mut Random) -> f32,
+    cell_size: f32,
+) -> Scene {
+    let mut random = Random(0x9E37_79B9_7F4A_7C15 ^ count as u64);
+    let mut boxes = Vec::with_capacity(count);
+    let mut velocities = Vec::with_capacity(count);
+    for _ in 0..count {
+        let center = Vec2::new(random.range(0.0, area), random.range(0.0, area));
+        let half = Vec2::new(size(&mut random), size(&mut random)) * 0.5;
+        boxes.push(Aabb::from_center(center, half));
+        velocities.push(Vec2::new(random.range(-1.0, 1.0), random.range(-1.0, 1.0)) * 0.2);
+    }
+    let regions = (0..1000)
+        .map(|_| {
+            let center = Vec2::new(random.range(0.0, area), random.range(0.0, area));
+            Aabb::from_center(center, Vec2::new(2.0, 2.0))
+        })
+        .collect();
+    let rays = (0..1000)
+        .map(|_| {
+            let origin = Vec2::new(random.range(0.0, area), random.range(0.0, area));
+            let direction = Vec2::X.rotate(random.range(0.0, std::f32::consts::TAU));
+            (origin, direction, area * 0.25)
+        })
+        .collect();
+    Scene {
+        name,
+        boxes,
+        velocities,
+        regions,
+        rays,
+        cell_size,
+    }
+}
+
+// The baseline: every query looks at every box.
+#[derive(Debug, Default)]
+struct BruteForce {
+    boxes: Vec<Option<Aabb>>,
+}
+
+impl BroadPhase for BruteForce {
+    fn insert(&mut self, key: usize, aabb: Aabb) {
+        if key >= self.boxes.len() {
+            self.boxes.resize(key + 1, None);
+        }
+        self.boxes[key] = Some(aabb);
+    }
+
+    fn update(&mut self, key: usize, aabb: Aabb) {
+        self.insert(key, aabb);
+    }
+
+    fn remove(&mut self, key: usize) -> bool {
+        self.boxes.get_mut(key).and_then(Option::take).is_some()
+    }
+
+    fn clear(&mut self) {
+        self.boxes.clear();
+    }
+
+    fn len(&self) -> usize {
+        self.boxes.iter().flatten().count()
+    }
+
+    fn aabb(&self, key: usize) -> Option<Aabb> {
+        self.boxes.get(key).copied().flatten()
+    }
+
+    fn visit_region(&self, region: &Aabb, visit: &mut dyn FnMut(usize)) {
+        for (key, aabb) in self.boxes.iter().enumerate() {
+            if aabb.is_some_and(|aabb| aabb.overlaps(region)) {
+                visit(key);
+            }
+        }
+    }
+
+    fn visit_ray(
+        &self,
+        origin: Vec2,
+        direction: Vec2,
+        mut max_t: f32,
+        visit: &mut dyn FnMut(usize, f32) -> f32,
+    ) {
+        for (key, aabb) in self.boxes.iter().enumerate() {
+            if let Some(t) = aabb.and_then(|aabb| aabb.ray_entry(origin, direction, max_t)) {
+                max_t = visit(key, t);
+                if max_t <= 0.0 {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn visit_pairs(&self, visit: &mut dyn FnMut(usize, usize)) {
+        for (a, box_a) in self.boxes.iter().enumerate() {
+            let Some(box_a) = box_a else { continue };
+            for (b, box_b) in self.boxes.iter().enumerate().skip(a + 1) {
+                if box_b.is_some_and(|box_b| box_a.overlaps(&box_b)) {
+                    visit(a, b);
+                }
+            }
+        }
+    }
+}
+
+// What a structure answered, to compare against the brute force.
+#[derive(Debug, PartialEq)]
+struct Answers {
+    pairs: Vec<(usize, usize)>,
+    regions: usize,
+    nearest_rays: Vec<Option<f32>>, // Distance to the nearest box along each ray.
+}
+
+fn time(f: impl FnOnce()) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
+
+fn run(label: &str, scene: &Scene, broad_phase: &mut dyn BroadPhase) -> Answers {
+    broad_phase.clear();
+    let build = time(|| {
+        for (key, aabb) in scene.boxes.iter().enumerate() {
+            broad_phase.insert(key, *aabb);
+        }
+    });
+
+    // Ten frames of every box moving a little.
+    let update = time(|| {
+        for frame in 1..=10 {
+            for (key, (aabb, velocity)) in scene.boxes.iter().zip(&scene.velocities).enumerate() {
+                let offset = *velocity * frame as f32;
+                broad_phase.update(key, Aabb::new(aabb.min + offset, aabb.max + offset));
+            }
+        }
+    });
+    // Back to where they started, so the answers can be compared.
+    for (key, aabb) in scene.boxes.iter().enumerate() {
+        broad_phase.update(key, *aabb);
+    }
+
+    let mut pairs = Vec::new();
+    let pair_time = time(|| pairs = broad_phase.pairs());
+
+    let mut regions = 0;
+    let region_time = time(|| {
+        for region in &scene.regions {
+            broad_phase.visit_region(region, &mut |_| regions += 1);
+        }
+    });
+
+    let mut nearest_rays = Vec::with_capacity(scene.rays.len());
+    let ray_time = time(|| {
+        for &(origin, direction, max_t) in &scene.rays {
+            let mut nearest: Option<f32> = None;
+            broad_phase.visit_ray(origin, direction, max_t, &mut |_, t| {
+                let t = nearest.map_or(t, |best| best.min(t));
+                nearest = Some(t);
+                t
+            });
+            nearest_rays.push(nearest);
+        }
+    });
+
+    let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+    println!(
+        "  {:<12} build {:>8.2} ms   10 updates {:>8.2} ms   pairs {:>8.2} ms ({} found)   \
+         1000 regions {:>7.2} ms   1000 rays {:>7.2} ms",
+        label,
+        ms(build),
+        ms(update),
+        ms(pair_time),
+        pairs.len(),
+        ms(region_time),
+        ms(ray_time),
+    );
+    Answers {
+        pairs,
+        regions,
+        nearest_rays,
+    }
+}
+
+fn check(label: &str, answers: &Answers, expected: &Answers) {
+    let wrong_rays = (answers.nearest_rays.iter())
+        .zip(&expected.nearest_rays)
+        .filter(|(a, b)| a != b)
+        .count();
+    assert!(
+        answers.pairs == expected.pairs && answers.regions == expected.regions && wrong_rays == 0,
+        "the {label} disagrees with the brute force: {} pairs instead of {}, {} region hits \
+         instead of {}, {wrong_rays} rays found a different nearest box",
+        answers.pairs.len(),
+        expected.pairs.len(),
+        answers.regions,
+        expected.regions,
+    );
+}
+
+fn main() {
+    // `cargo bench` passes `--bench`; any other argument picks scenes by name.
+    let filter: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .collect();
+
+    let scenes = [
+        // Thousands of bullets: small, similar sizes, spread over a large area.
+        scene("bullets", 5_000, 400.0, |_| 0.3, 1.0),
+        scene("crowd", 2_000, 100.0, |random| random.range(0.5, 1.5), 2.0),
+        // A few large objects among many small ones: hard for a single cell size.
+        scene(
+            "mixed sizes",
+            2_000,
+            200.0,
+            |random| {
+                if random.next() < 0.02 {
+                    random.range(10.0, 40.0)
+                } else {
+                    random.range(0.2, 1.0)
+                }
+            },
+            2.0,
+        ),
+    ];
+
+    for scene in &scenes {
+        if !filter.is_empty() && !filter.iter().any(|name| scene.name.contains(name.as_str())) {
+            continue;
+        }
+        println!("{} ({} boxes)", scene.name, scene.boxes.len());
+        let expected = run("brute force", scene, &mut BruteForce::default());
+        let hash = run(
+            "spatial hash",
+            scene,
+            &mut SpatialHash::new(scene.cell_size),
+        );
+        let tree = run("aabb tree", scene, &mut DynamicAabbTree::default());
+        check("spatial hash", &hash, &expected);
+        check("aabb tree", &tree, &expected);
+    }
+}
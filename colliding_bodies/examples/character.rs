@@ -0,0 +1,90 @@
+// -----------------------------------------------------------------------------
+// Character
+// -----------------------------------------------------------------------------
+//
+// Walks a kinematic character from left to right through one small level: over a tiled floor, up a
+// low step, up a gentle ramp and down the far side, over a gap it jumps and into a wall. Prints
+// where it is every few frames and what it stands on. Run with `cargo run --example character`;
+// `tests/character.rs` checks each of these on its own.
+
+use colliding_bodies::{CharacterController, Polygon, RigidBody, Shape, Vec2, World};
+
+const DT: f32 = 1.0 / 60.0;
+const GRAVITY: f32 = -9.81;
+const SPEED: f32 = 3.0;
+const JUMP_SPEED: f32 = 5.0;
+
+// A fixed triangle with the given corners.
+fn triangle(world: &mut World, corners: [Vec2; 3]) {
+    let centroid = (corners[0] + corners[1] + corners[2]) / 3.0;
+    let local: Vec<Vec2> = corners.iter().map(|&c| c - centroid).collect();
+    let polygon = Polygon::new(&local).expect("the triangle has area");
+    world.add_body(RigidBody::fixed(Shape::Polygon(polygon)).with_position(centroid));
+}
+
+// A floor of one-unit tiles with its top at y = 0, leaving out the tiles at `gap`.
+fn level() -> World {
+    let mut world = World::default();
+    let gap = 14..16;
+    for i in -2..24 {
+        if gap.contains(&i) {
+            continue;
+        }
+        let tile = RigidBody::fixed(Shape::aabb(1.0, 1.0)).with_position(Vec2::new(i as f32, -0.5));
+        world.add_body(tile);
+    }
+    // A 0.2 step from x = 3 to x = 6.
+    let step = RigidBody::fixed(Shape::aabb(3.0, 0.2)).with_position(Vec2::new(4.5, 0.1));
+    world.add_body(step);
+    // A 20 degree ramp up from x = 7 to x = 9 and back down to x = 11.
+    let rise = 2.0 * 20_f32.to_radians().tan();
+    triangle(
+        &mut world,
+        [
+            Vec2::new(7.0, 0.0),
+            Vec2::new(11.0, 0.0),
+            Vec2::new(9.0, rise),
+        ],
+    );
+    // A wall at the end.
+    let wall = RigidBody::fixed(Shape::aabb(1.0, 4.0)).with_position(Vec2::new(21.5, 2.0));
+    world.add_body(wall);
+    world
+}
+
+fn main() {
+    let world = level();
+    let controller = CharacterController::new(Shape::aabb(0.5, 1.0));
+    let (mut position, mut fall) = (Vec2::new(0.0, 0.51), 0.0);
+    let mut was_grounded = true;
+    for frame in 0..480 {
+        // Jump at the edge of the gap.
+        let jump = was_grounded && (13.0..13.1).contains(&position.x);
+        fall = if jump {
+            JUMP_SPEED
+        } else {
+            fall + GRAVITY * DT
+        };
+        let translation = Vec2::new(SPEED, fall) * DT;
+        let result = controller.move_character(&world, position, translation, |_, _| true);
+        if result.grounded && fall < 0.0 {
+            fall = 0.0;
+        }
+        position = result.position;
+        was_grounded = result.grounded;
+
+        if frame % 20 == 0 || jump {
+            let footing = match result.ground {
+                Some(ground) => {
+                    let slope = ground.normal.y.clamp(-1.0, 1.0).acos().to_degrees();
+                    format!("on the ground, sloped {slope:.0} degrees")
+                }
+                None => "in the air".to_owned(),
+            };
+            println!(
+                "frame {frame:>3}  at ({:6.2}, {:5.2})  {footing}",
+                position.x, position.y
+            );
+        }
+    }
+}
@@ -0,0 +1,150 @@
+// -----------------------------------------------------------------------------
+// Joints
+// -----------------------------------------------------------------------------
+//
+// One small scene per kind of joint, showing that the joint does what it promises: rods keep their
+// length, ropes never stretch, motors reach their speed, sliders stop at their limits, welds hold
+// and springs settle where Hooke's law says. Run with `cargo run --example joints`;
+// `tests/joints.rs` checks the same scenes.
+
+use colliding_bodies::{BodyHandle, Joint, JointHandle, RigidBody, Shape, Vec2, World};
+
+const DT: f32 = 1.0 / 60.0;
+
+// A world with a fixed pin at the origin to hang things from.
+fn world_with_pin() -> (World, BodyHandle) {
+    let mut world = World::default();
+    let pin = world.add_body(RigidBody::fixed(Shape::circle(0.05)));
+    (world, pin)
+}
+
+// Steps the world `steps` times, returning the largest error of `joint` seen.
+fn run(world: &mut World, joint: JointHandle, steps: usize) -> (f32, f32) {
+    let (mut linear, mut angular) = (0.0_f32, 0.0_f32);
+    for _ in 0..steps {
+        world.step(DT);
+        let error = world
+            .joint_error(joint)
+            .expect("the joint is never removed");
+        linear = linear.max(error.linear);
+        angular = angular.max(error.angular);
+    }
+    (linear, angular)
+}
+
+fn rod() {
+    let (mut world, pin) = world_with_pin();
+    let bob =
+        world.add_body(RigidBody::dynamic(Shape::circle(0.2)).with_position(Vec2::new(2.0, 0.0)));
+    let rod = world
+        .add_joint(Joint::distance(pin, bob, 2.0))
+        .expect("live bodies");
+    let (error, _) = run(&mut world, rod, 600);
+    println!("rod        a swinging pendulum's rod stayed within {error:.5} of its length");
+}
+
+fn rope() {
+    let (mut world, pin) = world_with_pin();
+    let bob =
+        world.add_body(RigidBody::dynamic(Shape::circle(0.2)).with_position(Vec2::new(1.0, 0.0)));
+    let rope = world
+        .add_joint(Joint::rope(pin, bob, 3.0))
+        .expect("live bodies");
+    let (error, _) = run(&mut world, rope, 600);
+    let length = world.body(bob).expect("live bob").position.length();
+    println!(
+        "rope       a falling bob was caught at {length:.4} units (stretched at most {error:.5})"
+    );
+}
+
+fn motor() {
+    let (mut world, pin) = world_with_pin();
+    let wheel = world.add_body(RigidBody::dynamic(Shape::circle(0.5)));
+    let joint = Joint::revolute(pin, wheel).with_motor(4.0, 50.0);
+    let motor = world.add_joint(joint).expect("live bodies");
+    let (drift, _) = run(&mut world, motor, 120);
+    let spin = world.body(wheel).expect("live wheel").angular_velocity;
+
+    // A motor too weak to lift a lever only slows its fall.
+    let (mut world, pin) = world_with_pin();
+    let lever = RigidBody::dynamic(Shape::rectangle(2.0, 0.1)).with_position(Vec2::new(1.0, 0.0));
+    let lever = world.add_body(lever);
+    let joint = Joint::revolute(pin, lever)
+        .with_anchors(Vec2::ZERO, Vec2::new(-1.0, 0.0))
+        .with_motor(1.0, 0.5);
+    let weak = world.add_joint(joint).expect("live bodies");
+    run(&mut world, weak, 30);
+    let angle = world.body(lever).expect("live lever").angle;
+    println!(
+        "motor      the wheel reached {spin:.4} rad/s (pin drifted {drift:.5}); a weak motor let its \
+         lever fall to {angle:.2}"
+    );
+}
+
+fn hinge_limits() {
+    let (mut world, pin) = world_with_pin();
+    let lever = RigidBody::dynamic(Shape::rectangle(2.0, 0.1)).with_position(Vec2::new(1.0, 0.0));
+    let lever = world.add_body(lever);
+    let joint = Joint::revolute(pin, lever)
+        .with_anchors(Vec2::ZERO, Vec2::new(-1.0, 0.0))
+        .with_limits(-0.5, 0.5);
+    let hinge = world.add_joint(joint).expect("live bodies");
+    let (linear, angular) = run(&mut world, hinge, 300);
+    let angle = world.body(lever).expect("live lever").angle;
+    println!(
+        "limits     a falling lever stopped at {angle:.4} rad, past its limit by at most \
+         {angular:.5} (pin drifted {linear:.5})"
+    );
+}
+
+fn slider() {
+    let (mut world, pin) = world_with_pin();
+    let platform = world.add_body(RigidBody::dynamic(Shape::rectangle(1.0, 0.2)));
+    let joint = Joint::prismatic(pin, platform, Vec2::new(0.0, 1.0))
+        .with_limits(0.0, 2.0)
+        .with_motor(1.0, 100.0);
+    let slider = world.add_joint(joint).expect("live bodies");
+    let (linear, angular) = run(&mut world, slider, 240);
+    let body = world.body(platform).expect("live platform");
+    println!(
+        "prismatic  the lift rose to {:.4} and stopped at its limit (off its axis by at most \
+         {linear:.5}, turned {angular:.5})",
+        body.position.y
+    );
+}
+
+fn weld() {
+    let (mut world, pin) = world_with_pin();
+    let beam = RigidBody::dynamic(Shape::rectangle(2.0, 0.2)).with_position(Vec2::new(1.0, 0.0));
+    let beam = world.add_body(beam);
+    let weight = RigidBody::dynamic(Shape::circle(0.3)).with_position(Vec2::new(2.0, 0.5));
+    world.add_body(weight);
+    let joint = Joint::weld(pin, beam).with_anchors(Vec2::ZERO, Vec2::new(-1.0, 0.0));
+    let weld = world.add_joint(joint).expect("live bodies");
+    let (linear, angular) = run(&mut world, weld, 300);
+    println!("weld       a loaded beam held within {linear:.5} units and {angular:.5} rad");
+}
+
+fn spring() {
+    let (mut world, pin) = world_with_pin();
+    let weight = RigidBody::dynamic(Shape::circle(0.25)).with_position(Vec2::new(0.0, -1.0));
+    let mass = weight.mass();
+    let weight = world.add_body(weight);
+    let (stiffness, damping) = (20.0, 2.0);
+    let joint = Joint::spring(pin, weight, 1.0, stiffness, damping);
+    let spring = world.add_joint(joint).expect("live bodies");
+    run(&mut world, spring, 600);
+    let length = world.body(weight).expect("live weight").position.length();
+    let expected = 1.0 + mass * 9.81 / stiffness;
+    println!("spring     settled at {length:.4}, Hooke's law says {expected:.4}");
+}
+
+fn main() {
+    rod();
+    rope();
+    motor();
+    hinge_limits();
+    slider();
+    weld();
+    spring();
+}
@@ -0,0 +1,109 @@
+// -----------------------------------------------------------------------------
+// Pathfinding
+// -----------------------------------------------------------------------------
+//
+// Finds paths on a few small maps and prints them: A* and jump point search through a tile maze,
+// drawn over the maze, and navigation mesh paths around a box, for a point and for a round agent,
+// and across overlapping and concave obstacles. Run with `cargo run --example pathfinding`.
+
+use colliding_bodies::{Aabb, Cell, Grid, NavMesh, Vec2};
+
+const MAZE: &str = "
+    ..........
+    .########.
+    .#......#.
+    .#.####.#.
+    .#.#..#.#.
+    .#.#.##.#.
+    .#.#....#.
+    .#.######.
+    .#........
+    .#########
+";
+
+// Draws the grid with `#` for blocked cells and `o` for the cells of the path, top row first.
+fn draw(grid: &Grid, path: &[Cell]) {
+    for y in (0..grid.height() as i32).rev() {
+        let row: String = (0..grid.width() as i32)
+            .map(|x| match (grid.is_open((x, y)), path.contains(&(x, y))) {
+                (_, true) => 'o',
+                (true, false) => '.',
+                (false, false) => '#',
+            })
+            .collect();
+        println!("    {row}");
+    }
+}
+
+fn print_path(name: &str, path: Option<Vec<Vec2>>) {
+    let Some(path) = path else {
+        println!("{name:<12} no path");
+        return;
+    };
+    let length: f32 = path.windows(2).map(|pair| pair[0].distance(pair[1])).sum();
+    let points: Vec<String> = path
+        .iter()
+        .map(|point| format!("({:.2}, {:.2})", point.x, point.y))
+        .collect();
+    println!("{name:<12} {length:.3} long: {}", points.join(" -> "));
+}
+
+fn corners(min: Vec2, max: Vec2) -> Vec<Vec2> {
+    vec![min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)]
+}
+
+fn maze() {
+    let grid = Grid::from_ascii(MAZE);
+    // From the middle of the spiral to its way out at the bottom right.
+    let (start, goal) = ((4, 5), (9, 1));
+    match (grid.find_path(start, goal), grid.find_path_jps(start, goal)) {
+        (Some(astar), Some(jps)) => {
+            println!(
+                "maze         A* took {:.3} cells, jump point search {:.3}",
+                astar.cost, jps.cost
+            );
+            draw(&grid, &astar.cells);
+        }
+        _ => println!("maze         no way out"),
+    }
+}
+
+fn around_a_box() {
+    let bounds = Aabb::new(Vec2::ZERO, Vec2::new(10.0, 10.0));
+    let obstacle = corners(Vec2::new(4.0, 3.0), Vec2::new(6.0, 7.5));
+    let (start, goal) = (Vec2::new(1.0, 5.0), Vec2::new(9.0, 5.0));
+    for (name, radius) in [("box", 0.0), ("box, r 0.5", 0.5)] {
+        match NavMesh::new(bounds, std::slice::from_ref(&obstacle), radius) {
+            Ok(mesh) => print_path(name, mesh.find_path(start, goal)),
+            Err(error) => println!("{name:<12} {error}"),
+        }
+    }
+}
+
+fn awkward_obstacles() {
+    // Overlapping obstacles, one sticking out of the bounds, and a concave one.
+    let bounds = Aabb::new(Vec2::ZERO, Vec2::new(20.0, 10.0));
+    let obstacles = vec![
+        corners(Vec2::new(4.0, -2.0), Vec2::new(6.0, 6.0)),
+        corners(Vec2::new(5.0, 5.0), Vec2::new(9.0, 7.0)),
+        vec![
+            Vec2::new(12.0, 2.0),
+            Vec2::new(16.0, 2.0),
+            Vec2::new(16.0, 9.0),
+            Vec2::new(15.0, 9.0),
+            Vec2::new(15.0, 3.0),
+            Vec2::new(12.0, 3.0),
+        ],
+    ];
+    let (start, goal) = (Vec2::new(1.0, 1.0), Vec2::new(19.0, 1.0));
+    match NavMesh::new(bounds, &obstacles, 0.0) {
+        Ok(mesh) => print_path("awkward", mesh.find_path(start, goal)),
+        Err(error) => println!("awkward      {error}"),
+    }
+}
+
+fn main() {
+    maze();
+    around_a_box();
+    awkward_obstacles();
+}
@@ -0,0 +1,46 @@
+// -----------------------------------------------------------------------------
+// Tunnelling
+// -----------------------------------------------------------------------------
+//
+// Fires a fan of small, fast bullets at a thin wall, once with continuous collision detection and
+// once without, and prints where each bullet ended up. Run with `cargo run --example tunnelling`;
+// `tests/ccd.rs` checks that nothing gets through at any speed or angle.
+
+use colliding_bodies::{RigidBody, Shape, Vec2, World};
+
+const WALL_X: f32 = 5.0;
+const SPEED: f32 = 2_000.0;
+const ANGLES: [f32; 5] = [-30.0, -10.0, 0.0, 10.0, 30.0];
+
+fn main() {
+    for ccd in [true, false] {
+        println!("with ccd {}:", if ccd { "on" } else { "off" });
+        for angle in ANGLES {
+            // Each bullet gets a world of its own, so that they do not hit each other.
+            let mut world = World::new(Vec2::ZERO);
+            let wall = RigidBody::fixed(Shape::aabb(0.02, 100.0));
+            world.add_body(wall.with_position(Vec2::new(WALL_X, 0.0)));
+            let bullet = RigidBody::dynamic(Shape::rectangle(0.1, 0.04))
+                .with_velocity(Vec2::X.rotate(angle.to_radians()) * SPEED)
+                .with_angular_velocity(40.0)
+                .with_ccd(ccd);
+            let handle = world.add_body(bullet);
+            for _ in 0..60 {
+                world.step(1.0 / 60.0);
+            }
+            let position = world
+                .body(handle)
+                .expect("the bullet is never removed")
+                .position;
+            let side = if position.x < WALL_X {
+                "in front of"
+            } else {
+                "behind"
+            };
+            println!(
+                "  fired at {angle:>5}° it ended {side} the wall, at x = {:.1}",
+                position.x
+            );
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use crate::math::{Aabb, Vec2};
+use crate::shape::{Pose, Shape};
+
+/// How a body takes part in the simulation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyType {
+    /// Never moves; has infinite mass.
+    Static,
+    /// Moved by forces, gravity and collisions.
+    Dynamic,
+    /// Moved only by its velocity, which the user sets; pushes dynamic bodies but is never pushed.
+    Kinematic,
+}
+
+/// A rigid body: a shape with a position, velocity and material.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RigidBody {
+    pub shape: Shape,
+    pub body_type: BodyType,
+    pub position: Vec2,        // World position of the centre of mass.
+    pub angle: f32,            // Radians, counter-clockwise.
+    pub velocity: Vec2,        // Units per second.
+    pub angular_velocity: f32, // Radians per second.
+    pub force: Vec2,           // Accumulated until the next step, then cleared.
+    pub torque: f32,           // Accumulated until the next step, then cleared.
+    pub restitution: f32,      // Bounciness: 0.0 does not bounce, 1.0 bounces back at full speed.
+    pub friction: f32,         // Coulomb friction coefficient.
+    pub gravity_scale: f32,    // Multiplies the world's gravity for this body.
+    pub linear_damping: f32,   // Fraction of linear velocity lost per second.
+    pub angular_damping: f32,  // Fraction of angular velocity lost per second.
+    pub ccd: bool,             // Sweep the body's motion so it cannot pass through thin bodies.
+    mass: f32,
+    inverse_mass: f32,
+    inertia: f32,
+    inverse_inertia: f32,
+}
+
+impl RigidBody {
+    /// A body with a density of 1.0 at the origin.
+    pub fn new(shape: Shape, body_type: BodyType) -> Self {
+        let mut body = Self {
+            shape,
+            body_type,
+            position: Vec2::ZERO,
+            angle: 0.0,
+            velocity: Vec2::ZERO,
+            angular_velocity: 0.0,
+            force: Vec2::ZERO,
+            torque: 0.0,
+            restitution: 0.2,
+            friction: 0.5,
+            gravity_scale: 1.0,
+            linear_damping: 0.0,
+            angular_damping: 0.0,
+            ccd: false,
+            mass: 0.0,
+            inverse_mass: 0.0,
+            inertia: 0.0,
+            inverse_inertia: 0.0,
+        };
+        body.set_density(1.0);
+        body
+    }
+
+    /// A dynamic body.
+    pub fn dynamic(shape: Shape) -> Self {
+        Self::new(shape, BodyType::Dynamic)
+    }
+
+    /// A static body.
+    pub fn fixed(shape: Shape) -> Self {
+        Self::new(shape, BodyType::Static)
+    }
+
+    /// A kinematic body.
+    pub fn kinematic(shape: Shape) -> Self {
+        Self::new(shape, BodyType::Kinematic)
+    }
+
+    pub fn with_position(mut self, position: Vec2) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_angle(mut self, angle: f32) -> Self {
+        self.angle = angle;
+        self
+    }
+
+    pub fn with_velocity(mut self, velocity: Vec2) -> Self {
+        self.velocity = velocity;
+        self
+    }
+
+    pub fn with_angular_velocity(mut self, angular_velocity: f32) -> Self {
+        self.angular_velocity = angular_velocity;
+        self
+    }
+
+    pub fn with_density(mut self, density: f32) -> Self {
+        self.set_density(density);
+        self
+    }
+
+    pub fn with_restitution(mut self, restitution: f32) -> Self {
+        self.restitution = restitution;
+        self
+    }
+
+    pub fn with_friction(mut self, friction: f32) -> Self {
+        self.friction = friction;
+        self
+    }
+
+    pub fn with_gravity_scale(mut self, gravity_scale: f32) -> Self {
+        self.gravity_scale = gravity_scale;
+        self
+    }
+
+    /// Enables continuous collision detection, for fast bodies such as bullets.
+    pub fn with_ccd(mut self, ccd: bool) -> Self {
+        self.ccd = ccd;
+        self
+    }
+
+    pub fn with_damping(mut self, linear: f32, angular: f32) -> Self {
+        self.linear_damping = linear;
+        self.angular_damping = angular;
+        self
+    }
+
+    /// Recomputes the mass and inertia from the shape. Only dynamic bodies have mass.
+    pub fn set_density(&mut self, density: f32) {
+        let properties = self.shape.mass_properties(density);
+        let dynamic = self.body_type == BodyType::Dynamic;
+        self.mass = if dynamic { properties.mass } else { 0.0 };
+        self.inertia = if dynamic { properties.inertia } else { 0.0 };
+        self.inverse_mass = inverse(self.mass);
+        self.inverse_inertia = if self.shape.can_rotate() {
+            inverse(self.inertia)
+        } else {
+            0.0
+        };
+    }
+
+    /// Mass in kilograms; 0.0 for static and kinematic bodies.
+    pub fn mass(&self) -> f32 {
+        self.mass
+    }
+
+    /// Rotational inertia about the centre of mass; 0.0 for bodies that cannot be rotated.
+    pub fn inertia(&self) -> f32 {
+        self.inertia
+    }
+
+    pub fn inverse_mass(&self) -> f32 {
+        self.inverse_mass
+    }
+
+    pub fn inverse_inertia(&self) -> f32 {
+        self.inverse_inertia
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        self.body_type == BodyType::Dynamic
+    }
+
+    /// Adds a force through the centre of mass, applied during the next step.
+    pub fn apply_force(&mut self, force: Vec2) {
+        self.force += force;
+    }
+
+    /// Adds a force at a world point, which also spins the body.
+    pub fn apply_force_at(&mut self, force: Vec2, point: Vec2) {
+        self.force += force;
+        self.torque += (point - self.position).cross(force);
+    }
+
+    pub fn apply_torque(&mut self, torque: f32) {
+        self.torque += torque;
+    }
+
+    /// Changes the velocity immediately by `impulse / mass`.
+    pub fn apply_impulse(&mut self, impulse: Vec2) {
+        self.velocity += impulse * self.inverse_mass;
+    }
+
+    /// An impulse at a world point, changing both the linear and angular velocity.
+    pub fn apply_impulse_at(&mut self, impulse: Vec2, point: Vec2) {
+        self.velocity += impulse * self.inverse_mass;
+        self.angular_velocity += self.inverse_inertia * (point - self.position).cross(impulse);
+    }
+
+    /// The velocity of a world point attached to the body.
+    pub fn velocity_at(&self, point: Vec2) -> Vec2 {
+        self.velocity + Vec2::cross_scalar(self.angular_velocity, point - self.position)
+    }
+
+    /// Linear momentum.
+    pub fn momentum(&self) -> Vec2 {
+        self.velocity * self.mass
+    }
+
+    pub fn kinetic_energy(&self) -> f32 {
+        0.5 * (self.mass * self.velocity.length_squared()
+            + self.inertia * self.angular_velocity * self.angular_velocity)
+    }
+
+    pub fn pose(&self) -> Pose {
+        Pose::new(self.position, self.angle)
+    }
+
+    /// The world-space bounding box of the body's shape.
+    pub fn aabb(&self) -> Aabb {
+        self.shape.aabb_at(self.pose())
+    }
+}
+
+fn inverse(value: f32) -> f32 {
+    if value > 0.0 {
+        1.0 / value
+    } else {
+        0.0
+    }
+}
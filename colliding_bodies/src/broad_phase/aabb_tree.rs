@@ -0,0 +1,373 @@
+use std::collections::HashMap;
+
+use super::BroadPhase;
+use crate::math::{Aabb, Vec2};
+
+const NULL: usize = usize::MAX;
+
+#[derive(Debug, Clone)]
+struct Node {
+    fat: Aabb,  // Covers the children, or the leaf's box plus the margin.
+    aabb: Aabb, // The exact box of a leaf.
+    parent: usize,
+    children: [usize; 2], // Both `NULL` for leaves.
+    height: i32,          // 0 for leaves, -1 for free nodes.
+    key: usize,
+}
+
+impl Node {
+    fn is_leaf(&self) -> bool {
+        self.children[0] == NULL
+    }
+}
+
+/// A balanced binary tree of bounding boxes, as used by Box2D.
+///
+/// Leaves store their box grown by a margin, so objects moving a little do not change the tree.
+/// Inserting picks the sibling that grows the tree's total perimeter the least, and rotations keep
+/// the tree balanced. Handles any mix of object sizes and spread.
+#[derive(Debug, Clone)]
+pub struct DynamicAabbTree {
+    nodes: Vec<Node>,
+    free: Vec<usize>,
+    root: usize,
+    leaves: HashMap<usize, usize>, // Key to leaf node.
+    margin: f32,
+}
+
+impl Default for DynamicAabbTree {
+    fn default() -> Self {
+        Self::new(0.1)
+    }
+}
+
+impl DynamicAabbTree {
+    /// A tree that grows each box by `margin` on every side.
+    pub fn new(margin: f32) -> Self {
+        Self {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            root: NULL,
+            leaves: HashMap::new(),
+            margin: margin.max(0.0),
+        }
+    }
+
+    pub fn margin(&self) -> f32 {
+        self.margin
+    }
+
+    /// The height of the tree: 0 for an empty tree or a single leaf.
+    pub fn height(&self) -> i32 {
+        if self.root == NULL {
+            0
+        } else {
+            self.nodes[self.root].height
+        }
+    }
+
+    fn allocate(&mut self) -> usize {
+        let node = Node {
+            fat: Aabb::new(Vec2::ZERO, Vec2::ZERO),
+            aabb: Aabb::new(Vec2::ZERO, Vec2::ZERO),
+            parent: NULL,
+            children: [NULL, NULL],
+            height: 0,
+            key: 0,
+        };
+        match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn release(&mut self, index: usize) {
+        self.nodes[index].height = -1;
+        self.free.push(index);
+    }
+
+    fn insert_leaf(&mut self, leaf: usize) {
+        if self.root == NULL {
+            self.root = leaf;
+            self.nodes[leaf].parent = NULL;
+            return;
+        }
+
+        // Walk down to the cheapest sibling.
+        let leaf_box = self.nodes[leaf].fat;
+        let mut index = self.root;
+        while !self.nodes[index].is_leaf() {
+            let node = &self.nodes[index];
+            let perimeter = node.fat.perimeter();
+            let combined = node.fat.union(&leaf_box).perimeter();
+            // Cost of making a new parent for this node and the leaf.
+            let cost = 2.0 * combined;
+            // Cost of pushing the leaf further down, which grows this node's box.
+            let inheritance = 2.0 * (combined - perimeter);
+            let child_cost = |child: usize| {
+                let child = &self.nodes[child];
+                let grown = leaf_box.union(&child.fat).perimeter();
+                if child.is_leaf() {
+                    grown + inheritance
+                } else {
+                    grown - child.fat.perimeter() + inheritance
+                }
+            };
+            let [left, right] = node.children;
+            let (cost_left, cost_right) = (child_cost(left), child_cost(right));
+            if cost < cost_left && cost < cost_right {
+                break;
+            }
+            index = if cost_left < cost_right { left } else { right };
+        }
+
+        // Make a new parent for the sibling and the leaf.
+        let sibling = index;
+        let old_parent = self.nodes[sibling].parent;
+        let new_parent = self.allocate();
+        self.nodes[new_parent].parent = old_parent;
+        self.nodes[new_parent].fat = leaf_box.union(&self.nodes[sibling].fat);
+        self.nodes[new_parent].height = self.nodes[sibling].height + 1;
+        self.nodes[new_parent].children = [sibling, leaf];
+        self.nodes[sibling].parent = new_parent;
+        self.nodes[leaf].parent = new_parent;
+        if old_parent == NULL {
+            self.root = new_parent;
+        } else {
+            self.replace_child(old_parent, sibling, new_parent);
+        }
+
+        self.refit_from(self.nodes[leaf].parent);
+    }
+
+    fn remove_leaf(&mut self, leaf: usize) {
+        if leaf == self.root {
+            self.root = NULL;
+            return;
+        }
+        let parent = self.nodes[leaf].parent;
+        let grandparent = self.nodes[parent].parent;
+        let [left, right] = self.nodes[parent].children;
+        let sibling = if left == leaf { right } else { left };
+
+        self.nodes[sibling].parent = grandparent;
+        self.release(parent);
+        if grandparent == NULL {
+            self.root = sibling;
+        } else {
+            self.replace_child(grandparent, parent, sibling);
+            self.refit_from(grandparent);
+        }
+    }
+
+    fn replace_child(&mut self, parent: usize, old: usize, new: usize) {
+        let children = &mut self.nodes[parent].children;
+        if children[0] == old {
+            children[0] = new;
+        } else {
+            children[1] = new;
+        }
+    }
+
+    // Rebalances and recomputes the boxes and heights from `index` up to the root.
+    fn refit_from(&mut self, mut index: usize) {
+        while index != NULL {
+            index = self.balance(index);
+            let [left, right] = self.nodes[index].children;
+            self.nodes[index].height = 1 + self.nodes[left].height.max(self.nodes[right].height);
+            self.nodes[index].fat = self.nodes[left].fat.union(&self.nodes[right].fat);
+            index = self.nodes[index].parent;
+        }
+    }
+
+    // If one child of `a` is more than one level taller than the other, rotates it up to take
+    // `a`'s place. Returns the node now in that place.
+    fn balance(&mut self, a: usize) -> usize {
+        if self.nodes[a].is_leaf() || self.nodes[a].height < 2 {
+            return a;
+        }
+        let [b, c] = self.nodes[a].children;
+        let balance = self.nodes[c].height - self.nodes[b].height;
+        if balance > 1 {
+            self.rotate_up(a, c, 1)
+        } else if balance < -1 {
+            self.rotate_up(a, b, 0)
+        } else {
+            a
+        }
+    }
+
+    // Rotates `child` (the child of `a` on `side`) up into `a`'s place. `a` keeps its other child
+    // and takes the shorter of `child`'s children; `child` keeps the taller one.
+    fn rotate_up(&mut self, a: usize, child: usize, side: usize) -> usize {
+        let other = self.nodes[a].children[1 - side];
+        let [f, g] = self.nodes[child].children;
+
+        // `child` replaces `a` under `a`'s parent.
+        let parent = self.nodes[a].parent;
+        self.nodes[child].children[0] = a;
+        self.nodes[child].parent = parent;
+        self.nodes[a].parent = child;
+        if parent == NULL {
+            self.root = child;
+        } else {
+            self.replace_child(parent, a, child);
+        }
+
+        let (taller, shorter) = if self.nodes[f].height > self.nodes[g].height {
+            (f, g)
+        } else {
+            (g, f)
+        };
+        self.nodes[child].children[1] = taller;
+        self.nodes[a].children[side] = shorter;
+        self.nodes[shorter].parent = a;
+
+        self.nodes[a].fat = self.nodes[other].fat.union(&self.nodes[shorter].fat);
+        self.nodes[a].height = 1 + self.nodes[other].height.max(self.nodes[shorter].height);
+        self.nodes[child].fat = self.nodes[a].fat.union(&self.nodes[taller].fat);
+        self.nodes[child].height = 1 + self.nodes[a].height.max(self.nodes[taller].height);
+        child
+    }
+
+    // Calls `visit` with every leaf whose fat box overlaps `region`.
+    fn visit_fat(&self, region: &Aabb, mut visit: impl FnMut(usize)) {
+        if self.root == NULL {
+            return;
+        }
+        let mut stack = vec![self.root];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.fat.overlaps(region) {
+                continue;
+            }
+            if node.is_leaf() {
+                visit(index);
+            } else {
+                stack.extend(node.children);
+            }
+        }
+    }
+}
+
+impl BroadPhase for DynamicAabbTree {
+    fn insert(&mut self, key: usize, aabb: Aabb) {
+        self.remove(key);
+        let leaf = self.allocate();
+        self.nodes[leaf].aabb = aabb;
+        self.nodes[leaf].fat = aabb.expanded(self.margin);
+        self.nodes[leaf].key = key;
+        self.leaves.insert(key, leaf);
+        self.insert_leaf(leaf);
+    }
+
+    fn update(&mut self, key: usize, aabb: Aabb) {
+        let Some(&leaf) = self.leaves.get(&key) else {
+            self.insert(key, aabb);
+            return;
+        };
+        self.nodes[leaf].aabb = aabb;
+        if self.nodes[leaf].fat.contains(&aabb) {
+            return;
+        }
+        self.remove_leaf(leaf);
+        self.nodes[leaf].fat = aabb.expanded(self.margin);
+        self.insert_leaf(leaf);
+    }
+
+    fn remove(&mut self, key: usize) -> bool {
+        let Some(leaf) = self.leaves.remove(&key) else {
+            return false;
+        };
+        self.remove_leaf(leaf);
+        self.release(leaf);
+        true
+    }
+
+    fn clear(&mut self) {
+        self.nodes.clear();
+        self.free.clear();
+        self.leaves.clear();
+        self.root = NULL;
+    }
+
+    fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    fn aabb(&self, key: usize) -> Option<Aabb> {
+        self.leaves.get(&key).map(|&leaf| self.nodes[leaf].aabb)
+    }
+
+    fn visit_region(&self, region: &Aabb, visit: &mut dyn FnMut(usize)) {
+        self.visit_fat(region, |leaf| {
+            let node = &self.nodes[leaf];
+            if node.aabb.overlaps(region) {
+                visit(node.key);
+            }
+        });
+    }
+
+    fn visit_ray(
+        &self,
+        origin: Vec2,
+        direction: Vec2,
+        max_t: f32,
+        visit: &mut dyn FnMut(usize, f32) -> f32,
+    ) {
+        if self.root == NULL {
+            return;
+        }
+        let mut max_t = max_t;
+        let mut stack = vec![self.root];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.fat.ray_entry(origin, direction, max_t).is_none() {
+                continue;
+            }
+            if !node.is_leaf() {
+                // Visit the nearer child first, so hits found early can clip the rest.
+                let [left, right] = node.children;
+                let entry = |child: usize| {
+                    self.nodes[child]
+                        .fat
+                        .ray_entry(origin, direction, max_t)
+                        .unwrap_or(f32::INFINITY)
+                };
+                if entry(left) < entry(right) {
+                    stack.extend([right, left]);
+                } else {
+                    stack.extend([left, right]);
+                }
+                continue;
+            }
+            if let Some(t) = node.aabb.ray_entry(origin, direction, max_t) {
+                max_t = visit(node.key, t);
+                if max_t <= 0.0 {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn visit_pairs(&self, visit: &mut dyn FnMut(usize, usize)) {
+        for (a, node) in self.nodes.iter().enumerate() {
+            if node.height != 0 {
+                continue; // Internal or free node.
+            }
+            self.visit_fat(&node.aabb, |b| {
+                // Each pair is found from both leaves; report it from the first.
+                let other = &self.nodes[b];
+                if a < b && node.aabb.overlaps(&other.aabb) {
+                    visit(node.key, other.key);
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! Broad phase: finds the objects that might touch, using only their bounding boxes.
+//!
+//! Each structure stores boxes under a key chosen by the caller (the world uses body indices) and
+//! answers region, ray and pair queries about them. [`SpatialHash`] suits many similar-sized
+//! objects such as bullets; [`DynamicAabbTree`] copes with any mix of sizes and large empty areas.
+
+mod aabb_tree;
+mod spatial_hash;
+
+pub use aabb_tree::DynamicAabbTree;
+pub use spatial_hash::SpatialHash;
+
+use crate::math::{Aabb, Vec2};
+
+/// A structure that keeps track of bounding boxes and finds the ones near a region, ray or each other.
+pub trait BroadPhase: std::fmt::Debug + Send + Sync {
+    /// Adds a box under `key`, replacing any box already stored under it.
+    fn insert(&mut self, key: usize, aabb: Aabb);
+
+    /// Moves the box stored under `key`, inserting it if there was none.
+    fn update(&mut self, key: usize, aabb: Aabb);
+
+    /// Removes the box stored under `key`. Returns false if there was none.
+    fn remove(&mut self, key: usize) -> bool;
+
+    fn clear(&mut self);
+
+    /// The number of boxes stored.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The box stored under `key`.
+    fn aabb(&self, key: usize) -> Option<Aabb>;
+
+    /// Calls `visit` once with the key of every box that overlaps `region`.
+    fn visit_region(&self, region: &Aabb, visit: &mut dyn FnMut(usize));
+
+    /// Calls `visit` with the key of every box the ray `origin + t * direction` crosses for `t` in
+    /// `0..=max_t`, roughly nearest first.
+    ///
+    /// `visit` returns the new `max_t`: return it unchanged to see every box, a smaller value to
+    /// only look for boxes closer than a hit already found, or 0.0 to stop.
+    fn visit_ray(
+        &self,
+        origin: Vec2,
+        direction: Vec2,
+        max_t: f32,
+        visit: &mut dyn FnMut(usize, f32) -> f32,
+    );
+
+    /// Calls `visit` once for every pair of overlapping boxes.
+    fn visit_pairs(&self, visit: &mut dyn FnMut(usize, usize));
+
+    /// The keys of the boxes that overlap `region`.
+    fn query_region(&self, region: &Aabb) -> Vec<usize> {
+        let mut keys = Vec::new();
+        self.visit_region(region, &mut |key| keys.push(key));
+        keys
+    }
+
+    /// Every pair of overlapping boxes, with the smaller key first, sorted.
+    fn pairs(&self) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        self.visit_pairs(&mut |a, b| pairs.push((a.min(b), a.max(b))));
+        pairs.sort_unstable();
+        pairs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Small deterministic random numbers (xorshift64*), so failures can be replayed.
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> f32 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            (self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 40) as f32 / (1u64 << 24) as f32
+        }
+
+        fn range(&mut self, min: f32, max: f32) -> f32 {
+            min + (max - min) * self.next()
+        }
+
+        // A box somewhere in a 50 unit square, mostly small but now and then huge.
+        fn aabb(&mut self) -> Aabb {
+            let center = Vec2::new(self.range(-25.0, 25.0), self.range(-25.0, 25.0));
+            let scale = if self.next() < 0.05 { 30.0 } else { 2.0 };
+            let half = Vec2::new(self.range(0.05, scale), self.range(0.05, scale)) * 0.5;
+            Aabb::from_center(center, half)
+        }
+    }
+
+    // The reference answers: every box checked against everything.
+    #[derive(Default)]
+    struct BruteForce(Vec<Option<Aabb>>);
+
+    impl BruteForce {
+        fn live(&self) -> impl Iterator<Item = (usize, Aabb)> + '_ {
+            self.0
+                .iter()
+                .enumerate()
+                .filter_map(|(key, aabb)| aabb.map(|aabb| (key, aabb)))
+        }
+
+        fn pairs(&self) -> Vec<(usize, usize)> {
+            let mut pairs = Vec::new();
+            for (a, box_a) in self.live() {
+                for (b, box_b) in self.live() {
+                    if a < b && box_a.overlaps(&box_b) {
+                        pairs.push((a, b));
+                    }
+                }
+            }
+            pairs
+        }
+
+        fn region(&self, region: &Aabb) -> Vec<usize> {
+            self.live()
+                .filter(|(_, aabb)| aabb.overlaps(region))
+                .map(|(key, _)| key)
+                .collect()
+        }
+
+        fn ray(&self, origin: Vec2, direction: Vec2, max_t: f32) -> Vec<(usize, f32)> {
+            self.live()
+                .filter_map(|(key, aabb)| Some((key, aabb.ray_entry(origin, direction, max_t)?)))
+                .collect()
+        }
+    }
+
+    // Checks every query of `broad_phase` against the brute force.
+    fn assert_matches(broad_phase: &dyn BroadPhase, reference: &BruteForce, random: &mut Random) {
+        assert_eq!(broad_phase.len(), reference.live().count());
+        for (key, aabb) in reference.live() {
+            assert_eq!(broad_phase.aabb(key), Some(aabb));
+        }
+        assert_eq!(broad_phase.pairs(), reference.pairs());
+
+        for _ in 0..50 {
+            let region = random.aabb();
+            let mut found = broad_phase.query_region(&region);
+            found.sort_unstable();
+            assert_eq!(found, reference.region(&region), "in {region:?}");
+        }
+
+        for _ in 0..50 {
+            let origin = Vec2::new(random.range(-40.0, 40.0), random.range(-40.0, 40.0));
+            let direction = Vec2::X.rotate(random.range(0.0, std::f32::consts::TAU));
+            let max_t = random.range(5.0, 80.0);
+            let expected = reference.ray(origin, direction, max_t);
+
+            // Every box crossed, once each, at the same entry.
+            let mut crossed = Vec::new();
+            broad_phase.visit_ray(origin, direction, max_t, &mut |key, t| {
+                crossed.push((key, t));
+                max_t
+            });
+            crossed.sort_unstable_by_key(|&(key, _)| key);
+            assert_eq!(crossed, expected, "ray from {origin:?} along {direction:?}");
+
+            // Clipping to each hit finds the nearest one.
+            let mut nearest = None;
+            broad_phase.visit_ray(origin, direction, max_t, &mut |key, t| {
+                nearest = Some((key, t));
+                t
+            });
+            let closest = expected.iter().map(|&(_, t)| t).reduce(f32::min);
+            assert_eq!(nearest.map(|(_, t)| t), closest);
+        }
+    }
+
+    // Inserts, queries, moves, queries, removes and queries again, checking against brute force
+    // each time.
+    fn check_against_brute_force(mut broad_phase: impl BroadPhase) {
+        let mut random = Random(0x9E37_79B9_7F4A_7C15);
+        let mut reference = BruteForce::default();
+        for key in 0..300 {
+            let aabb = random.aabb();
+            broad_phase.insert(key, aabb);
+            reference.0.push(Some(aabb));
+        }
+        assert_matches(&broad_phase, &reference, &mut random);
+
+        // Nudge most boxes a little and throw some far away.
+        for key in 0..300 {
+            let aabb = if key % 7 == 0 {
+                random.aabb()
+            } else {
+                let nudge = Vec2::new(random.range(-0.3, 0.3), random.range(-0.3, 0.3));
+                let aabb = reference.0[key].expect("every box is live");
+                Aabb::new(aabb.min + nudge, aabb.max + nudge)
+            };
+            broad_phase.update(key, aabb);
+            reference.0[key] = Some(aabb);
+        }
+        assert_matches(&broad_phase, &reference, &mut random);
+
+        for key in (0..300).step_by(3) {
+            assert!(broad_phase.remove(key));
+            assert!(!broad_phase.remove(key), "{key} was removed twice");
+            reference.0[key] = None;
+        }
+        assert_matches(&broad_phase, &reference, &mut random);
+
+        // Removed keys can be used again.
+        for key in (0..300).step_by(6) {
+            let aabb = random.aabb();
+            broad_phase.insert(key, aabb);
+            reference.0[key] = Some(aabb);
+        }
+        assert_matches(&broad_phase, &reference, &mut random);
+
+        broad_phase.clear();
+        assert!(broad_phase.is_empty());
+        assert!(broad_phase.pairs().is_empty());
+    }
+
+    #[test]
+    fn spatial_hash_matches_brute_force() {
+        check_against_brute_force(SpatialHash::new(2.5));
+    }
+
+    #[test]
+    fn spatial_hash_with_small_cells_matches_brute_force() {
+        // Most boxes cover several cells and the large ones go in the oversized list.
+        check_against_brute_force(SpatialHash::new(0.5));
+    }
+
+    #[test]
+    fn aabb_tree_matches_brute_force() {
+        check_against_brute_force(DynamicAabbTree::default());
+    }
+
+    #[test]
+    fn aabb_tree_without_margin_matches_brute_force() {
+        check_against_brute_force(DynamicAabbTree::new(0.0));
+    }
+
+    #[test]
+    fn aabb_tree_stays_balanced() {
+        let mut tree = DynamicAabbTree::default();
+        // Boxes in a row are the worst case for an unbalanced tree.
+        for key in 0..1024 {
+            let center = Vec2::new(key as f32, 0.0);
+            tree.insert(key, Aabb::from_center(center, Vec2::new(0.4, 0.4)));
+        }
+        assert!(tree.height() < 24, "height {}", tree.height());
+    }
+}
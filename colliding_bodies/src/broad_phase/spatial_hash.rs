@@ -0,0 +1,277 @@
+use std::collections::{HashMap, HashSet};
+
+use super::BroadPhase;
+use crate::math::{Aabb, Vec2};
+
+// Boxes covering more cells than this are kept in a separate list and checked against everything,
+// so one huge floor does not fill thousands of cells.
+const MAX_CELLS_PER_BOX: i64 = 64;
+
+type Cell = (i32, i32);
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    aabb: Aabb,
+    cells: Option<(Cell, Cell)>, // First and last cell covered; `None` for oversized boxes.
+}
+
+/// A uniform grid of square cells, stored sparsely in a hash map.
+///
+/// Each box is listed in every cell it covers. Works best when the cell size is a little larger
+/// than the typical object, and most objects have a similar size.
+#[derive(Debug, Clone)]
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<usize>>,
+    entries: HashMap<usize, Entry>,
+    oversized: Vec<usize>,
+    bounds: Option<Aabb>, // Covers every box inserted since the last `clear`.
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "the cell size must be positive");
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            entries: HashMap::new(),
+            oversized: Vec::new(),
+            bounds: None,
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// The number of cells holding at least one box.
+    pub fn occupied_cells(&self) -> usize {
+        self.cells.len()
+    }
+
+    fn cell_of(&self, point: Vec2) -> Cell {
+        (
+            (point.x / self.cell_size).floor() as i32,
+            (point.y / self.cell_size).floor() as i32,
+        )
+    }
+
+    fn cell_range(&self, aabb: &Aabb) -> (Cell, Cell) {
+        (self.cell_of(aabb.min), self.cell_of(aabb.max))
+    }
+
+    fn add_to_cells(&mut self, key: usize, (min, max): (Cell, Cell)) {
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                self.cells.entry((x, y)).or_default().push(key);
+            }
+        }
+    }
+
+    fn remove_from_cells(&mut self, key: usize, (min, max): (Cell, Cell)) {
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                if let Some(keys) = self.cells.get_mut(&(x, y)) {
+                    if let Some(position) = keys.iter().position(|&k| k == key) {
+                        keys.swap_remove(position);
+                    }
+                    if keys.is_empty() {
+                        self.cells.remove(&(x, y));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl BroadPhase for SpatialHash {
+    fn insert(&mut self, key: usize, aabb: Aabb) {
+        self.remove(key);
+        let (min, max) = self.cell_range(&aabb);
+        let count = (max.0 as i64 - min.0 as i64 + 1) * (max.1 as i64 - min.1 as i64 + 1);
+        let cells = if count > MAX_CELLS_PER_BOX {
+            self.oversized.push(key);
+            None
+        } else {
+            self.add_to_cells(key, (min, max));
+            Some((min, max))
+        };
+        self.entries.insert(key, Entry { aabb, cells });
+        self.bounds = Some(self.bounds.map_or(aabb, |bounds| bounds.union(&aabb)));
+    }
+
+    fn update(&mut self, key: usize, aabb: Aabb) {
+        let range = self.cell_range(&aabb);
+        match self.entries.get_mut(&key) {
+            // Still in the same cells: only the box changes.
+            Some(entry) if entry.cells == Some(range) => {
+                entry.aabb = aabb;
+                self.bounds = Some(self.bounds.map_or(aabb, |bounds| bounds.union(&aabb)));
+            }
+            _ => self.insert(key, aabb),
+        }
+    }
+
+    fn remove(&mut self, key: usize) -> bool {
+        let Some(entry) = self.entries.remove(&key) else {
+            return false;
+        };
+        match entry.cells {
+            Some(range) => self.remove_from_cells(key, range),
+            None => self.oversized.retain(|&k| k != key),
+        }
+        true
+    }
+
+    fn clear(&mut self) {
+        self.cells.clear();
+        self.entries.clear();
+        self.oversized.clear();
+        self.bounds = None;
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn aabb(&self, key: usize) -> Option<Aabb> {
+        self.entries.get(&key).map(|entry| entry.aabb)
+    }
+
+    fn visit_region(&self, region: &Aabb, visit: &mut dyn FnMut(usize)) {
+        let (min, max) = self.cell_range(region);
+        let count = (max.0 as i64 - min.0 as i64 + 1) * (max.1 as i64 - min.1 as i64 + 1);
+        if count > self.cells.len() as i64 {
+            // Bigger than the occupied part of the grid: cheaper to look at every box.
+            for (&key, entry) in &self.entries {
+                if entry.aabb.overlaps(region) {
+                    visit(key);
+                }
+            }
+            return;
+        }
+
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                let Some(keys) = self.cells.get(&(x, y)) else {
+                    continue;
+                };
+                for &key in keys {
+                    let aabb = self.entries[&key].aabb;
+                    // A box spanning several cells is reported from the cell holding the corner
+                    // where it starts to overlap the region, and so only once.
+                    if aabb.overlaps(region) && self.cell_of(aabb.min.max(region.min)) == (x, y) {
+                        visit(key);
+                    }
+                }
+            }
+        }
+        for &key in &self.oversized {
+            if self.entries[&key].aabb.overlaps(region) {
+                visit(key);
+            }
+        }
+    }
+
+    fn visit_ray(
+        &self,
+        origin: Vec2,
+        direction: Vec2,
+        max_t: f32,
+        visit: &mut dyn FnMut(usize, f32) -> f32,
+    ) {
+        let mut max_t = max_t;
+        let mut seen = HashSet::new();
+
+        // Oversized boxes first, as they are not in the cells.
+        for &key in &self.oversized {
+            if let Some(t) = self.entries[&key].aabb.ray_entry(origin, direction, max_t) {
+                max_t = visit(key, t);
+                if max_t <= 0.0 {
+                    return;
+                }
+            }
+        }
+
+        // Only walk the part of the ray inside the area where boxes have been.
+        let Some((start, end)) = self
+            .bounds
+            .and_then(|bounds| bounds.ray_interval(origin, direction, max_t))
+        else {
+            return;
+        };
+
+        // Walk the cells along the ray (Amanatides and Woo).
+        let mut cell = self.cell_of(origin + direction * start);
+        let step = (
+            if direction.x < 0.0 { -1 } else { 1 },
+            if direction.y < 0.0 { -1 } else { 1 },
+        );
+        let boundary = |cell: i32, step: i32, origin: f32, direction: f32| {
+            if direction.abs() < f32::EPSILON {
+                return (f32::INFINITY, f32::INFINITY);
+            }
+            let next = (cell + (step > 0) as i32) as f32 * self.cell_size;
+            (
+                (next - origin) / direction,
+                self.cell_size / direction.abs(),
+            )
+        };
+        let (mut next_x, delta_x) = boundary(cell.0, step.0, origin.x, direction.x);
+        let (mut next_y, delta_y) = boundary(cell.1, step.1, origin.y, direction.y);
+        let mut cell_start = start;
+        while cell_start <= max_t.min(end) {
+            if let Some(keys) = self.cells.get(&cell) {
+                for &key in keys {
+                    if !seen.insert(key) {
+                        continue;
+                    }
+                    if let Some(t) = self.entries[&key].aabb.ray_entry(origin, direction, max_t) {
+                        max_t = visit(key, t);
+                        if max_t <= 0.0 {
+                            return;
+                        }
+                    }
+                }
+            }
+            if next_x < next_y {
+                cell_start = next_x;
+                next_x += delta_x;
+                cell.0 += step.0;
+            } else {
+                cell_start = next_y;
+                next_y += delta_y;
+                cell.1 += step.1;
+            }
+        }
+    }
+
+    fn visit_pairs(&self, visit: &mut dyn FnMut(usize, usize)) {
+        for (&cell, keys) in &self.cells {
+            for (i, &a) in keys.iter().enumerate() {
+                let box_a = self.entries[&a].aabb;
+                for &b in &keys[i + 1..] {
+                    let box_b = self.entries[&b].aabb;
+                    // Report each pair only from the cell holding the corner of their overlap.
+                    if box_a.overlaps(&box_b) && self.cell_of(box_a.min.max(box_b.min)) == cell {
+                        visit(a, b);
+                    }
+                }
+            }
+        }
+        for (i, &a) in self.oversized.iter().enumerate() {
+            let box_a = self.entries[&a].aabb;
+            for (&b, entry) in &self.entries {
+                // Pairs of oversized boxes are reported by the first of the two.
+                let earlier_oversized =
+                    entry.cells.is_none() && !self.oversized[i + 1..].contains(&b);
+                if b == a || earlier_oversized {
+                    continue;
+                }
+                if box_a.overlaps(&entry.aabb) {
+                    visit(a, b);
+                }
+            }
+        }
+    }
+}
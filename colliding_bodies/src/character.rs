@@ -0,0 +1,361 @@
+//! A kinematic character controller: moves a shape through a world with shape casts, sliding along
+//! walls, walking up steps and gentle slopes and following the ground down.
+//!
+//! The character is not simulated. It goes exactly where it is told unless something is in the
+//! way, and is never pushed by the bodies around it. It can also be a body in the world (usually a
+//! kinematic one, so that dynamic bodies bump into it) as long as the filter leaves that body out;
+//! [`CharacterController::move_body`] does this for you.
+
+use crate::body::RigidBody;
+use crate::collision::collide_with_margin;
+use crate::math::Vec2;
+use crate::shape::{Pose, Shape};
+use crate::world::{BodyHandle, ShapeCastHit, World};
+
+const DEPENETRATION_ITERATIONS: usize = 4;
+const MIN_MOVE: f32 = 1e-5; // Moves shorter than this are not worth a shape cast.
+
+/// How a character moves, and the shape it moves with.
+///
+/// The shape never turns, so boxes, circles and upright polygons all make good characters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharacterController {
+    pub shape: Shape,
+    pub up: Vec2,             // Unit vector pointing away from the ground.
+    pub max_slope_angle: f32, // Steepest slope, in radians, the character can stand on and walk up.
+    pub step_height: f32,     // Tallest ledge the character walks up without jumping.
+    pub snap_distance: f32,   // How far down the character follows the ground it walks off.
+    pub skin_width: f32,      // Gap kept between the character and everything around it.
+    pub max_slides: u32,      // Surfaces the character can slide along in one move.
+}
+
+/// Something the character touched while moving.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CharacterContact {
+    pub body: BodyHandle,
+    pub point: Vec2,  // World position of the contact on the body.
+    pub normal: Vec2, // The body's surface normal at the contact, facing the character.
+}
+
+/// Where a character ended up after a move, and what it met on the way.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharacterMove {
+    pub position: Vec2,                   // The character's new position.
+    pub translation: Vec2,                // How far it actually moved.
+    pub grounded: bool,                   // Standing on a walkable surface at the end of the move.
+    pub ground: Option<CharacterContact>, // What it stands on, when grounded.
+    pub contacts: Vec<CharacterContact>,  // Everything it ran into, in order.
+}
+
+// One attempt at moving, kept until it is known to be the best one.
+#[derive(Debug, Clone)]
+struct Slide {
+    position: Vec2,
+    blocked: bool, // Stopped or deflected by something too steep to walk on.
+    contacts: Vec<CharacterContact>,
+}
+
+impl CharacterController {
+    /// A controller for `shape` that walks up slopes of up to 45 degrees and steps of up to 0.3
+    /// units, in a world where up is +y.
+    pub fn new(shape: Shape) -> Self {
+        Self {
+            shape,
+            up: Vec2::new(0.0, 1.0),
+            max_slope_angle: std::f32::consts::FRAC_PI_4,
+            step_height: 0.3,
+            snap_distance: 0.2,
+            skin_width: 0.01,
+            max_slides: 4,
+        }
+    }
+
+    pub fn with_up(mut self, up: Vec2) -> Self {
+        self.up = up.normalize_or_zero();
+        self
+    }
+
+    pub fn with_max_slope_angle(mut self, max_slope_angle: f32) -> Self {
+        self.max_slope_angle = max_slope_angle;
+        self
+    }
+
+    pub fn with_step_height(mut self, step_height: f32) -> Self {
+        self.step_height = step_height;
+        self
+    }
+
+    pub fn with_snap_distance(mut self, snap_distance: f32) -> Self {
+        self.snap_distance = snap_distance;
+        self
+    }
+
+    pub fn with_skin_width(mut self, skin_width: f32) -> Self {
+        self.skin_width = skin_width;
+        self
+    }
+
+    /// Returns true if the character can stand on a surface with this normal.
+    pub fn is_walkable(&self, normal: Vec2) -> bool {
+        normal.dot(self.up) >= self.max_slope_angle.cos() - 1e-6
+    }
+
+    /// Moves a character at `position` by `translation` through the bodies `filter` accepts.
+    ///
+    /// The world is only read: use the returned position to place the character.
+    pub fn move_character(
+        &self,
+        world: &World,
+        position: Vec2,
+        translation: Vec2,
+        filter: impl Fn(BodyHandle, &RigidBody) -> bool,
+    ) -> CharacterMove {
+        let mover = Mover {
+            controller: self,
+            world,
+            filter: &filter,
+        };
+        let start = mover.depenetrate(position);
+        let was_grounded = mover.ground(start).is_some();
+
+        let mut slide = mover.slide(start, translation, was_grounded);
+        if was_grounded && slide.blocked && self.step_height > 0.0 {
+            let horizontal = translation - self.up * translation.dot(self.up);
+            let progress = |position: Vec2| (position - start).dot(horizontal);
+            if let Some(step) = mover.step_up(start, horizontal) {
+                if progress(step.position) > progress(slide.position) + MIN_MOVE {
+                    slide = step;
+                }
+            }
+        }
+
+        // Follow the ground down slopes and off small ledges, unless moving up (jumping).
+        let mut end = slide.position;
+        if was_grounded && translation.dot(self.up) <= 0.0 && mover.ground(end).is_none() {
+            if let (snapped, Some(hit)) = mover.advance(end, -self.up * self.snap_distance) {
+                if self.is_walkable(hit.intersection.normal) {
+                    end = snapped;
+                }
+            }
+        }
+
+        let ground = mover.ground(end);
+        CharacterMove {
+            position: end,
+            translation: end - position,
+            grounded: ground.is_some(),
+            ground,
+            contacts: slide.contacts,
+        }
+    }
+
+    /// Moves the character's own body in `world` by `translation`, colliding with every other
+    /// body. Returns `None` if the handle is stale.
+    pub fn move_body(
+        &self,
+        world: &mut World,
+        handle: BodyHandle,
+        translation: Vec2,
+    ) -> Option<CharacterMove> {
+        let position = world.body(handle)?.position;
+        let result = self.move_character(world, position, translation, |other, _| other != handle);
+        world.body_mut(handle)?.position = result.position;
+        Some(result)
+    }
+}
+
+// The world and filter a character moves through, for the duration of one move.
+struct Mover<'a, F> {
+    controller: &'a CharacterController,
+    world: &'a World,
+    filter: &'a F,
+}
+
+impl<F: Fn(BodyHandle, &RigidBody) -> bool> Mover<'_, F> {
+    fn cast(&self, position: Vec2, translation: Vec2) -> Option<ShapeCastHit> {
+        let pose = Pose::new(position, 0.0);
+        self.world
+            .shape_cast(&self.controller.shape, pose, translation, self.filter)
+    }
+
+    // Moves by `translation` until something is in the way, stopping the skin width short of it
+    // along the way it came. Stepping back along the motion rather than out along the surface
+    // normal keeps a character pushing into a steep slope from creeping up it.
+    fn advance(&self, position: Vec2, translation: Vec2) -> (Vec2, Option<ShapeCastHit>) {
+        let distance = translation.length();
+        if distance < MIN_MOVE {
+            return (position, None);
+        }
+        let Some(hit) = self.cast(position, translation) else {
+            return (position + translation, None);
+        };
+        let direction = translation / distance;
+        let travelled = distance * hit.intersection.time;
+        let approach = -direction.dot(hit.intersection.normal);
+        let back = if approach > 0.0 {
+            (self.controller.skin_width / approach).min(travelled)
+        } else {
+            travelled
+        };
+        (position + direction * (travelled - back), Some(hit))
+    }
+
+    // The walkable surface right under the character, if any. Steep surfaces touching the
+    // character, such as the foot of a slope it is pushing against, are looked past.
+    fn ground(&self, position: Vec2) -> Option<CharacterContact> {
+        let pose = Pose::new(position, 0.0);
+        let probe = -self.controller.up * (self.controller.skin_width * 2.0);
+        let mut steep = Vec::new();
+        for _ in 0..self.controller.max_slides {
+            let hit =
+                self.world
+                    .shape_cast(&self.controller.shape, pose, probe, |handle, body| {
+                        !steep.contains(&handle) && (self.filter)(handle, body)
+                    })?;
+            if self.controller.is_walkable(hit.intersection.normal) {
+                return Some(contact(hit));
+            }
+            steep.push(hit.body);
+        }
+        None
+    }
+
+    // Pushes the character out of anything closer than the skin width, so that a move starts from a
+    // clean gap wherever the character was put.
+    fn depenetrate(&self, mut position: Vec2) -> Vec2 {
+        let (shape, skin) = (&self.controller.shape, self.controller.skin_width);
+        for _ in 0..DEPENETRATION_ITERATIONS {
+            let pose = Pose::new(position, 0.0);
+            let region = shape.aabb_at(pose).expanded(skin);
+            let mut moved = false;
+            for handle in self.world.query_region(&region) {
+                let Some(body) = self.world.body(handle) else {
+                    continue;
+                };
+                if !(self.filter)(handle, body) {
+                    continue;
+                }
+                let pose = Pose::new(position, 0.0);
+                let Some(manifold) =
+                    collide_with_margin(shape, pose, &body.shape, body.pose(), skin)
+                else {
+                    continue;
+                };
+                let depth = manifold
+                    .contacts
+                    .iter()
+                    .map(|c| c.depth)
+                    .fold(f32::MIN, f32::max);
+                let push = depth + skin;
+                if push > skin * 0.01 {
+                    position -= manifold.normal * push;
+                    moved = true;
+                }
+            }
+            if !moved {
+                break;
+            }
+        }
+        position
+    }
+
+    // Moves as far along `translation` as possible, sliding along whatever is hit. When
+    // `grounded`, surfaces too steep to walk on are treated as upright walls, so the character
+    // cannot climb them by sliding.
+    fn slide(&self, mut position: Vec2, translation: Vec2, mut grounded: bool) -> Slide {
+        let controller = self.controller;
+        let up = controller.up;
+        let mut remaining = translation;
+        let mut blocked = false;
+        let mut contacts = Vec::new();
+        for _ in 0..controller.max_slides {
+            if remaining.length() < MIN_MOVE {
+                break;
+            }
+            let (moved, hit) = self.advance(position, remaining);
+            remaining -= moved - position;
+            position = moved;
+            let Some(hit) = hit else {
+                break;
+            };
+            contacts.push(contact(hit));
+
+            let mut normal = hit.intersection.normal;
+            if controller.is_walkable(normal) {
+                // Landing on or walking into the ground: keep going along it, without sliding
+                // down.
+                grounded = true;
+                remaining -= up * remaining.dot(up).min(0.0);
+            } else {
+                blocked = true;
+                let flat = normal - up * normal.dot(up);
+                if grounded && flat != Vec2::ZERO {
+                    normal = flat.normalize_or_zero();
+                }
+            }
+            let into = remaining.dot(normal);
+            if into < 0.0 {
+                remaining -= normal * into;
+            }
+            // Never turn back: that only happens when wedged into a corner.
+            if remaining.dot(translation) <= 0.0 {
+                break;
+            }
+        }
+        Slide {
+            position,
+            blocked,
+            contacts,
+        }
+    }
+
+    // Tries to walk over a ledge: up by the step height, across, and back down onto walkable
+    // ground.
+    fn step_up(&self, start: Vec2, horizontal: Vec2) -> Option<Slide> {
+        let controller = self.controller;
+        if horizontal.length() < MIN_MOVE {
+            return None;
+        }
+        let lift = controller.up * controller.step_height;
+        let (raised, _) = self.advance(start, lift);
+        let mut across = self.slide(raised, horizontal, true);
+        let drop = -controller.up * (raised - start).dot(controller.up);
+        let (landed, hit) = self.advance(across.position, drop);
+        let hit = hit.filter(|hit| controller.is_walkable(hit.intersection.normal))?;
+        across.position = landed;
+        across.contacts.push(contact(hit));
+        Some(across)
+    }
+}
+
+fn contact(hit: ShapeCastHit) -> CharacterContact {
+    CharacterContact {
+        body: hit.body,
+        point: hit.intersection.point,
+        normal: hit.intersection.normal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Zero-length moves come from snapping with no snap distance and from step-ups that could not
+    // lift; touching something at the start must not turn them into a NaN direction.
+    #[test]
+    fn zero_moves_while_touching_stay_put() {
+        let mut world = World::default();
+        world.add_body(RigidBody::fixed(Shape::aabb(4.0, 1.0)).with_position(Vec2::new(0.0, -0.5)));
+        let controller = CharacterController::new(Shape::aabb(0.5, 1.0));
+        let mover = Mover {
+            controller: &controller,
+            world: &world,
+            filter: &|_, _: &RigidBody| true,
+        };
+        let touching = Vec2::new(0.0, 0.5);
+        assert!(mover.cast(touching, Vec2::ZERO).is_some());
+        let (position, hit) = mover.advance(touching, Vec2::ZERO);
+        assert_eq!(position, touching);
+        assert!(hit.is_none());
+    }
+}
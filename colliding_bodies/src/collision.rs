@@ -0,0 +1,387 @@
+//! Narrow phase: exact contact generation between two placed shapes using the separating axis test.
+
+use crate::math::Vec2;
+use crate::shape::{Polygon, Pose, Shape};
+
+/// A point where two shapes touch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contact {
+    pub point: Vec2,          // World position, halfway between the two surfaces.
+    pub depth: f32,           // Overlap along the normal; negative for a gap within the margin.
+    pub normal_impulse: f32,  // Impulse the world applied along the normal in the last step.
+    pub tangent_impulse: f32, // Friction impulse the world applied in the last step.
+}
+
+impl Contact {
+    fn new(point: Vec2, depth: f32) -> Self {
+        Self {
+            point,
+            depth,
+            normal_impulse: 0.0,
+            tangent_impulse: 0.0,
+        }
+    }
+}
+
+/// The contacts between two overlapping shapes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifold {
+    pub normal: Vec2, // Unit vector pointing from the first shape to the second.
+    pub contacts: Vec<Contact>, // One or two points.
+}
+
+impl Manifold {
+    /// The deepest overlap of the manifold.
+    pub fn depth(&self) -> f32 {
+        self.contacts.iter().map(|c| c.depth).fold(0.0, f32::max)
+    }
+
+    // The same contacts seen from the other shape.
+    fn flipped(mut self) -> Self {
+        self.normal = -self.normal;
+        self
+    }
+}
+
+/// Finds the contacts between shape `a` at `pose_a` and shape `b` at `pose_b`, if they overlap.
+pub fn collide(a: &Shape, pose_a: Pose, b: &Shape, pose_b: Pose) -> Option<Manifold> {
+    collide_with_margin(a, pose_a, b, pose_b, 0.0)
+}
+
+/// Like `collide`, but also reports contacts between shapes less than `margin` apart.
+///
+/// These speculative contacts have a negative depth. The world uses them so that resting contacts
+/// do not come and go when bodies separate by a hair.
+pub fn collide_with_margin(
+    a: &Shape,
+    pose_a: Pose,
+    b: &Shape,
+    pose_b: Pose,
+    margin: f32,
+) -> Option<Manifold> {
+    // Boxes never rotate, whatever angle their body reports.
+    let pose_a = fix_pose(a, pose_a);
+    let pose_b = fix_pose(b, pose_b);
+    match (a, b) {
+        (Shape::Circle { radius: ra }, Shape::Circle { radius: rb }) => {
+            circle_circle(pose_a.position, *ra, pose_b.position, *rb, margin)
+        }
+        (Shape::Circle { radius }, _) => {
+            let polygon = b.as_polygon()?;
+            polygon_circle(&polygon, pose_b, pose_a.position, *radius, margin)
+                .map(Manifold::flipped)
+        }
+        (_, Shape::Circle { radius }) => {
+            let polygon = a.as_polygon()?;
+            polygon_circle(&polygon, pose_a, pose_b.position, *radius, margin)
+        }
+        _ => {
+            let (polygon_a, polygon_b) = (a.as_polygon()?, b.as_polygon()?);
+            polygon_polygon(&polygon_a, pose_a, &polygon_b, pose_b, margin)
+        }
+    }
+}
+
+// Axis-aligned boxes ignore the body angle.
+fn fix_pose(shape: &Shape, pose: Pose) -> Pose {
+    match shape {
+        Shape::Aabb { .. } => Pose::new(pose.position, 0.0),
+        _ => pose,
+    }
+}
+
+fn circle_circle(a: Vec2, ra: f32, b: Vec2, rb: f32, margin: f32) -> Option<Manifold> {
+    let offset = b - a;
+    let distance_squared = offset.length_squared();
+    let radii = ra + rb;
+    if distance_squared > (radii + margin) * (radii + margin) {
+        return None;
+    }
+    let distance = distance_squared.sqrt();
+    // Concentric circles have no direction to separate in, so pick one.
+    let normal = if distance > f32::EPSILON {
+        offset / distance
+    } else {
+        Vec2::Y
+    };
+    let depth = radii - distance;
+    Some(Manifold {
+        normal,
+        contacts: vec![Contact::new(a + normal * (ra - depth * 0.5), depth)],
+    })
+}
+
+// Contacts between a polygon and a circle; the normal points from the polygon to the circle.
+fn polygon_circle(
+    polygon: &Polygon,
+    pose: Pose,
+    center: Vec2,
+    radius: f32,
+    margin: f32,
+) -> Option<Manifold> {
+    let local = pose.inverse_apply(center);
+    let vertices = polygon.vertices();
+    let normals = polygon.normals();
+
+    // Find the edge the centre is furthest in front of.
+    let (face, separation) = normals
+        .iter()
+        .zip(vertices)
+        .map(|(n, v)| n.dot(local - *v))
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+    let reach = radius + margin;
+    if separation > reach {
+        return None;
+    }
+
+    let v1 = vertices[face];
+    let v2 = vertices[(face + 1) % vertices.len()];
+    let (normal, depth) = if separation < f32::EPSILON {
+        // The centre is inside the polygon: push out through the nearest face.
+        (normals[face], radius - separation)
+    } else if (local - v1).dot(v2 - v1) <= 0.0 {
+        // Closest to the first corner of the edge.
+        let offset = local - v1;
+        if offset.length_squared() > reach * reach {
+            return None;
+        }
+        (offset.normalize_or_zero(), radius - offset.length())
+    } else if (local - v2).dot(v1 - v2) <= 0.0 {
+        // Closest to the second corner.
+        let offset = local - v2;
+        if offset.length_squared() > reach * reach {
+            return None;
+        }
+        (offset.normalize_or_zero(), radius - offset.length())
+    } else {
+        // Closest to the face itself.
+        (normals[face], radius - separation)
+    };
+
+    let normal = normal.rotate(pose.angle);
+    Some(Manifold {
+        normal,
+        contacts: vec![Contact::new(
+            center - normal * (radius - depth * 0.5),
+            depth,
+        )],
+    })
+}
+
+// The edge of `a` whose normal separates `b` the most, and that separation.
+fn max_separation(a: &Polygon, pose_a: Pose, b: &Polygon, pose_b: Pose) -> (usize, f32) {
+    let mut best = (0, f32::NEG_INFINITY);
+    for (i, (&normal, &vertex)) in a.normals().iter().zip(a.vertices()).enumerate() {
+        // Work in b's local space.
+        let normal_b = normal.rotate(pose_a.angle - pose_b.angle);
+        let vertex_b = pose_b.inverse_apply(pose_a.apply(vertex));
+        let deepest = b.support(-normal_b);
+        let separation = normal_b.dot(deepest - vertex_b);
+        if separation > best.1 {
+            best = (i, separation);
+        }
+    }
+    best
+}
+
+// Contacts between two convex polygons, by clipping the incident edge against the reference face.
+fn polygon_polygon(
+    a: &Polygon,
+    pose_a: Pose,
+    b: &Polygon,
+    pose_b: Pose,
+    margin: f32,
+) -> Option<Manifold> {
+    let (edge_a, separation_a) = max_separation(a, pose_a, b, pose_b);
+    if separation_a > margin {
+        return None;
+    }
+    let (edge_b, separation_b) = max_separation(b, pose_b, a, pose_a);
+    if separation_b > margin {
+        return None;
+    }
+
+    // Prefer `a` as the reference so contacts do not flicker between near-equal faces.
+    const RELATIVE_TOLERANCE: f32 = 0.98;
+    const ABSOLUTE_TOLERANCE: f32 = 0.001;
+    let flip = separation_b > RELATIVE_TOLERANCE * separation_a + ABSOLUTE_TOLERANCE;
+    let (reference, reference_pose, incident, incident_pose, face) = if flip {
+        (b, pose_b, a, pose_a, edge_b)
+    } else {
+        (a, pose_a, b, pose_b, edge_a)
+    };
+
+    // Reference face in world space.
+    let count = reference.vertices().len();
+    let v1 = reference_pose.apply(reference.vertices()[face]);
+    let v2 = reference_pose.apply(reference.vertices()[(face + 1) % count]);
+    let normal = reference.normals()[face].rotate(reference_pose.angle);
+    let tangent = (v2 - v1).normalize_or_zero();
+
+    // The incident edge is the one on the other polygon facing most against the reference normal.
+    let incident_count = incident.vertices().len();
+    let local_normal = normal.rotate(-incident_pose.angle);
+    let incident_edge = (0..incident_count)
+        .min_by(|&i, &j| {
+            incident.normals()[i]
+                .dot(local_normal)
+                .total_cmp(&incident.normals()[j].dot(local_normal))
+        })
+        .unwrap_or(0);
+    let mut points = [
+        incident_pose.apply(incident.vertices()[incident_edge]),
+        incident_pose.apply(incident.vertices()[(incident_edge + 1) % incident_count]),
+    ];
+
+    // Clip the incident edge to the sides of the reference face.
+    for (side_normal, offset) in [(-tangent, -tangent.dot(v1)), (tangent, tangent.dot(v2))] {
+        let distances = points.map(|p| side_normal.dot(p) - offset);
+        match (distances[0] <= 0.0, distances[1] <= 0.0) {
+            (true, true) => {}
+            (false, false) => return None,
+            (inside_0, _) => {
+                let t = distances[0] / (distances[0] - distances[1]);
+                let clipped = points[0].lerp(points[1], t);
+                if inside_0 {
+                    points[1] = clipped;
+                } else {
+                    points[0] = clipped;
+                }
+            }
+        }
+    }
+
+    // Keep the points that are behind the reference face, or within the margin in front of it.
+    let contacts: Vec<Contact> = points
+        .iter()
+        .filter_map(|&point| {
+            let separation = normal.dot(point - v1);
+            (separation <= margin)
+                .then(|| Contact::new(point - normal * (separation * 0.5), -separation))
+        })
+        .collect();
+    if contacts.is_empty() {
+        return None;
+    }
+
+    Some(Manifold {
+        normal: if flip { -normal } else { normal },
+        contacts,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn at(x: f32, y: f32) -> Pose {
+        Pose::new(Vec2::new(x, y), 0.0)
+    }
+
+    fn assert_close(actual: Vec2, expected: Vec2) {
+        assert!(
+            (actual - expected).length() < EPSILON,
+            "{actual:?} is not {expected:?}"
+        );
+    }
+
+    #[test]
+    fn overlapping_boxes_share_a_face() {
+        let unit = Shape::rectangle(1.0, 1.0);
+        let manifold = collide(&unit, at(0.0, 0.0), &unit, at(0.9, 0.0)).expect("they overlap");
+        assert_close(manifold.normal, Vec2::X);
+        assert_eq!(manifold.contacts.len(), 2);
+        for contact in &manifold.contacts {
+            assert!((contact.depth - 0.1).abs() < EPSILON);
+            assert!((contact.point.x - 0.45).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn touching_boxes_collide_with_no_depth() {
+        let unit = Shape::aabb(1.0, 1.0);
+        let manifold = collide(&unit, at(0.0, 0.0), &unit, at(0.0, 1.0)).expect("they touch");
+        assert_close(manifold.normal, Vec2::Y);
+        assert!(manifold.contacts.iter().all(|c| c.depth.abs() < EPSILON));
+        assert_eq!(manifold.depth(), 0.0);
+    }
+
+    #[test]
+    fn separated_shapes_only_collide_within_the_margin() {
+        let unit = Shape::aabb(1.0, 1.0);
+        let circle = Shape::circle(0.5);
+        assert!(collide(&unit, at(0.0, 0.0), &unit, at(1.1, 0.0)).is_none());
+        assert!(collide(&unit, at(0.0, 0.0), &circle, at(0.0, 1.1)).is_none());
+        assert!(collide(&circle, at(0.0, 0.0), &circle, at(1.1, 0.0)).is_none());
+
+        let manifold = collide_with_margin(&unit, at(0.0, 0.0), &unit, at(1.1, 0.0), 0.2)
+            .expect("within the margin");
+        assert!(manifold
+            .contacts
+            .iter()
+            .all(|c| (c.depth + 0.1).abs() < EPSILON));
+        assert!(collide_with_margin(&unit, at(0.0, 0.0), &unit, at(1.1, 0.0), 0.05).is_none());
+    }
+
+    #[test]
+    fn a_rotated_box_lands_on_one_corner() {
+        let floor = Shape::rectangle(4.0, 1.0);
+        let diamond = Shape::rectangle(1.0, 1.0);
+        // The diamond's lowest corner is 0.1 below the floor's top face.
+        let height = 0.5 + std::f32::consts::FRAC_1_SQRT_2 - 0.1;
+        let pose = Pose::new(Vec2::new(0.3, height), std::f32::consts::FRAC_PI_4);
+        let manifold = collide(&floor, at(0.0, 0.0), &diamond, pose).expect("they overlap");
+        assert_close(manifold.normal, Vec2::Y);
+        assert_eq!(manifold.contacts.len(), 1);
+        assert!((manifold.depth() - 0.1).abs() < EPSILON);
+        assert_close(manifold.contacts[0].point, Vec2::new(0.3, 0.45));
+
+        // Seen from the diamond, the normal flips.
+        let manifold = collide(&diamond, pose, &floor, at(0.0, 0.0)).expect("they overlap");
+        assert_close(manifold.normal, -Vec2::Y);
+    }
+
+    #[test]
+    fn boxes_ignore_the_angle_of_their_pose() {
+        let unit = Shape::aabb(1.0, 1.0);
+        let turned = Pose::new(Vec2::new(1.05, 0.0), 0.7);
+        // Turned, the box would reach the other; upright it is 0.05 away.
+        assert!(collide(&unit, at(0.0, 0.0), &unit, turned).is_none());
+    }
+
+    #[test]
+    fn circles_push_out_along_the_line_between_centres() {
+        let circle = Shape::circle(1.0);
+        let manifold = collide(&circle, at(0.0, 0.0), &circle, at(1.2, 1.6)).expect("they touch");
+        assert_close(manifold.normal, Vec2::new(0.6, 0.8));
+        assert!(manifold.depth().abs() < EPSILON);
+        let manifold = collide(&circle, at(0.0, 0.0), &circle, at(0.6, 0.8)).expect("they overlap");
+        assert!((manifold.depth() - 1.0).abs() < EPSILON);
+        assert_close(manifold.contacts[0].point, Vec2::new(0.3, 0.4));
+    }
+
+    #[test]
+    fn circles_against_box_corners_and_faces() {
+        let unit = Shape::aabb(2.0, 2.0);
+        let circle = Shape::circle(0.5);
+        // Off the corner at (1, 1), along the diagonal.
+        let offset = 0.4 * std::f32::consts::FRAC_1_SQRT_2;
+        let corner = at(1.0 + offset, 1.0 + offset);
+        let manifold = collide(&unit, at(0.0, 0.0), &circle, corner).expect("they overlap");
+        let diagonal = Vec2::new(1.0, 1.0).normalize_or_zero();
+        assert_close(manifold.normal, diagonal);
+        assert!((manifold.depth() - 0.1).abs() < EPSILON);
+
+        // The same circle seen from the other side.
+        let manifold = collide(&circle, corner, &unit, at(0.0, 0.0)).expect("they overlap");
+        assert_close(manifold.normal, -diagonal);
+
+        // On the face, away from the corners.
+        let manifold = collide(&unit, at(0.0, 0.0), &circle, at(0.2, -1.3)).expect("they overlap");
+        assert_close(manifold.normal, -Vec2::Y);
+        assert!((manifold.depth() - 0.2).abs() < EPSILON);
+    }
+}
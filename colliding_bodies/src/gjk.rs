@@ -0,0 +1,293 @@
+//! The Gilbert–Johnson–Keerthi algorithm: distance and closest points between two convex shapes.
+//!
+//! Circles are handled as a point with a radius, so GJK only has to work on the shapes' cores and
+//! the radii are subtracted at the end.
+
+use crate::math::Vec2;
+use crate::shape::{Pose, Shape};
+
+/// The result of a distance query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DistanceResult {
+    pub distance: f32, // Gap between the surfaces; 0.0 when they touch or overlap.
+    pub point_a: Vec2, // Closest point on the surface of the first shape.
+    pub point_b: Vec2, // Closest point on the surface of the second shape.
+    pub normal: Vec2,  // Unit vector from `point_a` to `point_b` (zero if the cores overlap).
+}
+
+// A vertex of the Minkowski difference, remembering which points of the shapes made it.
+#[derive(Debug, Clone, Copy)]
+struct SupportPoint {
+    a: Vec2,
+    b: Vec2,
+    point: Vec2, // a - b
+}
+
+const MAX_ITERATIONS: usize = 32;
+
+/// The distance between two placed shapes and their closest points.
+pub fn distance(a: &Shape, pose_a: Pose, b: &Shape, pose_b: Pose) -> DistanceResult {
+    let pose_a = fix_pose(a, pose_a);
+    let pose_b = fix_pose(b, pose_b);
+    let (_, radius_a) = a.support(Vec2::X);
+    let (_, radius_b) = b.support(Vec2::X);
+
+    let support = |direction: Vec2| {
+        let (local_a, _) = a.support(direction.rotate(-pose_a.angle));
+        let (local_b, _) = b.support((-direction).rotate(-pose_b.angle));
+        let (point_a, point_b) = (pose_a.apply(local_a), pose_b.apply(local_b));
+        SupportPoint {
+            a: point_a,
+            b: point_b,
+            point: point_a - point_b,
+        }
+    };
+
+    let mut simplex = vec![support(pose_b.position - pose_a.position)];
+    let mut weights = vec![1.0];
+    for _ in 0..MAX_ITERATIONS {
+        (simplex, weights) = solve(&simplex);
+        if simplex.len() == 3 {
+            break; // The origin is inside: the cores overlap.
+        }
+        let direction = search_direction(&simplex);
+        if direction == Vec2::ZERO {
+            break; // The origin is on the simplex: the cores touch.
+        }
+        let next = support(direction);
+        let duplicate = simplex
+            .iter()
+            .any(|s| (s.point - next.point).length_squared() < 1e-12);
+        // Stop when the new point gets no closer to the origin, as far as rounding can tell.
+        let progress = (next.point - simplex[0].point).dot(direction);
+        let scale = next.point.length().max(simplex[0].point.length());
+        if duplicate || progress <= 1e-6 * scale {
+            break;
+        }
+        simplex.push(next);
+    }
+
+    let mut point_a = combine(&simplex, &weights, |s| s.a);
+    let mut point_b = combine(&simplex, &weights, |s| s.b);
+    // Measured along the search direction rather than between the combined points, which lose
+    // precision on long edges.
+    let (core_distance, normal) = match simplex.len() {
+        3 => (0.0, Vec2::ZERO),
+        _ => {
+            let direction = search_direction(&simplex);
+            let core_distance = -simplex[0].point.dot(direction);
+            if core_distance > f32::EPSILON {
+                (core_distance, direction)
+            } else {
+                (0.0, Vec2::ZERO)
+            }
+        }
+    };
+
+    // Move the closest points out from the cores by the radii.
+    point_a += normal * radius_a;
+    point_b -= normal * radius_b;
+    DistanceResult {
+        distance: (core_distance - radius_a - radius_b).max(0.0),
+        point_a,
+        point_b,
+        normal,
+    }
+}
+
+/// Returns true if the two placed shapes touch or overlap.
+pub fn intersects(a: &Shape, pose_a: Pose, b: &Shape, pose_b: Pose) -> bool {
+    distance(a, pose_a, b, pose_b).distance <= 0.0
+}
+
+// Boxes never rotate, whatever angle their body reports.
+fn fix_pose(shape: &Shape, pose: Pose) -> Pose {
+    match shape {
+        Shape::Aabb { .. } => Pose::new(pose.position, 0.0),
+        _ => pose,
+    }
+}
+
+// The unit direction from the simplex towards the origin, or zero if the origin is on it. For a
+// segment this is its perpendicular, which stays accurate however long the segment is.
+fn search_direction(simplex: &[SupportPoint]) -> Vec2 {
+    match *simplex {
+        [w1] => (-w1.point).normalize_or_zero(),
+        [w1, w2] => {
+            let edge = w2.point - w1.point;
+            let perpendicular = if edge.cross(-w1.point) > 0.0 {
+                edge.perp()
+            } else {
+                -edge.perp()
+            };
+            perpendicular.normalize_or_zero()
+        }
+        _ => Vec2::ZERO,
+    }
+}
+
+fn combine(simplex: &[SupportPoint], weights: &[f32], f: impl Fn(&SupportPoint) -> Vec2) -> Vec2 {
+    simplex
+        .iter()
+        .zip(weights)
+        .fold(Vec2::ZERO, |sum, (s, &w)| sum + f(s) * w)
+}
+
+// Reduces the simplex to the smallest part that contains the point closest to the origin, and
+// returns it with the barycentric weights of that point.
+fn solve(simplex: &[SupportPoint]) -> (Vec<SupportPoint>, Vec<f32>) {
+    match *simplex {
+        [w1] => (vec![w1], vec![1.0]),
+        [w1, w2] => solve_segment(w1, w2),
+        [w1, w2, w3] => solve_triangle(w1, w2, w3),
+        _ => unreachable!("the simplex never has more than three points"),
+    }
+}
+
+fn solve_segment(w1: SupportPoint, w2: SupportPoint) -> (Vec<SupportPoint>, Vec<f32>) {
+    let e12 = w2.point - w1.point;
+    let d12_2 = -w1.point.dot(e12);
+    if d12_2 <= 0.0 {
+        return (vec![w1], vec![1.0]);
+    }
+    let d12_1 = w2.point.dot(e12);
+    if d12_1 <= 0.0 {
+        return (vec![w2], vec![1.0]);
+    }
+    let total = d12_1 + d12_2;
+    (vec![w1, w2], vec![d12_1 / total, d12_2 / total])
+}
+
+fn solve_triangle(
+    w1: SupportPoint,
+    w2: SupportPoint,
+    w3: SupportPoint,
+) -> (Vec<SupportPoint>, Vec<f32>) {
+    let (p1, p2, p3) = (w1.point, w2.point, w3.point);
+
+    let e12 = p2 - p1;
+    let (d12_1, d12_2) = (p2.dot(e12), -p1.dot(e12));
+    let e13 = p3 - p1;
+    let (d13_1, d13_2) = (p3.dot(e13), -p1.dot(e13));
+    let e23 = p3 - p2;
+    let (d23_1, d23_2) = (p3.dot(e23), -p2.dot(e23));
+
+    let n123 = e12.cross(e13);
+    let d123_1 = n123 * p2.cross(p3);
+    let d123_2 = n123 * p3.cross(p1);
+    let d123_3 = n123 * p1.cross(p2);
+
+    let edge = |a, b, da: f32, db: f32| (vec![a, b], vec![da / (da + db), db / (da + db)]);
+    if d12_2 <= 0.0 && d13_2 <= 0.0 {
+        (vec![w1], vec![1.0])
+    } else if d12_1 > 0.0 && d12_2 > 0.0 && d123_3 <= 0.0 {
+        edge(w1, w2, d12_1, d12_2)
+    } else if d13_1 > 0.0 && d13_2 > 0.0 && d123_2 <= 0.0 {
+        edge(w1, w3, d13_1, d13_2)
+    } else if d12_1 <= 0.0 && d23_2 <= 0.0 {
+        (vec![w2], vec![1.0])
+    } else if d13_1 <= 0.0 && d23_1 <= 0.0 {
+        (vec![w3], vec![1.0])
+    } else if d23_1 > 0.0 && d23_2 > 0.0 && d123_1 <= 0.0 {
+        edge(w2, w3, d23_1, d23_2)
+    } else {
+        // The origin is inside the triangle.
+        let total = d123_1 + d123_2 + d123_3;
+        (
+            vec![w1, w2, w3],
+            vec![d123_1 / total, d123_2 / total, d123_3 / total],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn at(x: f32, y: f32) -> Pose {
+        Pose::new(Vec2::new(x, y), 0.0)
+    }
+
+    fn assert_close(actual: Vec2, expected: Vec2) {
+        assert!(
+            (actual - expected).length() < EPSILON,
+            "{actual:?} is not {expected:?}"
+        );
+    }
+
+    #[test]
+    fn separated_boxes() {
+        let unit = Shape::rectangle(1.0, 1.0);
+        let result = distance(&unit, at(0.0, 0.0), &unit, at(3.0, 0.0));
+        assert!((result.distance - 2.0).abs() < EPSILON);
+        assert_close(result.normal, Vec2::X);
+        assert!((result.point_a.x - 0.5).abs() < EPSILON);
+        assert!((result.point_b.x - 2.5).abs() < EPSILON);
+        assert!(!intersects(&unit, at(0.0, 0.0), &unit, at(3.0, 0.0)));
+    }
+
+    #[test]
+    fn box_corner_to_corner() {
+        let unit = Shape::aabb(1.0, 1.0);
+        let result = distance(&unit, at(0.0, 0.0), &unit, at(4.0, 5.0));
+        assert!((result.distance - 5.0).abs() < EPSILON);
+        assert_close(result.point_a, Vec2::new(0.5, 0.5));
+        assert_close(result.point_b, Vec2::new(3.5, 4.5));
+        assert_close(result.normal, Vec2::new(0.6, 0.8));
+    }
+
+    #[test]
+    fn rotated_box_corner_to_face() {
+        let unit = Shape::rectangle(1.0, 1.0);
+        let diamond = Pose::new(Vec2::new(3.0, 0.2), std::f32::consts::FRAC_PI_4);
+        let result = distance(&unit, at(0.0, 0.0), &unit, diamond);
+        let expected = 3.0 - std::f32::consts::FRAC_1_SQRT_2 - 0.5;
+        assert!((result.distance - expected).abs() < EPSILON);
+        assert_close(result.normal, Vec2::X);
+        assert_close(
+            result.point_b,
+            Vec2::new(3.0 - std::f32::consts::FRAC_1_SQRT_2, 0.2),
+        );
+    }
+
+    #[test]
+    fn circles_subtract_their_radii() {
+        let circle = Shape::circle(0.5);
+        let unit = Shape::aabb(2.0, 2.0);
+        let result = distance(&circle, at(0.0, 4.0), &unit, at(0.0, 0.0));
+        assert!((result.distance - 2.5).abs() < EPSILON);
+        assert_close(result.normal, -Vec2::Y);
+        assert_close(result.point_a, Vec2::new(0.0, 3.5));
+        assert_close(result.point_b, Vec2::new(0.0, 1.0));
+
+        let result = distance(&circle, at(0.0, 0.0), &circle, at(0.0, 3.0));
+        assert!((result.distance - 2.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn touching_and_overlapping_shapes_intersect() {
+        let unit = Shape::aabb(1.0, 1.0);
+        let result = distance(&unit, at(0.0, 0.0), &unit, at(1.0, 0.3));
+        assert!(result.distance.abs() < EPSILON);
+        assert!(intersects(&unit, at(0.0, 0.0), &unit, at(0.5, 0.5)));
+        let overlapping = distance(&unit, at(0.0, 0.0), &unit, at(0.2, 0.1));
+        assert_eq!(overlapping.distance, 0.0);
+        assert_eq!(overlapping.normal, Vec2::ZERO);
+
+        // Circles whose centres are apart but whose edges overlap.
+        let circle = Shape::circle(1.0);
+        assert!(intersects(&circle, at(0.0, 0.0), &circle, at(1.5, 0.0)));
+        assert!(!intersects(&circle, at(0.0, 0.0), &circle, at(2.5, 0.0)));
+    }
+
+    #[test]
+    fn long_thin_edges_keep_their_precision() {
+        let plank = Shape::rectangle(200.0, 0.1);
+        let circle = Shape::circle(0.25);
+        let result = distance(&plank, at(0.0, 0.0), &circle, at(73.0, 1.3));
+        assert!((result.distance - 1.0).abs() < EPSILON, "{result:?}");
+        assert_close(result.normal, Vec2::Y);
+    }
+}
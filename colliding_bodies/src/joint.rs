@@ -0,0 +1,727 @@
+//! Joints: constraints that keep two bodies attached to each other.
+//!
+//! Joints are solved together with the contacts, by the same sequential-impulse solver: each
+//! iteration gives every joint the impulse that fixes its relative velocity, starting from the
+//! impulses of the last step. Any drift left over is then corrected directly on the positions.
+
+use crate::body::RigidBody;
+use crate::math::Vec2;
+use crate::world::{relative_velocity, BodyHandle, SolverBody};
+
+// The most a joint's angle is corrected by in one position pass, in radians.
+const MAX_ANGULAR_CORRECTION: f32 = 0.14;
+
+/// A range a joint's angle, translation or length is kept within.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    pub lower: f32,
+    pub upper: f32,
+}
+
+impl Limits {
+    pub fn new(lower: f32, upper: f32) -> Self {
+        Self {
+            lower: lower.min(upper),
+            upper: upper.max(lower),
+        }
+    }
+}
+
+/// Drives a joint at a target speed, with limited strength.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Motor {
+    pub speed: f32, // Radians per second for revolute joints, units per second for prismatic.
+    pub max_force: f32, // Largest torque (revolute) or force (prismatic) the motor can apply.
+}
+
+/// How a joint constrains its two bodies.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JointKind {
+    /// Keeps the anchors between `min_length` and `max_length` apart: a rod when the two are
+    /// equal, a rope when `min_length` is 0.0.
+    Distance { min_length: f32, max_length: f32 },
+    /// Pins the anchors together and lets the bodies turn about them, like a hinge.
+    Revolute {
+        limits: Option<Limits>, // Of `b`'s angle relative to `a`, minus the reference angle.
+        motor: Option<Motor>,
+    },
+    /// Lets the anchors slide apart along `axis` only, without the bodies turning.
+    Prismatic {
+        axis: Vec2,             // In `a`'s frame; turns with `a`.
+        limits: Option<Limits>, // Of the translation along the axis.
+        motor: Option<Motor>,
+    },
+    /// Holds the bodies together rigidly.
+    Weld,
+    /// A damped spring pulling the anchors towards `rest_length` apart.
+    Spring {
+        rest_length: f32,
+        stiffness: f32, // Force per unit of stretch.
+        damping: f32,   // Force per unit of stretching speed.
+    },
+}
+
+/// A constraint between two bodies, added with `World::add_joint`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Joint {
+    pub a: BodyHandle,
+    pub b: BodyHandle,
+    pub kind: JointKind,
+    pub local_anchor_a: Vec2, // From `a`'s centre of mass, in `a`'s frame.
+    pub local_anchor_b: Vec2, // From `b`'s centre of mass, in `b`'s frame.
+    pub reference_angle: f32, // `b`'s angle minus `a`'s when the joint is at rest.
+    pub collide_connected: bool, // Whether the two bodies still collide with each other.
+    pub(crate) impulses: JointImpulses,
+}
+
+// The impulses applied during the last step, to warm start the next one.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub(crate) struct JointImpulses {
+    point: Vec2,  // Holding the anchors together, or `x` across a prismatic joint's axis.
+    angular: f32, // Holding the angle.
+    axial: f32,   // Along the joint: a rod, spring or motor.
+    lower: f32,   // Pushing away from the lower limit.
+    upper: f32,   // Pushing away from the upper limit.
+}
+
+/// How far a joint is from being satisfied.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct JointError {
+    pub linear: f32,  // Units the anchors are out of place by.
+    pub angular: f32, // Radians the angle is out of place by.
+}
+
+impl Joint {
+    /// A joint with both anchors at the bodies' centres and a reference angle of 0.0.
+    pub fn new(kind: JointKind, a: BodyHandle, b: BodyHandle) -> Self {
+        Self {
+            a,
+            b,
+            kind,
+            local_anchor_a: Vec2::ZERO,
+            local_anchor_b: Vec2::ZERO,
+            reference_angle: 0.0,
+            collide_connected: false,
+            impulses: JointImpulses::default(),
+        }
+    }
+
+    /// A rigid rod keeping the anchors `length` apart.
+    pub fn distance(a: BodyHandle, b: BodyHandle, length: f32) -> Self {
+        let kind = JointKind::Distance {
+            min_length: length,
+            max_length: length,
+        };
+        Self::new(kind, a, b)
+    }
+
+    /// A rope: the anchors can come closer, but not further than `max_length` apart.
+    pub fn rope(a: BodyHandle, b: BodyHandle, max_length: f32) -> Self {
+        let kind = JointKind::Distance {
+            min_length: 0.0,
+            max_length,
+        };
+        Self::new(kind, a, b)
+    }
+
+    pub fn revolute(a: BodyHandle, b: BodyHandle) -> Self {
+        let kind = JointKind::Revolute {
+            limits: None,
+            motor: None,
+        };
+        Self::new(kind, a, b)
+    }
+
+    /// Lets `b` slide along `axis`, given in `a`'s frame.
+    pub fn prismatic(a: BodyHandle, b: BodyHandle, axis: Vec2) -> Self {
+        let kind = JointKind::Prismatic {
+            axis: axis.normalize_or_zero(),
+            limits: None,
+            motor: None,
+        };
+        Self::new(kind, a, b)
+    }
+
+    pub fn weld(a: BodyHandle, b: BodyHandle) -> Self {
+        Self::new(JointKind::Weld, a, b)
+    }
+
+    pub fn spring(
+        a: BodyHandle,
+        b: BodyHandle,
+        rest_length: f32,
+        stiffness: f32,
+        damping: f32,
+    ) -> Self {
+        let kind = JointKind::Spring {
+            rest_length,
+            stiffness,
+            damping,
+        };
+        Self::new(kind, a, b)
+    }
+
+    /// Sets where the joint attaches, relative to each body's centre in its own frame.
+    pub fn with_anchors(mut self, local_anchor_a: Vec2, local_anchor_b: Vec2) -> Self {
+        self.local_anchor_a = local_anchor_a;
+        self.local_anchor_b = local_anchor_b;
+        self
+    }
+
+    pub fn with_reference_angle(mut self, reference_angle: f32) -> Self {
+        self.reference_angle = reference_angle;
+        self
+    }
+
+    pub fn with_collide_connected(mut self, collide_connected: bool) -> Self {
+        self.collide_connected = collide_connected;
+        self
+    }
+
+    /// Limits a revolute joint's angle, a prismatic joint's translation or a distance joint's
+    /// length. Other joints have nothing to limit and are unchanged.
+    pub fn with_limits(mut self, lower: f32, upper: f32) -> Self {
+        let range = Limits::new(lower, upper);
+        match &mut self.kind {
+            JointKind::Distance {
+                min_length,
+                max_length,
+            } => {
+                *min_length = range.lower.max(0.0);
+                *max_length = range.upper.max(0.0);
+            }
+            JointKind::Revolute { limits, .. } | JointKind::Prismatic { limits, .. } => {
+                *limits = Some(range);
+            }
+            JointKind::Weld | JointKind::Spring { .. } => {}
+        }
+        self
+    }
+
+    /// Drives a revolute or prismatic joint. Other joints have no motor and are unchanged.
+    pub fn with_motor(mut self, speed: f32, max_force: f32) -> Self {
+        if let JointKind::Revolute { motor, .. } | JointKind::Prismatic { motor, .. } =
+            &mut self.kind
+        {
+            *motor = Some(Motor {
+                speed,
+                max_force: max_force.max(0.0),
+            });
+        }
+        self
+    }
+
+    /// How far the joint is from being satisfied with the bodies where they are. Springs are never
+    /// in error.
+    pub fn error(&self, a: &RigidBody, b: &RigidBody) -> JointError {
+        let frame = Frame::new(self, a, b);
+        let outside = |value: f32, limits: Option<Limits>| {
+            limits.map_or(0.0, |limits| {
+                (limits.lower - value).max(value - limits.upper).max(0.0)
+            })
+        };
+        match self.kind {
+            JointKind::Distance {
+                min_length,
+                max_length,
+            } => JointError {
+                linear: outside(frame.d.length(), Some(Limits::new(min_length, max_length))),
+                angular: 0.0,
+            },
+            JointKind::Revolute { limits, .. } => JointError {
+                linear: frame.d.length(),
+                angular: outside(frame.angle, limits),
+            },
+            JointKind::Prismatic { axis, limits, .. } => {
+                let axis = axis.rotate(a.angle);
+                let across = frame.d.dot(axis.perp()).abs();
+                JointError {
+                    linear: across + outside(frame.d.dot(axis), limits),
+                    angular: frame.angle.abs(),
+                }
+            }
+            JointKind::Weld => JointError {
+                linear: frame.d.length(),
+                angular: frame.angle.abs(),
+            },
+            JointKind::Spring { .. } => JointError::default(),
+        }
+    }
+
+    /// Moves and turns the bodies to remove the joint's error, as far as one pass allows. Returns
+    /// false if there was nothing worth correcting.
+    pub(crate) fn correct_positions(
+        &self,
+        a: &mut RigidBody,
+        b: &mut RigidBody,
+        slop: f32,
+        max_correction: f32,
+    ) -> bool {
+        let error = self.error(a, b);
+        if error.linear <= slop && error.angular <= slop {
+            return false;
+        }
+        match self.kind {
+            JointKind::Distance {
+                min_length,
+                max_length,
+            } => {
+                let frame = Frame::new(self, a, b);
+                let length = frame.d.length();
+                let axis = frame.d.normalize_or_zero();
+                let error = length - length.clamp(min_length, max_length);
+                let correction = error.clamp(-max_correction, max_correction);
+                let mass = axial_mass(a, b, frame.r_a, frame.r_b, axis);
+                push_positions(a, b, frame.r_a, frame.r_b, axis * (-correction * mass));
+            }
+            JointKind::Revolute { limits, .. } => {
+                if let Some(limits) = limits {
+                    let frame = Frame::new(self, a, b);
+                    let error = frame.angle - frame.angle.clamp(limits.lower, limits.upper);
+                    turn_positions(a, b, error);
+                }
+                correct_point(self, a, b, max_correction);
+            }
+            JointKind::Prismatic { axis, limits, .. } => {
+                turn_positions(a, b, Frame::new(self, a, b).angle);
+                let frame = Frame::new(self, a, b);
+                let axis = axis.rotate(a.angle);
+                let perp = axis.perp();
+                let translation = frame.d.dot(axis);
+                let along = limits.map_or(0.0, |limits| {
+                    translation - translation.clamp(limits.lower, limits.upper)
+                });
+                let across = frame.d.dot(perp);
+                let correction = (perp * across + axis * along).clamp_length(max_correction);
+                // Applied at `b`'s anchor, like the velocity constraints.
+                let r_a = frame.r_a + frame.d;
+                for (direction, error) in
+                    [(perp, correction.dot(perp)), (axis, correction.dot(axis))]
+                {
+                    let mass = axial_mass(a, b, r_a, frame.r_b, direction);
+                    push_positions(a, b, r_a, frame.r_b, direction * (-error * mass));
+                }
+            }
+            JointKind::Weld => {
+                turn_positions(a, b, Frame::new(self, a, b).angle);
+                correct_point(self, a, b, max_correction);
+            }
+            JointKind::Spring { .. } => return false,
+        }
+        true
+    }
+}
+
+// The anchors and relative angle of a joint with its bodies where they are.
+struct Frame {
+    r_a: Vec2,  // From `a`'s centre to its anchor, in world space.
+    r_b: Vec2,  // From `b`'s centre to its anchor, in world space.
+    d: Vec2,    // From `a`'s anchor to `b`'s.
+    angle: f32, // `b`'s angle relative to `a`, minus the reference angle.
+}
+
+impl Frame {
+    fn new(joint: &Joint, a: &RigidBody, b: &RigidBody) -> Self {
+        let r_a = joint.local_anchor_a.rotate(a.angle);
+        let r_b = joint.local_anchor_b.rotate(b.angle);
+        Self {
+            r_a,
+            r_b,
+            d: (b.position + r_b) - (a.position + r_a),
+            angle: b.angle - a.angle - joint.reference_angle,
+        }
+    }
+}
+
+// Pulls the anchors of a revolute or weld joint together.
+fn correct_point(joint: &Joint, a: &mut RigidBody, b: &mut RigidBody, max_correction: f32) {
+    let frame = Frame::new(joint, a, b);
+    let error = frame.d.clamp_length(max_correction);
+    let k = point_mass_matrix(
+        (a.inverse_mass(), a.inverse_inertia()),
+        (b.inverse_mass(), b.inverse_inertia()),
+        frame.r_a,
+        frame.r_b,
+    );
+    push_positions(a, b, frame.r_a, frame.r_b, -solve_2x2(k, error));
+}
+
+// The position version of an impulse: moves `b` along `impulse / mass` and `a` the other way.
+fn push_positions(a: &mut RigidBody, b: &mut RigidBody, r_a: Vec2, r_b: Vec2, impulse: Vec2) {
+    a.position -= impulse * a.inverse_mass();
+    a.angle -= a.inverse_inertia() * r_a.cross(impulse);
+    b.position += impulse * b.inverse_mass();
+    b.angle += b.inverse_inertia() * r_b.cross(impulse);
+}
+
+// Turns the bodies to take `error` radians out of their relative angle.
+fn turn_positions(a: &mut RigidBody, b: &mut RigidBody, error: f32) {
+    let total = a.inverse_inertia() + b.inverse_inertia();
+    if total <= 0.0 {
+        return;
+    }
+    let impulse = -error.clamp(-MAX_ANGULAR_CORRECTION, MAX_ANGULAR_CORRECTION) / total;
+    a.angle -= a.inverse_inertia() * impulse;
+    b.angle += b.inverse_inertia() * impulse;
+}
+
+fn axial_mass(a: &RigidBody, b: &RigidBody, r_a: Vec2, r_b: Vec2, axis: Vec2) -> f32 {
+    inverse(effective_inverse_mass(
+        (a.inverse_mass(), a.inverse_inertia()),
+        (b.inverse_mass(), b.inverse_inertia()),
+        r_a,
+        r_b,
+        axis,
+    ))
+}
+
+// One over the mass the bodies resist an impulse along `axis` with, applied at the anchors.
+fn effective_inverse_mass(
+    (mass_a, inertia_a): (f32, f32),
+    (mass_b, inertia_b): (f32, f32),
+    r_a: Vec2,
+    r_b: Vec2,
+    axis: Vec2,
+) -> f32 {
+    let (rn_a, rn_b) = (r_a.cross(axis), r_b.cross(axis));
+    mass_a + mass_b + inertia_a * rn_a * rn_a + inertia_b * rn_b * rn_b
+}
+
+// The matrix relating an impulse at the anchors to the change in their relative velocity.
+fn point_mass_matrix(
+    (mass_a, inertia_a): (f32, f32),
+    (mass_b, inertia_b): (f32, f32),
+    r_a: Vec2,
+    r_b: Vec2,
+) -> [[f32; 2]; 2] {
+    let mass = mass_a + mass_b;
+    let xy = -inertia_a * r_a.x * r_a.y - inertia_b * r_b.x * r_b.y;
+    [
+        [
+            mass + inertia_a * r_a.y * r_a.y + inertia_b * r_b.y * r_b.y,
+            xy,
+        ],
+        [
+            xy,
+            mass + inertia_a * r_a.x * r_a.x + inertia_b * r_b.x * r_b.x,
+        ],
+    ]
+}
+
+// Solves `k * x = b`, or returns zero if `k` is singular.
+fn solve_2x2(k: [[f32; 2]; 2], b: Vec2) -> Vec2 {
+    let determinant = k[0][0] * k[1][1] - k[0][1] * k[1][0];
+    if determinant.abs() <= f32::EPSILON * f32::EPSILON {
+        return Vec2::ZERO;
+    }
+    let inverse = 1.0 / determinant;
+    Vec2::new(
+        inverse * (k[1][1] * b.x - k[0][1] * b.y),
+        inverse * (k[0][0] * b.y - k[1][0] * b.x),
+    )
+}
+
+fn inverse(value: f32) -> f32 {
+    if value > 0.0 {
+        1.0 / value
+    } else {
+        0.0
+    }
+}
+
+/// A joint prepared for one step of the velocity solver.
+#[derive(Debug, Clone)]
+pub(crate) struct JointConstraint {
+    a: usize, // Index into the solver's bodies.
+    b: usize,
+    kind: JointKind,
+    r_a: Vec2,
+    r_b: Vec2,
+    d: Vec2,           // From `a`'s anchor to `b`'s.
+    axis: Vec2,        // Along a distance or spring joint, or a prismatic joint's axis.
+    translation: f32,  // Length of a distance or spring joint, or along a prismatic axis.
+    angle: f32,        // Relative angle minus the reference angle.
+    inverse_dt: f32,   // For speculative limits: the gap that may still close this step.
+    spring_gamma: f32, // Softness of a spring.
+    spring_bias: f32,  // Velocity a spring pushes with to correct its stretch.
+    pub(crate) impulses: JointImpulses,
+}
+
+impl JointConstraint {
+    pub(crate) fn new(
+        joint: &Joint,
+        (a, body_a): (usize, &RigidBody),
+        (b, body_b): (usize, &RigidBody),
+        dt: f32,
+        warm_starting: bool,
+    ) -> Self {
+        let frame = Frame::new(joint, body_a, body_b);
+        let (axis, translation) = match joint.kind {
+            JointKind::Prismatic { axis, .. } => {
+                let axis = axis.rotate(body_a.angle);
+                (axis, frame.d.dot(axis))
+            }
+            _ => (frame.d.normalize_or_zero(), frame.d.length()),
+        };
+
+        let (mut spring_gamma, mut spring_bias) = (0.0, 0.0);
+        if let JointKind::Spring {
+            rest_length,
+            stiffness,
+            damping,
+        } = joint.kind
+        {
+            // A soft constraint (Catto, "Soft Constraints"): stable for any stiffness.
+            spring_gamma = inverse(dt * (damping + dt * stiffness));
+            spring_bias = (translation - rest_length) * dt * stiffness * spring_gamma;
+        }
+
+        Self {
+            a,
+            b,
+            kind: joint.kind,
+            r_a: frame.r_a,
+            r_b: frame.r_b,
+            d: frame.d,
+            axis,
+            translation,
+            angle: frame.angle,
+            inverse_dt: 1.0 / dt,
+            spring_gamma,
+            spring_bias,
+            impulses: if warm_starting {
+                joint.impulses
+            } else {
+                JointImpulses::default()
+            },
+        }
+    }
+
+    // `r_a` for a prismatic joint, whose impulses act on `a` at `b`'s anchor.
+    fn slider_r_a(&self) -> Vec2 {
+        self.r_a + self.d
+    }
+
+    /// Applies last step's impulses up front.
+    pub(crate) fn warm_start(&self, bodies: &mut [SolverBody]) {
+        let (mut a, mut b) = (bodies[self.a], bodies[self.b]);
+        let impulses = self.impulses;
+        match self.kind {
+            JointKind::Distance { .. } | JointKind::Spring { .. } => {
+                let total = impulses.axial + impulses.lower - impulses.upper;
+                push(&mut a, &mut b, self.r_a, self.r_b, self.axis * total);
+            }
+            JointKind::Revolute { .. } => {
+                push(&mut a, &mut b, self.r_a, self.r_b, impulses.point);
+                turn(
+                    &mut a,
+                    &mut b,
+                    impulses.axial + impulses.lower - impulses.upper,
+                );
+            }
+            JointKind::Prismatic { .. } => {
+                let along = impulses.axial + impulses.lower - impulses.upper;
+                let impulse = self.axis.perp() * impulses.point.x + self.axis * along;
+                push(&mut a, &mut b, self.slider_r_a(), self.r_b, impulse);
+                turn(&mut a, &mut b, impulses.angular);
+            }
+            JointKind::Weld => {
+                push(&mut a, &mut b, self.r_a, self.r_b, impulses.point);
+                turn(&mut a, &mut b, impulses.angular);
+            }
+        }
+        bodies[self.a] = a;
+        bodies[self.b] = b;
+    }
+
+    /// One iteration: the impulses that bring the joint's relative velocity in line.
+    pub(crate) fn solve(&mut self, bodies: &mut [SolverBody], dt: f32) {
+        let (mut a, mut b) = (bodies[self.a], bodies[self.b]);
+        match self.kind {
+            JointKind::Distance {
+                min_length,
+                max_length,
+            } => {
+                let mass = inverse(mass_along(&a, &b, self.r_a, self.r_b, self.axis));
+                if (max_length - min_length).abs() < f32::EPSILON {
+                    let speed = speed_along(&a, &b, self.r_a, self.r_b, self.axis);
+                    let impulse = -speed * mass;
+                    self.impulses.axial += impulse;
+                    push(&mut a, &mut b, self.r_a, self.r_b, self.axis * impulse);
+                } else {
+                    self.solve_limits(
+                        &mut a,
+                        &mut b,
+                        (self.r_a, self.r_b),
+                        Limits::new(min_length, max_length),
+                        mass,
+                    );
+                }
+            }
+            JointKind::Spring { .. } => {
+                let inverse_mass = mass_along(&a, &b, self.r_a, self.r_b, self.axis);
+                let mass = inverse(inverse_mass + self.spring_gamma);
+                let speed = speed_along(&a, &b, self.r_a, self.r_b, self.axis);
+                let impulse =
+                    -mass * (speed + self.spring_bias + self.spring_gamma * self.impulses.axial);
+                self.impulses.axial += impulse;
+                push(&mut a, &mut b, self.r_a, self.r_b, self.axis * impulse);
+            }
+            JointKind::Revolute { limits, motor } => {
+                let angular_mass = inverse(a.inverse_inertia + b.inverse_inertia);
+                if let Some(motor) = motor {
+                    let speed = b.angular_velocity - a.angular_velocity;
+                    let impulse = (motor.speed - speed) * angular_mass;
+                    let limit = motor.max_force * dt;
+                    let total = (self.impulses.axial + impulse).clamp(-limit, limit);
+                    turn(&mut a, &mut b, total - self.impulses.axial);
+                    self.impulses.axial = total;
+                }
+                if let Some(limits) = limits {
+                    self.solve_angle_limits(&mut a, &mut b, limits, angular_mass);
+                }
+                self.solve_point(&mut a, &mut b);
+            }
+            JointKind::Prismatic { limits, motor, .. } => {
+                let r_a = self.slider_r_a();
+                let mass = inverse(mass_along(&a, &b, r_a, self.r_b, self.axis));
+                if let Some(motor) = motor {
+                    let speed = speed_along(&a, &b, r_a, self.r_b, self.axis);
+                    let impulse = (motor.speed - speed) * mass;
+                    let limit = motor.max_force * dt;
+                    let total = (self.impulses.axial + impulse).clamp(-limit, limit);
+                    push(
+                        &mut a,
+                        &mut b,
+                        r_a,
+                        self.r_b,
+                        self.axis * (total - self.impulses.axial),
+                    );
+                    self.impulses.axial = total;
+                }
+                if let Some(limits) = limits {
+                    self.solve_limits(&mut a, &mut b, (r_a, self.r_b), limits, mass);
+                }
+
+                // Across the axis and the angle together, as they are coupled.
+                let perp = self.axis.perp();
+                let (s_a, s_b) = (r_a.cross(perp), self.r_b.cross(perp));
+                let coupling = a.inverse_inertia * s_a + b.inverse_inertia * s_b;
+                let angular = a.inverse_inertia + b.inverse_inertia;
+                let k = [
+                    [mass_along(&a, &b, r_a, self.r_b, perp), coupling],
+                    [coupling, if angular > 0.0 { angular } else { 1.0 }],
+                ];
+                let speed = Vec2::new(
+                    speed_along(&a, &b, r_a, self.r_b, perp),
+                    b.angular_velocity - a.angular_velocity,
+                );
+                let impulse = solve_2x2(k, -speed);
+                self.impulses.point.x += impulse.x;
+                self.impulses.angular += impulse.y;
+                push(&mut a, &mut b, r_a, self.r_b, perp * impulse.x);
+                turn(&mut a, &mut b, impulse.y);
+            }
+            JointKind::Weld => {
+                let angular_mass = inverse(a.inverse_inertia + b.inverse_inertia);
+                let impulse = -(b.angular_velocity - a.angular_velocity) * angular_mass;
+                self.impulses.angular += impulse;
+                turn(&mut a, &mut b, impulse);
+                self.solve_point(&mut a, &mut b);
+            }
+        }
+        bodies[self.a] = a;
+        bodies[self.b] = b;
+    }
+
+    // Keeps the anchors moving together.
+    fn solve_point(&mut self, a: &mut SolverBody, b: &mut SolverBody) {
+        let k = point_mass_matrix(
+            (a.inverse_mass, a.inverse_inertia),
+            (b.inverse_mass, b.inverse_inertia),
+            self.r_a,
+            self.r_b,
+        );
+        let relative = relative_velocity(a, b, self.r_a, self.r_b);
+        let impulse = solve_2x2(k, -relative);
+        self.impulses.point += impulse;
+        push(a, b, self.r_a, self.r_b, impulse);
+    }
+
+    // Keeps `translation` along `axis` within the limits. A limit not yet reached lets the joint
+    // move just far enough to reach it this step, so the joint stops at the limit rather than
+    // bouncing off it.
+    fn solve_limits(
+        &mut self,
+        a: &mut SolverBody,
+        b: &mut SolverBody,
+        (r_a, r_b): (Vec2, Vec2),
+        limits: Limits,
+        mass: f32,
+    ) {
+        let gap = self.translation - limits.lower;
+        let speed = speed_along(a, b, r_a, r_b, self.axis);
+        let impulse = -mass * (speed + gap.max(0.0) * self.inverse_dt);
+        let total = (self.impulses.lower + impulse).max(0.0);
+        push(a, b, r_a, r_b, self.axis * (total - self.impulses.lower));
+        self.impulses.lower = total;
+
+        let gap = limits.upper - self.translation;
+        let speed = -speed_along(a, b, r_a, r_b, self.axis);
+        let impulse = -mass * (speed + gap.max(0.0) * self.inverse_dt);
+        let total = (self.impulses.upper + impulse).max(0.0);
+        push(a, b, r_a, r_b, self.axis * -(total - self.impulses.upper));
+        self.impulses.upper = total;
+    }
+
+    fn solve_angle_limits(
+        &mut self,
+        a: &mut SolverBody,
+        b: &mut SolverBody,
+        limits: Limits,
+        mass: f32,
+    ) {
+        let gap = self.angle - limits.lower;
+        let speed = b.angular_velocity - a.angular_velocity;
+        let impulse = -mass * (speed + gap.max(0.0) * self.inverse_dt);
+        let total = (self.impulses.lower + impulse).max(0.0);
+        turn(a, b, total - self.impulses.lower);
+        self.impulses.lower = total;
+
+        let gap = limits.upper - self.angle;
+        let speed = a.angular_velocity - b.angular_velocity;
+        let impulse = -mass * (speed + gap.max(0.0) * self.inverse_dt);
+        let total = (self.impulses.upper + impulse).max(0.0);
+        turn(a, b, -(total - self.impulses.upper));
+        self.impulses.upper = total;
+    }
+}
+
+fn mass_along(a: &SolverBody, b: &SolverBody, r_a: Vec2, r_b: Vec2, axis: Vec2) -> f32 {
+    effective_inverse_mass(
+        (a.inverse_mass, a.inverse_inertia),
+        (b.inverse_mass, b.inverse_inertia),
+        r_a,
+        r_b,
+        axis,
+    )
+}
+
+// How fast `b`'s anchor moves away from `a`'s along `axis`.
+fn speed_along(a: &SolverBody, b: &SolverBody, r_a: Vec2, r_b: Vec2, axis: Vec2) -> f32 {
+    relative_velocity(a, b, r_a, r_b).dot(axis)
+}
+
+// Applies `impulse` to `b` at its anchor, and its opposite to `a`.
+fn push(a: &mut SolverBody, b: &mut SolverBody, r_a: Vec2, r_b: Vec2, impulse: Vec2) {
+    a.velocity -= impulse * a.inverse_mass;
+    a.angular_velocity -= a.inverse_inertia * r_a.cross(impulse);
+    b.velocity += impulse * b.inverse_mass;
+    b.angular_velocity += b.inverse_inertia * r_b.cross(impulse);
+}
+
+// Applies an angular impulse to `b`, and its opposite to `a`.
+fn turn(a: &mut SolverBody, b: &mut SolverBody, impulse: f32) {
+    a.angular_velocity -= a.inverse_inertia * impulse;
+    b.angular_velocity += b.inverse_inertia * impulse;
+}
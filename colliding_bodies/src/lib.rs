@@ -0,0 +1,35 @@
+//! A small 2D rigid-body physics engine.
+//!
+//! Add [`RigidBody`]s to a [`World`] and call [`World::step`] (or [`World::advance`] with the frame
+//! time) to simulate them. Collisions are detected with the separating axis test and resolved with
+//! sequential impulses, along with any [`Joint`]s holding bodies together; [`gjk`] answers distance
+//! queries between shapes. Bodies flagged with [`RigidBody::ccd`] are swept through each step so
+//! they cannot tunnel through thin bodies. A [`BroadPhase`] keeps the number of shape pairs tested
+//! low and speeds up the world's ray, shape and region queries, which a [`CharacterController`] uses
+//! to walk characters around the world without simulating them. The [`navigation`] module finds
+//! paths for them on tile grids and navigation meshes.
+
+pub mod body;
+pub mod broad_phase;
+pub mod character;
+pub mod collision;
+pub mod gjk;
+pub mod joint;
+pub mod math;
+pub mod navigation;
+pub mod query;
+pub mod shape;
+pub mod world;
+
+pub use crate::body::{BodyType, RigidBody};
+pub use crate::broad_phase::{BroadPhase, DynamicAabbTree, SpatialHash};
+pub use crate::character::{CharacterContact, CharacterController, CharacterMove};
+pub use crate::collision::{collide, Contact, Manifold};
+pub use crate::joint::{Joint, JointError, JointKind, Limits, Motor};
+pub use crate::math::{Aabb, Vec2};
+pub use crate::navigation::{Cell, Grid, GridPath, NavMesh, NavMeshError};
+pub use crate::query::{RayIntersection, ShapeCastIntersection, Sweep};
+pub use crate::shape::{MassProperties, Polygon, Pose, PrimitiveShape2D, Shape, ShapeError};
+pub use crate::world::{
+    BodyHandle, ContactPair, JointHandle, RayHit, ShapeCastHit, World, WorldSettings,
+};
@@ -1,3 +1,86 @@
-fn main() {
-    println!("Hello, world!");
+// -----------------------------------------------------------------------------
+// Colliding Bodies
+// -----------------------------------------------------------------------------
+
+use colliding_bodies::{PrimitiveShape2D, RigidBody, Shape, Vec2, World};
+
+fn main() -> Result<(), colliding_bodies::ShapeError> {
+    let mut world = World::default();
+
+    // A static floor and a few bodies dropped on it, described like in the enums crate.
+    let ground = Shape::try_from(PrimitiveShape2D::Rectangle {
+        width: 20.0,
+        height: 1.0,
+    })?;
+    world.add_body(RigidBody::fixed(ground).with_position(Vec2::new(0.0, -0.5)));
+
+    let shapes = [
+        ("circle", PrimitiveShape2D::Circle { radius: 0.5 }),
+        (
+            "box",
+            PrimitiveShape2D::Rectangle {
+                width: 1.0,
+                height: 1.0,
+            },
+        ),
+        (
+            "triangle",
+            PrimitiveShape2D::Polygon {
+                vertices: vec![(0.0, 0.0), (1.0, 0.0), (0.5, 1.0)],
+            },
+        ),
+    ];
+    let mut handles = Vec::new();
+    for (i, (name, shape)) in shapes.into_iter().enumerate() {
+        let body = RigidBody::dynamic(Shape::try_from(shape)?)
+            .with_position(Vec2::new(i as f32 * 2.0 - 2.0, 3.0 + i as f32))
+            .with_restitution(0.4);
+        handles.push((name, world.add_body(body)));
+    }
+    // A rotating box thrown sideways.
+    let thrown = RigidBody::dynamic(Shape::rectangle(0.8, 0.4))
+        .with_position(Vec2::new(-6.0, 2.0))
+        .with_velocity(Vec2::new(4.0, 2.0))
+        .with_angular_velocity(3.0);
+    handles.push(("thrown box", world.add_body(thrown)));
+
+    // Four seconds at 60 steps per second.
+    for step in 0..=240 {
+        if step % 60 == 0 {
+            println!("t = {:.1}s", step as f32 / 60.0);
+            for (name, handle) in &handles {
+                let body = world.body(*handle).expect("bodies are never removed");
+                println!(
+                    "  {:<10} at ({:>6.2}, {:>6.2}), angle {:>6.2}, speed {:>5.2}",
+                    name,
+                    body.position.x,
+                    body.position.y,
+                    body.angle,
+                    body.velocity.length()
+                );
+            }
+        }
+        world.step(1.0 / 60.0);
+    }
+
+    // Everything has come to rest on top of the floor by now.
+    println!("{} contacts at rest", world.contacts().len());
+
+    // Look down from above the thrown box to see what landed there.
+    let origin = world
+        .body(handles[3].1)
+        .expect("bodies are never removed")
+        .position
+        + Vec2::new(0.0, 5.0);
+    if let Some(hit) = world.ray_cast(origin, Vec2::new(0.0, -1.0), 10.0, |_, _| true) {
+        let name = handles
+            .iter()
+            .find(|(_, handle)| *handle == hit.body)
+            .map_or("the floor", |(name, _)| name);
+        println!(
+            "a ray down from {origin:?} hits {name} after {:.2} units",
+            hit.intersection.distance
+        );
+    }
+    Ok(())
 }
@@ -0,0 +1,259 @@
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
+
+/// A 2D vector, used for positions, velocities, forces and directions.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Vec2 {
+    pub const ZERO: Vec2 = Vec2 { x: 0.0, y: 0.0 };
+    pub const X: Vec2 = Vec2 { x: 1.0, y: 0.0 };
+    pub const Y: Vec2 = Vec2 { x: 0.0, y: 1.0 };
+
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    pub fn dot(self, other: Vec2) -> f32 {
+        self.x * other.x + self.y * other.y
+    }
+
+    /// The z component of the 3D cross product; positive when `other` is counter-clockwise from `self`.
+    pub fn cross(self, other: Vec2) -> f32 {
+        self.x * other.y - self.y * other.x
+    }
+
+    /// The vector rotated 90 degrees counter-clockwise. Equal to `cross(scalar, self)` with scalar 1.
+    pub fn perp(self) -> Vec2 {
+        Vec2::new(-self.y, self.x)
+    }
+
+    /// `cross(s, v)` for an angular velocity `s`: the linear velocity of a point at offset `v`.
+    pub fn cross_scalar(s: f32, v: Vec2) -> Vec2 {
+        Vec2::new(-s * v.y, s * v.x)
+    }
+
+    pub fn length_squared(self) -> f32 {
+        self.dot(self)
+    }
+
+    pub fn length(self) -> f32 {
+        self.length_squared().sqrt()
+    }
+
+    /// The unit vector in the same direction, or zero for a zero vector.
+    pub fn normalize_or_zero(self) -> Vec2 {
+        let length = self.length();
+        if length > f32::EPSILON {
+            self / length
+        } else {
+            Vec2::ZERO
+        }
+    }
+
+    /// The same vector, shortened to `max` if it is longer.
+    pub fn clamp_length(self, max: f32) -> Vec2 {
+        let length = self.length();
+        if length > max {
+            self * (max / length)
+        } else {
+            self
+        }
+    }
+
+    /// The vector rotated by `angle` radians counter-clockwise.
+    pub fn rotate(self, angle: f32) -> Vec2 {
+        let (sin, cos) = angle.sin_cos();
+        Vec2::new(cos * self.x - sin * self.y, sin * self.x + cos * self.y)
+    }
+
+    pub fn min(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x.min(other.x), self.y.min(other.y))
+    }
+
+    pub fn max(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x.max(other.x), self.y.max(other.y))
+    }
+
+    pub fn abs(self) -> Vec2 {
+        Vec2::new(self.x.abs(), self.y.abs())
+    }
+
+    pub fn lerp(self, other: Vec2, t: f32) -> Vec2 {
+        self + (other - self) * t
+    }
+
+    pub fn distance(self, other: Vec2) -> f32 {
+        (other - self).length()
+    }
+}
+
+impl From<(f32, f32)> for Vec2 {
+    fn from((x, y): (f32, f32)) -> Self {
+        Vec2::new(x, y)
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Vec2;
+    fn add(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Vec2;
+    fn sub(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Mul<f32> for Vec2 {
+    type Output = Vec2;
+    fn mul(self, s: f32) -> Vec2 {
+        Vec2::new(self.x * s, self.y * s)
+    }
+}
+
+impl Mul<Vec2> for f32 {
+    type Output = Vec2;
+    fn mul(self, v: Vec2) -> Vec2 {
+        v * self
+    }
+}
+
+impl Div<f32> for Vec2 {
+    type Output = Vec2;
+    fn div(self, s: f32) -> Vec2 {
+        Vec2::new(self.x / s, self.y / s)
+    }
+}
+
+impl Neg for Vec2 {
+    type Output = Vec2;
+    fn neg(self) -> Vec2 {
+        Vec2::new(-self.x, -self.y)
+    }
+}
+
+impl AddAssign for Vec2 {
+    fn add_assign(&mut self, other: Vec2) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Vec2 {
+    fn sub_assign(&mut self, other: Vec2) {
+        *self = *self - other;
+    }
+}
+
+impl MulAssign<f32> for Vec2 {
+    fn mul_assign(&mut self, s: f32) {
+        *self = *self * s;
+    }
+}
+
+/// An axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Aabb {
+    pub fn new(min: Vec2, max: Vec2) -> Self {
+        Self { min, max }
+    }
+
+    /// A box centred on `center` with the given half width and half height.
+    pub fn from_center(center: Vec2, half_extents: Vec2) -> Self {
+        Self::new(center - half_extents, center + half_extents)
+    }
+
+    pub fn center(&self) -> Vec2 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vec2 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+    }
+
+    pub fn contains_point(&self, point: Vec2) -> bool {
+        point.x >= self.min.x
+            && point.x <= self.max.x
+            && point.y >= self.min.y
+            && point.y <= self.max.y
+    }
+
+    /// Returns true if `other` lies completely inside this box.
+    pub fn contains(&self, other: &Aabb) -> bool {
+        self.contains_point(other.min) && self.contains_point(other.max)
+    }
+
+    /// The smallest box containing both boxes.
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    /// The box grown by `margin` on every side.
+    pub fn expanded(&self, margin: f32) -> Aabb {
+        Aabb::new(
+            self.min - Vec2::new(margin, margin),
+            self.max + Vec2::new(margin, margin),
+        )
+    }
+
+    /// The box stretched to also cover itself moved by `displacement`.
+    pub fn swept(&self, displacement: Vec2) -> Aabb {
+        self.union(&Aabb::new(self.min + displacement, self.max + displacement))
+    }
+
+    pub fn perimeter(&self) -> f32 {
+        let size = self.max - self.min;
+        2.0 * (size.x + size.y)
+    }
+
+    /// Where the ray `origin + t * direction` enters the box, for `t` in `0..=max_t`.
+    pub fn ray_entry(&self, origin: Vec2, direction: Vec2, max_t: f32) -> Option<f32> {
+        self.ray_interval(origin, direction, max_t)
+            .map(|(entry, _)| entry)
+    }
+
+    /// The part of `0..=max_t` for which the ray `origin + t * direction` is inside the box.
+    pub fn ray_interval(&self, origin: Vec2, direction: Vec2, max_t: f32) -> Option<(f32, f32)> {
+        let mut t_min = 0.0_f32;
+        let mut t_max = max_t;
+        for (origin, direction, min, max) in [
+            (origin.x, direction.x, self.min.x, self.max.x),
+            (origin.y, direction.y, self.min.y, self.max.y),
+        ] {
+            if direction.abs() < f32::EPSILON {
+                if origin < min || origin > max {
+                    return None;
+                }
+            } else {
+                let inverse = 1.0 / direction;
+                let (mut t0, mut t1) = ((min - origin) * inverse, (max - origin) * inverse);
+                if t0 > t1 {
+                    std::mem::swap(&mut t0, &mut t1);
+                }
+                t_min = t_min.max(t0);
+                t_max = t_max.min(t1);
+                if t_min > t_max {
+                    return None;
+                }
+            }
+        }
+        Some((t_min, t_max))
+    }
+}
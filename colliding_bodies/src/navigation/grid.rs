@@ -0,0 +1,495 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::math::Vec2;
+
+const DIAGONAL: f32 = std::f32::consts::SQRT_2;
+
+/// A cell's column and row.
+pub type Cell = (i32, i32);
+
+/// A rectangle of square tiles, each open or blocked, laid over the world.
+///
+/// Paths move between the eight neighbouring tiles, but never cut the corner of a blocked tile: a
+/// diagonal step needs both tiles beside it to be open.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grid {
+    width: i32,
+    height: i32,
+    blocked: Vec<bool>,
+    pub origin: Vec2,   // World position of the corner of cell (0, 0).
+    pub cell_size: f32, // World size of a cell's side.
+}
+
+/// A path through a grid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GridPath {
+    pub cells: Vec<Cell>, // Every cell from the start to the goal, both included.
+    pub cost: f32,        // Length in cells, counting diagonal steps as the square root of two.
+}
+
+impl Grid {
+    /// An open grid of `width` by `height` unit cells, with cell (0, 0) at the origin.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width: width as i32,
+            height: height as i32,
+            blocked: vec![false; width * height],
+            origin: Vec2::ZERO,
+            cell_size: 1.0,
+        }
+    }
+
+    /// A grid drawn as text, one line per row with the first line at the top: `#` marks a blocked
+    /// cell and anything else an open one. Short lines are padded with open cells.
+    pub fn from_ascii(map: &str) -> Self {
+        let lines: Vec<&str> = map
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        let width = lines
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut grid = Self::new(width, lines.len());
+        for (row, line) in lines.iter().enumerate() {
+            let y = (lines.len() - 1 - row) as i32;
+            for (x, c) in line.chars().enumerate() {
+                grid.set_blocked((x as i32, y), c == '#');
+            }
+        }
+        grid
+    }
+
+    pub fn with_origin(mut self, origin: Vec2) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    pub fn with_cell_size(mut self, cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "the cell size must be positive");
+        self.cell_size = cell_size;
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width as usize
+    }
+
+    pub fn height(&self) -> usize {
+        self.height as usize
+    }
+
+    pub fn contains(&self, (x, y): Cell) -> bool {
+        x >= 0 && y >= 0 && x < self.width && y < self.height
+    }
+
+    /// Blocks or opens a cell. Cells outside the grid are ignored.
+    pub fn set_blocked(&mut self, cell: Cell, blocked: bool) {
+        if self.contains(cell) {
+            let index = self.index(cell);
+            self.blocked[index] = blocked;
+        }
+    }
+
+    /// Returns true if the cell is inside the grid and open.
+    pub fn is_open(&self, cell: Cell) -> bool {
+        self.contains(cell) && !self.blocked[self.index(cell)]
+    }
+
+    /// The cell containing a world position, if it is inside the grid.
+    pub fn cell_at(&self, point: Vec2) -> Option<Cell> {
+        let local = (point - self.origin) / self.cell_size;
+        let cell = (local.x.floor() as i32, local.y.floor() as i32);
+        self.contains(cell).then_some(cell)
+    }
+
+    /// The world position of a cell's centre.
+    pub fn cell_center(&self, (x, y): Cell) -> Vec2 {
+        self.origin + Vec2::new(x as f32 + 0.5, y as f32 + 0.5) * self.cell_size
+    }
+
+    /// The shortest path between two open cells, found with A*.
+    pub fn find_path(&self, start: Cell, goal: Cell) -> Option<GridPath> {
+        self.search(start, goal, |grid, cell, _| {
+            grid.neighbours(cell)
+                .into_iter()
+                .map(|next| (next, step_cost(cell, next)))
+                .collect()
+        })
+    }
+
+    /// The shortest path between two open cells, found with jump point search.
+    ///
+    /// Gives paths as short as [`Grid::find_path`], but on open maps looks at far fewer cells: it
+    /// only stops at cells where a path might have to turn, skipping straight over the rest.
+    pub fn find_path_jps(&self, start: Cell, goal: Cell) -> Option<GridPath> {
+        self.search(start, goal, |grid, cell, parent| {
+            grid.pruned_neighbours(cell, parent)
+                .into_iter()
+                .filter_map(|next| {
+                    let direction = (next.0 - cell.0, next.1 - cell.1);
+                    let jump = grid.jump(next, direction, goal)?;
+                    Some((jump, octile(cell, jump)))
+                })
+                .collect()
+        })
+    }
+
+    fn index(&self, (x, y): Cell) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    // A* from `start` to `goal`. `successors` lists the cells reachable from a cell, given the
+    // cell it was reached from, and what it costs to get to each; cells along the way are filled
+    // in afterwards, so successors may be far away as long as they lie in a straight or diagonal
+    // line.
+    fn search(
+        &self,
+        start: Cell,
+        goal: Cell,
+        successors: impl Fn(&Grid, Cell, Option<Cell>) -> Vec<(Cell, f32)>,
+    ) -> Option<GridPath> {
+        if !self.is_open(start) || !self.is_open(goal) {
+            return None;
+        }
+        let mut open = BinaryHeap::from([Open {
+            estimate: octile(start, goal),
+            cell: start,
+        }]);
+        let mut costs = HashMap::from([(start, 0.0)]);
+        let mut came_from: HashMap<Cell, Cell> = HashMap::new();
+        while let Some(Open { cell, estimate }) = open.pop() {
+            let cost = costs[&cell];
+            if estimate > cost + octile(cell, goal) + 1e-4 {
+                continue; // Already reached more cheaply.
+            }
+            if cell == goal {
+                let mut corners = vec![goal];
+                let mut cell = goal;
+                while let Some(&previous) = came_from.get(&cell) {
+                    corners.push(previous);
+                    cell = previous;
+                }
+                corners.reverse();
+                return Some(GridPath {
+                    cells: fill_in(&corners),
+                    cost,
+                });
+            }
+            for (next, step) in successors(self, cell, came_from.get(&cell).copied()) {
+                let next_cost = cost + step;
+                if costs.get(&next).is_some_and(|&known| known <= next_cost) {
+                    continue;
+                }
+                costs.insert(next, next_cost);
+                came_from.insert(next, cell);
+                open.push(Open {
+                    estimate: next_cost + octile(next, goal),
+                    cell: next,
+                });
+            }
+        }
+        None
+    }
+
+    // Can a step go from `from` by `(dx, dy)`? Diagonal steps may not cut corners.
+    fn can_step(&self, (x, y): Cell, (dx, dy): Cell) -> bool {
+        self.is_open((x + dx, y + dy))
+            && (dx == 0 || dy == 0 || (self.is_open((x + dx, y)) && self.is_open((x, y + dy))))
+    }
+
+    fn neighbours(&self, cell: Cell) -> Vec<Cell> {
+        let mut neighbours = Vec::with_capacity(8);
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                if (dx, dy) != (0, 0) && self.can_step(cell, (dx, dy)) {
+                    neighbours.push((cell.0 + dx, cell.1 + dy));
+                }
+            }
+        }
+        neighbours
+    }
+
+    // The neighbours worth looking at when arriving at `cell` from `parent`: going on the same
+    // way, and turning where an obstacle next to the path forces it.
+    fn pruned_neighbours(&self, cell: Cell, parent: Option<Cell>) -> Vec<Cell> {
+        let Some(parent) = parent else {
+            return self.neighbours(cell);
+        };
+        let (x, y) = cell;
+        let dx = (x - parent.0).signum();
+        let dy = (y - parent.1).signum();
+        let mut directions = Vec::with_capacity(5);
+        if dx != 0 && dy != 0 {
+            directions.extend([(dx, 0), (0, dy), (dx, dy)]);
+        } else if dx != 0 {
+            directions.extend([(dx, 0), (dx, 1), (dx, -1), (0, 1), (0, -1)]);
+        } else {
+            directions.extend([(0, dy), (1, dy), (-1, dy), (1, 0), (-1, 0)]);
+        }
+        directions
+            .into_iter()
+            .filter(|&direction| self.can_step(cell, direction))
+            .map(|(ddx, ddy)| (x + ddx, y + ddy))
+            .collect()
+    }
+
+    // Heads from `cell` in `direction` until reaching a cell where the path might turn: the goal,
+    // a cell with a neighbour only reachable through it, or, going diagonally, a cell from which
+    // a straight jump finds one. `None` if the way is blocked first.
+    fn jump(&self, mut cell: Cell, (dx, dy): Cell, goal: Cell) -> Option<Cell> {
+        loop {
+            let (x, y) = cell;
+            if !self.is_open(cell) {
+                return None;
+            }
+            if cell == goal {
+                return Some(cell);
+            }
+            if dx != 0 && dy != 0 {
+                if self.jump((x + dx, y), (dx, 0), goal).is_some()
+                    || self.jump((x, y + dy), (0, dy), goal).is_some()
+                {
+                    return Some(cell);
+                }
+            } else if dx != 0 {
+                let forced =
+                    |side: i32| self.is_open((x, y + side)) && !self.is_open((x - dx, y + side));
+                if forced(1) || forced(-1) {
+                    return Some(cell);
+                }
+            } else {
+                let forced =
+                    |side: i32| self.is_open((x + side, y)) && !self.is_open((x + side, y - dy));
+                if forced(1) || forced(-1) {
+                    return Some(cell);
+                }
+            }
+            if !self.can_step(cell, (dx, dy)) {
+                return None;
+            }
+            cell = (x + dx, y + dy);
+        }
+    }
+}
+
+// A cell waiting to be looked at, cheapest estimate first.
+struct Open {
+    estimate: f32,
+    cell: Cell,
+}
+
+impl PartialEq for Open {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Open {}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+// The length of the shortest path between two cells on an open grid.
+fn octile(a: Cell, b: Cell) -> f32 {
+    let dx = (a.0 - b.0).abs() as f32;
+    let dy = (a.1 - b.1).abs() as f32;
+    dx.max(dy) + (DIAGONAL - 1.0) * dx.min(dy)
+}
+
+fn step_cost(a: Cell, b: Cell) -> f32 {
+    if a.0 != b.0 && a.1 != b.1 {
+        DIAGONAL
+    } else {
+        1.0
+    }
+}
+
+// Every cell along straight and diagonal runs between corners.
+fn fill_in(corners: &[Cell]) -> Vec<Cell> {
+    let mut cells = vec![corners[0]];
+    for pair in corners.windows(2) {
+        let (mut x, mut y) = pair[0];
+        let (dx, dy) = ((pair[1].0 - x).signum(), (pair[1].1 - y).signum());
+        while (x, y) != pair[1] {
+            x += dx;
+            y += dy;
+            cells.push((x, y));
+        }
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAZE: &str = "
+        ..#.......
+        ..#.####..
+        ..#....#..
+        ..####.#..
+        .......#..
+        .####..###
+        ....#.....
+    ";
+
+    // Path costs from `start` to every cell, by Dijkstra over single steps.
+    fn costs_from(grid: &Grid, start: Cell) -> HashMap<Cell, f32> {
+        let mut costs = HashMap::from([(start, 0.0)]);
+        let mut open = BinaryHeap::from([Open {
+            estimate: 0.0,
+            cell: start,
+        }]);
+        while let Some(Open { estimate, cell }) = open.pop() {
+            if estimate > costs[&cell] {
+                continue;
+            }
+            for next in grid.neighbours(cell) {
+                let cost = estimate + step_cost(cell, next);
+                if costs.get(&next).is_none_or(|&known| cost < known) {
+                    costs.insert(next, cost);
+                    open.push(Open {
+                        estimate: cost,
+                        cell: next,
+                    });
+                }
+            }
+        }
+        costs
+    }
+
+    // Checks that a path runs from `start` to `goal` in single steps through open cells, without
+    // cutting corners, and costs what its steps add up to.
+    fn assert_walkable(grid: &Grid, path: &GridPath, start: Cell, goal: Cell) {
+        assert_eq!(path.cells.first(), Some(&start));
+        assert_eq!(path.cells.last(), Some(&goal));
+        let mut cost = 0.0;
+        for pair in path.cells.windows(2) {
+            let step = (pair[1].0 - pair[0].0, pair[1].1 - pair[0].1);
+            assert!(step.0.abs() <= 1 && step.1.abs() <= 1 && step != (0, 0));
+            assert!(grid.can_step(pair[0], step), "{pair:?} is not a step");
+            cost += step_cost(pair[0], pair[1]);
+        }
+        assert!((path.cost - cost).abs() < 1e-4, "{} != {cost}", path.cost);
+    }
+
+    #[test]
+    fn open_grid_paths_cost_their_octile_distance() {
+        let grid = Grid::new(6, 6);
+        let path = grid.find_path((0, 0), (5, 0)).unwrap();
+        assert_eq!(path.cells.len(), 6);
+        assert_eq!(path.cost, 5.0);
+
+        let path = grid.find_path((0, 0), (5, 3)).unwrap();
+        assert_walkable(&grid, &path, (0, 0), (5, 3));
+        assert!((path.cost - (2.0 + 3.0 * DIAGONAL)).abs() < 1e-4);
+
+        let path = grid.find_path((2, 2), (2, 2)).unwrap();
+        assert_eq!(path.cells, [(2, 2)]);
+        assert_eq!(path.cost, 0.0);
+    }
+
+    #[test]
+    fn paths_go_around_a_wall() {
+        let grid = Grid::from_ascii(
+            "
+            .....
+            ..#..
+            ..#..
+            ..#..
+            .....
+        ",
+        );
+        let path = grid.find_path((0, 2), (4, 2)).unwrap();
+        assert_walkable(&grid, &path, (0, 2), (4, 2));
+        // Up beside the wall, along past its end and back down, without cutting its corners.
+        assert!((path.cost - (4.0 + 2.0 * DIAGONAL)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn paths_are_as_short_as_any_other() {
+        let grid = Grid::from_ascii(MAZE);
+        let open: Vec<Cell> = (0..grid.width() as i32)
+            .flat_map(|x| (0..grid.height() as i32).map(move |y| (x, y)))
+            .filter(|&cell| grid.is_open(cell))
+            .collect();
+        for &start in &open {
+            let costs = costs_from(&grid, start);
+            for &goal in &open {
+                let (a_star, jps) = (grid.find_path(start, goal), grid.find_path_jps(start, goal));
+                let Some(&best) = costs.get(&goal) else {
+                    assert_eq!((a_star, jps), (None, None), "{start:?} to {goal:?}");
+                    continue;
+                };
+                for path in [a_star.unwrap(), jps.unwrap()] {
+                    assert_walkable(&grid, &path, start, goal);
+                    assert!((path.cost - best).abs() < 1e-4, "{start:?} to {goal:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn diagonal_steps_do_not_cut_corners() {
+        let grid = Grid::from_ascii(
+            "
+            .#
+            #.
+        ",
+        );
+        assert_eq!(grid.find_path((0, 1), (1, 0)), None);
+        assert_eq!(grid.find_path_jps((0, 1), (1, 0)), None);
+
+        let grid = Grid::from_ascii(
+            "
+            ...
+            .#.
+            ...
+        ",
+        );
+        let path = grid.find_path((0, 0), (2, 2)).unwrap();
+        assert_walkable(&grid, &path, (0, 0), (2, 2));
+        assert_eq!(path.cost, 4.0);
+    }
+
+    #[test]
+    fn no_path_to_walled_off_or_blocked_cells() {
+        let grid = Grid::from_ascii(
+            "
+            ..#...
+            ..#.#.
+            ###...
+        ",
+        );
+        assert_eq!(grid.find_path((0, 2), (5, 0)), None);
+        assert_eq!(grid.find_path_jps((0, 2), (5, 0)), None);
+        assert_eq!(grid.find_path((0, 2), (2, 1)), None);
+        assert_eq!(grid.find_path((2, 1), (0, 2)), None);
+        assert_eq!(grid.find_path((0, 2), (-1, 2)), None);
+        assert_eq!(grid.find_path((0, 2), (0, 3)), None);
+    }
+
+    #[test]
+    fn cells_map_to_and_from_the_world() {
+        let grid = Grid::new(4, 3)
+            .with_origin(Vec2::new(-2.0, 1.0))
+            .with_cell_size(0.5);
+        assert_eq!(grid.cell_at(Vec2::new(-2.0, 1.0)), Some((0, 0)));
+        assert_eq!(grid.cell_at(Vec2::new(-0.1, 2.4)), Some((3, 2)));
+        assert_eq!(grid.cell_at(Vec2::new(0.1, 1.5)), None);
+        assert_eq!(grid.cell_center((1, 2)), Vec2::new(-1.25, 2.25));
+    }
+}
@@ -0,0 +1,14 @@
+//! Navigation: finding paths around obstacles for characters to follow.
+//!
+//! A [`Grid`] of open and blocked tiles finds paths with A* or jump point search, and suits
+//! tile-based levels. A [`NavMesh`] covers the open space between polygon obstacles with convex
+//! regions and finds short paths that turn only at obstacle corners. Neither needs a [`World`];
+//! both work on plain geometry.
+//!
+//! [`World`]: crate::World
+
+mod grid;
+mod navmesh;
+
+pub use grid::{Cell, Grid, GridPath};
+pub use navmesh::{NavMesh, NavMeshError};
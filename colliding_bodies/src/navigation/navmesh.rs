@@ -0,0 +1,598 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::math::{Aabb, Vec2};
+
+const MAX_MITER: f32 = 4.0; // Sharp obstacle corners grow by at most this many agent radii.
+
+/// The open space of a rectangle with obstacles cut out of it, split into convex regions that
+/// paths can cross in straight lines.
+///
+/// The space is cut into vertical slabs at every obstacle corner and crossing, and every slab into
+/// the trapezoids between obstacle edges. Obstacles may be convex or concave, overlap each other
+/// and stick out of the bounds.
+#[derive(Debug, Clone)]
+pub struct NavMesh {
+    slabs: Vec<Slab>,
+    regions: Vec<Region>,
+    epsilon: f32, // Lengths shorter than this are treated as zero.
+}
+
+/// Why a navigation mesh could not be built.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NavMeshError {
+    EmptyBounds,               // The bounds leave no room for the agent.
+    DegenerateObstacle(usize), // The obstacle at this index has no area.
+}
+
+impl std::fmt::Display for NavMeshError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NavMeshError::EmptyBounds => write!(f, "the bounds leave no room for the agent"),
+            NavMeshError::DegenerateObstacle(index) => write!(f, "obstacle {index} has no area"),
+        }
+    }
+}
+
+impl std::error::Error for NavMeshError {}
+
+// A strip of the mesh between two vertical lines, crossed by obstacle edges.
+#[derive(Debug, Clone)]
+struct Slab {
+    x0: f32,
+    x1: f32,
+    regions: Vec<usize>, // Bottom to top.
+}
+
+// A trapezoid of open space inside a slab.
+#[derive(Debug, Clone)]
+struct Region {
+    polygon: Vec<Vec2>, // Counter-clockwise.
+    bottom: (f32, f32), // Heights of the lower side at the slab's left and right edges.
+    top: (f32, f32),    // Heights of the upper side.
+    portals: Vec<Portal>,
+}
+
+// An opening into a neighbouring region.
+#[derive(Debug, Clone, Copy)]
+struct Portal {
+    to: usize,
+    a: Vec2, // The opening's ends, in counter-clockwise order around this region.
+    b: Vec2,
+}
+
+// An obstacle edge, with `a` left of `b`.
+#[derive(Debug, Clone, Copy)]
+struct Edge {
+    obstacle: usize,
+    a: Vec2,
+    b: Vec2,
+}
+
+impl Edge {
+    fn y_at(&self, x: f32) -> f32 {
+        let t = (x - self.a.x) / (self.b.x - self.a.x);
+        self.a.y + (self.b.y - self.a.y) * t
+    }
+}
+
+impl NavMesh {
+    /// The space inside `bounds` that an agent of `agent_radius` can be centred in without
+    /// touching any obstacle. Each obstacle is a polygon given by its corners, in either order.
+    pub fn new(
+        bounds: Aabb,
+        obstacles: &[Vec<Vec2>],
+        agent_radius: f32,
+    ) -> Result<Self, NavMeshError> {
+        let bounds = bounds.expanded(-agent_radius);
+        if bounds.max.x <= bounds.min.x || bounds.max.y <= bounds.min.y {
+            return Err(NavMeshError::EmptyBounds);
+        }
+        let size = bounds.max - bounds.min;
+        let epsilon = (size.x + size.y) * 1e-6;
+
+        let mut edges = Vec::new();
+        for (index, obstacle) in obstacles.iter().enumerate() {
+            let area = signed_area(obstacle);
+            if obstacle.len() < 3 || area.abs() <= epsilon * epsilon {
+                return Err(NavMeshError::DegenerateObstacle(index));
+            }
+            let inflated = inflate(obstacle, area, agent_radius);
+            for (i, &a) in inflated.iter().enumerate() {
+                let b = inflated[(i + 1) % inflated.len()];
+                if (b.x - a.x).abs() > epsilon {
+                    let (a, b) = if a.x < b.x { (a, b) } else { (b, a) };
+                    edges.push(Edge {
+                        obstacle: index,
+                        a,
+                        b,
+                    });
+                }
+            }
+        }
+
+        // Cut wherever an edge starts, ends or crosses another edge or the bounds, so that
+        // inside each slab edges run from side to side without meeting.
+        let mut xs = vec![bounds.min.x, bounds.max.x];
+        for (i, edge) in edges.iter().enumerate() {
+            xs.extend([edge.a.x, edge.b.x]);
+            for y in [bounds.min.y, bounds.max.y] {
+                if (edge.a.y - y) * (edge.b.y - y) < 0.0 {
+                    xs.push(
+                        edge.a.x + (edge.b.x - edge.a.x) * (y - edge.a.y) / (edge.b.y - edge.a.y),
+                    );
+                }
+            }
+            for other in &edges[i + 1..] {
+                if let Some(x) = crossing(edge, other) {
+                    xs.push(x);
+                }
+            }
+        }
+        xs.retain(|&x| x >= bounds.min.x && x <= bounds.max.x);
+        xs.sort_by(f32::total_cmp);
+        xs.dedup_by(|b, a| *b - *a <= epsilon);
+
+        let mut mesh = NavMesh {
+            slabs: Vec::new(),
+            regions: Vec::new(),
+            epsilon,
+        };
+        for pair in xs.windows(2) {
+            mesh.add_slab(pair[0], pair[1], &edges, obstacles.len(), &bounds);
+        }
+        mesh.connect_slabs();
+        Ok(mesh)
+    }
+
+    /// The convex regions making up the mesh, each a counter-clockwise polygon.
+    pub fn regions(&self) -> impl Iterator<Item = &[Vec2]> {
+        self.regions.iter().map(|region| region.polygon.as_slice())
+    }
+
+    /// The index of the region containing `point`, if it is in open space.
+    pub fn region_at(&self, point: Vec2) -> Option<usize> {
+        let slab = self.slabs.partition_point(|slab| slab.x1 < point.x);
+        let slab = self.slabs.get(slab).filter(|slab| slab.x0 <= point.x)?;
+        let t = (point.x - slab.x0) / (slab.x1 - slab.x0);
+        let lerp = |(y0, y1): (f32, f32)| y0 + (y1 - y0) * t;
+        slab.regions.iter().copied().find(|&index| {
+            let region = &self.regions[index];
+            point.y >= lerp(region.bottom) - self.epsilon
+                && point.y <= lerp(region.top) + self.epsilon
+        })
+    }
+
+    /// The point of open space closest to `point`, and the region it is in. `None` if the mesh is
+    /// empty.
+    pub fn closest_point(&self, point: Vec2) -> Option<(usize, Vec2)> {
+        if let Some(region) = self.region_at(point) {
+            return Some((region, point));
+        }
+        self.regions
+            .iter()
+            .enumerate()
+            .flat_map(|(index, region)| {
+                let polygon = &region.polygon;
+                (0..polygon.len()).map(move |i| {
+                    let edge = (polygon[i], polygon[(i + 1) % polygon.len()]);
+                    (index, closest_on_segment(point, edge))
+                })
+            })
+            .min_by(|a, b| point.distance(a.1).total_cmp(&point.distance(b.1)))
+    }
+
+    /// A short path from `start` to `goal` through open space, as the points where it turns,
+    /// starting at `start` and ending at `goal`. Points outside open space are moved to the
+    /// nearest open point first. `None` if the two are not connected.
+    ///
+    /// The regions to cross are found with A*, and the path through them pulled tight with the
+    /// funnel algorithm, so it only turns at obstacle corners.
+    pub fn find_path(&self, start: Vec2, goal: Vec2) -> Option<Vec<Vec2>> {
+        let (start_region, start) = self.closest_point(start)?;
+        let (goal_region, goal) = self.closest_point(goal)?;
+        let portals = self.corridor(start_region, start, goal_region, goal)?;
+        Some(self.funnel(start, goal, &portals))
+    }
+
+    fn add_slab(&mut self, x0: f32, x1: f32, edges: &[Edge], obstacles: usize, bounds: &Aabb) {
+        let middle = (x0 + x1) * 0.5;
+        let mut crossing: Vec<(f32, &Edge)> = edges
+            .iter()
+            .filter(|edge| edge.a.x < middle && edge.b.x > middle)
+            .map(|edge| (edge.y_at(middle), edge))
+            .collect();
+        crossing.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        // Walk up the slab, counting the obstacles we are inside of.
+        let mut inside = vec![false; obstacles];
+        let mut covering = 0;
+        let mut below = Some((bounds.min.y, bounds.min.y));
+        let mut slab = Slab {
+            x0,
+            x1,
+            regions: Vec::new(),
+        };
+        for (_, edge) in crossing {
+            let heights = (edge.y_at(x0), edge.y_at(x1));
+            if let Some(bottom) = below.take() {
+                self.add_region(&mut slab, bottom, heights, bounds);
+            }
+            inside[edge.obstacle] = !inside[edge.obstacle];
+            covering = if inside[edge.obstacle] {
+                covering + 1
+            } else {
+                covering - 1
+            };
+            if covering == 0 {
+                below = Some(heights);
+            }
+        }
+        if let Some(bottom) = below {
+            self.add_region(&mut slab, bottom, (bounds.max.y, bounds.max.y), bounds);
+        }
+        self.slabs.push(slab);
+    }
+
+    fn add_region(&mut self, slab: &mut Slab, bottom: (f32, f32), top: (f32, f32), bounds: &Aabb) {
+        let bottom = (bottom.0.max(bounds.min.y), bottom.1.max(bounds.min.y));
+        let top = (top.0.min(bounds.max.y), top.1.min(bounds.max.y));
+        if (top.0 + top.1) - (bottom.0 + bottom.1) <= 2.0 * self.epsilon {
+            return;
+        }
+        let top = (top.0.max(bottom.0), top.1.max(bottom.1));
+        let mut polygon = vec![
+            Vec2::new(slab.x0, bottom.0),
+            Vec2::new(slab.x1, bottom.1),
+            Vec2::new(slab.x1, top.1),
+            Vec2::new(slab.x0, top.0),
+        ];
+        polygon.dedup_by(|b, a| a.distance(*b) <= self.epsilon);
+        if polygon.len() > 3 && polygon[0].distance(polygon[3]) <= self.epsilon {
+            polygon.pop();
+        }
+        slab.regions.push(self.regions.len());
+        self.regions.push(Region {
+            polygon,
+            bottom,
+            top,
+            portals: Vec::new(),
+        });
+    }
+
+    // Opens portals between regions in neighbouring slabs that share part of their side.
+    fn connect_slabs(&mut self) {
+        for i in 1..self.slabs.len() {
+            let x = self.slabs[i].x0;
+            for &left in &self.slabs[i - 1].regions {
+                for &right in &self.slabs[i].regions {
+                    let low = self.regions[left]
+                        .bottom
+                        .1
+                        .max(self.regions[right].bottom.0);
+                    let high = self.regions[left].top.1.min(self.regions[right].top.0);
+                    if high - low <= self.epsilon {
+                        continue;
+                    }
+                    let (low, high) = (Vec2::new(x, low), Vec2::new(x, high));
+                    self.regions[left].portals.push(Portal {
+                        to: right,
+                        a: low,
+                        b: high,
+                    });
+                    self.regions[right].portals.push(Portal {
+                        to: left,
+                        a: high,
+                        b: low,
+                    });
+                }
+            }
+        }
+    }
+
+    // The portals, as (left, right) pairs seen from the traveller, crossed on the way from the
+    // start region to the goal region, found with A* between portal midpoints.
+    fn corridor(
+        &self,
+        start_region: usize,
+        start: Vec2,
+        goal_region: usize,
+        goal: Vec2,
+    ) -> Option<Vec<(Vec2, Vec2)>> {
+        let mut open = BinaryHeap::from([Open {
+            estimate: start.distance(goal),
+            region: start_region,
+        }]);
+        let mut costs = HashMap::from([(start_region, 0.0)]);
+        let mut entries = HashMap::from([(start_region, start)]);
+        let mut came_from: HashMap<usize, (usize, Portal)> = HashMap::new();
+        while let Some(Open { region, estimate }) = open.pop() {
+            let (cost, entry) = (costs[&region], entries[&region]);
+            if estimate > cost + entry.distance(goal) + self.epsilon {
+                continue; // Already reached more cheaply.
+            }
+            if region == goal_region {
+                let mut portals = Vec::new();
+                let mut region = region;
+                while let Some(&(previous, portal)) = came_from.get(&region) {
+                    portals.push((portal.b, portal.a));
+                    region = previous;
+                }
+                portals.reverse();
+                return Some(portals);
+            }
+            for portal in &self.regions[region].portals {
+                let middle = (portal.a + portal.b) * 0.5;
+                let next_cost = cost + entry.distance(middle);
+                if costs
+                    .get(&portal.to)
+                    .is_some_and(|&known| known <= next_cost)
+                {
+                    continue;
+                }
+                costs.insert(portal.to, next_cost);
+                entries.insert(portal.to, middle);
+                came_from.insert(portal.to, (region, *portal));
+                open.push(Open {
+                    estimate: next_cost + middle.distance(goal),
+                    region: portal.to,
+                });
+            }
+        }
+        None
+    }
+
+    // Pulls a path tight through a corridor of portals, keeping a funnel of the directions still
+    // open from the last corner and adding a corner whenever one side of the funnel crosses the
+    // other.
+    fn funnel(&self, start: Vec2, goal: Vec2, portals: &[(Vec2, Vec2)]) -> Vec<Vec2> {
+        let mut points = vec![(start, start)];
+        points.extend_from_slice(portals);
+        points.push((goal, goal));
+
+        let same = |a: Vec2, b: Vec2| a.distance(b) <= self.epsilon;
+        let mut path = vec![start];
+        let (mut apex, mut left, mut right) = (start, start, start);
+        let (mut left_index, mut right_index) = (0, 0);
+        let mut i = 1;
+        while i < points.len() {
+            let (next_left, next_right) = points[i];
+
+            // Narrow the right side, unless it would cross the left.
+            if (right - apex).cross(next_right - apex) >= 0.0 {
+                if same(apex, right) || (left - apex).cross(next_right - apex) < 0.0 {
+                    right = next_right;
+                    right_index = i;
+                } else {
+                    path.push(left);
+                    apex = left;
+                    right = apex;
+                    right_index = left_index;
+                    i = left_index + 1;
+                    continue;
+                }
+            }
+
+            // Narrow the left side, unless it would cross the right.
+            if (left - apex).cross(next_left - apex) <= 0.0 {
+                if same(apex, left) || (right - apex).cross(next_left - apex) > 0.0 {
+                    left = next_left;
+                    left_index = i;
+                } else {
+                    path.push(right);
+                    apex = right;
+                    left = apex;
+                    left_index = right_index;
+                    i = right_index + 1;
+                    continue;
+                }
+            }
+            i += 1;
+        }
+        path.push(goal);
+        path.dedup_by(|b, a| same(*a, *b));
+        path
+    }
+}
+
+// A region waiting to be looked at, cheapest estimate first.
+struct Open {
+    estimate: f32,
+    region: usize,
+}
+
+impl PartialEq for Open {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Open {}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+// Twice the area of a polygon; positive when its corners go counter-clockwise.
+fn signed_area(points: &[Vec2]) -> f32 {
+    (0..points.len())
+        .map(|i| points[i].cross(points[(i + 1) % points.len()]))
+        .sum()
+}
+
+// Moves every edge of a polygon out by `radius`, mitring the corners.
+fn inflate(points: &[Vec2], signed_area: f32, radius: f32) -> Vec<Vec2> {
+    if radius <= 0.0 {
+        return points.to_vec();
+    }
+    let n = points.len();
+    // Outward normals of the edges leaving each corner.
+    let outward = |i: usize| {
+        let edge = (points[(i + 1) % n] - points[i]).normalize_or_zero();
+        if signed_area > 0.0 {
+            -edge.perp()
+        } else {
+            edge.perp()
+        }
+    };
+    (0..n)
+        .map(|i| {
+            let (before, after) = (outward((i + n - 1) % n), outward(i));
+            let miter = (before + after) / (1.0 + before.dot(after)).max(1e-6);
+            points[i] + miter.clamp_length(MAX_MITER) * radius
+        })
+        .collect()
+}
+
+// Where two edges cross, strictly inside both.
+fn crossing(p: &Edge, q: &Edge) -> Option<f32> {
+    let (r, s) = (p.b - p.a, q.b - q.a);
+    let denominator = r.cross(s);
+    if denominator.abs() < f32::EPSILON {
+        return None;
+    }
+    let t = (q.a - p.a).cross(s) / denominator;
+    let u = (q.a - p.a).cross(r) / denominator;
+    (t > 0.0 && t < 1.0 && u > 0.0 && u < 1.0).then_some(p.a.x + r.x * t)
+}
+
+fn closest_on_segment(point: Vec2, (a, b): (Vec2, Vec2)) -> Vec2 {
+    let ab = b - a;
+    let t = ((point - a).dot(ab) / ab.length_squared().max(f32::EPSILON)).clamp(0.0, 1.0);
+    a + ab * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(center: Vec2, half: f32) -> Vec<Vec2> {
+        vec![
+            center + Vec2::new(-half, -half),
+            center + Vec2::new(half, -half),
+            center + Vec2::new(half, half),
+            center + Vec2::new(-half, half),
+        ]
+    }
+
+    fn room() -> Aabb {
+        Aabb::new(Vec2::ZERO, Vec2::new(10.0, 10.0))
+    }
+
+    fn assert_path(path: &[Vec2], expected: &[Vec2]) {
+        assert_eq!(path.len(), expected.len(), "{path:?}");
+        for (point, expected) in path.iter().zip(expected) {
+            assert!(point.distance(*expected) < 1e-3, "{path:?}");
+        }
+    }
+
+    #[test]
+    fn paths_in_open_space_are_straight() {
+        let mesh = NavMesh::new(room(), &[square(Vec2::new(5.0, 8.0), 1.0)], 0.5).unwrap();
+        let (start, goal) = (Vec2::new(1.0, 2.0), Vec2::new(9.0, 3.0));
+        assert_path(&mesh.find_path(start, goal).unwrap(), &[start, goal]);
+    }
+
+    #[test]
+    fn paths_turn_at_the_corners_of_an_obstacle() {
+        // The box grows by the agent's radius, so the path turns half a unit clear of it.
+        let mesh = NavMesh::new(room(), &[square(Vec2::new(5.0, 5.0), 1.0)], 0.5).unwrap();
+        let (start, goal) = (Vec2::new(2.0, 5.5), Vec2::new(8.0, 5.5));
+        let path = mesh.find_path(start, goal).unwrap();
+        assert_path(
+            &path,
+            &[start, Vec2::new(3.5, 6.5), Vec2::new(6.5, 6.5), goal],
+        );
+    }
+
+    #[test]
+    fn paths_go_the_short_way_around_concave_obstacles() {
+        // A cup opening upwards, with the start inside it.
+        let cup = vec![
+            Vec2::new(3.0, 3.0),
+            Vec2::new(7.0, 3.0),
+            Vec2::new(7.0, 7.0),
+            Vec2::new(6.0, 7.0),
+            Vec2::new(6.0, 4.0),
+            Vec2::new(4.0, 4.0),
+            Vec2::new(4.0, 7.0),
+            Vec2::new(3.0, 7.0),
+        ];
+        let mesh = NavMesh::new(room(), &[cup], 0.25).unwrap();
+        let (start, goal) = (Vec2::new(5.0, 5.0), Vec2::new(5.0, 1.0));
+        let path = mesh.find_path(start, goal).unwrap();
+        // Out over one rim and down the outside of that wall; either side is as short.
+        let left = [
+            start,
+            Vec2::new(4.25, 7.25),
+            Vec2::new(2.75, 7.25),
+            Vec2::new(2.75, 2.75),
+            goal,
+        ];
+        let right = left.map(|point| Vec2::new(10.0 - point.x, point.y));
+        if path[1].x < start.x {
+            assert_path(&path, &left);
+        } else {
+            assert_path(&path, &right);
+        }
+    }
+
+    #[test]
+    fn no_path_through_a_wall() {
+        let wall = vec![
+            Vec2::new(4.0, -1.0),
+            Vec2::new(6.0, -1.0),
+            Vec2::new(6.0, 11.0),
+            Vec2::new(4.0, 11.0),
+        ];
+        let mesh = NavMesh::new(room(), &[wall], 0.5).unwrap();
+        assert_eq!(
+            mesh.find_path(Vec2::new(2.0, 5.0), Vec2::new(8.0, 5.0)),
+            None
+        );
+        assert!(mesh
+            .find_path(Vec2::new(2.0, 5.0), Vec2::new(1.0, 9.0))
+            .is_some());
+    }
+
+    #[test]
+    fn points_inside_obstacles_move_to_open_space() {
+        let mesh = NavMesh::new(room(), &[square(Vec2::new(5.0, 5.0), 1.0)], 0.5).unwrap();
+        assert_eq!(mesh.region_at(Vec2::new(5.0, 5.0)), None);
+        let (region, point) = mesh.closest_point(Vec2::new(4.9, 5.0)).unwrap();
+        assert_eq!(mesh.region_at(point), Some(region));
+        assert!(point.distance(Vec2::new(3.5, 5.0)) < 1e-3, "{point:?}");
+
+        let goal = Vec2::new(5.0, 5.2);
+        let path = mesh.find_path(Vec2::new(1.0, 5.0), goal).unwrap();
+        assert!(
+            path.last().unwrap().distance(Vec2::new(5.0, 6.5)) < 1e-3,
+            "{path:?}"
+        );
+    }
+
+    #[test]
+    fn bad_input_is_rejected() {
+        assert_eq!(
+            NavMesh::new(room(), &[], 5.0).unwrap_err(),
+            NavMeshError::EmptyBounds
+        );
+        let line = vec![
+            Vec2::new(1.0, 1.0),
+            Vec2::new(2.0, 2.0),
+            Vec2::new(3.0, 3.0),
+        ];
+        assert_eq!(
+            NavMesh::new(room(), &[square(Vec2::new(5.0, 5.0), 1.0), line], 0.5).unwrap_err(),
+            NavMeshError::DegenerateObstacle(1)
+        );
+    }
+}
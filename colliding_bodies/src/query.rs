@@ -0,0 +1,345 @@
+//! Exact ray casts, shape casts and time of impact against a single placed shape.
+
+use crate::gjk;
+use crate::math::Vec2;
+use crate::shape::{Pose, Shape};
+
+/// Where a ray hits a shape.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayIntersection {
+    pub distance: f32, // Along the ray's unit direction.
+    pub point: Vec2,   // World position of the hit.
+    pub normal: Vec2,  // Surface normal at the hit, facing the ray.
+}
+
+/// Where a moving shape first touches another.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapeCastIntersection {
+    pub time: f32,    // Fraction of the translation travelled before touching, 0.0 to 1.0.
+    pub point: Vec2,  // World position of the contact on the other shape.
+    pub normal: Vec2, // Normal of the other shape at the contact, facing the moving shape.
+}
+
+/// Shapes closer than this are touching, for shape casts.
+pub const CAST_TOLERANCE: f32 = 0.001;
+
+const MAX_CAST_ITERATIONS: usize = 32;
+
+/// Casts the ray `origin + t * direction` against a placed shape, for `t` up to `max_distance`.
+///
+/// `direction` must have unit length. Rays starting inside the shape do not hit it.
+pub fn ray_cast(
+    shape: &Shape,
+    pose: Pose,
+    origin: Vec2,
+    direction: Vec2,
+    max_distance: f32,
+) -> Option<RayIntersection> {
+    let pose = match shape {
+        Shape::Aabb { .. } => Pose::new(pose.position, 0.0),
+        _ => pose,
+    };
+    let local_origin = pose.inverse_apply(origin);
+    let local_direction = direction.rotate(-pose.angle);
+
+    let (distance, local_normal) = match shape {
+        Shape::Circle { radius } => {
+            let b = local_origin.dot(local_direction);
+            let c = local_origin.length_squared() - radius * radius;
+            let discriminant = b * b - c;
+            if c < 0.0 || discriminant < 0.0 {
+                return None;
+            }
+            let t = -b - discriminant.sqrt();
+            if !(0.0..=max_distance).contains(&t) {
+                return None;
+            }
+            let point = local_origin + local_direction * t;
+            (t, point / *radius)
+        }
+        _ => {
+            // Clip the ray against every edge's half-plane.
+            let polygon = shape.as_polygon()?;
+            let (mut lower, mut upper) = (0.0_f32, max_distance);
+            let mut entering = None;
+            for (&vertex, &normal) in polygon.vertices().iter().zip(polygon.normals()) {
+                let numerator = normal.dot(vertex - local_origin);
+                let denominator = normal.dot(local_direction);
+                if denominator == 0.0 {
+                    if numerator < 0.0 {
+                        return None; // Parallel to and outside this edge.
+                    }
+                } else if denominator < 0.0 && numerator < lower * denominator {
+                    lower = numerator / denominator;
+                    entering = Some(normal);
+                } else if denominator > 0.0 && numerator < upper * denominator {
+                    upper = numerator / denominator;
+                }
+                if upper < lower {
+                    return None;
+                }
+            }
+            // No entering edge means the ray starts inside.
+            (lower, entering?)
+        }
+    };
+
+    Some(RayIntersection {
+        distance,
+        point: origin + direction * distance,
+        normal: local_normal.rotate(pose.angle),
+    })
+}
+
+/// Moves shape `a` from `pose_a` by `translation` and finds when it first touches shape `b`.
+///
+/// Uses conservative advancement: the shape is repeatedly moved by the distance GJK says is free.
+/// Shapes that already overlap hit at time 0.0.
+pub fn shape_cast(
+    a: &Shape,
+    pose_a: Pose,
+    translation: Vec2,
+    b: &Shape,
+    pose_b: Pose,
+) -> Option<ShapeCastIntersection> {
+    let mut time = 0.0;
+    for _ in 0..MAX_CAST_ITERATIONS {
+        let moved = Pose::new(pose_a.position + translation * time, pose_a.angle);
+        let result = gjk::distance(a, moved, b, pose_b);
+        if result.distance < CAST_TOLERANCE {
+            // Overlapping cores have no normal; push back against the movement instead.
+            let normal = if result.normal == Vec2::ZERO {
+                -translation.normalize_or_zero()
+            } else {
+                -result.normal
+            };
+            return Some(ShapeCastIntersection {
+                time,
+                point: result.point_b,
+                normal,
+            });
+        }
+        let approach = translation.dot(result.normal);
+        if approach <= 0.0 {
+            return None; // Moving apart, or sliding past.
+        }
+        // Stop half a tolerance short, so the loop ends touching rather than overlapping.
+        time += (result.distance - CAST_TOLERANCE * 0.5) / approach;
+        if time > 1.0 {
+            return None;
+        }
+    }
+    None
+}
+
+/// A shape's motion during a step: its pose moves linearly from `start` to `end`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Sweep {
+    pub start: Pose,
+    pub end: Pose,
+}
+
+impl Sweep {
+    pub fn new(start: Pose, end: Pose) -> Self {
+        Self { start, end }
+    }
+
+    /// A shape that does not move.
+    pub fn fixed(pose: Pose) -> Self {
+        Self::new(pose, pose)
+    }
+
+    /// The pose at `time`, from 0.0 at the start to 1.0 at the end.
+    pub fn at(&self, time: f32) -> Pose {
+        Pose::new(
+            self.start.position.lerp(self.end.position, time),
+            self.start.angle + (self.end.angle - self.start.angle) * time,
+        )
+    }
+}
+
+/// The first time, from 0.0 to 1.0, at which two moving shapes come within `target` of each other.
+///
+/// Uses conservative advancement, bounding how fast rotation can close the gap by the shapes'
+/// bounding radii, so it never steps past the impact. Shapes already within `target` only count as
+/// hitting if they are moving closer; shapes that start out overlapping are left to the solver.
+pub fn time_of_impact(
+    a: &Shape,
+    sweep_a: Sweep,
+    b: &Shape,
+    sweep_b: Sweep,
+    target: f32,
+) -> Option<f32> {
+    let relative = (sweep_a.end.position - sweep_a.start.position)
+        - (sweep_b.end.position - sweep_b.start.position);
+    let turn = |shape: &Shape, sweep: Sweep| {
+        if shape.can_rotate() {
+            (sweep.end.angle - sweep.start.angle).abs() * shape.bounding_radius()
+        } else {
+            0.0
+        }
+    };
+    let rotation_bound = turn(a, sweep_a) + turn(b, sweep_b);
+
+    let mut time = 0.0;
+    for _ in 0..MAX_CAST_ITERATIONS {
+        let result = gjk::distance(a, sweep_a.at(time), b, sweep_b.at(time));
+        if result.normal == Vec2::ZERO {
+            // The cores overlap: too deep for a time of impact to mean anything.
+            return (time > 0.0).then_some(time);
+        }
+        let closing_speed = relative.dot(result.normal) + rotation_bound;
+        if result.distance < target + CAST_TOLERANCE {
+            return (closing_speed > 0.0).then_some(time);
+        }
+        if closing_speed <= 0.0 {
+            return None;
+        }
+        time += (result.distance - target) / closing_speed;
+        if time >= 1.0 {
+            return None;
+        }
+    }
+    // Not converged, but every step so far was safe.
+    Some(time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_4, SQRT_2};
+
+    const EPSILON: f32 = 1e-4;
+
+    fn at(x: f32, y: f32) -> Pose {
+        Pose::new(Vec2::new(x, y), 0.0)
+    }
+
+    fn assert_close(actual: Vec2, expected: Vec2) {
+        assert!(
+            (actual - expected).length() < EPSILON,
+            "{actual:?} is not {expected:?}"
+        );
+    }
+
+    #[test]
+    fn rays_hit_box_faces() {
+        let unit = Shape::aabb(1.0, 1.0);
+        let hit = ray_cast(&unit, at(0.0, 0.0), Vec2::new(-5.0, 0.2), Vec2::X, 10.0)
+            .expect("the ray points at the box");
+        assert!((hit.distance - 4.5).abs() < EPSILON);
+        assert_close(hit.point, Vec2::new(-0.5, 0.2));
+        assert_close(hit.normal, -Vec2::X);
+
+        // Too short, pointing away, or starting inside.
+        assert!(ray_cast(&unit, at(0.0, 0.0), Vec2::new(-5.0, 0.2), Vec2::X, 4.0).is_none());
+        assert!(ray_cast(&unit, at(0.0, 0.0), Vec2::new(-5.0, 0.2), -Vec2::X, 10.0).is_none());
+        assert!(ray_cast(&unit, at(0.0, 0.0), Vec2::ZERO, Vec2::X, 10.0).is_none());
+    }
+
+    #[test]
+    fn rays_hit_rotated_boxes() {
+        let diamond = Shape::rectangle(1.0, 1.0);
+        let pose = Pose::new(Vec2::ZERO, FRAC_PI_4);
+        let hit = ray_cast(&diamond, pose, Vec2::new(-5.0, 0.0), Vec2::X, 10.0)
+            .expect("the ray points at the diamond");
+        assert!((hit.distance - (5.0 - FRAC_1_SQRT_2)).abs() < EPSILON);
+        // Both faces meeting at the corner face the ray; either normal will do.
+        assert!(hit.normal.x < 0.0 && hit.normal.y.abs() > 0.5);
+
+        let hit = ray_cast(&diamond, pose, Vec2::new(-5.0, 0.2), Vec2::X, 10.0)
+            .expect("the ray points at the diamond");
+        assert!((hit.distance - (5.0 - FRAC_1_SQRT_2 + 0.2)).abs() < EPSILON);
+        assert_close(hit.normal, Vec2::new(-FRAC_1_SQRT_2, FRAC_1_SQRT_2));
+    }
+
+    #[test]
+    fn rays_graze_box_vertices() {
+        let unit = Shape::rectangle(2.0, 2.0);
+        // The line x + y = 2 only touches the corner at (1, 1).
+        let direction = Vec2::new(1.0, -1.0).normalize_or_zero();
+        let origin = Vec2::new(-1.0, 3.0);
+        let hit = ray_cast(&unit, at(0.0, 0.0), origin, direction, 10.0)
+            .expect("the ray touches the corner");
+        assert!((hit.distance - 2.0 * SQRT_2).abs() < EPSILON);
+        assert_close(hit.point, Vec2::new(1.0, 1.0));
+
+        // A hair further out, it misses.
+        let origin = Vec2::new(-1.0, 3.01);
+        assert!(ray_cast(&unit, at(0.0, 0.0), origin, direction, 10.0).is_none());
+
+        // Running along the top face, it enters at the corner.
+        let hit = ray_cast(&unit, at(0.0, 0.0), Vec2::new(-3.0, 1.0), Vec2::X, 10.0)
+            .expect("the ray runs along the face");
+        assert!((hit.distance - 2.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn rays_hit_circles() {
+        let circle = Shape::circle(1.0);
+        let hit = ray_cast(&circle, at(0.0, 0.0), Vec2::new(-5.0, 0.6), Vec2::X, 10.0)
+            .expect("the ray points at the circle");
+        assert!((hit.distance - 4.2).abs() < EPSILON);
+        assert_close(hit.normal, Vec2::new(-0.8, 0.6));
+        assert!(ray_cast(&circle, at(0.0, 0.0), Vec2::new(-5.0, 1.1), Vec2::X, 10.0).is_none());
+    }
+
+    #[test]
+    fn shape_casts_stop_when_touching() {
+        let circle = Shape::circle(0.5);
+        let unit = Shape::aabb(1.0, 1.0);
+        let hit = shape_cast(
+            &circle,
+            at(-5.0, 0.3),
+            Vec2::new(10.0, 0.0),
+            &unit,
+            at(0.0, 0.0),
+        )
+        .expect("the circle runs into the box");
+        assert!((hit.time - 0.4).abs() < CAST_TOLERANCE, "{hit:?}");
+        assert_close(hit.normal, -Vec2::X);
+        assert_close(hit.point, Vec2::new(-0.5, 0.3));
+
+        // Too short, passing by, or moving away.
+        let short = Vec2::new(3.0, 0.0);
+        assert!(shape_cast(&circle, at(-5.0, 0.3), short, &unit, at(0.0, 0.0)).is_none());
+        let by = Vec2::new(10.0, 0.0);
+        assert!(shape_cast(&circle, at(-5.0, 1.1), by, &unit, at(0.0, 0.0)).is_none());
+        assert!(shape_cast(&circle, at(-5.0, 0.3), -by, &unit, at(0.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn shape_casts_of_rotated_boxes_meet_corners() {
+        let diamond = Shape::rectangle(1.0, 1.0);
+        let floor = Shape::aabb(10.0, 1.0);
+        let start = Pose::new(Vec2::new(0.0, 3.0), FRAC_PI_4);
+        let hit = shape_cast(&diamond, start, Vec2::new(0.0, -4.0), &floor, at(0.0, 0.0))
+            .expect("the diamond falls onto the floor");
+        let fall = 3.0 - FRAC_1_SQRT_2 - 0.5;
+        assert!(
+            (hit.time * 4.0 - fall).abs() < CAST_TOLERANCE * 4.0,
+            "{hit:?}"
+        );
+        assert_close(hit.normal, Vec2::Y);
+        assert!((hit.point.x).abs() < 0.01 && (hit.point.y - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn overlapping_shapes_cast_at_time_zero() {
+        let unit = Shape::aabb(1.0, 1.0);
+        let hit = shape_cast(&unit, at(0.2, 0.0), Vec2::X, &unit, at(0.0, 0.0))
+            .expect("they already overlap");
+        assert_eq!(hit.time, 0.0);
+    }
+
+    #[test]
+    fn time_of_impact_of_two_moving_circles() {
+        let circle = Shape::circle(0.5);
+        let a = Sweep::new(at(-5.0, 0.0), at(5.0, 0.0));
+        let b = Sweep::new(at(5.0, 0.0), at(-5.0, 0.0));
+        let time = time_of_impact(&circle, a, &circle, b, 0.0).expect("they meet");
+        // They close 20 units per step and meet once 9 apart have closed.
+        assert!((time - 0.45).abs() < CAST_TOLERANCE, "{time}");
+        assert!(time_of_impact(&circle, a, &circle, Sweep::fixed(at(0.0, 2.0)), 0.0).is_none());
+    }
+}
@@ -0,0 +1,424 @@
+use crate::math::{Aabb, Vec2};
+
+/// The shape description used by the `enums` crate, accepted as is by the physics engine.
+///
+/// Polygon vertices may be in any order and position; they are turned into their convex hull and
+/// centred on its centroid. A `Rectangle` becomes an axis-aligned box.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrimitiveShape2D {
+    Polygon { vertices: Vec<(f32, f32)> },
+    Circle { radius: f32 },
+    Rectangle { width: f32, height: f32 },
+}
+
+/// The collision shape of a body, in the body's local space (centred on its centre of mass).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    /// A circle around the body's position.
+    Circle { radius: f32 },
+    /// An axis-aligned box. Bodies with this shape never rotate, so the box stays axis aligned.
+    Aabb { half_extents: Vec2 },
+    /// A convex polygon that rotates with the body.
+    Polygon(Polygon),
+}
+
+/// Why a shape description could not be turned into a `Shape`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShapeError {
+    NonPositiveSize, // A radius, width or height was zero or negative.
+    Degenerate,      // A polygon had fewer than three distinct, non-collinear vertices.
+}
+
+impl std::fmt::Display for ShapeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShapeError::NonPositiveSize => write!(f, "shape sizes must be positive"),
+            ShapeError::Degenerate => write!(f, "polygon has no area"),
+        }
+    }
+}
+
+impl std::error::Error for ShapeError {}
+
+impl TryFrom<PrimitiveShape2D> for Shape {
+    type Error = ShapeError;
+
+    fn try_from(shape: PrimitiveShape2D) -> Result<Self, ShapeError> {
+        match shape {
+            PrimitiveShape2D::Circle { radius } if radius > 0.0 => Ok(Shape::Circle { radius }),
+            PrimitiveShape2D::Rectangle { width, height } if width > 0.0 && height > 0.0 => {
+                Ok(Shape::Aabb {
+                    half_extents: Vec2::new(width * 0.5, height * 0.5),
+                })
+            }
+            PrimitiveShape2D::Polygon { vertices } => {
+                let points: Vec<Vec2> = vertices.into_iter().map(Vec2::from).collect();
+                Ok(Shape::Polygon(Polygon::new(&points)?))
+            }
+            _ => Err(ShapeError::NonPositiveSize),
+        }
+    }
+}
+
+/// Mass and rotational inertia of a shape with a given density.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MassProperties {
+    pub mass: f32,
+    pub inertia: f32, // About the centre of mass; 0.0 means the shape cannot rotate.
+}
+
+impl Shape {
+    pub fn circle(radius: f32) -> Self {
+        Shape::Circle { radius }
+    }
+
+    /// An axis-aligned box `width` wide and `height` high.
+    pub fn aabb(width: f32, height: f32) -> Self {
+        Shape::Aabb {
+            half_extents: Vec2::new(width * 0.5, height * 0.5),
+        }
+    }
+
+    /// A rotating box `width` wide and `height` high.
+    pub fn rectangle(width: f32, height: f32) -> Self {
+        Shape::Polygon(Polygon::rectangle(Vec2::new(width * 0.5, height * 0.5)))
+    }
+
+    /// The mass and inertia of the shape for a density (mass per unit area).
+    pub fn mass_properties(&self, density: f32) -> MassProperties {
+        match self {
+            Shape::Circle { radius } => {
+                let mass = density * std::f32::consts::PI * radius * radius;
+                MassProperties {
+                    mass,
+                    inertia: 0.5 * mass * radius * radius,
+                }
+            }
+            Shape::Aabb { half_extents } => MassProperties {
+                mass: density * 4.0 * half_extents.x * half_extents.y,
+                inertia: 0.0,
+            },
+            Shape::Polygon(polygon) => polygon.mass_properties(density),
+        }
+    }
+
+    /// The bounding box of the shape placed at `pose`.
+    pub fn aabb_at(&self, pose: Pose) -> Aabb {
+        match self {
+            Shape::Circle { radius } => {
+                Aabb::from_center(pose.position, Vec2::new(*radius, *radius))
+            }
+            Shape::Aabb { half_extents } => Aabb::from_center(pose.position, *half_extents),
+            Shape::Polygon(polygon) => {
+                let mut vertices = polygon.vertices.iter().map(|&v| pose.apply(v));
+                let first = vertices.next().unwrap_or(pose.position);
+                let (min, max) =
+                    vertices.fold((first, first), |(min, max), v| (min.min(v), max.max(v)));
+                Aabb::new(min, max)
+            }
+        }
+    }
+
+    /// The point of the shape's core furthest along a local direction, and the radius around the core.
+    ///
+    /// Circles are a point with a radius; polygons have no radius. Used by GJK.
+    pub(crate) fn support(&self, direction: Vec2) -> (Vec2, f32) {
+        match self {
+            Shape::Circle { radius } => (Vec2::ZERO, *radius),
+            Shape::Aabb { half_extents } => (
+                Vec2::new(
+                    half_extents.x.copysign(direction.x),
+                    half_extents.y.copysign(direction.y),
+                ),
+                0.0,
+            ),
+            Shape::Polygon(polygon) => (polygon.support(direction), 0.0),
+        }
+    }
+
+    /// The radius of the smallest circle around the centre that contains the shape.
+    pub fn bounding_radius(&self) -> f32 {
+        match self {
+            Shape::Circle { radius } => *radius,
+            Shape::Aabb { half_extents } => half_extents.length(),
+            Shape::Polygon(polygon) => polygon
+                .vertices
+                .iter()
+                .map(|v| v.length())
+                .fold(0.0, f32::max),
+        }
+    }
+
+    /// Returns true if a world-space point lies inside the shape placed at `pose`.
+    pub fn contains_point(&self, pose: Pose, point: Vec2) -> bool {
+        let local = pose.inverse_apply(point);
+        match self {
+            Shape::Circle { radius } => local.length_squared() <= radius * radius,
+            Shape::Aabb { half_extents } => {
+                local.x.abs() <= half_extents.x && local.y.abs() <= half_extents.y
+            }
+            Shape::Polygon(polygon) => polygon
+                .vertices
+                .iter()
+                .zip(&polygon.normals)
+                .all(|(&v, &n)| n.dot(local - v) <= 0.0),
+        }
+    }
+
+    /// The shape as a polygon, for shapes that have one (boxes are turned into one).
+    pub(crate) fn as_polygon(&self) -> Option<std::borrow::Cow<'_, Polygon>> {
+        match self {
+            Shape::Circle { .. } => None,
+            Shape::Aabb { half_extents } => {
+                Some(std::borrow::Cow::Owned(Polygon::rectangle(*half_extents)))
+            }
+            Shape::Polygon(polygon) => Some(std::borrow::Cow::Borrowed(polygon)),
+        }
+    }
+
+    /// Returns false for shapes that must keep their orientation.
+    pub fn can_rotate(&self) -> bool {
+        !matches!(self, Shape::Aabb { .. })
+    }
+}
+
+/// A convex polygon with counter-clockwise vertices, centred on its centroid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    vertices: Vec<Vec2>, // Counter-clockwise, in local space.
+    normals: Vec<Vec2>,  // Outward unit normal of the edge from vertex `i` to vertex `i + 1`.
+}
+
+impl Polygon {
+    /// Builds the convex hull of `points`, moved so its centroid is at the origin.
+    pub fn new(points: &[Vec2]) -> Result<Self, ShapeError> {
+        let hull = convex_hull(points);
+        if hull.len() < 3 {
+            return Err(ShapeError::Degenerate);
+        }
+        let (area, centroid) = area_and_centroid(&hull);
+        if area <= f32::EPSILON {
+            return Err(ShapeError::Degenerate);
+        }
+        Ok(Self::from_hull(
+            hull.into_iter().map(|v| v - centroid).collect(),
+        ))
+    }
+
+    /// A box centred on the origin.
+    pub fn rectangle(half_extents: Vec2) -> Self {
+        let (x, y) = (half_extents.x, half_extents.y);
+        Self::from_hull(vec![
+            Vec2::new(-x, -y),
+            Vec2::new(x, -y),
+            Vec2::new(x, y),
+            Vec2::new(-x, y),
+        ])
+    }
+
+    /// A regular polygon with `sides` corners on a circle of `radius`.
+    pub fn regular(sides: usize, radius: f32) -> Self {
+        let sides = sides.max(3);
+        Self::from_hull(
+            (0..sides)
+                .map(|i| Vec2::X.rotate(i as f32 / sides as f32 * std::f32::consts::TAU) * radius)
+                .collect(),
+        )
+    }
+
+    // Builds a polygon from vertices that are already convex and counter-clockwise.
+    fn from_hull(vertices: Vec<Vec2>) -> Self {
+        let normals = (0..vertices.len())
+            .map(|i| {
+                let edge = vertices[(i + 1) % vertices.len()] - vertices[i];
+                Vec2::new(edge.y, -edge.x).normalize_or_zero()
+            })
+            .collect();
+        Self { vertices, normals }
+    }
+
+    pub fn vertices(&self) -> &[Vec2] {
+        &self.vertices
+    }
+
+    pub fn normals(&self) -> &[Vec2] {
+        &self.normals
+    }
+
+    /// The vertex furthest along `direction`.
+    pub fn support(&self, direction: Vec2) -> Vec2 {
+        self.vertices
+            .iter()
+            .copied()
+            .max_by(|a, b| a.dot(direction).total_cmp(&b.dot(direction)))
+            .unwrap_or(Vec2::ZERO)
+    }
+
+    fn mass_properties(&self, density: f32) -> MassProperties {
+        // Sum the triangles fanning out from the centroid (the origin).
+        let (mut area, mut inertia) = (0.0, 0.0);
+        for (i, &a) in self.vertices.iter().enumerate() {
+            let b = self.vertices[(i + 1) % self.vertices.len()];
+            let cross = a.cross(b);
+            area += cross * 0.5;
+            inertia += cross * (a.dot(a) + a.dot(b) + b.dot(b)) / 12.0;
+        }
+        MassProperties {
+            mass: density * area,
+            inertia: density * inertia,
+        }
+    }
+}
+
+/// The position and rotation of a shape.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Pose {
+    pub position: Vec2,
+    pub angle: f32, // Radians, counter-clockwise.
+}
+
+impl Pose {
+    pub fn new(position: Vec2, angle: f32) -> Self {
+        Self { position, angle }
+    }
+
+    /// Local to world space.
+    pub fn apply(&self, point: Vec2) -> Vec2 {
+        point.rotate(self.angle) + self.position
+    }
+
+    /// World to local space.
+    pub fn inverse_apply(&self, point: Vec2) -> Vec2 {
+        (point - self.position).rotate(-self.angle)
+    }
+}
+
+// Andrew's monotone chain; returns the hull counter-clockwise without collinear points.
+fn convex_hull(points: &[Vec2]) -> Vec<Vec2> {
+    let mut points = points.to_vec();
+    points.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+
+    let turn = |o: Vec2, a: Vec2, b: Vec2| (a - o).cross(b - o);
+    let mut hull: Vec<Vec2> = Vec::with_capacity(points.len() * 2);
+    for pass in [points.clone(), points.into_iter().rev().collect()] {
+        let start = hull.len();
+        for point in pass {
+            while hull.len() >= start + 2
+                && turn(hull[hull.len() - 2], hull[hull.len() - 1], point) <= 0.0
+            {
+                hull.pop();
+            }
+            hull.push(point);
+        }
+        // The last point of each half is the first of the other.
+        hull.pop();
+    }
+    hull
+}
+
+fn area_and_centroid(vertices: &[Vec2]) -> (f32, Vec2) {
+    let (mut area, mut centroid) = (0.0, Vec2::ZERO);
+    let origin = vertices[0];
+    for pair in vertices[1..].windows(2) {
+        let (a, b) = (pair[0] - origin, pair[1] - origin);
+        let triangle = a.cross(b) * 0.5;
+        area += triangle;
+        centroid += (a + b) * (triangle / 3.0);
+    }
+    (area, origin + centroid / area.max(f32::EPSILON))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // True if every turn of the polygon is strictly to the left.
+    fn is_convex_counter_clockwise(vertices: &[Vec2]) -> bool {
+        (0..vertices.len()).all(|i| {
+            let (a, b, c) = (
+                vertices[i],
+                vertices[(i + 1) % vertices.len()],
+                vertices[(i + 2) % vertices.len()],
+            );
+            (b - a).cross(c - b) > 0.0
+        })
+    }
+
+    #[test]
+    fn hull_drops_inner_duplicate_and_collinear_points() {
+        let points = [
+            Vec2::new(1.0, 1.0),
+            Vec2::new(0.0, 0.0),
+            Vec2::new(2.0, 0.0),
+            Vec2::new(1.0, 0.0), // On the bottom edge.
+            Vec2::new(2.0, 2.0),
+            Vec2::new(0.5, 1.5), // Inside.
+            Vec2::new(0.0, 2.0),
+            Vec2::new(2.0, 0.0), // Duplicate.
+        ];
+        let hull = convex_hull(&points);
+        assert_eq!(
+            hull,
+            vec![
+                Vec2::new(0.0, 0.0),
+                Vec2::new(2.0, 0.0),
+                Vec2::new(2.0, 2.0),
+                Vec2::new(0.0, 2.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn hull_of_a_shuffled_regular_polygon_is_convex_and_counter_clockwise() {
+        let mut points = Polygon::regular(9, 2.0).vertices().to_vec();
+        points.reverse();
+        points.swap(1, 6);
+        points.swap(3, 8);
+        let hull = convex_hull(&points);
+        assert_eq!(hull.len(), 9);
+        assert!(is_convex_counter_clockwise(&hull), "{hull:?}");
+    }
+
+    #[test]
+    fn polygons_are_centred_on_their_centroid() {
+        let triangle = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(3.0, 0.0),
+            Vec2::new(0.0, 3.0),
+        ];
+        let polygon = Polygon::new(&triangle).expect("the triangle has area");
+        assert!(is_convex_counter_clockwise(polygon.vertices()));
+        let sum = polygon
+            .vertices()
+            .iter()
+            .fold(Vec2::ZERO, |sum, &v| sum + v);
+        assert!(sum.length() < 1e-5, "centroid off by {sum:?}");
+        for (i, &normal) in polygon.normals().iter().enumerate() {
+            let edge = polygon.vertices()[(i + 1) % 3] - polygon.vertices()[i];
+            assert!(normal.dot(edge).abs() < 1e-6 && (normal.length() - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn polygons_without_area_are_degenerate() {
+        let line = [Vec2::ZERO, Vec2::new(1.0, 1.0), Vec2::new(2.0, 2.0)];
+        assert_eq!(Polygon::new(&line), Err(ShapeError::Degenerate));
+        assert_eq!(
+            Polygon::new(&[Vec2::ZERO, Vec2::X]),
+            Err(ShapeError::Degenerate)
+        );
+    }
+
+    #[test]
+    fn rotated_polygons_contain_points_in_world_space() {
+        let square = Shape::Polygon(Polygon::rectangle(Vec2::new(1.0, 1.0)));
+        let pose = Pose::new(Vec2::new(5.0, 0.0), std::f32::consts::FRAC_PI_4);
+        // The corners are turned onto the axes, about 1.41 from the centre.
+        assert!(square.contains_point(pose, Vec2::new(6.3, 0.0)));
+        assert!(!square.contains_point(pose, Vec2::new(5.9, 0.9)));
+    }
+}
@@ -0,0 +1,1050 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::body::{BodyType, RigidBody};
+use crate::broad_phase::{BroadPhase, DynamicAabbTree};
+use crate::collision::{collide_with_margin, Manifold};
+use crate::joint::{Joint, JointConstraint, JointError};
+use crate::math::{Aabb, Vec2};
+use crate::query::{self, RayIntersection, ShapeCastIntersection, Sweep};
+use crate::shape::{Pose, Shape};
+
+/// Refers to a body in a `World`. Stays invalid after the body is removed, even if its slot is reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BodyHandle {
+    index: u32,
+    generation: u32,
+}
+
+impl BodyHandle {
+    /// The slot of the body in the world; unique among the bodies alive at the same time.
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+}
+
+/// Refers to a joint in a `World`, like a `BodyHandle` does to a body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct JointHandle {
+    index: u32,
+    generation: u32,
+}
+
+/// Solver settings, the defaults suit objects roughly between 0.1 and 10 units in size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldSettings {
+    pub timestep: f32,              // Seconds per step when using `advance`.
+    pub max_steps_per_advance: u32, // Time beyond this many steps is dropped instead of caught up.
+    pub velocity_iterations: u32,   // Passes of the impulse solver per step.
+    pub position_iterations: u32,   // Passes of overlap correction per step.
+    pub contact_margin: f32,        // Bodies closer than this are already treated as touching.
+    pub slop: f32,                  // Overlap allowed without correction, to keep contacts stable.
+    pub correction_factor: f32,     // Fraction of the remaining overlap corrected per pass.
+    pub max_correction: f32,        // Largest distance a body is moved by one correction.
+    pub warm_starting: bool,        // Start the solver from the last step's impulses.
+    pub restitution_threshold: f32, // Impacts slower than this (units/s) do not bounce.
+    pub ccd_substeps: u32,          // Impacts a `ccd` body can bounce off within one step.
+}
+
+impl Default for WorldSettings {
+    fn default() -> Self {
+        Self {
+            timestep: 1.0 / 60.0,
+            max_steps_per_advance: 8,
+            velocity_iterations: 8,
+            position_iterations: 3,
+            contact_margin: 0.02,
+            slop: 0.005,
+            correction_factor: 0.2,
+            max_correction: 0.2,
+            warm_starting: true,
+            restitution_threshold: 1.0,
+            ccd_substeps: 4,
+        }
+    }
+}
+
+/// Two bodies touching during the last step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContactPair {
+    pub a: BodyHandle,
+    pub b: BodyHandle,
+    pub manifold: Manifold, // The normal points from `a` to `b`.
+}
+
+#[derive(Debug, Clone)]
+struct Slot {
+    generation: u32,
+    body: Option<RigidBody>,
+}
+
+#[derive(Debug, Clone)]
+struct JointSlot {
+    generation: u32,
+    joint: Option<Joint>,
+}
+
+/// The closest body hit by a ray cast through the world.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub body: BodyHandle,
+    pub intersection: RayIntersection,
+}
+
+/// The first body touched by a shape cast through the world.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapeCastHit {
+    pub body: BodyHandle,
+    pub intersection: ShapeCastIntersection,
+}
+
+/// A collection of rigid bodies simulated together.
+#[derive(Debug)]
+pub struct World {
+    pub gravity: Vec2,
+    pub settings: WorldSettings,
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    contacts: Vec<ContactPair>,
+    joints: Vec<JointSlot>,
+    free_joints: Vec<u32>,
+    accumulator: f32,
+    broad_phase: Box<dyn BroadPhase>, // Keyed by slot index.
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new(Vec2::new(0.0, -9.81))
+    }
+}
+
+impl World {
+    pub fn new(gravity: Vec2) -> Self {
+        Self {
+            gravity,
+            settings: WorldSettings::default(),
+            slots: Vec::new(),
+            free: Vec::new(),
+            contacts: Vec::new(),
+            joints: Vec::new(),
+            free_joints: Vec::new(),
+            accumulator: 0.0,
+            broad_phase: Box::new(DynamicAabbTree::default()),
+        }
+    }
+
+    /// Replaces the structure used to find bodies near each other. The default is a
+    /// `DynamicAabbTree`; a `SpatialHash` can be faster for many small, similar bodies.
+    pub fn set_broad_phase(&mut self, broad_phase: impl BroadPhase + 'static) {
+        self.broad_phase = Box::new(broad_phase);
+        self.update_broad_phase();
+    }
+
+    pub fn broad_phase(&self) -> &dyn BroadPhase {
+        self.broad_phase.as_ref()
+    }
+
+    pub fn add_body(&mut self, body: RigidBody) -> BodyHandle {
+        let aabb = self.broad_phase_box(&body);
+        let handle = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.body = Some(body);
+                BodyHandle {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    body: Some(body),
+                });
+                BodyHandle {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        };
+        self.broad_phase.insert(handle.index(), aabb);
+        handle
+    }
+
+    /// Removes a body and its joints, returning it if the handle was still valid.
+    pub fn remove_body(&mut self, handle: BodyHandle) -> Option<RigidBody> {
+        let slot = self.slots.get_mut(handle.index as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        let body = slot.body.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(handle.index);
+        self.broad_phase.remove(handle.index());
+        self.contacts
+            .retain(|pair| pair.a != handle && pair.b != handle);
+        let attached: Vec<JointHandle> = self
+            .joints()
+            .filter(|(_, joint)| joint.a == handle || joint.b == handle)
+            .map(|(joint_handle, _)| joint_handle)
+            .collect();
+        for joint_handle in attached {
+            self.remove_joint(joint_handle);
+        }
+        Some(body)
+    }
+
+    pub fn body(&self, handle: BodyHandle) -> Option<&RigidBody> {
+        let slot = self.slots.get(handle.index as usize)?;
+        (slot.generation == handle.generation)
+            .then_some(slot.body.as_ref())
+            .flatten()
+    }
+
+    pub fn body_mut(&mut self, handle: BodyHandle) -> Option<&mut RigidBody> {
+        let slot = self.slots.get_mut(handle.index as usize)?;
+        (slot.generation == handle.generation)
+            .then_some(slot.body.as_mut())
+            .flatten()
+    }
+
+    /// All bodies in the world with their handles.
+    pub fn bodies(&self) -> impl Iterator<Item = (BodyHandle, &RigidBody)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let handle = BodyHandle {
+                index: index as u32,
+                generation: slot.generation,
+            };
+            slot.body.as_ref().map(|body| (handle, body))
+        })
+    }
+
+    pub fn body_count(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    /// The contacts found during the last step.
+    pub fn contacts(&self) -> &[ContactPair] {
+        &self.contacts
+    }
+
+    /// Adds a joint between two live bodies. Returns `None` if either handle is stale or both
+    /// refer to the same body.
+    pub fn add_joint(&mut self, joint: Joint) -> Option<JointHandle> {
+        if joint.a == joint.b || self.body(joint.a).is_none() || self.body(joint.b).is_none() {
+            return None;
+        }
+        let handle = match self.free_joints.pop() {
+            Some(index) => {
+                let slot = &mut self.joints[index as usize];
+                slot.joint = Some(joint);
+                JointHandle {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.joints.push(JointSlot {
+                    generation: 0,
+                    joint: Some(joint),
+                });
+                JointHandle {
+                    index: self.joints.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        };
+        Some(handle)
+    }
+
+    /// Removes a joint, returning it if the handle was still valid.
+    pub fn remove_joint(&mut self, handle: JointHandle) -> Option<Joint> {
+        let slot = self.joints.get_mut(handle.index as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        let joint = slot.joint.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_joints.push(handle.index);
+        Some(joint)
+    }
+
+    pub fn joint(&self, handle: JointHandle) -> Option<&Joint> {
+        let slot = self.joints.get(handle.index as usize)?;
+        (slot.generation == handle.generation)
+            .then_some(slot.joint.as_ref())
+            .flatten()
+    }
+
+    pub fn joint_mut(&mut self, handle: JointHandle) -> Option<&mut Joint> {
+        let slot = self.joints.get_mut(handle.index as usize)?;
+        (slot.generation == handle.generation)
+            .then_some(slot.joint.as_mut())
+            .flatten()
+    }
+
+    /// All joints in the world with their handles.
+    pub fn joints(&self) -> impl Iterator<Item = (JointHandle, &Joint)> {
+        self.joints.iter().enumerate().filter_map(|(index, slot)| {
+            let handle = JointHandle {
+                index: index as u32,
+                generation: slot.generation,
+            };
+            slot.joint.as_ref().map(|joint| (handle, joint))
+        })
+    }
+
+    pub fn joint_count(&self) -> usize {
+        self.joints.len() - self.free_joints.len()
+    }
+
+    /// How far a joint is from being satisfied with the bodies where they are now.
+    pub fn joint_error(&self, handle: JointHandle) -> Option<JointError> {
+        let joint = self.joint(handle)?;
+        Some(joint.error(self.body(joint.a)?, self.body(joint.b)?))
+    }
+
+    /// Runs as many fixed steps of `settings.timestep` as fit in the time elapsed since the last call.
+    ///
+    /// Leftover time is carried over to the next call. Returns the number of steps taken.
+    pub fn advance(&mut self, elapsed: f32) -> u32 {
+        self.accumulator += elapsed.max(0.0);
+        let mut steps = 0;
+        while self.accumulator >= self.settings.timestep {
+            if steps == self.settings.max_steps_per_advance {
+                // Too far behind: drop the time rather than spiral into ever longer frames.
+                self.accumulator = 0.0;
+                break;
+            }
+            self.step(self.settings.timestep);
+            self.accumulator -= self.settings.timestep;
+            steps += 1;
+        }
+        steps
+    }
+
+    /// Advances the simulation by `dt` seconds.
+    pub fn step(&mut self, dt: f32) {
+        if dt <= 0.0 {
+            return;
+        }
+        self.integrate_velocities(dt);
+        self.update_broad_phase();
+        self.contacts = self.find_contacts();
+
+        let mut solver = Solver::new(
+            &self.slots,
+            &self.contacts,
+            &self.joints,
+            &self.settings,
+            dt,
+        );
+        for _ in 0..self.settings.velocity_iterations {
+            solver.solve(dt);
+        }
+        solver.store(&mut self.slots, &mut self.contacts, &mut self.joints);
+
+        let fast_starts = self.fast_body_poses();
+        self.integrate_positions(dt);
+        self.solve_continuous(&fast_starts, dt);
+        for _ in 0..self.settings.position_iterations {
+            // Both always run, so joints are corrected even once the contacts are settled. Joints
+            // go last, as they must hold while contacts may give a little.
+            let contacts_corrected = self.correct_positions();
+            if !self.correct_joints() && !contacts_corrected {
+                break;
+            }
+        }
+    }
+
+    fn integrate_velocities(&mut self, dt: f32) {
+        let gravity = self.gravity;
+        for body in self.slots.iter_mut().filter_map(|slot| slot.body.as_mut()) {
+            if body.is_dynamic() {
+                body.velocity +=
+                    (gravity * body.gravity_scale + body.force * body.inverse_mass()) * dt;
+                body.angular_velocity += body.torque * body.inverse_inertia() * dt;
+                body.velocity *= 1.0 / (1.0 + dt * body.linear_damping);
+                body.angular_velocity *= 1.0 / (1.0 + dt * body.angular_damping);
+            }
+            body.force = Vec2::ZERO;
+            body.torque = 0.0;
+        }
+    }
+
+    fn integrate_positions(&mut self, dt: f32) {
+        for body in self.slots.iter_mut().filter_map(|slot| slot.body.as_mut()) {
+            if body.body_type == BodyType::Static {
+                continue;
+            }
+            body.position += body.velocity * dt;
+            if body.shape.can_rotate() {
+                body.angle += body.angular_velocity * dt;
+            }
+        }
+    }
+
+    // Where the dynamic bodies with continuous collision detection are before moving.
+    fn fast_body_poses(&self) -> Vec<(usize, Pose)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let body = slot.body.as_ref()?;
+                (body.ccd && body.is_dynamic()).then(|| (index, body.pose()))
+            })
+            .collect()
+    }
+
+    // Continuous collision detection. Each fast body's move this step is swept against the other
+    // bodies, which are held where they ended up. At the first impact the body stops, bounces, and
+    // spends the rest of the step moving on from there, up to `ccd_substeps` times; out of
+    // substeps, it waits at the last impact for the next step.
+    fn solve_continuous(&mut self, starts: &[(usize, Pose)], dt: f32) {
+        if starts.is_empty() {
+            return;
+        }
+        self.update_broad_phase();
+        let substeps = self.settings.ccd_substeps.max(1);
+        for &(index, mut start) in starts {
+            let mut remaining = dt;
+            for substep in 1..=substeps {
+                let sweep = Sweep::new(start, self.slot_body(index).pose());
+                let Some((time, other)) = self.first_impact(index, sweep) else {
+                    break;
+                };
+                let impact = sweep.at(time);
+                let body = self.slots[index].body.as_mut().expect("live body");
+                body.position = impact.position;
+                body.angle = impact.angle;
+                self.respond_to_impact(index, other);
+                if substep == substeps {
+                    break;
+                }
+
+                remaining *= 1.0 - time;
+                start = impact;
+                let body = self.slots[index].body.as_mut().expect("live body");
+                body.position += body.velocity * remaining;
+                if body.shape.can_rotate() {
+                    body.angle += body.angular_velocity * remaining;
+                }
+            }
+            let aabb = self.broad_phase_box(self.slot_body(index));
+            self.broad_phase.update(index, aabb);
+        }
+    }
+
+    // The earliest time of impact of a body's sweep with any other body, and that body. Ties go to
+    // the lower index.
+    fn first_impact(&self, index: usize, sweep: Sweep) -> Option<(f32, usize)> {
+        let body = self.slot_body(index);
+        let region = (body.shape.aabb_at(sweep.start))
+            .union(&body.shape.aabb_at(sweep.end))
+            .expanded(self.settings.contact_margin);
+        let mut first: Option<(f32, usize)> = None;
+        for other in self.broad_phase.query_region(&region) {
+            if other == index {
+                continue;
+            }
+            let obstacle = self.slot_body(other);
+            let Some(time) = query::time_of_impact(
+                &body.shape,
+                sweep,
+                &obstacle.shape,
+                Sweep::fixed(obstacle.pose()),
+                self.settings.slop,
+            ) else {
+                continue;
+            };
+            if first.is_none_or(|best| (time, other) < best) {
+                first = Some((time, other));
+            }
+        }
+        first
+    }
+
+    // Applies the impulse that stops a fast body moving into what it hit, with the usual
+    // restitution and friction, as the contact solver would on the next step.
+    fn respond_to_impact(&mut self, index: usize, other: usize) {
+        let (body, obstacle) = (self.slot_body(index), self.slot_body(other));
+        // The sweep stops within the slop, so the margin always finds the contact.
+        let Some(manifold) = collide_with_margin(
+            &body.shape,
+            body.pose(),
+            &obstacle.shape,
+            obstacle.pose(),
+            self.settings.contact_margin,
+        ) else {
+            return;
+        };
+        let count = manifold.contacts.len() as f32;
+        let point = (manifold.contacts.iter()).fold(Vec2::ZERO, |sum, contact| sum + contact.point)
+            * (1.0 / count);
+        let normal = manifold.normal;
+        let relative = obstacle.velocity_at(point) - body.velocity_at(point);
+        let approach = -relative.dot(normal);
+        if approach <= 0.0 {
+            return;
+        }
+
+        let (r_a, r_b) = (point - body.position, point - obstacle.position);
+        let effective_mass = |direction: Vec2| {
+            let (rn_a, rn_b) = (r_a.cross(direction), r_b.cross(direction));
+            let k = body.inverse_mass()
+                + obstacle.inverse_mass()
+                + body.inverse_inertia() * rn_a * rn_a
+                + obstacle.inverse_inertia() * rn_b * rn_b;
+            if k > 0.0 {
+                1.0 / k
+            } else {
+                0.0
+            }
+        };
+        let restitution = if approach > self.settings.restitution_threshold {
+            body.restitution.max(obstacle.restitution)
+        } else {
+            0.0
+        };
+        let normal_impulse = (1.0 + restitution) * approach * effective_mass(normal);
+        let tangent = normal.perp();
+        let friction = (body.friction * obstacle.friction).sqrt() * normal_impulse;
+        let tangent_impulse =
+            (-relative.dot(tangent) * effective_mass(tangent)).clamp(-friction, friction);
+        let impulse = normal * normal_impulse + tangent * tangent_impulse;
+
+        self.slots[index]
+            .body
+            .as_mut()
+            .expect("live body")
+            .apply_impulse_at(-impulse, point);
+        self.slots[other]
+            .body
+            .as_mut()
+            .expect("live body")
+            .apply_impulse_at(impulse, point);
+    }
+
+    /// Brings the broad phase up to date with the bodies' positions.
+    ///
+    /// `step` does this itself; call it before running queries on bodies moved with `body_mut`.
+    pub fn update_broad_phase(&mut self) {
+        for index in 0..self.slots.len() {
+            if let Some(body) = &self.slots[index].body {
+                let aabb = self.broad_phase_box(body);
+                self.broad_phase.update(index, aabb);
+            }
+        }
+    }
+
+    // Boxes are grown by half the contact margin each, so bodies within the margin are paired.
+    fn broad_phase_box(&self, body: &RigidBody) -> Aabb {
+        body.aabb().expanded(self.settings.contact_margin * 0.5)
+    }
+
+    // Pairs of bodies whose bounding boxes overlap and that can push each other, in a fixed order
+    // so the simulation is deterministic. Bodies joined by a joint do not collide unless it says so.
+    fn candidate_pairs(&self) -> Vec<(usize, usize)> {
+        let jointed: HashSet<(usize, usize)> = self
+            .joints()
+            .filter(|(_, joint)| !joint.collide_connected)
+            .map(|(_, joint)| {
+                let (a, b) = (joint.a.index(), joint.b.index());
+                (a.min(b), a.max(b))
+            })
+            .collect();
+        let mut pairs = self.broad_phase.pairs();
+        pairs.retain(|&(a, b)| {
+            (self.slot_body(a).is_dynamic() || self.slot_body(b).is_dynamic())
+                && !jointed.contains(&(a, b))
+        });
+        pairs
+    }
+
+    /// The bodies whose bounding boxes overlap `region`.
+    pub fn query_region(&self, region: &Aabb) -> Vec<BodyHandle> {
+        let mut bodies: Vec<BodyHandle> = self
+            .broad_phase
+            .query_region(region)
+            .into_iter()
+            .filter(|&index| self.slot_body(index).aabb().overlaps(region))
+            .map(|index| self.handle(index))
+            .collect();
+        bodies.sort_unstable_by_key(|handle| handle.index);
+        bodies
+    }
+
+    /// The bodies whose shape contains `point`.
+    pub fn query_point(&self, point: Vec2) -> Vec<BodyHandle> {
+        self.query_region(&Aabb::new(point, point))
+            .into_iter()
+            .filter(|&handle| {
+                let body = self.slot_body(handle.index());
+                body.shape.contains_point(body.pose(), point)
+            })
+            .collect()
+    }
+
+    /// Casts a ray from `origin` along `direction` and returns the closest body it hits within
+    /// `max_distance`, among those `filter` accepts.
+    pub fn ray_cast(
+        &self,
+        origin: Vec2,
+        direction: Vec2,
+        max_distance: f32,
+        filter: impl Fn(BodyHandle, &RigidBody) -> bool,
+    ) -> Option<RayHit> {
+        let direction = direction.normalize_or_zero();
+        if direction == Vec2::ZERO {
+            return None;
+        }
+        let mut closest: Option<RayHit> = None;
+        self.broad_phase
+            .visit_ray(origin, direction, max_distance, &mut |index, _| {
+                let body = self.slot_body(index);
+                let handle = self.handle(index);
+                let max = closest.map_or(max_distance, |hit| hit.intersection.distance);
+                if !filter(handle, body) {
+                    return max;
+                }
+                match query::ray_cast(&body.shape, body.pose(), origin, direction, max) {
+                    Some(intersection) => {
+                        closest = Some(RayHit {
+                            body: handle,
+                            intersection,
+                        });
+                        intersection.distance
+                    }
+                    None => max,
+                }
+            });
+        closest
+    }
+
+    /// Moves `shape` from `pose` by `translation` and returns the first body it touches, among
+    /// those `filter` accepts.
+    pub fn shape_cast(
+        &self,
+        shape: &Shape,
+        pose: Pose,
+        translation: Vec2,
+        filter: impl Fn(BodyHandle, &RigidBody) -> bool,
+    ) -> Option<ShapeCastHit> {
+        let swept = shape
+            .aabb_at(pose)
+            .swept(translation)
+            .expanded(query::CAST_TOLERANCE);
+        let mut closest: Option<ShapeCastHit> = None;
+        for index in self.broad_phase.query_region(&swept) {
+            let (body, handle) = (self.slot_body(index), self.handle(index));
+            if !filter(handle, body) {
+                continue;
+            }
+            let Some(intersection) =
+                query::shape_cast(shape, pose, translation, &body.shape, body.pose())
+            else {
+                continue;
+            };
+            // Ties go to the lower index, so results do not depend on the broad phase's order.
+            let closer = closest.is_none_or(|hit| {
+                (intersection.time, handle.index) < (hit.intersection.time, hit.body.index)
+            });
+            if closer {
+                closest = Some(ShapeCastHit {
+                    body: handle,
+                    intersection,
+                });
+            }
+        }
+        closest
+    }
+
+    // Collides the candidate pairs, carrying over the impulses of contacts that persist from the
+    // last step so the solver can start from them.
+    fn find_contacts(&self) -> Vec<ContactPair> {
+        let previous: HashMap<(BodyHandle, BodyHandle), &Manifold> = self
+            .contacts
+            .iter()
+            .map(|pair| ((pair.a, pair.b), &pair.manifold))
+            .collect();
+        self.candidate_pairs()
+            .into_iter()
+            .filter_map(|(a, b)| {
+                let (body_a, body_b) = (self.slot_body(a), self.slot_body(b));
+                let mut manifold = collide_with_margin(
+                    &body_a.shape,
+                    body_a.pose(),
+                    &body_b.shape,
+                    body_b.pose(),
+                    self.settings.contact_margin,
+                )?;
+                if let Some(old) = previous.get(&(self.handle(a), self.handle(b))) {
+                    for contact in &mut manifold.contacts {
+                        // The same contact has barely moved since the last step.
+                        let matching = old.contacts.iter().find(|old| {
+                            old.point.distance(contact.point) < PERSISTENT_CONTACT_DISTANCE
+                        });
+                        if let Some(old) = matching {
+                            contact.normal_impulse = old.normal_impulse;
+                            contact.tangent_impulse = old.tangent_impulse;
+                        }
+                    }
+                }
+                Some(ContactPair {
+                    a: self.handle(a),
+                    b: self.handle(b),
+                    manifold,
+                })
+            })
+            .collect()
+    }
+
+    // Moves and turns overlapping bodies apart directly, without adding velocity, so resting
+    // contacts do not sink or drift. Returns false once every overlap is within the slop.
+    fn correct_positions(&mut self) -> bool {
+        let settings = self.settings;
+        let mut corrected = false;
+        for pair in &self.contacts {
+            let (a, b) = (pair.a.index(), pair.b.index());
+            for point in 0..pair.manifold.contacts.len() {
+                let (body_a, body_b) = (self.slot_body(a), self.slot_body(b));
+                // Recollide, as earlier corrections have moved the bodies.
+                let Some(manifold) = collide_with_margin(
+                    &body_a.shape,
+                    body_a.pose(),
+                    &body_b.shape,
+                    body_b.pose(),
+                    0.0,
+                ) else {
+                    break;
+                };
+                let Some(contact) = manifold.contacts.get(point) else {
+                    break;
+                };
+                let overlap = contact.depth - settings.slop;
+                if overlap <= 0.0 {
+                    continue;
+                }
+                corrected = true;
+
+                let normal = manifold.normal;
+                let (r_a, r_b) = (
+                    contact.point - body_a.position,
+                    contact.point - body_b.position,
+                );
+                let (rn_a, rn_b) = (r_a.cross(normal), r_b.cross(normal));
+                let k = body_a.inverse_mass()
+                    + body_b.inverse_mass()
+                    + body_a.inverse_inertia() * rn_a * rn_a
+                    + body_b.inverse_inertia() * rn_b * rn_b;
+                if k <= 0.0 {
+                    break;
+                }
+                let correction =
+                    (overlap * settings.correction_factor).min(settings.max_correction);
+                let impulse = normal * (correction / k);
+                for (index, sign, r) in [(a, -1.0, r_a), (b, 1.0, r_b)] {
+                    let body = self.slots[index].body.as_mut().expect("live body");
+                    body.position += impulse * (sign * body.inverse_mass());
+                    body.angle += sign * body.inverse_inertia() * r.cross(impulse);
+                }
+            }
+        }
+        corrected
+    }
+
+    // Moves the bodies of every joint back into place. Returns false once every joint is within
+    // the slop.
+    fn correct_joints(&mut self) -> bool {
+        let settings = self.settings;
+        let mut corrected = false;
+        for slot in &self.joints {
+            let Some(joint) = &slot.joint else { continue };
+            let (a, b) = (joint.a.index(), joint.b.index());
+            // Two mutable bodies from the same list: split it between them.
+            let (low, high) = self.slots.split_at_mut(a.max(b));
+            let (first, second) = (
+                low[a.min(b)]
+                    .body
+                    .as_mut()
+                    .expect("joints only join live bodies"),
+                high[0].body.as_mut().expect("joints only join live bodies"),
+            );
+            let (body_a, body_b) = if a < b {
+                (first, second)
+            } else {
+                (second, first)
+            };
+            corrected |=
+                joint.correct_positions(body_a, body_b, settings.slop, settings.max_correction);
+        }
+        corrected
+    }
+
+    fn slot_body(&self, index: usize) -> &RigidBody {
+        self.slots[index]
+            .body
+            .as_ref()
+            .expect("contacts only refer to live bodies")
+    }
+
+    fn handle(&self, index: usize) -> BodyHandle {
+        BodyHandle {
+            index: index as u32,
+            generation: self.slots[index].generation,
+        }
+    }
+}
+
+// The velocities of one body while the solver works on them.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SolverBody {
+    pub(crate) velocity: Vec2,
+    pub(crate) angular_velocity: f32,
+    pub(crate) inverse_mass: f32,
+    pub(crate) inverse_inertia: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ContactPoint {
+    r_a: Vec2,            // From the centre of mass of `a` to the contact point.
+    r_b: Vec2,            // From the centre of mass of `b` to the contact point.
+    normal_mass: f32,     // Inverse of the effective mass along the normal.
+    tangent_mass: f32,    // Inverse of the effective mass along the tangent.
+    target: f32,          // Separating speed to reach: a bounce, or the gap that may still close.
+    normal_impulse: f32,  // Accumulated over the iterations, never negative.
+    tangent_impulse: f32, // Accumulated over the iterations, within the friction cone.
+}
+
+#[derive(Debug, Clone)]
+struct ContactConstraint {
+    a: usize, // Index into `Solver::bodies`.
+    b: usize,
+    normal: Vec2,
+    friction: f32,
+    points: Vec<ContactPoint>,
+}
+
+// Sequential impulses: each joint and then each contact in turn is given the impulse that fixes
+// its relative velocity, with the total impulse clamped so contacts only push and friction stays
+// within the cone.
+struct Solver {
+    bodies: Vec<SolverBody>,
+    slots: Vec<usize>,        // The world slot of each solver body.
+    solver_index: Vec<usize>, // The solver body of each world slot, or `usize::MAX`.
+    constraints: Vec<ContactConstraint>,
+    joints: Vec<(usize, JointConstraint)>, // With the joint slot each came from.
+}
+
+impl Solver {
+    fn new(
+        slots: &[Slot],
+        contacts: &[ContactPair],
+        joints: &[JointSlot],
+        settings: &WorldSettings,
+        dt: f32,
+    ) -> Self {
+        let mut solver = Self {
+            bodies: Vec::new(),
+            slots: Vec::new(),
+            solver_index: vec![usize::MAX; slots.len()],
+            constraints: Vec::with_capacity(contacts.len()),
+            joints: Vec::new(),
+        };
+
+        for (index, slot) in joints.iter().enumerate() {
+            let Some(joint) = &slot.joint else { continue };
+            let (a, b) = (joint.a.index(), joint.b.index());
+            let body_a = (solver.add_body(slots, a), slot_body(slots, a));
+            let body_b = (solver.add_body(slots, b), slot_body(slots, b));
+            let constraint =
+                JointConstraint::new(joint, body_a, body_b, dt, settings.warm_starting);
+            solver.joints.push((index, constraint));
+        }
+
+        for pair in contacts {
+            let [a, b] = [pair.a.index(), pair.b.index()].map(|slot| solver.add_body(slots, slot));
+
+            let body_a = slot_body(slots, pair.a.index());
+            let body_b = slot_body(slots, pair.b.index());
+            let (sa, sb) = (solver.bodies[a], solver.bodies[b]);
+            let normal = pair.manifold.normal;
+            let tangent = -normal.perp();
+            let restitution = body_a.restitution.max(body_b.restitution);
+            let points = pair
+                .manifold
+                .contacts
+                .iter()
+                .map(|contact| {
+                    let r_a = contact.point - body_a.position;
+                    let r_b = contact.point - body_b.position;
+                    let effective_mass = |axis: Vec2| {
+                        let (ra_axis, rb_axis) = (r_a.cross(axis), r_b.cross(axis));
+                        let k = sa.inverse_mass
+                            + sb.inverse_mass
+                            + sa.inverse_inertia * ra_axis * ra_axis
+                            + sb.inverse_inertia * rb_axis * rb_axis;
+                        if k > 0.0 {
+                            1.0 / k
+                        } else {
+                            0.0
+                        }
+                    };
+                    let relative = relative_velocity(&sa, &sb, r_a, r_b);
+                    let approach = relative.dot(normal);
+                    ContactPoint {
+                        r_a,
+                        r_b,
+                        normal_mass: effective_mass(normal),
+                        tangent_mass: effective_mass(tangent),
+                        target: if contact.depth < 0.0 {
+                            // Not touching yet: allow approaching just enough to close the gap.
+                            contact.depth / dt
+                        } else if approach < -settings.restitution_threshold {
+                            -restitution * approach
+                        } else {
+                            0.0
+                        },
+                        normal_impulse: if settings.warm_starting {
+                            contact.normal_impulse
+                        } else {
+                            0.0
+                        },
+                        tangent_impulse: if settings.warm_starting {
+                            contact.tangent_impulse
+                        } else {
+                            0.0
+                        },
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            solver.constraints.push(ContactConstraint {
+                a,
+                b,
+                normal,
+                friction: (body_a.friction * body_b.friction).sqrt(),
+                points,
+            });
+        }
+
+        // Warm start: apply last step's impulses up front, so resting contacts and joints start out
+        // solved. This comes after every bounce was measured, from the velocities before any
+        // impulse.
+        for (_, joint) in &solver.joints {
+            joint.warm_start(&mut solver.bodies);
+        }
+        for constraint in &solver.constraints {
+            let (mut a, mut b) = (solver.bodies[constraint.a], solver.bodies[constraint.b]);
+            let tangent = -constraint.normal.perp();
+            for point in &constraint.points {
+                let impulse =
+                    constraint.normal * point.normal_impulse + tangent * point.tangent_impulse;
+                apply(&mut a, &mut b, point, impulse);
+            }
+            solver.bodies[constraint.a] = a;
+            solver.bodies[constraint.b] = b;
+        }
+        solver
+    }
+
+    // The solver body for a world slot, added the first time it is needed.
+    fn add_body(&mut self, slots: &[Slot], slot: usize) -> usize {
+        if self.solver_index[slot] == usize::MAX {
+            let body = slot_body(slots, slot);
+            self.solver_index[slot] = self.bodies.len();
+            self.slots.push(slot);
+            self.bodies.push(SolverBody {
+                velocity: body.velocity,
+                angular_velocity: body.angular_velocity,
+                inverse_mass: body.inverse_mass(),
+                inverse_inertia: body.inverse_inertia(),
+            });
+        }
+        self.solver_index[slot]
+    }
+
+    fn solve(&mut self, dt: f32) {
+        for (_, joint) in &mut self.joints {
+            joint.solve(&mut self.bodies, dt);
+        }
+        for constraint in &mut self.constraints {
+            let (mut a, mut b) = (self.bodies[constraint.a], self.bodies[constraint.b]);
+            let normal = constraint.normal;
+            let tangent = -normal.perp();
+            for point in &mut constraint.points {
+                // Friction first, so the normal impulse (which matters more) is solved last.
+                let relative = relative_velocity(&a, &b, point.r_a, point.r_b);
+                let limit = constraint.friction * point.normal_impulse;
+                let lambda = -relative.dot(tangent) * point.tangent_mass;
+                let total = (point.tangent_impulse + lambda).clamp(-limit, limit);
+                apply(
+                    &mut a,
+                    &mut b,
+                    point,
+                    tangent * (total - point.tangent_impulse),
+                );
+                point.tangent_impulse = total;
+
+                let relative = relative_velocity(&a, &b, point.r_a, point.r_b);
+                let lambda = (point.target - relative.dot(normal)) * point.normal_mass;
+                let total = (point.normal_impulse + lambda).max(0.0);
+                apply(
+                    &mut a,
+                    &mut b,
+                    point,
+                    normal * (total - point.normal_impulse),
+                );
+                point.normal_impulse = total;
+            }
+            self.bodies[constraint.a] = a;
+            self.bodies[constraint.b] = b;
+        }
+    }
+
+    // Writes the solved velocities back to the bodies, and the impulses back to the contacts and
+    // joints.
+    fn store(&self, slots: &mut [Slot], contacts: &mut [ContactPair], joints: &mut [JointSlot]) {
+        for (index, constraint) in &self.joints {
+            if let Some(joint) = joints[*index].joint.as_mut() {
+                joint.impulses = constraint.impulses;
+            }
+        }
+        for (constraint, pair) in self.constraints.iter().zip(contacts) {
+            for (point, contact) in constraint.points.iter().zip(&mut pair.manifold.contacts) {
+                contact.normal_impulse = point.normal_impulse;
+                contact.tangent_impulse = point.tangent_impulse;
+            }
+        }
+        for (solver_body, &slot) in self.bodies.iter().zip(&self.slots) {
+            if let Some(body) = slots[slot].body.as_mut() {
+                if body.is_dynamic() {
+                    body.velocity = solver_body.velocity;
+                    body.angular_velocity = solver_body.angular_velocity;
+                }
+            }
+        }
+    }
+}
+
+fn slot_body(slots: &[Slot], index: usize) -> &RigidBody {
+    slots[index]
+        .body
+        .as_ref()
+        .expect("contacts and joints only refer to live bodies")
+}
+
+// Contacts closer than this to one from the last step are taken to be the same contact.
+const PERSISTENT_CONTACT_DISTANCE: f32 = 0.05;
+
+// Velocity of the contact point on `b` relative to the one on `a`.
+pub(crate) fn relative_velocity(a: &SolverBody, b: &SolverBody, r_a: Vec2, r_b: Vec2) -> Vec2 {
+    (b.velocity + Vec2::cross_scalar(b.angular_velocity, r_b))
+        - (a.velocity + Vec2::cross_scalar(a.angular_velocity, r_a))
+}
+
+// Applies `impulse` to `b` and its opposite to `a`.
+fn apply(a: &mut SolverBody, b: &mut SolverBody, point: &ContactPoint, impulse: Vec2) {
+    a.velocity -= impulse * a.inverse_mass;
+    a.angular_velocity -= a.inverse_inertia * point.r_a.cross(impulse);
+    b.velocity += impulse * b.inverse_mass;
+    b.angular_velocity += b.inverse_inertia * point.r_b.cross(impulse);
+}
@@ -0,0 +1,94 @@
+// Fires small, fast bodies at a thin wall and checks that continuous collision detection stops
+// every one of them, at any speed and angle, while the same shots without it pass straight through.
+
+use colliding_bodies::{RigidBody, Shape, Vec2, World};
+
+const WALL_X: f32 = 5.0;
+const WALL_HALF_THICKNESS: f32 = 0.01;
+const STEPS: usize = 120;
+
+const SPEEDS: [f32; 5] = [20.0, 100.0, 500.0, 2_000.0, 10_000.0];
+const ANGLES: [f32; 7] = [-60.0, -30.0, -10.0, 0.0, 10.0, 30.0, 60.0];
+
+// Fires `bullet` from the origin at `velocity` and returns the furthest point it reached past the
+// front of the wall, so anything above zero went into or through it.
+fn fire(bullet: RigidBody, velocity: Vec2) -> f32 {
+    let mut world = World::new(Vec2::ZERO);
+    let wall = Shape::aabb(WALL_HALF_THICKNESS * 2.0, 100.0);
+    world.add_body(RigidBody::fixed(wall).with_position(Vec2::new(WALL_X, 0.0)));
+    let handle = world.add_body(bullet.with_velocity(velocity));
+
+    let front = WALL_X - WALL_HALF_THICKNESS;
+    let mut furthest = f32::NEG_INFINITY;
+    for _ in 0..STEPS {
+        world.step(1.0 / 60.0);
+        let body = world.body(handle).expect("the bullet is never removed");
+        furthest = furthest.max(body.aabb().max.x - front);
+    }
+    furthest
+}
+
+// Fires a spinning `shape` with CCD at every speed and angle, asserting it never enters the wall.
+fn assert_stopped(name: &str, shape: Shape) {
+    for speed in SPEEDS {
+        for angle in ANGLES {
+            let velocity = Vec2::X.rotate(angle.to_radians()) * speed;
+            let bullet = RigidBody::dynamic(shape.clone())
+                .with_angular_velocity(40.0)
+                .with_ccd(true);
+            let furthest = fire(bullet, velocity);
+            assert!(
+                furthest < 0.01,
+                "the {name} fired at {speed} units/s and {angle}° went {furthest} into the wall"
+            );
+        }
+    }
+}
+
+#[test]
+fn fast_circles_stop_at_thin_walls() {
+    assert_stopped("circle", Shape::circle(0.05));
+}
+
+#[test]
+fn fast_spinning_boxes_stop_at_thin_walls() {
+    assert_stopped("spinning box", Shape::rectangle(0.1, 0.04));
+}
+
+#[test]
+fn fast_circles_tunnel_without_ccd() {
+    let unswept = fire(
+        RigidBody::dynamic(Shape::circle(0.05)),
+        Vec2::new(2_000.0, 0.0),
+    );
+    assert!(unswept > 1.0, "the unswept bullet should have tunnelled");
+}
+
+// A perfectly bouncy bullet between two close walls hits several times per step, using up the
+// substeps; it must stay between them.
+#[test]
+fn bouncing_bullets_stay_between_close_walls() {
+    let mut world = World::new(Vec2::ZERO);
+    for x in [-0.5, 0.5] {
+        let wall = Shape::aabb(WALL_HALF_THICKNESS * 2.0, 10.0);
+        world.add_body(RigidBody::fixed(wall).with_position(Vec2::new(x, 0.0)));
+    }
+    let bullet = RigidBody::dynamic(Shape::circle(0.05))
+        .with_restitution(1.0)
+        .with_velocity(Vec2::new(300.0, 1.0))
+        .with_ccd(true);
+    let handle = world.add_body(bullet);
+    for _ in 0..STEPS {
+        world.step(1.0 / 60.0);
+        let position = world
+            .body(handle)
+            .expect("the bullet is never removed")
+            .position;
+        assert!(
+            position.x.abs() < 0.5,
+            "the bouncing bullet escaped to {position:?}"
+        );
+    }
+    let body = world.body(handle).expect("the bullet is never removed");
+    assert!(body.velocity.length() > 100.0, "the bullet lost its speed");
+}
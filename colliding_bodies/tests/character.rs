@@ -0,0 +1,288 @@
+// Walks a kinematic character through small levels, one per thing the controller promises: it runs
+// over tiled floors without catching on the seams, slides along walls, walks up gentle slopes but
+// not steep ones, climbs low steps, follows the ground down slopes and lands after jumping.
+
+use colliding_bodies::{
+    CharacterController, CharacterMove, Polygon, RigidBody, Shape, Vec2, World,
+};
+
+const DT: f32 = 1.0 / 60.0;
+const GRAVITY: f32 = -9.81;
+const WIDTH: f32 = 0.5;
+const HEIGHT: f32 = 1.0;
+
+fn controller() -> CharacterController {
+    CharacterController::new(Shape::aabb(WIDTH, HEIGHT))
+}
+
+// A world with a floor made of one-unit tiles, its top at y = 0.
+fn tiled_floor() -> World {
+    let mut world = World::default();
+    for i in -20..20 {
+        let tile = RigidBody::fixed(Shape::aabb(1.0, 1.0)).with_position(Vec2::new(i as f32, -0.5));
+        world.add_body(tile);
+    }
+    world
+}
+
+// A fixed triangle with the given corners, such as a ramp.
+fn triangle(world: &mut World, corners: [Vec2; 3]) {
+    let centroid = (corners[0] + corners[1] + corners[2]) / 3.0;
+    let local: Vec<Vec2> = corners.iter().map(|&c| c - centroid).collect();
+    let polygon = Polygon::new(&local).expect("the triangle has area");
+    world.add_body(RigidBody::fixed(Shape::Polygon(polygon)).with_position(centroid));
+}
+
+// A ramp rising `rise` over `run` to the right of `foot`.
+fn ramp(world: &mut World, foot: Vec2, run: f32, rise: f32) {
+    triangle(
+        world,
+        [
+            foot,
+            foot + Vec2::new(run, 0.0),
+            foot + Vec2::new(run, rise),
+        ],
+    );
+}
+
+// Where the character stands on flat ground at height `ground`.
+fn standing(x: f32, ground: f32) -> Vec2 {
+    Vec2::new(x, ground + HEIGHT * 0.5 + 0.01)
+}
+
+// Runs the character for `steps` frames at `speed` to the right, under gravity, jumping on the
+// frames listed in `jumps`. Returns every frame's move.
+fn walk(
+    world: &World,
+    controller: &CharacterController,
+    start: Vec2,
+    speed: f32,
+    steps: usize,
+    jumps: &[usize],
+) -> Vec<CharacterMove> {
+    let (mut position, mut fall) = (start, 0.0);
+    let mut moves = Vec::new();
+    for frame in 0..steps {
+        fall = if jumps.contains(&frame) {
+            5.0
+        } else {
+            fall + GRAVITY * DT
+        };
+        let result =
+            controller.move_character(world, position, Vec2::new(speed, fall) * DT, |_, _| true);
+        if result.grounded && fall < 0.0 {
+            fall = 0.0;
+        }
+        position = result.position;
+        moves.push(result);
+    }
+    moves
+}
+
+#[test]
+fn runs_over_tile_seams() {
+    let world = tiled_floor();
+    let start = standing(-5.0, 0.0);
+    let moves = walk(&world, &controller(), start, 3.0, 120, &[]);
+    for (frame, result) in moves.iter().enumerate() {
+        assert!(
+            result.grounded,
+            "the character left the floor on frame {frame}"
+        );
+        assert!(
+            (result.position.y - start.y).abs() < 0.005,
+            "the character bumped on a seam on frame {frame}: {:?}",
+            result.position
+        );
+    }
+    let end = moves.last().expect("frames were run").position;
+    assert!(
+        (end.x - (start.x + 6.0)).abs() < 0.01,
+        "the character was held back: {end:?}"
+    );
+}
+
+#[test]
+fn stops_at_walls() {
+    let mut world = tiled_floor();
+    let wall = RigidBody::fixed(Shape::aabb(1.0, 4.0)).with_position(Vec2::new(2.5, 2.0));
+    let wall = world.add_body(wall);
+    let moves = walk(&world, &controller(), standing(0.0, 0.0), 3.0, 60, &[]);
+    let last = moves.last().expect("frames were run");
+    let stop = 2.0 - WIDTH * 0.5;
+    assert!(last.grounded, "the character fell over at the wall");
+    assert!(
+        last.position.x < stop && last.position.x > stop - 0.03,
+        "the character should stop at the wall, not at {:?}",
+        last.position
+    );
+    let touched = moves
+        .iter()
+        .flat_map(|m| &m.contacts)
+        .find(|c| c.body == wall);
+    let normal = touched.expect("the wall is reported as a contact").normal;
+    assert!(
+        (normal - Vec2::new(-1.0, 0.0)).length() < 1e-3,
+        "odd wall normal {normal:?}"
+    );
+}
+
+#[test]
+fn slides_up_and_down_walls_when_jumping_at_them() {
+    let mut world = tiled_floor();
+    let wall = RigidBody::fixed(Shape::aabb(1.0, 4.0)).with_position(Vec2::new(2.5, 2.0));
+    world.add_body(wall);
+    let moves = walk(&world, &controller(), standing(1.0, 0.0), 3.0, 90, &[20]);
+    let highest = moves.iter().map(|m| m.position.y).fold(f32::MIN, f32::max);
+    let last = moves.last().expect("frames were run");
+    let stop = 2.0 - WIDTH * 0.5;
+    assert!(highest > 1.5, "the jump stopped at the wall at {highest}");
+    assert!(
+        last.grounded && last.position.x > stop - 0.03,
+        "ended at {:?}",
+        last.position
+    );
+}
+
+#[test]
+fn walks_up_gentle_slopes() {
+    // 30 degrees is walkable with the default 45 degree limit.
+    let mut world = tiled_floor();
+    ramp(
+        &mut world,
+        Vec2::new(1.0, 0.0),
+        4.0,
+        4.0 * 30_f32.to_radians().tan(),
+    );
+    let moves = walk(&world, &controller(), standing(-1.0, 0.0), 2.0, 120, &[]);
+    let climbed = moves.last().expect("frames were run");
+    assert!(climbed.grounded, "the character slid off the gentle slope");
+    assert!(
+        climbed.position.y > 1.0,
+        "the character did not walk up: {:?}",
+        climbed.position
+    );
+    let normal = climbed.ground.expect("grounded on the slope").normal;
+    assert!(
+        (normal.y - 30_f32.to_radians().cos()).abs() < 1e-3,
+        "odd ground normal {normal:?}"
+    );
+}
+
+#[test]
+fn stops_at_steep_slopes() {
+    // 60 degrees is past the default 45 degree limit.
+    let mut world = tiled_floor();
+    ramp(
+        &mut world,
+        Vec2::new(1.0, 0.0),
+        2.0,
+        2.0 * 60_f32.to_radians().tan(),
+    );
+    let moves = walk(&world, &controller(), standing(-1.0, 0.0), 2.0, 120, &[]);
+    let blocked = moves.last().expect("frames were run");
+    assert!(
+        blocked.grounded,
+        "the character should stand at the foot of the steep slope"
+    );
+    assert!(
+        blocked.position.y < 0.6,
+        "the character climbed the steep slope: {:?}",
+        blocked.position
+    );
+}
+
+// Walks at a step `height` tall and returns where the character ended up.
+fn climb(height: f32) -> Vec2 {
+    let mut world = tiled_floor();
+    let step =
+        RigidBody::fixed(Shape::aabb(4.0, height)).with_position(Vec2::new(3.0, height * 0.5));
+    world.add_body(step);
+    let moves = walk(&world, &controller(), standing(0.0, 0.0), 2.0, 90, &[]);
+    assert!(
+        moves.iter().all(|m| m.grounded),
+        "the character left the ground at a step"
+    );
+    moves.last().expect("frames were run").position
+}
+
+#[test]
+fn climbs_low_steps() {
+    let low = climb(0.2);
+    assert!(
+        low.x > 2.5 && (low.y - standing(0.0, 0.2).y).abs() < 0.01,
+        "did not climb the low step: {low:?}"
+    );
+}
+
+#[test]
+fn stops_at_steps_taller_than_the_step_height() {
+    let high = climb(0.5);
+    assert!(
+        high.x < 1.0 && high.y < 0.6,
+        "climbed a step taller than the step height: {high:?}"
+    );
+}
+
+// Walks down a 25 degree slope from the edge of a plateau.
+fn downhill(controller: CharacterController) -> Vec<CharacterMove> {
+    let mut world = tiled_floor();
+    let (run, rise) = (4.0, 4.0 * 25_f32.to_radians().tan());
+    let plateau =
+        RigidBody::fixed(Shape::aabb(4.0, rise)).with_position(Vec2::new(-2.0, rise * 0.5));
+    world.add_body(plateau);
+    // Sloping down to the right from the plateau's edge.
+    triangle(
+        &mut world,
+        [Vec2::new(0.0, rise), Vec2::ZERO, Vec2::new(run, 0.0)],
+    );
+    walk(&world, &controller, standing(-1.0, rise), 4.0, 75, &[])
+}
+
+#[test]
+fn follows_the_ground_down_slopes() {
+    let snapped = downhill(controller());
+    assert!(
+        snapped.iter().all(|m| m.grounded),
+        "the character left the slope while walking down"
+    );
+}
+
+#[test]
+fn flies_off_slopes_without_snapping() {
+    let loose = downhill(controller().with_snap_distance(0.0));
+    assert!(
+        loose.iter().any(|m| !m.grounded),
+        "without snapping the character should fly off the slope"
+    );
+    assert!(
+        loose
+            .iter()
+            .all(|m| m.position.x.is_finite() && m.position.y.is_finite()),
+        "a zero snap distance broke the character's position"
+    );
+}
+
+#[test]
+fn lands_after_jumping() {
+    let world = tiled_floor();
+    let moves = walk(&world, &controller(), standing(0.0, 0.0), 0.0, 120, &[10]);
+    assert!(
+        moves[..10].iter().all(|m| m.grounded),
+        "the character should stand before jumping"
+    );
+    let airborne = moves.iter().filter(|m| !m.grounded).count();
+    let peak = moves.iter().map(|m| m.position.y).fold(f32::MIN, f32::max);
+    let last = moves.last().expect("frames were run");
+    assert!(airborne > 30, "the jump was too short: {airborne} frames");
+    assert!(
+        last.grounded && (last.position.y - standing(0.0, 0.0).y).abs() < 0.005,
+        "landed at {:?}",
+        last.position
+    );
+    let expected = standing(0.0, 0.0).y + 5.0 * 5.0 / (2.0 * -GRAVITY);
+    assert!(
+        (peak - expected).abs() < 0.1,
+        "peaked at {peak}, not {expected}"
+    );
+}
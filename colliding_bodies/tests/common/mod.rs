@@ -0,0 +1,21 @@
+// Helpers shared by the integration tests.
+
+use colliding_bodies::World;
+
+// The exact bits of every body's position, angle and velocities, to check that two runs of the
+// same scene match bit for bit.
+pub fn state(world: &World) -> Vec<[u32; 6]> {
+    world
+        .bodies()
+        .map(|(_, body)| {
+            [
+                body.position.x.to_bits(),
+                body.position.y.to_bits(),
+                body.angle.to_bits(),
+                body.velocity.x.to_bits(),
+                body.velocity.y.to_bits(),
+                body.angular_velocity.to_bits(),
+            ]
+        })
+        .collect()
+}
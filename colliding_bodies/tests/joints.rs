@@ -0,0 +1,253 @@
+// One small scene per kind of joint, each checking that the joint does what it promises: rods keep
+// their length, ropes never stretch, motors reach their speed, sliders stop at their limits, welds
+// hold and springs settle where Hooke's law says. Every scene runs a fixed number of steps and
+// ends up bit for bit the same when run again.
+
+mod common;
+
+use colliding_bodies::{BodyHandle, Joint, JointError, JointHandle, RigidBody, Shape, Vec2, World};
+use common::state;
+
+const DT: f32 = 1.0 / 60.0;
+
+// A scene: the world, the joint under test and the body it moves.
+struct Scene {
+    world: World,
+    joint: JointHandle,
+    body: BodyHandle,
+}
+
+// A world with a fixed pin at the origin to hang things from.
+fn world_with_pin() -> (World, BodyHandle) {
+    let mut world = World::default();
+    let pin = world.add_body(RigidBody::fixed(Shape::circle(0.05)));
+    (world, pin)
+}
+
+// Steps the scene `steps` times, returning the largest error of its joint seen.
+fn run(scene: &mut Scene, steps: usize) -> JointError {
+    let mut worst = JointError::default();
+    for _ in 0..steps {
+        scene.world.step(DT);
+        let error = scene
+            .world
+            .joint_error(scene.joint)
+            .expect("the joint is never removed");
+        worst.linear = worst.linear.max(error.linear);
+        worst.angular = worst.angular.max(error.angular);
+    }
+    worst
+}
+
+// Runs the scene `steps` steps, then again from scratch, and checks both runs match bit for bit.
+fn assert_repeatable(build: fn() -> Scene, steps: usize) {
+    let mut first = build();
+    let mut second = build();
+    run(&mut first, steps);
+    run(&mut second, steps);
+    assert_eq!(state(&first.world), state(&second.world), "the runs differ");
+}
+
+fn pendulum() -> Scene {
+    let (mut world, pin) = world_with_pin();
+    let body =
+        world.add_body(RigidBody::dynamic(Shape::circle(0.2)).with_position(Vec2::new(2.0, 0.0)));
+    let joint = world
+        .add_joint(Joint::distance(pin, body, 2.0))
+        .expect("live bodies");
+    Scene { world, joint, body }
+}
+
+fn falling_on_rope() -> Scene {
+    let (mut world, pin) = world_with_pin();
+    let body =
+        world.add_body(RigidBody::dynamic(Shape::circle(0.2)).with_position(Vec2::new(1.0, 0.0)));
+    let joint = world
+        .add_joint(Joint::rope(pin, body, 3.0))
+        .expect("live bodies");
+    Scene { world, joint, body }
+}
+
+fn motor_wheel() -> Scene {
+    let (mut world, pin) = world_with_pin();
+    let body = world.add_body(RigidBody::dynamic(Shape::circle(0.5)));
+    let joint = world
+        .add_joint(Joint::revolute(pin, body).with_motor(4.0, 50.0))
+        .expect("live bodies");
+    Scene { world, joint, body }
+}
+
+// A 2 unit lever hinged at its left end, level to start with.
+fn lever(configure: impl FnOnce(Joint) -> Joint) -> Scene {
+    let (mut world, pin) = world_with_pin();
+    let lever = RigidBody::dynamic(Shape::rectangle(2.0, 0.1)).with_position(Vec2::new(1.0, 0.0));
+    let body = world.add_body(lever);
+    let joint = Joint::revolute(pin, body).with_anchors(Vec2::ZERO, Vec2::new(-1.0, 0.0));
+    let joint = world.add_joint(configure(joint)).expect("live bodies");
+    Scene { world, joint, body }
+}
+
+fn weak_motor_lever() -> Scene {
+    lever(|joint| joint.with_motor(1.0, 0.5))
+}
+
+fn limited_lever() -> Scene {
+    lever(|joint| joint.with_limits(-0.5, 0.5))
+}
+
+fn lift() -> Scene {
+    let (mut world, pin) = world_with_pin();
+    let body = world.add_body(RigidBody::dynamic(Shape::rectangle(1.0, 0.2)));
+    let joint = Joint::prismatic(pin, body, Vec2::new(0.0, 1.0))
+        .with_limits(0.0, 2.0)
+        .with_motor(1.0, 100.0);
+    let joint = world.add_joint(joint).expect("live bodies");
+    Scene { world, joint, body }
+}
+
+fn loaded_beam() -> Scene {
+    let (mut world, pin) = world_with_pin();
+    let beam = RigidBody::dynamic(Shape::rectangle(2.0, 0.2)).with_position(Vec2::new(1.0, 0.0));
+    let body = world.add_body(beam);
+    let weight = RigidBody::dynamic(Shape::circle(0.3)).with_position(Vec2::new(2.0, 0.5));
+    world.add_body(weight);
+    let joint = Joint::weld(pin, body).with_anchors(Vec2::ZERO, Vec2::new(-1.0, 0.0));
+    let joint = world.add_joint(joint).expect("live bodies");
+    Scene { world, joint, body }
+}
+
+const STIFFNESS: f32 = 20.0;
+
+fn hanging_spring() -> Scene {
+    let (mut world, pin) = world_with_pin();
+    let weight = RigidBody::dynamic(Shape::circle(0.25)).with_position(Vec2::new(0.0, -1.0));
+    let body = world.add_body(weight);
+    let joint = Joint::spring(pin, body, 1.0, STIFFNESS, 2.0);
+    let joint = world.add_joint(joint).expect("live bodies");
+    Scene { world, joint, body }
+}
+
+#[test]
+fn rods_keep_their_length() {
+    let mut scene = pendulum();
+    let error = run(&mut scene, 600);
+    assert!(
+        error.linear < 0.01,
+        "the pendulum's rod stretched by {error:?}"
+    );
+}
+
+#[test]
+fn ropes_catch_without_stretching() {
+    let mut scene = falling_on_rope();
+    let error = run(&mut scene, 600);
+    let length = scene
+        .world
+        .body(scene.body)
+        .expect("live bob")
+        .position
+        .length();
+    assert!(error.linear < 0.02, "the rope stretched by {error:?}");
+    assert!(
+        (length - 3.0).abs() < 0.02,
+        "the bob should hang at the rope's end, not {length}"
+    );
+}
+
+#[test]
+fn motors_reach_their_speed() {
+    let mut scene = motor_wheel();
+    let error = run(&mut scene, 120);
+    let spin = scene
+        .world
+        .body(scene.body)
+        .expect("live wheel")
+        .angular_velocity;
+    assert!(
+        (spin - 4.0).abs() < 0.01,
+        "the wheel turns at {spin} rad/s, not 4"
+    );
+    assert!(error.linear < 0.01, "the axle moved by {error:?}");
+}
+
+#[test]
+fn weak_motors_only_slow_a_fall() {
+    let mut scene = weak_motor_lever();
+    run(&mut scene, 30);
+    let angle = scene.world.body(scene.body).expect("live lever").angle;
+    assert!(
+        angle < 0.0,
+        "the weak motor should not lift the lever, but it is at {angle}"
+    );
+}
+
+#[test]
+fn hinges_stop_at_their_limits() {
+    let mut scene = limited_lever();
+    let error = run(&mut scene, 300);
+    let angle = scene.world.body(scene.body).expect("live lever").angle;
+    assert!(
+        error.linear < 0.01 && error.angular < 0.02,
+        "the hinge was off by {error:?}"
+    );
+    assert!(
+        (angle + 0.5).abs() < 0.02,
+        "the lever should rest on its lower limit, not at {angle}"
+    );
+}
+
+#[test]
+fn sliders_stop_at_their_limits() {
+    let mut scene = lift();
+    let error = run(&mut scene, 240);
+    let body = scene.world.body(scene.body).expect("live platform");
+    assert!(
+        error.linear < 0.01 && error.angular < 0.01,
+        "the slider was off by {error:?}"
+    );
+    assert!(
+        (body.position.y - 2.0).abs() < 0.01 && body.position.x.abs() < 0.001,
+        "the lift should stop at its upper limit, not {:?}",
+        body.position
+    );
+}
+
+#[test]
+fn welds_hold_under_load() {
+    let mut scene = loaded_beam();
+    let error = run(&mut scene, 300);
+    assert!(
+        error.linear < 0.01 && error.angular < 0.01,
+        "the weld gave way by {error:?}"
+    );
+}
+
+#[test]
+fn springs_settle_where_hookes_law_says() {
+    let mut scene = hanging_spring();
+    run(&mut scene, 600);
+    let body = scene.world.body(scene.body).expect("live weight");
+    let length = body.position.length();
+    let expected = 1.0 + body.mass() * 9.81 / STIFFNESS;
+    assert!(
+        (length - expected).abs() < 0.01 * expected,
+        "the spring settled at {length}, not {expected}"
+    );
+}
+
+#[test]
+fn every_scene_is_deterministic() {
+    let scenes: [(fn() -> Scene, usize); 8] = [
+        (pendulum, 600),
+        (falling_on_rope, 600),
+        (motor_wheel, 120),
+        (weak_motor_lever, 30),
+        (limited_lever, 300),
+        (lift, 240),
+        (loaded_beam, 300),
+        (hanging_spring, 600),
+    ];
+    for (build, steps) in scenes {
+        assert_repeatable(build, steps);
+    }
+}
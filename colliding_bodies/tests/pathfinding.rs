@@ -0,0 +1,233 @@
+// Finds paths on random and awkward maps: A* and jump point search agree on random tile maps,
+// navigation mesh paths bend only at obstacle corners and keep an agent clear of the obstacles,
+// and a mesh path is never longer than the tile path through the same map.
+
+use colliding_bodies::{Aabb, Cell, Grid, GridPath, NavMesh, Vec2};
+
+// A small random number generator, so the random maps are the same on every run.
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> f32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn cell(&mut self, grid: &Grid) -> Cell {
+        (
+            (self.next() * grid.width() as f32) as i32,
+            (self.next() * grid.height() as f32) as i32,
+        )
+    }
+}
+
+// Checks that a path runs from `start` to `goal` over open cells in single steps that do not cut
+// corners, and that its cost matches its steps.
+fn assert_walkable(grid: &Grid, path: &GridPath, start: Cell, goal: Cell) {
+    assert_eq!(path.cells.first(), Some(&start));
+    assert_eq!(path.cells.last(), Some(&goal));
+    let mut cost = 0.0;
+    for pair in path.cells.windows(2) {
+        let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        assert!(
+            dx.abs() <= 1 && dy.abs() <= 1 && (dx, dy) != (0, 0),
+            "jumped {pair:?}"
+        );
+        assert!(grid.is_open(pair[1]), "walked into {:?}", pair[1]);
+        if dx != 0 && dy != 0 {
+            assert!(
+                grid.is_open((x1, y0)) && grid.is_open((x0, y1)),
+                "cut a corner at {pair:?}"
+            );
+            cost += std::f32::consts::SQRT_2;
+        } else {
+            cost += 1.0;
+        }
+    }
+    assert!(
+        (cost - path.cost).abs() < 1e-3,
+        "cost {} for {cost}",
+        path.cost
+    );
+}
+
+fn path_length(path: &[Vec2]) -> f32 {
+    path.windows(2).map(|pair| pair[0].distance(pair[1])).sum()
+}
+
+// The distance from `point` to the inside of an axis-aligned box.
+fn distance_to_box(point: Vec2, min: Vec2, max: Vec2) -> f32 {
+    let outside = (min - point).max(point - max).max(Vec2::ZERO);
+    outside.length()
+}
+
+// Checks every point along a path for clearance from the boxes.
+fn assert_clear(path: &[Vec2], boxes: &[(Vec2, Vec2)], clearance: f32) {
+    for pair in path.windows(2) {
+        for i in 0..=100 {
+            let point = pair[0].lerp(pair[1], i as f32 / 100.0);
+            for &(min, max) in boxes {
+                let distance = distance_to_box(point, min, max);
+                assert!(
+                    distance >= clearance - 1e-3,
+                    "{point:?} is {distance} from the box {min:?} {max:?}"
+                );
+            }
+        }
+    }
+}
+
+// Checks that every turn of a path is at one of `corners`.
+fn assert_turns_at(path: &[Vec2], corners: &[Vec2]) {
+    for turn in &path[1..path.len() - 1] {
+        assert!(
+            corners.iter().any(|corner| corner.distance(*turn) < 1e-3),
+            "{path:?} turns at {turn:?}, which is not a corner"
+        );
+    }
+}
+
+fn corners(min: Vec2, max: Vec2) -> Vec<Vec2> {
+    vec![min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)]
+}
+
+#[test]
+fn a_star_and_jump_point_search_agree_on_random_maps() {
+    let mut random = Random(7);
+    let mut found = 0;
+    for _ in 0..200 {
+        let mut grid = Grid::new(24, 16);
+        for x in 0..grid.width() as i32 {
+            for y in 0..grid.height() as i32 {
+                grid.set_blocked((x, y), random.next() < 0.3);
+            }
+        }
+        let (start, goal) = (random.cell(&grid), random.cell(&grid));
+        grid.set_blocked(start, false);
+        grid.set_blocked(goal, false);
+        match (grid.find_path(start, goal), grid.find_path_jps(start, goal)) {
+            (Some(astar), Some(jps)) => {
+                assert_walkable(&grid, &astar, start, goal);
+                assert_walkable(&grid, &jps, start, goal);
+                assert!(
+                    (astar.cost - jps.cost).abs() < 1e-3,
+                    "A* found {} but JPS {} from {start:?} to {goal:?}",
+                    astar.cost,
+                    jps.cost
+                );
+                found += 1;
+            }
+            (None, None) => {}
+            (astar, jps) => panic!("A* found {astar:?} but JPS found {jps:?}"),
+        }
+    }
+    assert!(found > 50, "only {found} of the maps had a path");
+}
+
+#[test]
+fn mesh_paths_bend_at_corners_and_keep_clear() {
+    let bounds = Aabb::new(Vec2::ZERO, Vec2::new(10.0, 10.0));
+    let (min, max) = (Vec2::new(4.0, 3.0), Vec2::new(6.0, 7.5));
+    let (start, goal) = (Vec2::new(1.0, 5.0), Vec2::new(9.0, 5.0));
+    let mesh = NavMesh::new(bounds, &[corners(min, max)], 0.0).unwrap();
+    let path = mesh.find_path(start, goal).unwrap();
+    assert_turns_at(&path, &corners(min, max));
+    assert_clear(&path, &[(min, max)], 0.0);
+
+    // A round agent keeps its distance, and cannot squeeze past the box where the gap is too
+    // narrow.
+    let mesh = NavMesh::new(bounds, &[corners(min, max)], 0.5).unwrap();
+    let path = mesh.find_path(start, goal).unwrap();
+    assert_clear(&path, &[(min, max)], 0.5);
+    let narrow = NavMesh::new(bounds, &[corners(Vec2::new(4.0, 0.7), max)], 1.0).unwrap();
+    let over = narrow.find_path(start, goal).unwrap();
+    assert!(
+        over.iter().all(|point| point.y >= 5.0 - 1e-3),
+        "went under: {over:?}"
+    );
+}
+
+#[test]
+fn mesh_paths_cross_overlapping_and_concave_obstacles() {
+    // Overlapping obstacles, one sticking out of the bounds, and a concave one.
+    let bounds = Aabb::new(Vec2::ZERO, Vec2::new(20.0, 10.0));
+    let concave = vec![
+        Vec2::new(12.0, 2.0),
+        Vec2::new(16.0, 2.0),
+        Vec2::new(16.0, 9.0),
+        Vec2::new(15.0, 9.0),
+        Vec2::new(15.0, 3.0),
+        Vec2::new(12.0, 3.0),
+    ];
+    let obstacles = vec![
+        corners(Vec2::new(4.0, -2.0), Vec2::new(6.0, 6.0)),
+        corners(Vec2::new(5.0, 5.0), Vec2::new(9.0, 7.0)),
+        concave.clone(),
+    ];
+    let boxes = [
+        (Vec2::new(4.0, -2.0), Vec2::new(6.0, 6.0)),
+        (Vec2::new(5.0, 5.0), Vec2::new(9.0, 7.0)),
+        (Vec2::new(12.0, 2.0), Vec2::new(16.0, 3.0)),
+        (Vec2::new(15.0, 3.0), Vec2::new(16.0, 9.0)),
+    ];
+    let mesh = NavMesh::new(bounds, &obstacles, 0.0).unwrap();
+    let (start, goal) = (Vec2::new(1.0, 1.0), Vec2::new(19.0, 1.0));
+    let path = mesh.find_path(start, goal).unwrap();
+    assert_clear(&path, &boxes, 0.0);
+    let all_corners: Vec<Vec2> = obstacles.iter().flatten().copied().collect();
+    assert_turns_at(&path, &all_corners);
+    assert!(
+        path.iter().all(|point| bounds.contains_point(*point)),
+        "left the bounds: {path:?}"
+    );
+}
+
+#[test]
+fn mesh_paths_are_never_longer_than_tile_paths() {
+    // Tiles blocked in a grid become square obstacles; paths on the mesh may cut across tiles, so
+    // they are never longer than the grid's, and exist exactly when the grid's do.
+    let mut random = Random(11);
+    let mut compared = 0;
+    for _ in 0..50 {
+        let mut grid = Grid::new(16, 12);
+        let mut obstacles = Vec::new();
+        let mut boxes = Vec::new();
+        for x in 0..grid.width() as i32 {
+            for y in 0..grid.height() as i32 {
+                if random.next() < 0.25 {
+                    grid.set_blocked((x, y), true);
+                    let min = Vec2::new(x as f32, y as f32);
+                    obstacles.push(corners(min, min + Vec2::new(1.0, 1.0)));
+                    boxes.push((min, min + Vec2::new(1.0, 1.0)));
+                }
+            }
+        }
+        let (start, goal) = (random.cell(&grid), random.cell(&grid));
+        if !grid.is_open(start) || !grid.is_open(goal) {
+            continue;
+        }
+        let size = Vec2::new(grid.width() as f32, grid.height() as f32);
+        let mesh = NavMesh::new(Aabb::new(Vec2::ZERO, size), &obstacles, 0.0).unwrap();
+        let on_grid = grid.find_path(start, goal);
+        let on_mesh = mesh.find_path(grid.cell_center(start), grid.cell_center(goal));
+        match (on_grid, on_mesh) {
+            (Some(tiles), Some(path)) => {
+                let length = path_length(&path);
+                assert!(
+                    length <= tiles.cost + 1e-3,
+                    "the mesh path {length} is longer than the grid path {}",
+                    tiles.cost
+                );
+                assert_clear(&path, &boxes, 0.0);
+                compared += 1;
+            }
+            (None, None) => {}
+            (tiles, path) => panic!("the grid found {tiles:?} but the mesh {path:?}"),
+        }
+    }
+    assert!(compared > 10, "only {compared} of the maps had a path");
+}
@@ -0,0 +1,191 @@
+// A ragdoll of ten parts held together by limited hinges, thrown spinning down a flight of stairs.
+// Checks that the joints stay together and within their limits however it lands, and that the
+// simulation is deterministic.
+
+mod common;
+
+use colliding_bodies::{BodyHandle, Joint, JointError, JointHandle, RigidBody, Shape, Vec2, World};
+use common::state;
+
+const STEPS: usize = 600;
+const SETTLE_STEPS: usize = 480; // After this, the ragdoll has come to rest.
+
+// Largest joint gap, and how far past its limits a joint may turn in radians: a little give is
+// allowed as the ragdoll hits the stairs, but once at rest it must hold.
+const TUMBLE_TOLERANCE: JointError = JointError {
+    linear: 0.05,
+    angular: 0.15,
+};
+const REST_TOLERANCE: JointError = JointError {
+    linear: 0.01,
+    angular: 0.02,
+};
+
+struct Ragdoll {
+    world: World,
+    parts: Vec<(&'static str, BodyHandle)>,
+    joints: Vec<(&'static str, JointHandle)>,
+}
+
+// Hinges `b` to `a` at a world point, letting `b` turn between `lower` and `upper` radians
+// relative to `a`.
+fn hinge(
+    world: &mut World,
+    a: BodyHandle,
+    b: BodyHandle,
+    at: Vec2,
+    lower: f32,
+    upper: f32,
+) -> JointHandle {
+    let anchor_a = world.body(a).expect("live part").pose().inverse_apply(at);
+    let anchor_b = world.body(b).expect("live part").pose().inverse_apply(at);
+    let joint = Joint::revolute(a, b)
+        .with_anchors(anchor_a, anchor_b)
+        .with_limits(lower, upper);
+    world.add_joint(joint).expect("both parts are alive")
+}
+
+fn build() -> Ragdoll {
+    let mut world = World::default();
+
+    // Stairs down to the floor.
+    world.add_body(RigidBody::fixed(Shape::aabb(40.0, 1.0)).with_position(Vec2::new(0.0, -0.5)));
+    for step in 0..6 {
+        let height = 0.5 * (step + 1) as f32;
+        let stair = RigidBody::fixed(Shape::aabb(1.0, height))
+            .with_position(Vec2::new(-step as f32, height * 0.5));
+        world.add_body(stair);
+    }
+
+    let mut parts = Vec::new();
+    let mut add = |world: &mut World, name, shape, x: f32, y: f32| {
+        let part = world.add_body(RigidBody::dynamic(shape).with_position(Vec2::new(x, y)));
+        parts.push((name, part));
+        part
+    };
+    let (x, y) = (-4.5, 4.0); // Where the hips start.
+    let torso = add(&mut world, "torso", Shape::rectangle(0.5, 1.0), x, y + 0.5);
+    let head = add(&mut world, "head", Shape::circle(0.22), x, y + 1.25);
+    let mut joints = vec![(
+        "neck",
+        hinge(&mut world, torso, head, Vec2::new(x, y + 1.0), -0.6, 0.6),
+    )];
+    for (side, sign) in [("left", -1.0), ("right", 1.0)] {
+        let shoulder = Vec2::new(x + sign * 0.35, y + 0.9);
+        let upper_arm = add(
+            &mut world,
+            "upper arm",
+            Shape::rectangle(0.18, 0.55),
+            shoulder.x,
+            y + 0.65,
+        );
+        let lower_arm = add(
+            &mut world,
+            "lower arm",
+            Shape::rectangle(0.16, 0.5),
+            shoulder.x,
+            y + 0.15,
+        );
+        let hip = Vec2::new(x + sign * 0.14, y);
+        let thigh = add(
+            &mut world,
+            "thigh",
+            Shape::rectangle(0.22, 0.6),
+            hip.x,
+            y - 0.3,
+        );
+        let shin = add(
+            &mut world,
+            "shin",
+            Shape::rectangle(0.2, 0.6),
+            hip.x,
+            y - 0.9,
+        );
+
+        joints.push((
+            side,
+            hinge(&mut world, torso, upper_arm, shoulder, -2.5, 2.5),
+        ));
+        let elbow = Vec2::new(shoulder.x, y + 0.4);
+        let (lower, upper) = if sign < 0.0 { (0.0, 2.4) } else { (-2.4, 0.0) };
+        joints.push((
+            side,
+            hinge(&mut world, upper_arm, lower_arm, elbow, lower, upper),
+        ));
+        joints.push((side, hinge(&mut world, torso, thigh, hip, -1.2, 1.6)));
+        let knee = Vec2::new(hip.x, y - 0.6);
+        joints.push((side, hinge(&mut world, thigh, shin, knee, -2.4, 0.0)));
+    }
+
+    // Thrown off the top stair, spinning as one.
+    let center = Vec2::new(x, y + 0.5);
+    for &(_, part) in &parts {
+        let body = world.body_mut(part).expect("live part");
+        body.angular_velocity = -2.0;
+        body.velocity =
+            Vec2::new(3.0, 2.0) + Vec2::cross_scalar(body.angular_velocity, body.position - center);
+    }
+
+    Ragdoll {
+        world,
+        parts,
+        joints,
+    }
+}
+
+fn worse(a: JointError, b: JointError) -> JointError {
+    JointError {
+        linear: a.linear.max(b.linear),
+        angular: a.angular.max(b.angular),
+    }
+}
+
+fn within(error: JointError, tolerance: JointError) -> bool {
+    error.linear < tolerance.linear && error.angular < tolerance.angular
+}
+
+#[test]
+fn joints_hold_while_tumbling_down_stairs() {
+    let mut ragdoll = build();
+    let (mut worst, mut worst_at_rest) = (JointError::default(), JointError::default());
+    for step in 1..=STEPS {
+        ragdoll.world.step(1.0 / 60.0);
+        for &(_, joint) in &ragdoll.joints {
+            let error = ragdoll
+                .world
+                .joint_error(joint)
+                .expect("joints are never removed");
+            worst = worse(worst, error);
+            if step > SETTLE_STEPS {
+                worst_at_rest = worse(worst_at_rest, error);
+            }
+        }
+    }
+    assert!(
+        within(worst, TUMBLE_TOLERANCE),
+        "a joint was off by {worst:?} while tumbling"
+    );
+    assert!(
+        within(worst_at_rest, REST_TOLERANCE),
+        "a joint stayed off by {worst_at_rest:?} at rest"
+    );
+
+    // It ends up lying on the floor, not through it.
+    for &(name, part) in &ragdoll.parts {
+        let body = ragdoll.world.body(part).expect("live part");
+        assert!(
+            body.aabb().min.y > -0.05,
+            "the {name} sank into the floor: {body:?}"
+        );
+    }
+}
+
+#[test]
+fn the_same_throw_ends_the_same() {
+    let (mut first, mut second) = (build(), build());
+    for _ in 0..STEPS {
+        first.world.step(1.0 / 60.0);
+        second.world.step(1.0 / 60.0);
+    }
+    assert_eq!(state(&first.world), state(&second.world), "the runs differ");
+}
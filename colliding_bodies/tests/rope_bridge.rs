@@ -0,0 +1,130 @@
+// A bridge of planks hinged end to end between two posts, with crates dropped on it. Checks that
+// every hinge stays together under the load and that the simulation is deterministic.
+
+mod common;
+
+use colliding_bodies::{BodyHandle, Joint, JointHandle, RigidBody, Shape, Vec2, World};
+use common::state;
+
+const PLANKS: usize = 12;
+const PLANK_LENGTH: f32 = 1.0;
+const STEPS: usize = 600;
+const SETTLE_STEPS: usize = 480; // After this, the bridge has settled.
+const IMPACT_TOLERANCE: f32 = 0.05; // Largest gap between hinged plank ends as the crates land.
+const REST_TOLERANCE: f32 = 0.01; // Largest gap once settled: 1% of a plank.
+
+struct Bridge {
+    world: World,
+    planks: Vec<BodyHandle>,
+    crates: Vec<BodyHandle>,
+    hinges: Vec<JointHandle>,
+}
+
+fn build() -> Bridge {
+    let mut world = World::default();
+    let ground = Shape::aabb(40.0, 1.0);
+    world.add_body(RigidBody::fixed(ground).with_position(Vec2::new(0.0, -6.0)));
+
+    // The posts are closer together than the planks are long, so the bridge hangs slack in a V.
+    let gap = PLANKS as f32 * PLANK_LENGTH * 0.9;
+    let step_x = gap / PLANKS as f32;
+    let step_y = (PLANK_LENGTH * PLANK_LENGTH - step_x * step_x).sqrt();
+    let post = |x: f32| RigidBody::fixed(Shape::aabb(0.4, 6.0)).with_position(Vec2::new(x, -3.0));
+    let left_post = world.add_body(post(-gap * 0.5 - 0.2));
+    let right_post = world.add_body(post(gap * 0.5 + 0.2));
+
+    // Each hinge joins the end of one plank (or post) to the start of the next.
+    let half = Vec2::new(PLANK_LENGTH * 0.5, 0.0);
+    let mut planks = Vec::new();
+    let mut hinges = Vec::new();
+    let mut previous = (left_post, Vec2::new(0.2, 3.0));
+    let mut start = Vec2::new(-gap * 0.5, 0.0);
+    for i in 0..PLANKS {
+        let down = if i < PLANKS / 2 { -step_y } else { step_y };
+        let end = start + Vec2::new(step_x, down);
+        let plank = RigidBody::dynamic(Shape::rectangle(PLANK_LENGTH, 0.15))
+            .with_position(start.lerp(end, 0.5))
+            .with_angle(down.atan2(step_x))
+            .with_density(2.0);
+        let plank = world.add_body(plank);
+        let hinge = Joint::revolute(previous.0, plank).with_anchors(previous.1, -half);
+        hinges.push(world.add_joint(hinge).expect("both bodies are alive"));
+        planks.push(plank);
+        previous = (plank, half);
+        start = end;
+    }
+    let last =
+        Joint::revolute(previous.0, right_post).with_anchors(previous.1, Vec2::new(-0.2, 3.0));
+    hinges.push(world.add_joint(last).expect("both bodies are alive"));
+
+    let crates = [-2.5, -0.5, 1.5]
+        .iter()
+        .enumerate()
+        .map(|(i, &x)| {
+            let size = 0.6 + 0.2 * i as f32;
+            let body = RigidBody::dynamic(Shape::rectangle(size, size))
+                .with_position(Vec2::new(x, 2.0 + i as f32))
+                .with_angle(0.3 * i as f32);
+            world.add_body(body)
+        })
+        .collect();
+
+    Bridge {
+        world,
+        planks,
+        crates,
+        hinges,
+    }
+}
+
+#[test]
+fn hinges_hold_under_falling_crates() {
+    let mut bridge = build();
+    let (mut worst, mut worst_at_rest) = (0.0_f32, 0.0_f32);
+    for step in 1..=STEPS {
+        bridge.world.step(1.0 / 60.0);
+        for &hinge in &bridge.hinges {
+            let error = bridge
+                .world
+                .joint_error(hinge)
+                .expect("hinges are never removed");
+            worst = worst.max(error.linear);
+            if step > SETTLE_STEPS {
+                worst_at_rest = worst_at_rest.max(error.linear);
+            }
+        }
+    }
+    assert!(worst < IMPACT_TOLERANCE, "a hinge came apart by {worst}");
+    assert!(
+        worst_at_rest < REST_TOLERANCE,
+        "a hinge stayed apart by {worst_at_rest} once settled"
+    );
+
+    // The bridge sags under the crates, but holds them up.
+    let middle = bridge
+        .world
+        .body(bridge.planks[PLANKS / 2])
+        .expect("live plank");
+    assert!(
+        middle.position.y < -1.0,
+        "the middle of the bridge should sag, but is at {:?}",
+        middle.position
+    );
+    for &handle in &bridge.crates {
+        let body = bridge.world.body(handle).expect("live crate");
+        assert!(
+            body.position.y > -4.0,
+            "a crate fell through the bridge: {body:?}"
+        );
+    }
+}
+
+#[test]
+fn the_same_bridge_ends_the_same() {
+    let (mut first, mut second) = (build(), build());
+    for _ in 0..STEPS {
+        first.world.step(1.0 / 60.0);
+        second.world.step(1.0 / 60.0);
+    }
+    assert_eq!(state(&first.world), state(&second.world), "the runs differ");
+}
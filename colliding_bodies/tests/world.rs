@@ -0,0 +1,130 @@
+// Checks the core of the world step: contacts come to rest, restitution and friction behave, the
+// fixed-step accumulator counts its steps and removed bodies cannot be reached through old handles.
+
+use colliding_bodies::{RigidBody, Shape, Vec2, World, WorldSettings};
+
+const DT: f32 = 1.0 / 60.0;
+
+// A world with a wide static floor whose top is at y = 0.
+fn world_with_floor() -> World {
+    let mut world = World::default();
+    let floor = RigidBody::fixed(Shape::rectangle(40.0, 1.0)).with_position(Vec2::new(0.0, -0.5));
+    world.add_body(floor);
+    world
+}
+
+fn run(world: &mut World, seconds: f32) {
+    for _ in 0..(seconds / DT) as usize {
+        world.step(DT);
+    }
+}
+
+#[test]
+fn stacked_boxes_come_to_rest_on_the_floor() {
+    let mut world = world_with_floor();
+    let boxes: Vec<_> = (0..3)
+        .map(|i| {
+            let body = RigidBody::dynamic(Shape::rectangle(1.0, 1.0))
+                .with_position(Vec2::new(0.0, 0.5 + i as f32 * 1.05));
+            world.add_body(body)
+        })
+        .collect();
+    run(&mut world, 4.0);
+
+    for (i, handle) in boxes.iter().enumerate() {
+        let body = world.body(*handle).unwrap();
+        let bottom = i as f32;
+        assert!(
+            (body.aabb().min.y - bottom).abs() < 0.05,
+            "box {i} rests at {} instead of {bottom}",
+            body.aabb().min.y
+        );
+        assert!(body.velocity.length() < 0.05, "box {i} is still moving");
+        assert!(body.position.x.abs() < 0.05, "box {i} slid off the stack");
+    }
+}
+
+// The highest a ball dropped from 3 units gets after landing, which takes under a second.
+fn rebound_height(restitution: f32) -> f32 {
+    let mut world = world_with_floor();
+    let ball = RigidBody::dynamic(Shape::circle(0.25))
+        .with_position(Vec2::new(0.0, 3.0))
+        .with_restitution(restitution);
+    let handle = world.add_body(ball);
+    run(&mut world, 1.0);
+
+    let mut highest = f32::NEG_INFINITY;
+    for _ in 0..120 {
+        world.step(DT);
+        highest = highest.max(world.body(handle).unwrap().aabb().min.y);
+    }
+    highest
+}
+
+#[test]
+fn bouncy_bodies_rebound_higher() {
+    let dead = rebound_height(0.0);
+    let bouncy = rebound_height(0.8);
+    assert!(
+        dead < 0.05,
+        "a ball without restitution rebounded to {dead}"
+    );
+    assert!(bouncy > 1.0, "a bouncy ball only rebounded to {bouncy}");
+}
+
+#[test]
+fn friction_stops_a_sliding_box() {
+    let mut world = world_with_floor();
+    let sliding = RigidBody::dynamic(Shape::rectangle(1.0, 1.0))
+        .with_position(Vec2::new(-10.0, 0.5))
+        .with_velocity(Vec2::new(5.0, 0.0))
+        .with_friction(0.5);
+    let handle = world.add_body(sliding);
+    run(&mut world, 3.0);
+
+    let body = world.body(handle).unwrap();
+    assert!(body.velocity.length() < 0.05, "the box is still sliding");
+    // At 5 units/s and 0.5 * 9.81 units/s² of deceleration it stops after about 2.5 units.
+    assert!(
+        body.position.x > -9.0 && body.position.x < -6.0,
+        "the box stopped at {}",
+        body.position.x
+    );
+}
+
+#[test]
+fn advance_takes_whole_steps_and_carries_the_rest() {
+    let mut world = World::default();
+    world.settings = WorldSettings {
+        timestep: 0.01,
+        max_steps_per_advance: 5,
+        ..WorldSettings::default()
+    };
+    assert_eq!(world.advance(0.025), 2);
+    // The half step left over makes up a whole one with this.
+    assert_eq!(world.advance(0.005), 1);
+    assert_eq!(world.advance(0.004), 0);
+    assert_eq!(world.advance(-1.0), 0);
+
+    // Falling far behind runs the most steps allowed and drops the rest.
+    assert_eq!(world.advance(1.0), 5);
+    assert_eq!(world.advance(0.0), 0);
+}
+
+#[test]
+fn removed_bodies_are_gone_from_old_handles() {
+    let mut world = World::default();
+    let first = world.add_body(RigidBody::dynamic(Shape::circle(1.0)));
+    let removed = world.remove_body(first).expect("the body was in the world");
+    assert_eq!(removed.position, Vec2::ZERO);
+    assert!(world.body(first).is_none());
+    assert!(world.remove_body(first).is_none());
+
+    // The slot is reused, but the old handle still refers to the removed body.
+    let second = world.add_body(RigidBody::dynamic(Shape::circle(2.0)));
+    assert_eq!(second.index(), first.index());
+    assert!(world.body(first).is_none());
+    assert!(world.body_mut(first).is_none());
+    assert!(world.body(second).is_some());
+    assert_eq!(world.body_count(), 1);
+}
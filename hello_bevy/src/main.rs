@@ -1,13 +1,30 @@
 //! A simple 3D scene with light shining over a cube sitting on a plane.
+//!
+//! The arrow keys turn the camera. Ctrl+R rebinds them: press the keys for turning left, right, up
+//! and down, in that order. The keys are saved to `camera_controls.ron` for next time.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
-use bevy::input::keyboard::KeyboardInput;
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 fn main() {
+    // If the controls fail to load, the camera uses the defaults and leaves the file alone.
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("camera_controls.ron");
+    let controls = CameraControls::load(path).unwrap_or_else(|error| {
+        warn!("{error}");
+        CameraControls::default()
+    });
+
     App::new()
         .add_plugins(DefaultPlugins)
+        .insert_resource(controls)
         .add_systems(Startup, setup)
-        .add_systems(Update, camera_rotation)
+        .add_systems(
+            Update,
+            (rebind_camera, camera_rotation, save_camera_controls).chain(),
+        )
         .run();
 }
 
@@ -49,28 +66,224 @@ fn setup(
     });
 }
 
+// The bevy_ecs crate has fuller controls, with gamepads, chords and a set per player, but it is a
+// separate binary on a newer Bevy, so the camera keeps just the four keys it needs here.
+
+/// The ways the camera turns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+enum Turn {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Turn {
+    /// The order rebinding asks for keys in.
+    const ALL: [Turn; 4] = [Turn::Left, Turn::Right, Turn::Up, Turn::Down];
+}
+
+/// The keys that start rebinding when held together.
+const REBIND: [KeyCode; 2] = [KeyCode::ControlLeft, KeyCode::KeyR];
+
+/// Which key turns the camera which way. Saved back to its RON file whenever it changes, so rebound
+/// keys stay rebound.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CameraControls {
+    keys: BTreeMap<Turn, KeyCode>,
+    #[serde(skip)]
+    path: Option<PathBuf>, // Where changes are saved to; nowhere when `None`.
+}
+
+/// The arrow keys turn the camera.
+impl Default for CameraControls {
+    fn default() -> Self {
+        let keys = BTreeMap::from([
+            (Turn::Left, KeyCode::ArrowLeft),
+            (Turn::Right, KeyCode::ArrowRight),
+            (Turn::Up, KeyCode::ArrowUp),
+            (Turn::Down, KeyCode::ArrowDown),
+        ]);
+        Self { keys, path: None }
+    }
+}
+
+impl CameraControls {
+    /// Reads the controls saved at `path`, or starts from the defaults if nothing has been saved
+    /// there yet. Either way, changes are saved back to `path`.
+    fn load(path: impl AsRef<Path>) -> Result<Self, ControlsError> {
+        let path = path.as_ref();
+        let mut controls = match std::fs::read_to_string(path) {
+            Ok(text) => ron::from_str(&text)
+                .map_err(|error| ControlsError::Ron(path.to_path_buf(), error.to_string()))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(error) => return Err(ControlsError::Io(path.to_path_buf(), error)),
+        };
+        controls.path = Some(path.to_path_buf());
+        Ok(controls)
+    }
+
+    fn save(&self) -> Result<(), ControlsError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|error| ControlsError::Ron(path.clone(), error.to_string()))?;
+        std::fs::write(path, text).map_err(|error| ControlsError::Io(path.clone(), error))
+    }
+
+    fn pressed(&self, turn: Turn, keys: &ButtonInput<KeyCode>) -> bool {
+        self.keys.get(&turn).is_some_and(|key| keys.pressed(*key))
+    }
+}
+
+#[derive(Debug)]
+enum ControlsError {
+    Io(PathBuf, std::io::Error), // The file could not be read or written.
+    Ron(PathBuf, String),        // The file is not valid controls, or they could not be written.
+}
+
+impl std::fmt::Display for ControlsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ControlsError::Io(path, error) => {
+                write!(f, "failed to access {}: {error}", path.display())
+            }
+            ControlsError::Ron(path, error) => {
+                write!(f, "bad camera controls in {}: {error}", path.display())
+            }
+        }
+    }
+}
+
+/// Present while the turns are being rebound, counting how many have their new key.
+#[derive(Resource, Debug, Default)]
+struct Rebinding {
+    done: usize,
+}
+
+/// Starts rebinding on Ctrl+R, then binds each turn in `Turn::ALL` to the next key pressed.
+/// Keys pressed in the same frame each bind a turn, in no particular order.
+fn rebind_camera(
+    mut commands: Commands,
+    rebinding: Option<ResMut<Rebinding>>,
+    mut controls: ResMut<CameraControls>,
+    keys: Res<ButtonInput<KeyCode>>,
+) {
+    let Some(mut rebinding) = rebinding else {
+        if keys.all_pressed(REBIND) && keys.any_just_pressed(REBIND) {
+            info!("Press the keys for turning the camera left, right, up and down");
+            commands.init_resource::<Rebinding>();
+        }
+        return;
+    };
+    for &key in keys.get_just_pressed() {
+        let turn = Turn::ALL[rebinding.done];
+        info!("Turning {turn:?} bound to {key:?}");
+        controls.keys.insert(turn, key);
+        rebinding.done += 1;
+        if rebinding.done == Turn::ALL.len() {
+            commands.remove_resource::<Rebinding>();
+            return;
+        }
+    }
+}
+
 fn camera_rotation(
     keys: Res<ButtonInput<KeyCode>>,
+    controls: Res<CameraControls>,
+    rebinding: Option<Res<Rebinding>>,
     mut query: Query<&mut Transform, With<Camera3d>>,
 ) {
     const ROTATION_SPEED: f32 = 1.0;
 
+    // The keys being pressed are new bindings, not turns.
+    if rebinding.is_some() {
+        return;
+    }
+
     for mut transform in query.iter_mut() {
-        if keys.pressed(KeyCode::ArrowLeft) {
+        if controls.pressed(Turn::Left, &keys) {
             transform.rotate_y(ROTATION_SPEED * -0.02);
         }
-        if keys.pressed(KeyCode::ArrowRight) {
+        if controls.pressed(Turn::Right, &keys) {
             transform.rotate_y(ROTATION_SPEED * 0.02);
         }
-        if keys.pressed(KeyCode::ArrowUp) {
+        if controls.pressed(Turn::Up, &keys) {
             transform.rotate_x(ROTATION_SPEED * -0.02);
         }
-        if keys.pressed(KeyCode::ArrowDown) {
+        if controls.pressed(Turn::Down, &keys) {
             transform.rotate_x(ROTATION_SPEED * 0.02);
         }
     }
 }
 
+fn save_camera_controls(controls: Res<CameraControls>) {
+    if !controls.is_changed() || controls.is_added() {
+        return;
+    }
+    if let Err(error) = controls.save() {
+        warn!("{error}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs a frame with `pressed` held down, newly pressed unless they were held the frame before.
+    fn press(app: &mut App, pressed: &[KeyCode]) {
+        let mut keys = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+        let held: Vec<KeyCode> = keys.get_pressed().copied().collect();
+        keys.reset_all();
+        for key in held.into_iter().filter(|key| pressed.contains(key)) {
+            keys.press(key);
+            keys.clear_just_pressed(key);
+        }
+        for key in pressed {
+            keys.press(*key);
+        }
+        app.update();
+    }
+
+    #[test]
+    fn rebound_keys_are_saved_and_loaded_again() {
+        let path = std::env::temp_dir().join(format!(
+            "hello_bevy-camera_controls-{}.ron",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let mut app = App::new();
+        app.init_resource::<ButtonInput<KeyCode>>()
+            .insert_resource(CameraControls::load(&path).unwrap())
+            .add_systems(
+                Update,
+                (rebind_camera, camera_rotation, save_camera_controls).chain(),
+            );
+        app.update();
+        assert!(!path.exists(), "the defaults were saved before any change");
+
+        press(&mut app, &REBIND);
+        assert!(app.world().contains_resource::<Rebinding>());
+        // Two keys in the same frame bind two turns.
+        press(&mut app, &[KeyCode::KeyA, KeyCode::KeyD]);
+        press(&mut app, &[KeyCode::KeyW]);
+        press(&mut app, &[KeyCode::KeyS]);
+        assert!(!app.world().contains_resource::<Rebinding>());
+
+        let rebound = app.world().resource::<CameraControls>().clone();
+        let mut sideways = [rebound.keys[&Turn::Left], rebound.keys[&Turn::Right]];
+        sideways.sort();
+        assert_eq!(sideways, [KeyCode::KeyA, KeyCode::KeyD]);
+        assert_eq!(rebound.keys[&Turn::Up], KeyCode::KeyW);
+        assert_eq!(rebound.keys[&Turn::Down], KeyCode::KeyS);
+
+        let loaded = CameraControls::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, rebound);
+    }
+}
+
 // //! Shows how to render simple primitive shapes with a single color.
 // //!
 // //! You can toggle wireframes with the space bar except on wasm. Wasm does not support
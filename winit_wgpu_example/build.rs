@@ -0,0 +1,26 @@
+//! Packs the `assets/` folder into an archive for the browser, which has no file system to load
+//! loose files from. `game::run` embeds it with `include_bytes!` on `wasm32`.
+
+use std::path::PathBuf;
+
+// The same archive format `pack_assets` writes and `Vfs` mounts.
+#[allow(dead_code)]
+#[path = "src/assets/archive.rs"]
+mod archive;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=assets");
+    if std::env::var("CARGO_CFG_TARGET_ARCH").as_deref() != Ok("wasm32") {
+        return; // Native builds read `assets/` (or a packed archive) at run time.
+    }
+
+    let output = PathBuf::from(std::env::var("OUT_DIR").expect("cargo sets OUT_DIR"));
+    let mut builder = archive::ArchiveBuilder::new();
+    if let Err(error) = builder.add_directory("assets") {
+        panic!("Failed to pack the assets folder: {error}");
+    }
+    if let Err(error) = builder.write(output.join("assets.pak")) {
+        panic!("Failed to write the packed assets: {error}");
+    }
+}
@@ -1,56 +1,287 @@
+use std::collections::HashMap; // HashMap is used to look up the state of each window by its id.
+use std::path::PathBuf; // Path of the settings file the window config is persisted to.
 use std::sync::Arc; // Arc is a thread-safe reference-counted smart pointer, used for shared ownership across threads.
 use winit::{
     application::ApplicationHandler, // Trait to handle application-level events in winit.
     dpi::PhysicalSize,               // Represents the size of the window in physical pixels.
     event::WindowEvent,              // Enum for window-related events (resize, close, etc.).
-    window::Window,                  // The window object for rendering.
+    event_loop::{ActiveEventLoop, EventLoopProxy}, // The running event loop, and a handle to send it events.
+    keyboard::ModifiersState, // Which modifier keys (Shift, Alt, etc.) are held down.
+    window::{Window, WindowAttributes, WindowId}, // The window object for rendering, its attributes and unique id.
 };
 
+use crate::assets::AssetServer; // Import the AssetServer the game loads its assets with.
+use crate::game::{Game, GameContext}; // Import the Game trait that the app drives.
 use crate::renderer::Renderer; // Import the Renderer struct from the renderer module.
+use crate::window_config::WindowConfig; // Import the settings used to create the main window.
 
-pub use std::time::Instant; // Re-export `Instant` for easy access in other parts of the program.
+pub use web_time::Instant; // Re-export `Instant` (`std::time::Instant` panics in the browser).
 
-#[derive(Default)] // Automatically implement the Default trait to create an App with default values.
-pub struct App {
-    window: Option<Arc<Window>>, // Optional reference to the window (wrapped in Arc for shared ownership).
-    renderer: Option<Renderer<'static>>, // Optional reference to the renderer (renderer type is defined in a different module).
-    last_render_time: Option<Instant>, // The time of the last frame rendered, used to calculate frame timings.
-    last_size: (u32, u32),             // Stores the last window size (width, height).
+// Where the profiler trace is written when F3 is pressed. Open it with `chrome://tracing` or Perfetto.
+const PROFILER_TRACE_PATH: &str = "profiler_trace.json";
+
+// Events the app sends to itself through the event loop.
+pub enum AppEvent {
+    // A renderer finished initialising in the background (used in the browser, where we cannot block).
+    RendererCreated {
+        window_id: WindowId,
+        renderer: Renderer<'static>,
+    },
 }
 
-impl ApplicationHandler for App {
-    // The `resumed` method is called when the application is resumed.
-    fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
-        // Create window attributes with default values.
-        let mut attributes = Window::default_attributes();
+// Everything needed to render to a single window.
+pub struct WindowState {
+    pub window: Arc<Window>, // The window (wrapped in Arc for shared ownership with its surface).
+    pub renderer: Renderer<'static>, // The renderer drawing into this window.
+    last_render_time: Instant, // The time of the last frame rendered, used to calculate frame timings.
+    last_size: (u32, u32),     // Stores the last window size (width, height).
+}
+
+// The application handler, which owns the windows and drives a `Game`.
+pub struct App<G: Game> {
+    game: Option<G>,     // The game, created once the main window exists.
+    assets: AssetServer, // The game's assets, updated once per frame of the main window.
+    windows: HashMap<WindowId, WindowState>, // All open windows, keyed by their id.
+    pending_windows: HashMap<WindowId, Arc<Window>>, // Windows whose renderer is still being created.
+    proxy: EventLoopProxy<AppEvent>, // Used to deliver renderers created in the background.
+    main_window: Option<WindowId>,   // The game view, i.e. the window the game was created for.
+    logger_initialized: bool,        // True once the logger has been set up.
+    window_config: WindowConfig,     // Settings for the main window.
+    settings_path: Option<PathBuf>,  // Where `window_config` is saved, or `None` to not persist it.
+    modifiers: ModifiersState,       // Modifier keys currently held down.
+}
 
-        // Set window title to "Standalone Winit/Wgpu Example".
-        attributes = attributes.with_title("Standalone Winit/Wgpu Example");
+impl<G: Game> App<G> {
+    /// Creates an app whose main window uses `window_config`.
+    ///
+    /// If `settings_path` is given, the last window position and size are saved there on exit.
+    pub fn new(
+        proxy: EventLoopProxy<AppEvent>,
+        window_config: WindowConfig,
+        settings_path: Option<PathBuf>,
+        assets: AssetServer,
+    ) -> Self {
+        Self {
+            game: None,
+            assets,
+            windows: HashMap::new(),
+            pending_windows: HashMap::new(),
+            proxy,
+            main_window: None,
+            logger_initialized: false,
+            window_config,
+            settings_path,
+            modifiers: ModifiersState::default(),
+        }
+    }
 
+    // The game, once it has been initialised.
+    pub fn game(&self) -> Option<&G> {
+        self.game.as_ref()
+    }
+
+    // The server the game's assets are loaded with.
+    pub fn assets(&self) -> &AssetServer {
+        &self.assets
+    }
+
+    /// Opens a new window with its own surface and renderer.
+    ///
+    /// The first window creates the GPU device and queue; later windows share them. Returns `None`,
+    /// after logging why, if the window cannot be created or the shared GPU cannot present to it.
+    pub fn open_window(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        attributes: WindowAttributes,
+    ) -> Option<WindowId> {
         // Try to create a window and handle errors.
-        if let Ok(window) = event_loop.create_window(attributes) {
-            let first_window_handle = self.window.is_none(); // Check if this is the first window creation.
-            let window_handle = Arc::new(window); // Wrap the window in an Arc to share ownership across threads.
-            self.window = Some(window_handle.clone()); // Store the window handle.
-
-            // If it's the first window, initialize the renderer and store the last size.
-            if first_window_handle {
-                let inner_size = window_handle.inner_size();
-                self.last_size = (inner_size.width, inner_size.height); // Store initial window size.
-
-                // Get the width and height of the window.
-                let (width, height) = (
-                    window_handle.inner_size().width,
-                    window_handle.inner_size().height,
-                );
-
-                env_logger::init(); // Initialize the logger for logging purposes.
-                                    // Create the renderer asynchronously using the window handle and size.
-                let renderer = pollster::block_on(async move {
-                    Renderer::new(window_handle.clone(), width, height).await
+        let window = match event_loop.create_window(attributes) {
+            Ok(window) => Arc::new(window), // Wrap the window in an Arc to share ownership across threads.
+            Err(error) => {
+                log::error!("Failed to create window: {error}");
+                return None;
+            }
+        };
+
+        // Get the width and height of the window.
+        let PhysicalSize { width, height } = window.inner_size();
+        let (width, height) = (width.max(1), height.max(1));
+
+        // Reuse the device and queue of an existing window, if there is one.
+        let context = self
+            .windows
+            .values()
+            .next()
+            .map(|state| state.renderer.context().clone());
+        let id = window.id();
+        let renderer = match context {
+            Some(context) => match Renderer::with_context(context, window.clone(), width, height) {
+                Ok(renderer) => renderer,
+                Err(error) => {
+                    // Dropping the window closes it again.
+                    log::error!("Failed to create window: {error}");
+                    return None;
+                }
+            },
+            // Create the renderer asynchronously using the window handle and size.
+            #[cfg(not(target_arch = "wasm32"))]
+            None => pollster::block_on(Renderer::new(window.clone(), width, height)),
+            // The browser cannot block, so finish creating the window in `user_event`.
+            #[cfg(target_arch = "wasm32")]
+            None => {
+                let proxy = self.proxy.clone();
+                self.pending_windows.insert(id, window.clone());
+                wasm_bindgen_futures::spawn_local(async move {
+                    let renderer = Renderer::new(window, width, height).await;
+                    let event = AppEvent::RendererCreated {
+                        window_id: id,
+                        renderer,
+                    };
+                    if proxy.send_event(event).is_err() {
+                        log::error!("Event loop closed before the renderer was ready");
+                    }
                 });
-                self.renderer = Some(renderer); // Store the renderer instance.
-                self.last_render_time = Some(Instant::now()); // Initialize the render time.
+                return Some(id);
+            }
+        };
+
+        self.insert_window(window, renderer);
+        Some(id)
+    }
+
+    // Starts tracking a window once its renderer exists. The first window becomes the main window;
+    // the game lives and dies with it, so no other window takes its place.
+    fn insert_window(&mut self, window: Arc<Window>, renderer: Renderer<'static>) {
+        let id = window.id();
+        let PhysicalSize { width, height } = window.inner_size();
+        window.request_redraw(); // Kick off the first frame.
+        let state = self.windows.entry(id).or_insert(WindowState {
+            window,
+            renderer,
+            last_render_time: Instant::now(), // Initialize the render time.
+            last_size: (width.max(1), height.max(1)), // Store initial window size.
+        });
+
+        if self.game.is_some() {
+            return;
+        }
+        self.main_window = Some(id);
+        self.window_config.apply_cursor(&state.window);
+
+        // Create the game now that the main window and GPU are ready.
+        let gpu = state.renderer.gpu();
+        self.game = Some(G::init(&mut GameContext {
+            device: gpu.device(),
+            queue: gpu.queue(),
+            surface_format: gpu.surface_format,
+            window: &state.window,
+            assets: &mut self.assets,
+        }));
+    }
+
+    /// Closes a single window, leaving the others open.
+    ///
+    /// Closing the main window ends the game, and no other window takes its place, so exit the event
+    /// loop afterwards as the app does when the user closes it.
+    pub fn close_window(&mut self, id: WindowId) {
+        // Dropping the state destroys the renderer's surface before the window itself.
+        let Some(state) = self.windows.remove(&id) else {
+            return;
+        };
+        if self.main_window == Some(id) {
+            self.main_window = None;
+            // Remember where the main window was, so it reopens in the same place.
+            self.window_config.remember_geometry(&state.window);
+            self.save_window_config();
+        }
+    }
+
+    // Closes a window, and exits the event loop if it was the main window or the last one.
+    fn close_and_exit(&mut self, event_loop: &ActiveEventLoop, id: WindowId) {
+        let was_main = self.main_window == Some(id);
+        self.close_window(id);
+        if was_main || self.windows.is_empty() {
+            log::info!("Main window closed. Exiting...");
+            // Close the remaining windows before the event loop stops, surfaces first.
+            self.windows.clear();
+            self.pending_windows.clear();
+            event_loop.exit();
+        }
+    }
+
+    // The current settings of the main window.
+    pub fn window_config(&self) -> &WindowConfig {
+        &self.window_config
+    }
+
+    // Writes the window settings to the settings file, if there is one.
+    fn save_window_config(&self) {
+        let Some(path) = &self.settings_path else {
+            return;
+        };
+        if let Err(error) = self.window_config.save(path) {
+            log::error!(
+                "Failed to save window settings to {}: {error}",
+                path.display()
+            );
+        }
+    }
+
+    // A handle for sending events to the app from other tasks.
+    pub fn proxy(&self) -> &EventLoopProxy<AppEvent> {
+        &self.proxy
+    }
+
+    // The id of the game view window, if the game is running.
+    pub fn main_window(&self) -> Option<WindowId> {
+        self.main_window
+    }
+
+    // The state of the window with the given id, if it is open.
+    pub fn window(&self, id: WindowId) -> Option<&WindowState> {
+        self.windows.get(&id)
+    }
+}
+
+// Sets up logging: to stderr on native platforms, and to the browser console on the web.
+fn init_logger() {
+    #[cfg(not(target_arch = "wasm32"))]
+    env_logger::init(); // Initialize the logger for logging purposes.
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        // Show Rust panics in the browser console instead of an opaque "unreachable" error.
+        std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+        console_log::init_with_level(log::Level::Info).expect("Failed to initialize logger!");
+    }
+}
+
+impl<G: Game> ApplicationHandler<AppEvent> for App<G> {
+    // The `resumed` method is called when the application is resumed.
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if !self.logger_initialized {
+            init_logger();
+            self.logger_initialized = true;
+        }
+
+        // Open the game view the first time the application is resumed.
+        if self.windows.is_empty() && self.pending_windows.is_empty() {
+            let attributes = self.window_config.to_attributes(event_loop);
+            self.open_window(event_loop, attributes);
+        }
+    }
+
+    // The `user_event` method handles events the app sent to itself.
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: AppEvent) {
+        match event {
+            AppEvent::RendererCreated {
+                window_id,
+                renderer,
+            } => {
+                // The window may have been closed while its renderer was being created.
+                if let Some(window) = self.pending_windows.remove(&window_id) {
+                    self.insert_window(window, renderer);
+                }
             }
         }
     }
@@ -58,18 +289,27 @@ impl ApplicationHandler for App {
     // The `window_event` method handles events related to the window.
     fn window_event(
         &mut self,
-        event_loop: &winit::event_loop::ActiveEventLoop, // The event loop to drive window events.
-        _window_id: winit::window::WindowId,             // Window ID, not used in this example.
+        event_loop: &ActiveEventLoop, // The event loop to drive window events.
+        window_id: WindowId,          // The window the event is for.
         event: winit::event::WindowEvent, // The specific window event (resize, keyboard input, etc.).
     ) {
-        // Ensure that the renderer, window, and last render time are all initialized before proceeding.
-        let (Some(renderer), Some(window), Some(last_render_time)) = (
-            self.renderer.as_mut(),
-            self.window.as_ref(),
-            self.last_render_time.as_mut(),
-        ) else {
-            return; // If any of these are not initialized, return early.
+        // Let the game see the event first.
+        if let Some(game) = self.game.as_mut() {
+            game.on_event(window_id, &event);
+        }
+
+        // Forget windows that are closed while their renderer is still being created.
+        if matches!(event, WindowEvent::CloseRequested)
+            && self.pending_windows.remove(&window_id).is_some()
+        {
+            return;
+        }
+
+        // Ignore events for windows we do not know about (e.g. ones that were just closed).
+        let Some(state) = self.windows.get_mut(&window_id) else {
+            return;
         };
+        let renderer = &mut state.renderer;
 
         match event {
             // If a keyboard key is pressed.
@@ -77,39 +317,99 @@ impl ApplicationHandler for App {
                 event:
                     winit::event::KeyEvent {
                         physical_key: winit::keyboard::PhysicalKey::Code(key_code), // Get the keycode.
+                        state: key_state, // Whether the key was pressed or released.
                         ..
                     },
                 ..
             } => {
-                // Exit the application if the escape key is pressed.
+                // Close the window if the escape key is pressed.
                 if matches!(key_code, winit::keyboard::KeyCode::Escape) {
-                    event_loop.exit(); // Exit the event loop and close the application.
+                    self.close_and_exit(event_loop, window_id);
+                    return;
+                }
+                // Write the profiler history as a Chrome trace if F3 is pressed.
+                if matches!(key_code, winit::keyboard::KeyCode::F3) && key_state.is_pressed() {
+                    match renderer.profiler().write_chrome_trace(PROFILER_TRACE_PATH) {
+                        Ok(()) => log::info!("Wrote profiler trace to {PROFILER_TRACE_PATH}"),
+                        Err(error) => log::error!("Failed to write profiler trace: {error}"),
+                    }
+                }
+                // Save a screenshot if F12 is pressed.
+                if matches!(key_code, winit::keyboard::KeyCode::F12) && key_state.is_pressed() {
+                    renderer.request_screenshot();
+                }
+                // Start or stop recording a frame sequence if F9 is pressed.
+                if matches!(key_code, winit::keyboard::KeyCode::F9) && key_state.is_pressed() {
+                    if renderer.is_recording() {
+                        renderer.stop_recording();
+                    } else {
+                        renderer.start_recording(crate::capture::DEFAULT_RECORDING_DURATION);
+                    }
+                }
+                // Toggle fullscreen on the main window if Alt+Enter is pressed.
+                if matches!(key_code, winit::keyboard::KeyCode::Enter)
+                    && key_state.is_pressed()
+                    && self.modifiers.alt_key()
+                    && self.main_window == Some(window_id)
+                {
+                    self.window_config.toggle_fullscreen(&state.window);
+                }
+                // Open a tool window next to the game view if F2 is pressed.
+                if matches!(key_code, winit::keyboard::KeyCode::F2) && key_state.is_pressed() {
+                    let attributes = WindowConfig::tool_window_attributes("Tools");
+                    self.open_window(event_loop, attributes);
+                    return;
                 }
             }
+            // Keep track of the modifier keys, for shortcuts such as Alt+Enter.
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+            }
+            // Re-apply the cursor settings when the main window regains focus, since grabs are lost on focus change.
+            WindowEvent::Focused(true) if self.main_window == Some(window_id) => {
+                self.window_config.apply_cursor(&state.window);
+            }
             // If the window is resized.
             WindowEvent::Resized(PhysicalSize { width, height }) => {
                 // Ensure the new width and height are at least 1 pixel.
                 let (width, height) = ((width).max(1), (height).max(1));
                 log::info!("Resizing renderer surface to: ({width}, {height})"); // Log the resizing action.
                 renderer.resize(width, height); // Resize the renderer's surface.
-                self.last_size = (width, height); // Update the last known window size.
+                state.last_size = (width, height); // Update the last known window size.
             }
             // If the window close button is pressed.
             WindowEvent::CloseRequested => {
-                log::info!("Close requested. Exiting..."); // Log the close request.
-                event_loop.exit(); // Exit the event loop and close the application.
+                log::info!("Close requested for {window_id:?}."); // Log the close request.
+                self.close_and_exit(event_loop, window_id);
+                return;
             }
             // If the window requests a redraw (i.e., the screen needs to be rendered again).
             WindowEvent::RedrawRequested => {
                 let now = Instant::now(); // Get the current time.
-                let delta_time = now - *last_render_time; // Calculate the time difference since the last render.
-                *last_render_time = now; // Update the last render time.
-                renderer.render_frame(delta_time); // Render a new frame with the delta time.
+                let delta_time = now - state.last_render_time; // Calculate the time difference since the last render.
+                state.last_render_time = now; // Update the last render time.
+                if let Some(game) = self.game.as_mut() {
+                    if self.main_window == Some(window_id) {
+                        // Upload the assets that finished loading and free the ones no longer used.
+                        let gpu = renderer.gpu();
+                        self.assets.update(gpu.device(), gpu.queue());
+                        self.assets.collect_unused();
+
+                        // Advance the game once per frame of the main window, then draw it.
+                        game.update(delta_time);
+                        renderer
+                            .render_frame(delta_time, &mut self.assets, |frame| game.render(frame));
+                    } else {
+                        renderer.render_frame(delta_time, &mut self.assets, |frame| {
+                            game.render_window(window_id, frame)
+                        });
+                    }
+                }
             }
             _ => (), // For any other window events, do nothing.
         }
 
         // Request the window to be redrawn after handling the event.
-        window.request_redraw();
+        state.window.request_redraw();
     }
 }
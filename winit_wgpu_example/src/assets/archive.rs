@@ -0,0 +1,519 @@
+//! A single-file asset archive.
+//!
+//! Layout (all integers little endian):
+//!
+//! ```text
+//! header:  magic "WWPK", version u32, entry count u32, index offset u64
+//! data:    the stored bytes of every file, back to back
+//! index:   per entry: path length u16, path (UTF-8, `/` separated), offset u64,
+//!          stored size u64, original size u64, compression u8, CRC-32 of the original bytes u32
+//! ```
+
+use std::collections::BTreeMap; // The index, sorted by path so archives are reproducible.
+use std::io::{Read, Seek, SeekFrom, Write}; // Reading entries out of an archive file.
+use std::path::{Component, Path, PathBuf}; // Turning file system paths into archive paths.
+use std::sync::Mutex; // The archive file is shared between loader threads.
+
+const MAGIC: &[u8; 4] = b"WWPK"; // Identifies an archive file.
+const VERSION: u32 = 1; // Bumped whenever the layout changes.
+const HEADER_SIZE: u64 = 20; // Magic, version, entry count and index offset.
+
+/// How the bytes of an entry are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,    // Stored as is.
+    Deflate, // Compressed with DEFLATE.
+}
+
+/// An entry in the archive index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveEntry {
+    pub offset: u64,              // Where the stored bytes start in the archive.
+    pub stored_size: u64,         // Size of the stored (possibly compressed) bytes.
+    pub size: u64,                // Size of the original file.
+    pub compression: Compression, // How the bytes are stored.
+    pub hash: u32,                // CRC-32 of the original file, checked when it is read.
+}
+
+// Where the archive bytes come from.
+enum Source {
+    File(Mutex<std::fs::File>), // An archive on disk; entries are read on demand.
+    Memory(Vec<u8>),            // An archive already in memory (e.g. fetched in the browser).
+}
+
+/// A packed asset archive opened for reading.
+pub struct Archive {
+    source: Source,
+    entries: BTreeMap<String, ArchiveEntry>,
+}
+
+impl Archive {
+    /// Opens an archive file and reads its index.
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let mut file = std::fs::File::open(path)?;
+        let mut header = [0; HEADER_SIZE as usize];
+        file.read_exact(&mut header)?;
+        let (count, index_offset) = parse_header(&header)?;
+        file.seek(SeekFrom::Start(index_offset))?;
+        let mut index = Vec::new();
+        file.read_to_end(&mut index)?;
+        Ok(Self {
+            entries: parse_index(&index, count, index_offset)?,
+            source: Source::File(Mutex::new(file)),
+        })
+    }
+
+    /// Reads an archive that is already in memory.
+    pub fn from_bytes(bytes: Vec<u8>) -> std::io::Result<Self> {
+        let header = bytes
+            .get(..HEADER_SIZE as usize)
+            .ok_or_else(|| invalid_data("archive is too short"))?;
+        let (count, index_offset) = parse_header(header)?;
+        let index = usize::try_from(index_offset)
+            .ok()
+            .and_then(|index_offset| bytes.get(index_offset..))
+            .ok_or_else(|| invalid_data("index offset is out of range"))?;
+        Ok(Self {
+            entries: parse_index(index, count, index_offset)?,
+            source: Source::Memory(bytes),
+        })
+    }
+
+    /// The index entry for `path`, if the archive contains it.
+    pub fn entry(&self, path: &Path) -> Option<&ArchiveEntry> {
+        self.entries.get(&archive_path(path)?)
+    }
+
+    /// Every file in the archive with its index entry, sorted by path.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &ArchiveEntry)> {
+        self.entries
+            .iter()
+            .map(|(path, entry)| (path.as_str(), entry))
+    }
+
+    /// Returns true if the archive contains `path`.
+    pub fn contains(&self, path: &Path) -> bool {
+        self.entry(path).is_some()
+    }
+
+    /// Reads, decompresses and verifies the file at `path`.
+    pub fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        let entry = self
+            .entry(path)
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))?;
+
+        // Entries were checked to lie inside the data when the index was read, so the sizes below
+        // are no larger than the archive itself.
+        let stored = match &self.source {
+            Source::File(file) => {
+                let mut file = file.lock().expect("archive reader panicked");
+                let mut stored = vec![0; entry.stored_size as usize];
+                file.seek(SeekFrom::Start(entry.offset))?;
+                file.read_exact(&mut stored)?;
+                stored
+            }
+            Source::Memory(bytes) => bytes
+                .get(entry.offset as usize..(entry.offset + entry.stored_size) as usize)
+                .ok_or_else(|| invalid_data("entry is out of range"))?
+                .to_vec(),
+        };
+
+        // Stop decompressing past the original size, so a corrupt entry cannot inflate without end.
+        let bytes = match entry.compression {
+            Compression::None => stored,
+            Compression::Deflate => {
+                let limit = usize::try_from(entry.size).unwrap_or(usize::MAX);
+                miniz_oxide::inflate::decompress_to_vec_with_limit(&stored, limit)
+                    .map_err(|e| invalid_data(&format!("corrupt compressed data: {e:?}")))?
+            }
+        };
+
+        if bytes.len() as u64 != entry.size || crc32fast::hash(&bytes) != entry.hash {
+            return Err(invalid_data("content hash mismatch"));
+        }
+        Ok(bytes)
+    }
+
+    /// Reads every entry and checks its hash. Returns the paths of the entries that failed.
+    pub fn verify(&self) -> Vec<String> {
+        self.entries
+            .keys()
+            .filter(|path| self.read(Path::new(path)).is_err())
+            .cloned()
+            .collect()
+    }
+}
+
+/// Builds an archive from files on disk or in memory.
+pub struct ArchiveBuilder {
+    files: BTreeMap<String, Vec<u8>>, // Original file contents by archive path.
+    compress: bool,                   // Whether to try compressing entries.
+    level: u8,                        // DEFLATE level, 0 (fastest) to 10 (smallest).
+}
+
+impl Default for ArchiveBuilder {
+    fn default() -> Self {
+        Self {
+            files: BTreeMap::new(),
+            compress: true,
+            level: 6,
+        }
+    }
+}
+
+impl ArchiveBuilder {
+    /// Creates an empty builder that compresses entries.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether entries are compressed. Entries that do not get smaller are always stored as is.
+    pub fn compress(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// Sets the DEFLATE level, from 0 (fastest) to 10 (smallest).
+    pub fn compression_level(mut self, level: u8) -> Self {
+        self.level = level.min(10);
+        self
+    }
+
+    /// Adds a file with the given archive path, replacing any file already at that path.
+    ///
+    /// Paths longer than 65535 bytes do not fit in the index and are refused.
+    pub fn add_file(&mut self, path: impl AsRef<Path>, bytes: Vec<u8>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let key = archive_path(path)
+            .ok_or_else(|| invalid_data(&format!("invalid archive path {}", path.display())))?;
+        if key.len() > u16::MAX as usize {
+            return Err(invalid_data(&format!(
+                "archive path is longer than {} bytes: {}",
+                u16::MAX,
+                path.display()
+            )));
+        }
+        self.files.insert(key, bytes);
+        Ok(())
+    }
+
+    /// Adds every file below `root`, using paths relative to `root`. Returns the number of files added.
+    pub fn add_directory(&mut self, root: impl AsRef<Path>) -> std::io::Result<usize> {
+        let root = root.as_ref();
+        let files = list_files(root)?;
+        for relative in &files {
+            self.add_file(relative, std::fs::read(root.join(relative))?)?;
+        }
+        Ok(files.len())
+    }
+
+    /// The number of files added so far.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Returns true if no files have been added.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Encodes the archive into memory.
+    pub fn build(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::new();
+        let mut index = Vec::new();
+        for (path, bytes) in &self.files {
+            let compressed = self
+                .compress
+                .then(|| miniz_oxide::deflate::compress_to_vec(bytes, self.level))
+                .filter(|compressed| compressed.len() < bytes.len());
+            let (stored, compression) = match &compressed {
+                Some(compressed) => (compressed.as_slice(), Compression::Deflate),
+                None => (bytes.as_slice(), Compression::None),
+            };
+
+            index.extend((path.len() as u16).to_le_bytes());
+            index.extend(path.as_bytes());
+            index.extend((HEADER_SIZE + data.len() as u64).to_le_bytes());
+            index.extend((stored.len() as u64).to_le_bytes());
+            index.extend((bytes.len() as u64).to_le_bytes());
+            index.push(compression as u8);
+            index.extend(crc32fast::hash(bytes).to_le_bytes());
+            data.extend(stored);
+        }
+
+        let mut archive = Vec::with_capacity(HEADER_SIZE as usize + data.len() + index.len());
+        archive.extend(MAGIC);
+        archive.extend(VERSION.to_le_bytes());
+        archive.extend((self.files.len() as u32).to_le_bytes());
+        archive.extend((HEADER_SIZE + data.len() as u64).to_le_bytes());
+        archive.extend(data);
+        archive.extend(index);
+        archive
+    }
+
+    /// Encodes the archive and writes it to `path`.
+    pub fn write(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut file = std::fs::File::create(path)?;
+        file.write_all(&self.build())?;
+        file.sync_all()
+    }
+}
+
+/// Lists every file below `root`, as paths relative to `root`, sorted.
+pub fn list_files(root: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut directories = vec![PathBuf::new()];
+    while let Some(relative) = directories.pop() {
+        for entry in std::fs::read_dir(root.join(&relative))? {
+            let entry = entry?;
+            let path = relative.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                directories.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Turns a relative path into the `/` separated form used in the index.
+///
+/// Returns `None` for absolute paths and paths that leave their root with `..`.
+pub fn archive_path(path: &Path) -> Option<String> {
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_str()?),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+// Checks the header and returns the entry count and index offset.
+fn parse_header(header: &[u8]) -> std::io::Result<(u32, u64)> {
+    if &header[0..4] != MAGIC {
+        return Err(invalid_data("not an asset archive"));
+    }
+    let version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+    if version != VERSION {
+        return Err(invalid_data(&format!(
+            "unsupported archive version {version}"
+        )));
+    }
+    let count = u32::from_le_bytes(header[8..12].try_into().unwrap());
+    let index_offset = u64::from_le_bytes(header[12..20].try_into().unwrap());
+    Ok((count, index_offset))
+}
+
+// Decodes `count` index entries, checking that each lies in the data between the header and
+// `data_end`.
+fn parse_index(
+    mut index: &[u8],
+    count: u32,
+    data_end: u64,
+) -> std::io::Result<BTreeMap<String, ArchiveEntry>> {
+    // Takes the next `n` bytes off the front of the index.
+    fn take<'a>(index: &mut &'a [u8], n: usize) -> std::io::Result<&'a [u8]> {
+        if index.len() < n {
+            return Err(invalid_data("index is truncated"));
+        }
+        let (taken, rest) = index.split_at(n);
+        *index = rest;
+        Ok(taken)
+    }
+    let u64_at = |bytes: &[u8]| u64::from_le_bytes(bytes.try_into().unwrap());
+
+    let mut entries = BTreeMap::new();
+    for _ in 0..count {
+        let length = u16::from_le_bytes(take(&mut index, 2)?.try_into().unwrap()) as usize;
+        let path = std::str::from_utf8(take(&mut index, length)?)
+            .map_err(|_| invalid_data("entry path is not UTF-8"))?
+            .to_string();
+        let offset = u64_at(take(&mut index, 8)?);
+        let stored_size = u64_at(take(&mut index, 8)?);
+        let size = u64_at(take(&mut index, 8)?);
+        let compression = match take(&mut index, 1)?[0] {
+            0 => Compression::None,
+            1 => Compression::Deflate,
+            other => return Err(invalid_data(&format!("unknown compression {other}"))),
+        };
+        let hash = u32::from_le_bytes(take(&mut index, 4)?.try_into().unwrap());
+        let in_data = offset >= HEADER_SIZE
+            && offset
+                .checked_add(stored_size)
+                .is_some_and(|end| end <= data_end);
+        if !in_data {
+            return Err(invalid_data(&format!("entry {path} is out of range")));
+        }
+        entries.insert(
+            path,
+            ArchiveEntry {
+                offset,
+                stored_size,
+                size,
+                compression,
+                hash,
+            },
+        );
+    }
+    Ok(entries)
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Offset of the first entry's offset field in the index of a single-entry archive.
+    fn offset_field(archive: &[u8], path: &str) -> usize {
+        let index_offset = u64::from_le_bytes(archive[12..20].try_into().unwrap()) as usize;
+        index_offset + 2 + path.len()
+    }
+
+    fn single_file(path: &str, bytes: &[u8]) -> Vec<u8> {
+        let mut builder = ArchiveBuilder::new();
+        builder.add_file(path, bytes.to_vec()).unwrap();
+        builder.build()
+    }
+
+    #[test]
+    fn packed_directory_reads_back() {
+        let (root, output) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        std::fs::create_dir_all(root.path().join("textures/ui")).unwrap();
+        let text = "fn main() {}\n".repeat(100);
+        let noise: Vec<u8> = (0..1000u32).map(|i| (i * 7919 % 251) as u8).collect();
+        std::fs::write(root.path().join("shader.wgsl"), &text).unwrap();
+        std::fs::write(root.path().join("textures/ui/noise.bin"), &noise).unwrap();
+        std::fs::write(root.path().join("empty"), b"").unwrap();
+
+        for compress in [true, false] {
+            let mut builder = ArchiveBuilder::new().compress(compress);
+            assert_eq!(builder.add_directory(root.path()).unwrap(), 3);
+            let archive_path = output.path().join("assets.pak");
+            builder.write(&archive_path).unwrap();
+
+            let from_file = Archive::open(&archive_path).unwrap();
+            let from_memory = Archive::from_bytes(builder.build()).unwrap();
+            for archive in [&from_file, &from_memory] {
+                let paths: Vec<_> = archive.entries().map(|(path, _)| path).collect();
+                assert_eq!(paths, ["empty", "shader.wgsl", "textures/ui/noise.bin"]);
+                assert_eq!(
+                    archive.read(Path::new("shader.wgsl")).unwrap(),
+                    text.as_bytes()
+                );
+                assert_eq!(
+                    archive.read(Path::new("textures/ui/noise.bin")).unwrap(),
+                    noise
+                );
+                assert_eq!(archive.read(Path::new("./empty")).unwrap(), b"");
+                assert!(archive.verify().is_empty());
+            }
+
+            let shader = from_file.entry(Path::new("shader.wgsl")).unwrap();
+            let expected = if compress {
+                Compression::Deflate
+            } else {
+                Compression::None
+            };
+            assert_eq!(shader.compression, expected);
+        }
+    }
+
+    #[test]
+    fn missing_entries_are_not_found() {
+        let archive = Archive::from_bytes(single_file("a.txt", b"a")).unwrap();
+        let error = archive.read(Path::new("b.txt")).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+        assert!(archive.entry(Path::new("../a.txt")).is_none());
+    }
+
+    #[test]
+    fn corrupted_contents_fail_the_checksum() {
+        for compress in [false, true] {
+            let mut builder = ArchiveBuilder::new().compress(compress);
+            builder
+                .add_file("a.txt", b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa".to_vec())
+                .unwrap();
+            builder
+                .add_file("b.txt", b"bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb".to_vec())
+                .unwrap();
+            let mut bytes = builder.build();
+            let archive = Archive::from_bytes(bytes.clone()).unwrap();
+            let b = archive.entry(Path::new("b.txt")).unwrap();
+            bytes[(b.offset + b.stored_size - 1) as usize] ^= 0xff;
+
+            let archive = Archive::from_bytes(bytes).unwrap();
+            assert_eq!(archive.read(Path::new("a.txt")).unwrap(), [b'a'; 32]);
+            let error = archive.read(Path::new("b.txt")).unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+            assert_eq!(archive.verify(), ["b.txt"]);
+        }
+    }
+
+    #[test]
+    fn corrupt_headers_are_refused() {
+        let bytes = single_file("a.txt", b"a");
+
+        let mut magic = bytes.clone();
+        magic[0] = b'X';
+        assert!(Archive::from_bytes(magic).is_err());
+
+        let mut version = bytes.clone();
+        version[4] = 99;
+        assert!(Archive::from_bytes(version).is_err());
+
+        let mut index_offset = bytes.clone();
+        index_offset[12..20].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(Archive::from_bytes(index_offset).is_err());
+
+        assert!(Archive::from_bytes(bytes[..10].to_vec()).is_err());
+        assert!(Archive::from_bytes(bytes[..bytes.len() - 1].to_vec()).is_err());
+    }
+
+    #[test]
+    fn entries_outside_the_data_are_refused() {
+        let bytes = single_file("a.txt", b"a");
+        let field = offset_field(&bytes, "a.txt");
+        let cases = [
+            (u64::MAX, 1),           // The end overflows.
+            (HEADER_SIZE, u64::MAX), // Claims more than the whole archive.
+            (HEADER_SIZE, 2),        // Runs into the index.
+            (0, 1),                  // Starts in the header.
+        ];
+        for (offset, stored_size) in cases {
+            let mut corrupt = bytes.clone();
+            corrupt[field..field + 8].copy_from_slice(&offset.to_le_bytes());
+            corrupt[field + 8..field + 16].copy_from_slice(&stored_size.to_le_bytes());
+
+            let error = Archive::from_bytes(corrupt.clone()).err().unwrap();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+            let file = tempfile::NamedTempFile::new().unwrap();
+            std::fs::write(file.path(), &corrupt).unwrap();
+            let error = Archive::open(file.path()).err().unwrap();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn overlong_and_escaping_paths_are_refused() {
+        let mut builder = ArchiveBuilder::new();
+        let long = "a".repeat(u16::MAX as usize + 1);
+        assert!(builder.add_file(&long, Vec::new()).is_err());
+        assert!(builder.add_file("../secret", Vec::new()).is_err());
+        assert!(builder.add_file("/etc/passwd", Vec::new()).is_err());
+        assert!(builder.is_empty());
+
+        let longest = "a".repeat(u16::MAX as usize);
+        builder.add_file(&longest, b"x".to_vec()).unwrap();
+        let archive = Archive::from_bytes(builder.build()).unwrap();
+        assert_eq!(archive.read(Path::new(&longest)).unwrap(), b"x");
+    }
+}
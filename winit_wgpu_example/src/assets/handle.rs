@@ -0,0 +1,71 @@
+use std::marker::PhantomData; // Ties a handle to the asset type it points at, without storing one.
+use std::sync::{Arc, Weak}; // Reference counting for handles.
+
+/// Identifies an asset inside an `AssetServer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AssetId(pub(crate) u64);
+
+/// A typed, reference-counted reference to an asset.
+///
+/// Handles are returned immediately by `AssetServer::load`, before the asset has finished loading.
+/// The asset stays in memory while at least one handle to it exists.
+pub struct Handle<T> {
+    id: AssetId,                    // The asset this handle refers to.
+    strong: Arc<()>, // Shared by every clone; the server keeps a `Weak` to count them.
+    marker: PhantomData<fn() -> T>, // `fn() -> T` keeps the handle `Send + Sync` whatever `T` is.
+}
+
+impl<T> Handle<T> {
+    // Creates a brand new handle and the weak reference the server uses to track it.
+    pub(crate) fn new(id: AssetId) -> (Self, Weak<()>) {
+        let strong = Arc::new(());
+        let weak = Arc::downgrade(&strong);
+        (Self::from_strong(id, strong), weak)
+    }
+
+    // Creates a handle sharing the reference count of existing handles.
+    pub(crate) fn from_strong(id: AssetId, strong: Arc<()>) -> Self {
+        Self {
+            id,
+            strong,
+            marker: PhantomData,
+        }
+    }
+
+    // The id of the asset.
+    pub fn id(&self) -> AssetId {
+        self.id
+    }
+
+    // How many handles to this asset currently exist.
+    pub fn strong_count(&self) -> usize {
+        Arc::strong_count(&self.strong)
+    }
+}
+
+// Implemented by hand so `T` does not need to be `Clone`.
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self::from_strong(self.id, self.strong.clone())
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> std::hash::Hash for Handle<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<T> std::fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Handle<{}>({})", std::any::type_name::<T>(), self.id.0)
+    }
+}
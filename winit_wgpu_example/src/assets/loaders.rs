@@ -0,0 +1,400 @@
+use std::path::{Path, PathBuf}; // Paths are used in error messages.
+
+use super::{Asset, AssetError, AssetLoader};
+use crate::vertex::Vertex; // Meshes use the same vertex layout as the scene.
+
+/// Decodes a PNG file into tightly packed 8-bit RGBA pixels, returning `(pixels, width, height)`.
+pub fn decode_png(bytes: &[u8]) -> Result<(Vec<u8>, u32, u32), String> {
+    let mut decoder = png::Decoder::new(bytes);
+    // Expand palettes and low bit depths so we only have to handle 8-bit RGB(A) and greyscale.
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|e| e.to_string())?;
+    let pixels = &buffer[..info.buffer_size()];
+
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => pixels.to_vec(),
+        png::ColorType::Rgb => pixels
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => pixels
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => pixels.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+        png::ColorType::Indexed => return Err("indexed PNGs are not supported".to_string()),
+    };
+
+    Ok((rgba, info.width, info.height))
+}
+
+/// A compiled WGSL shader module.
+pub struct Shader {
+    pub module: wgpu::ShaderModule, // The module to reference from pipelines.
+    pub source: String,             // The WGSL source, kept for reflection and debugging.
+}
+
+impl Asset for Shader {}
+
+/// Loads `.wgsl` files into `Shader`s.
+pub struct ShaderLoader;
+
+impl AssetLoader for ShaderLoader {
+    type Asset = Shader;
+    type Intermediate = (PathBuf, String);
+
+    fn extensions(&self) -> &[&'static str] {
+        &["wgsl"]
+    }
+
+    fn load(&self, bytes: Vec<u8>, path: &Path) -> Result<Self::Intermediate, AssetError> {
+        let source = String::from_utf8(bytes)
+            .map_err(|e| AssetError::Decode(path.to_path_buf(), e.to_string()))?;
+        Ok((path.to_path_buf(), source))
+    }
+
+    fn upload(
+        &self,
+        (path, source): Self::Intermediate,
+        device: &wgpu::Device,
+        _queue: &wgpu::Queue,
+    ) -> Result<Self::Asset, AssetError> {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: path.to_str(),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(&source)),
+        });
+        Ok(Shader { module, source })
+    }
+}
+
+/// A 2D texture with a view and a default sampler, ready to be bound.
+pub struct Texture {
+    pub texture: wgpu::Texture,  // The texture itself.
+    pub view: wgpu::TextureView, // A view of the whole texture.
+    pub sampler: wgpu::Sampler,  // Linear filtering, repeating at the edges.
+    pub width: u32,              // Width in pixels.
+    pub height: u32,             // Height in pixels.
+}
+
+impl Asset for Texture {}
+
+impl Texture {
+    // The format every loaded texture uses.
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    /// Creates a texture from tightly packed RGBA pixels.
+    pub fn from_rgba(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        label: Option<&str>,
+        rgba: &[u8],
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            rgba,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label,
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        Self {
+            texture,
+            view,
+            sampler,
+            width,
+            height,
+        }
+    }
+}
+
+// Decoded pixels waiting to be uploaded.
+pub struct DecodedImage {
+    path: PathBuf,
+    rgba: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+/// Loads `.png` files into `Texture`s.
+pub struct TextureLoader;
+
+impl AssetLoader for TextureLoader {
+    type Asset = Texture;
+    type Intermediate = DecodedImage;
+
+    fn extensions(&self) -> &[&'static str] {
+        &["png"]
+    }
+
+    fn load(&self, bytes: Vec<u8>, path: &Path) -> Result<Self::Intermediate, AssetError> {
+        let (rgba, width, height) =
+            decode_png(&bytes).map_err(|e| AssetError::Decode(path.to_path_buf(), e))?;
+        Ok(DecodedImage {
+            path: path.to_path_buf(),
+            rgba,
+            width,
+            height,
+        })
+    }
+
+    fn upload(
+        &self,
+        image: Self::Intermediate,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Self::Asset, AssetError> {
+        Ok(Texture::from_rgba(
+            device,
+            queue,
+            image.path.to_str(),
+            &image.rgba,
+            image.width,
+            image.height,
+        ))
+    }
+}
+
+/// Vertex and index buffers for a triangle list.
+pub struct Mesh {
+    pub vertex_buffer: wgpu::Buffer, // `Vertex` data.
+    pub index_buffer: wgpu::Buffer,  // `u32` indices, three per triangle.
+    pub index_count: u32,            // Number of indices to draw.
+}
+
+impl Asset for Mesh {}
+
+impl Mesh {
+    /// Creates a mesh from vertices and triangle-list indices.
+    pub fn new(
+        device: &wgpu::Device,
+        label: Option<&str>,
+        vertices: &[Vertex],
+        indices: &[u32],
+    ) -> Self {
+        let vertex_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label,
+                contents: bytemuck::cast_slice(vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            },
+        );
+        let index_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label,
+                contents: bytemuck::cast_slice(indices),
+                usage: wgpu::BufferUsages::INDEX,
+            },
+        );
+        Self {
+            vertex_buffer,
+            index_buffer,
+            index_count: indices.len() as u32,
+        }
+    }
+
+    /// Binds the buffers and draws the mesh.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.index_count, 0, 0..1);
+    }
+}
+
+/// Loads `.obj` files into `Mesh`es.
+///
+/// Only positions (with optional vertex colours, `v x y z r g b`) and faces are read; faces with
+/// more than three corners are split into a triangle fan.
+pub struct MeshLoader;
+
+impl AssetLoader for MeshLoader {
+    type Asset = Mesh;
+    type Intermediate = (PathBuf, Vec<Vertex>, Vec<u32>);
+
+    fn extensions(&self) -> &[&'static str] {
+        &["obj"]
+    }
+
+    fn load(&self, bytes: Vec<u8>, path: &Path) -> Result<Self::Intermediate, AssetError> {
+        let error = |line: usize, message: &str| {
+            AssetError::Decode(path.to_path_buf(), format!("line {}: {message}", line + 1))
+        };
+        let text = String::from_utf8(bytes)
+            .map_err(|e| AssetError::Decode(path.to_path_buf(), e.to_string()))?;
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("v") => {
+                    let values = words
+                        .map(str::parse::<f32>)
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|e| error(number, &e.to_string()))?;
+                    let (position, color) = match values.as_slice() {
+                        [x, y, z] => ([*x, *y, *z, 1.0], [1.0; 4]),
+                        [x, y, z, r, g, b] => ([*x, *y, *z, 1.0], [*r, *g, *b, 1.0]),
+                        _ => return Err(error(number, "expected 3 or 6 values")),
+                    };
+                    vertices.push(Vertex { position, color });
+                }
+                Some("f") => {
+                    // Corners look like `v`, `v/vt` or `v/vt/vn`; only the position index is used.
+                    let corners = words
+                        .map(|corner| {
+                            let index: i64 = corner
+                                .split('/')
+                                .next()
+                                .unwrap_or_default()
+                                .parse()
+                                .map_err(|_| error(number, "invalid face index"))?;
+                            // Indices start at 1; negative ones count back from the latest vertex.
+                            let index = if index < 0 {
+                                vertices.len() as i64 + index
+                            } else {
+                                index - 1
+                            };
+                            if !(0..vertices.len() as i64).contains(&index) {
+                                return Err(error(number, "face index out of range"));
+                            }
+                            Ok(index as u32)
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if corners.len() < 3 {
+                        return Err(error(number, "a face needs at least 3 corners"));
+                    }
+                    for pair in corners[1..].windows(2) {
+                        indices.extend([corners[0], pair[0], pair[1]]);
+                    }
+                }
+                // Comments, normals, texture coordinates, groups and materials are ignored.
+                _ => {}
+            }
+        }
+        Ok((path.to_path_buf(), vertices, indices))
+    }
+
+    fn upload(
+        &self,
+        (path, vertices, indices): Self::Intermediate,
+        device: &wgpu::Device,
+        _queue: &wgpu::Queue,
+    ) -> Result<Self::Asset, AssetError> {
+        Ok(Mesh::new(device, path.to_str(), &vertices, &indices))
+    }
+}
+
+/// The raw contents of a TrueType or OpenType font, for a text renderer to parse.
+pub struct Font {
+    pub data: Vec<u8>,
+}
+
+impl Asset for Font {}
+
+/// Loads `.ttf` and `.otf` files into `Font`s.
+pub struct FontLoader;
+
+impl AssetLoader for FontLoader {
+    type Asset = Font;
+    type Intermediate = Vec<u8>;
+
+    fn extensions(&self) -> &[&'static str] {
+        &["ttf", "otf"]
+    }
+
+    fn load(&self, bytes: Vec<u8>, path: &Path) -> Result<Self::Intermediate, AssetError> {
+        // Check the magic number so obviously wrong files fail early.
+        match bytes.get(..4) {
+            Some([0, 1, 0, 0] | b"OTTO" | b"true") => Ok(bytes),
+            _ => Err(AssetError::Decode(
+                path.to_path_buf(),
+                "not a TrueType or OpenType font".to_string(),
+            )),
+        }
+    }
+
+    fn upload(
+        &self,
+        data: Self::Intermediate,
+        _device: &wgpu::Device,
+        _queue: &wgpu::Queue,
+    ) -> Result<Self::Asset, AssetError> {
+        Ok(Font { data })
+    }
+}
+
+/// The encoded contents of a sound file, decoded by the audio system when it is played.
+pub struct Sound {
+    pub bytes: Vec<u8>,    // The file contents.
+    pub extension: String, // The file extension, which tells the audio system how to decode it.
+}
+
+impl Asset for Sound {}
+
+/// Loads `.wav`, `.ogg` and `.flac` files into `Sound`s.
+pub struct SoundLoader;
+
+impl AssetLoader for SoundLoader {
+    type Asset = Sound;
+    type Intermediate = Sound;
+
+    fn extensions(&self) -> &[&'static str] {
+        &["wav", "ogg", "flac"]
+    }
+
+    fn load(&self, bytes: Vec<u8>, path: &Path) -> Result<Self::Intermediate, AssetError> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        Ok(Sound { bytes, extension })
+    }
+
+    fn upload(
+        &self,
+        sound: Self::Intermediate,
+        _device: &wgpu::Device,
+        _queue: &wgpu::Queue,
+    ) -> Result<Self::Asset, AssetError> {
+        Ok(sound)
+    }
+}
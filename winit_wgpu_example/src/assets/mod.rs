@@ -0,0 +1,79 @@
+//! Loading of meshes, textures, shaders, fonts and sounds from files.
+//!
+//! Files are read and decoded on background threads; anything that needs the GPU is uploaded on the
+//! main thread when `AssetServer::update` is called. Files come from a `Vfs`, which can mount a
+//! directory during development and a packed `Archive` in release builds.
+
+pub mod archive;
+mod handle;
+mod loaders;
+mod server;
+mod vfs;
+
+use std::path::{Path, PathBuf};
+
+pub use archive::{Archive, ArchiveBuilder};
+pub use handle::{AssetId, Handle};
+pub use loaders::{
+    decode_png, Font, FontLoader, Mesh, MeshLoader, Shader, ShaderLoader, Sound, SoundLoader,
+    Texture, TextureLoader,
+};
+pub use server::AssetServer;
+pub use vfs::{DirectoryMount, Mount, Vfs};
+
+/// Marker trait for types that can be stored in an `AssetServer`.
+pub trait Asset: 'static {}
+
+/// Turns the bytes of a file into an asset.
+///
+/// Loading happens in two steps: `load` runs on a background thread and produces an intermediate
+/// value (e.g. decoded pixels), then `upload` runs on the main thread to create GPU resources.
+pub trait AssetLoader: Send + Sync + 'static {
+    type Asset: Asset; // The asset this loader produces.
+    type Intermediate: Send + 'static; // What `load` hands over to `upload`.
+
+    // File extensions (without the dot, lower case) this loader can read.
+    fn extensions(&self) -> &[&'static str];
+
+    // Decodes the file contents. Runs on a background thread.
+    fn load(&self, bytes: Vec<u8>, path: &Path) -> Result<Self::Intermediate, AssetError>;
+
+    // Creates the final asset. Runs on the main thread.
+    fn upload(
+        &self,
+        intermediate: Self::Intermediate,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Self::Asset, AssetError>;
+}
+
+/// Where an asset is in its loading process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadState {
+    NotLoaded,      // The handle is not known to this server.
+    Loading,        // Being read, decoded or waiting for upload.
+    Loaded,         // Ready to use.
+    Failed(String), // Loading failed; the message says why.
+}
+
+/// Errors that can occur while loading an asset.
+#[derive(Debug)]
+pub enum AssetError {
+    Io(PathBuf, std::io::Error), // The file could not be read.
+    NoLoader(PathBuf),           // No registered loader handles this file type.
+    Decode(PathBuf, String),     // The file contents were invalid.
+}
+
+impl std::fmt::Display for AssetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssetError::Io(path, error) => write!(f, "failed to read {}: {error}", path.display()),
+            AssetError::NoLoader(path) => write!(f, "no asset loader for {}", path.display()),
+            AssetError::Decode(path, message) => {
+                write!(f, "failed to decode {}: {message}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for AssetError {}
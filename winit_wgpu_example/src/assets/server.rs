@@ -0,0 +1,589 @@
+use std::any::{Any, TypeId}; // Type erasure, so one server can store every kind of asset.
+use std::collections::HashMap; // Lookup of assets by id and by path.
+use std::path::{Path, PathBuf}; // Asset paths.
+use std::sync::{mpsc, Arc, Mutex, Weak}; // Channels and shared state for the worker threads.
+
+use super::handle::{AssetId, Handle};
+use super::loaders::{FontLoader, MeshLoader, ShaderLoader, SoundLoader, TextureLoader};
+use super::vfs::Vfs;
+use super::{Asset, AssetError, AssetLoader, LoadState};
+
+/// Loads assets in the background, caches them by path and hands out typed handles.
+///
+/// Call `update` once per frame on the main thread to upload finished assets to the GPU.
+pub struct AssetServer {
+    vfs: Arc<Vfs>,                              // Where asset files are read from.
+    loaders: Vec<Arc<dyn ErasedLoader>>,        // Registered loaders; later ones take priority.
+    entries: HashMap<AssetId, AssetEntry>,      // Every asset known to the server.
+    cache: HashMap<(PathBuf, TypeId), AssetId>, // Assets loaded from a file, by path and type.
+    next_id: u64,                               // Id given to the next asset.
+    pool: WorkerPool,                           // Threads that read and decode files.
+    decoded_sender: mpsc::Sender<Decoded>,      // Cloned into every job to report its result.
+    decoded_receiver: mpsc::Receiver<Decoded>,  // Results waiting to be uploaded.
+}
+
+// Book-keeping for a single asset.
+struct AssetEntry {
+    path: Option<PathBuf>,                 // The file the asset came from, if any.
+    handles: Weak<()>,                     // Counts the live handles to the asset.
+    state: LoadState,                      // Where the asset is in its loading process.
+    asset: Option<Box<dyn Any>>,           // The asset itself, once loaded.
+    loader: Option<Arc<dyn ErasedLoader>>, // The loader used for the upload step.
+}
+
+// The result of reading and decoding a file on a worker thread.
+struct Decoded {
+    id: AssetId,
+    result: Result<Box<dyn Any + Send>, AssetError>,
+}
+
+impl AssetServer {
+    /// Creates a server that loads files relative to the directory `root`, with the built-in loaders
+    /// registered.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self::with_vfs(Vfs::directory(root))
+    }
+
+    /// Creates a server that loads files from a virtual file system (e.g. a packed archive).
+    pub fn with_vfs(vfs: Vfs) -> Self {
+        // Leave one core for the main thread. Browsers have no threads, so load inline there.
+        let threads = if cfg!(target_arch = "wasm32") {
+            0
+        } else {
+            std::thread::available_parallelism()
+                .map(|n| n.get().saturating_sub(1).max(1))
+                .unwrap_or(2)
+        };
+        Self::with_threads(vfs, threads)
+    }
+
+    /// Creates a server with a specific number of worker threads (0 loads files on the calling thread).
+    pub fn with_threads(vfs: Vfs, threads: usize) -> Self {
+        let (decoded_sender, decoded_receiver) = mpsc::channel();
+        let mut server = Self {
+            vfs: Arc::new(vfs),
+            loaders: Vec::new(),
+            entries: HashMap::new(),
+            cache: HashMap::new(),
+            next_id: 0,
+            pool: WorkerPool::new(threads),
+            decoded_sender,
+            decoded_receiver,
+        };
+        server.register_loader(ShaderLoader);
+        server.register_loader(TextureLoader);
+        server.register_loader(MeshLoader);
+        server.register_loader(FontLoader);
+        server.register_loader(SoundLoader);
+        server
+    }
+
+    /// Adds a loader. It takes priority over earlier loaders for the same asset type and extension.
+    pub fn register_loader<L: AssetLoader>(&mut self, loader: L) {
+        self.loaders.push(Arc::new(loader));
+    }
+
+    /// Starts loading the file at `path` from the file system and returns a handle to it.
+    ///
+    /// If the same file is already loaded (or loading) as a `T`, a handle to that asset is returned.
+    pub fn load<T: Asset>(&mut self, path: impl AsRef<Path>) -> Handle<T> {
+        let path = path.as_ref().to_path_buf();
+        let key = (path.clone(), TypeId::of::<T>());
+
+        // Reuse the cached asset if something still holds a handle to it.
+        if let Some(&id) = self.cache.get(&key) {
+            if let Some(strong) = self.entries.get(&id).and_then(|e| e.handles.upgrade()) {
+                return Handle::from_strong(id, strong);
+            }
+        }
+
+        let (handle, weak) = self.allocate::<T>();
+        let id = handle.id();
+        self.cache.insert(key, id);
+
+        let Some(loader) = self.find_loader(TypeId::of::<T>(), &path) else {
+            let error = AssetError::NoLoader(path.clone());
+            log::error!("{error}");
+            self.entries
+                .insert(id, AssetEntry::failed(Some(path), weak, &error));
+            return handle;
+        };
+
+        self.entries.insert(
+            id,
+            AssetEntry {
+                path: Some(path.clone()),
+                handles: weak,
+                state: LoadState::Loading,
+                asset: None,
+                loader: Some(loader.clone()),
+            },
+        );
+
+        // Read and decode the file on a worker thread.
+        let vfs = self.vfs.clone();
+        let sender = self.decoded_sender.clone();
+        self.pool.execute(Box::new(move || {
+            let result = vfs
+                .read(&path)
+                .map_err(|error| AssetError::Io(path.clone(), error))
+                .and_then(|bytes| loader.load(bytes, &path));
+            // The server may have been dropped in the meantime, in which case nobody cares.
+            let _ = sender.send(Decoded { id, result });
+        }));
+
+        handle
+    }
+
+    /// Adds an asset created in code (e.g. a procedural mesh) and returns a handle to it.
+    pub fn insert<T: Asset>(&mut self, asset: T) -> Handle<T> {
+        let (handle, weak) = self.allocate::<T>();
+        self.entries.insert(
+            handle.id(),
+            AssetEntry {
+                path: None,
+                handles: weak,
+                state: LoadState::Loaded,
+                asset: Some(Box::new(asset)),
+                loader: None,
+            },
+        );
+        handle
+    }
+
+    /// Uploads assets that finished decoding. Call once per frame on the main thread.
+    ///
+    /// Returns the ids of the assets that became loaded or failed during this call.
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> Vec<AssetId> {
+        self.finish_loads(|loader, intermediate| loader.upload(intermediate, device, queue))
+    }
+
+    // Hands every decoded asset to `upload` and records the outcome. Kept apart from `update` so the
+    // book-keeping can be tested without a GPU.
+    fn finish_loads(
+        &mut self,
+        mut upload: impl FnMut(
+            &dyn ErasedLoader,
+            Box<dyn Any + Send>,
+        ) -> Result<Box<dyn Any>, AssetError>,
+    ) -> Vec<AssetId> {
+        let mut finished = Vec::new();
+        while let Ok(Decoded { id, result }) = self.decoded_receiver.try_recv() {
+            // The asset may have been collected while it was loading.
+            let Some(entry) = self.entries.get_mut(&id) else {
+                continue;
+            };
+            let result = result.and_then(|intermediate| match &entry.loader {
+                Some(loader) => upload(loader.as_ref(), intermediate),
+                None => unreachable!("assets loaded from files always have a loader"),
+            });
+            match result {
+                Ok(asset) => {
+                    entry.asset = Some(asset);
+                    entry.state = LoadState::Loaded;
+                }
+                Err(error) => {
+                    log::error!("{error}");
+                    entry.state = LoadState::Failed(error.to_string());
+                }
+            }
+            finished.push(id);
+        }
+        finished
+    }
+
+    /// The asset behind `handle`, if it has finished loading.
+    pub fn get<T: Asset>(&self, handle: &Handle<T>) -> Option<&T> {
+        self.entries
+            .get(&handle.id())?
+            .asset
+            .as_ref()?
+            .downcast_ref::<T>()
+    }
+
+    /// Mutable access to the asset behind `handle`, if it has finished loading.
+    pub fn get_mut<T: Asset>(&mut self, handle: &Handle<T>) -> Option<&mut T> {
+        self.entries
+            .get_mut(&handle.id())?
+            .asset
+            .as_mut()?
+            .downcast_mut::<T>()
+    }
+
+    /// Where the asset behind `handle` is in its loading process.
+    pub fn load_state<T>(&self, handle: &Handle<T>) -> LoadState {
+        self.entries
+            .get(&handle.id())
+            .map_or(LoadState::NotLoaded, |entry| entry.state.clone())
+    }
+
+    // Returns true if the asset behind `handle` is ready to use.
+    pub fn is_loaded<T>(&self, handle: &Handle<T>) -> bool {
+        self.load_state(handle) == LoadState::Loaded
+    }
+
+    // The number of assets that are still being read, decoded or uploaded.
+    pub fn pending_count(&self) -> usize {
+        self.entries
+            .values()
+            .filter(|entry| entry.state == LoadState::Loading)
+            .count()
+    }
+
+    // The file the asset behind `handle` was loaded from.
+    pub fn path<T>(&self, handle: &Handle<T>) -> Option<&Path> {
+        self.entries.get(&handle.id())?.path.as_deref()
+    }
+
+    /// The file system assets are read from.
+    pub fn vfs(&self) -> &Vfs {
+        &self.vfs
+    }
+
+    /// Frees every asset that no handle refers to any more. Returns how many were freed.
+    pub fn collect_unused(&mut self) -> usize {
+        let before = self.entries.len();
+        self.entries
+            .retain(|_, entry| entry.handles.strong_count() > 0);
+        let entries = &self.entries;
+        self.cache.retain(|_, id| entries.contains_key(id));
+        before - self.entries.len()
+    }
+
+    // Creates a new handle with a fresh id.
+    fn allocate<T>(&mut self) -> (Handle<T>, Weak<()>) {
+        let id = AssetId(self.next_id);
+        self.next_id += 1;
+        Handle::new(id)
+    }
+
+    // Finds the most recently registered loader for an asset type and file extension.
+    fn find_loader(&self, asset_type: TypeId, path: &Path) -> Option<Arc<dyn ErasedLoader>> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        self.loaders
+            .iter()
+            .rev()
+            .find(|loader| {
+                loader.asset_type() == asset_type && loader.extensions().contains(&&*extension)
+            })
+            .cloned()
+    }
+}
+
+impl AssetEntry {
+    // An entry for an asset that could not be loaded.
+    fn failed(path: Option<PathBuf>, handles: Weak<()>, error: &AssetError) -> Self {
+        Self {
+            path,
+            handles,
+            state: LoadState::Failed(error.to_string()),
+            asset: None,
+            loader: None,
+        }
+    }
+}
+
+// `AssetLoader` with its associated types erased, so loaders of different assets fit in one list.
+trait ErasedLoader: Send + Sync {
+    fn asset_type(&self) -> TypeId;
+    fn extensions(&self) -> &[&'static str];
+    fn load(&self, bytes: Vec<u8>, path: &Path) -> Result<Box<dyn Any + Send>, AssetError>;
+    fn upload(
+        &self,
+        intermediate: Box<dyn Any + Send>,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Box<dyn Any>, AssetError>;
+}
+
+impl<L: AssetLoader> ErasedLoader for L {
+    fn asset_type(&self) -> TypeId {
+        TypeId::of::<L::Asset>()
+    }
+
+    fn extensions(&self) -> &[&'static str] {
+        AssetLoader::extensions(self)
+    }
+
+    fn load(&self, bytes: Vec<u8>, path: &Path) -> Result<Box<dyn Any + Send>, AssetError> {
+        Ok(Box::new(AssetLoader::load(self, bytes, path)?))
+    }
+
+    fn upload(
+        &self,
+        intermediate: Box<dyn Any + Send>,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Box<dyn Any>, AssetError> {
+        let intermediate = intermediate
+            .downcast::<L::Intermediate>()
+            .expect("intermediate type always matches its loader");
+        Ok(Box::new(AssetLoader::upload(
+            self,
+            *intermediate,
+            device,
+            queue,
+        )?))
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+// A fixed set of threads that run jobs from a shared queue.
+struct WorkerPool {
+    sender: Option<mpsc::Sender<Job>>, // `None` once the pool is shutting down.
+    workers: Vec<std::thread::JoinHandle<()>>, // Joined when the pool is dropped.
+}
+
+impl WorkerPool {
+    fn new(threads: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..threads)
+            .map(|index| {
+                let receiver = receiver.clone();
+                std::thread::Builder::new()
+                    .name(format!("asset-worker-{index}"))
+                    .spawn(move || loop {
+                        // Hold the lock only while waiting for a job, not while running it.
+                        let job = receiver.lock().expect("asset worker panicked").recv();
+                        match job {
+                            Ok(job) => job(),
+                            Err(_) => break, // The pool was dropped.
+                        }
+                    })
+                    .expect("Failed to spawn asset worker thread!")
+            })
+            .collect();
+        Self {
+            sender: Some(sender),
+            workers,
+        }
+    }
+
+    // Runs a job on a worker thread, or right away if the pool has no threads.
+    fn execute(&self, job: Job) {
+        match (&self.sender, self.workers.is_empty()) {
+            (Some(sender), false) => sender.send(job).expect("asset workers stopped"),
+            _ => job(),
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        // Closing the channel makes every worker leave its loop once the queue is empty.
+        self.sender.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets::{Archive, ArchiveBuilder};
+
+    struct Text(String);
+
+    impl Asset for Text {}
+
+    // Loads `.txt` files as `Text`, decoding them completely on the worker so the upload step has
+    // nothing left to do.
+    struct TextLoader;
+
+    impl AssetLoader for TextLoader {
+        type Asset = Text;
+        type Intermediate = Text;
+
+        fn extensions(&self) -> &[&'static str] {
+            &["txt"]
+        }
+
+        fn load(&self, bytes: Vec<u8>, path: &Path) -> Result<Text, AssetError> {
+            String::from_utf8(bytes)
+                .map(Text)
+                .map_err(|error| AssetError::Decode(path.to_path_buf(), error.to_string()))
+        }
+
+        fn upload(
+            &self,
+            text: Text,
+            _: &wgpu::Device,
+            _: &wgpu::Queue,
+        ) -> Result<Text, AssetError> {
+            Ok(text)
+        }
+    }
+
+    // Like `TextLoader`, but shouts, to tell the two apart.
+    struct LoudTextLoader;
+
+    impl AssetLoader for LoudTextLoader {
+        type Asset = Text;
+        type Intermediate = Text;
+
+        fn extensions(&self) -> &[&'static str] {
+            &["txt"]
+        }
+
+        fn load(&self, bytes: Vec<u8>, path: &Path) -> Result<Text, AssetError> {
+            let Text(text) = AssetLoader::load(&TextLoader, bytes, path)?;
+            Ok(Text(text.to_uppercase()))
+        }
+
+        fn upload(
+            &self,
+            text: Text,
+            _: &wgpu::Device,
+            _: &wgpu::Queue,
+        ) -> Result<Text, AssetError> {
+            Ok(text)
+        }
+    }
+
+    // A server without worker threads, reading from an in-memory archive holding `hello.txt` and
+    // `bad.txt`, which is not UTF-8.
+    fn server() -> AssetServer {
+        let mut builder = ArchiveBuilder::new();
+        builder.add_file("hello.txt", b"hello".to_vec()).unwrap();
+        builder.add_file("bad.txt", vec![0xff, 0xfe]).unwrap();
+        let mut vfs = Vfs::new();
+        vfs.mount(Archive::from_bytes(builder.build()).unwrap());
+        let mut server = AssetServer::with_threads(vfs, 0);
+        server.register_loader(TextLoader);
+        server
+    }
+
+    // `update` without the GPU: the test loaders' intermediates are already the finished assets.
+    fn finish(server: &mut AssetServer) -> Vec<AssetId> {
+        server.finish_loads(|_, intermediate| Ok(intermediate))
+    }
+
+    fn text<'a>(server: &'a AssetServer, handle: &Handle<Text>) -> Option<&'a str> {
+        server.get(handle).map(|Text(text)| text.as_str())
+    }
+
+    #[test]
+    fn loads_go_from_loading_to_loaded_on_update() {
+        let mut server = server();
+        let handle = server.load::<Text>("hello.txt");
+        assert_eq!(server.load_state(&handle), LoadState::Loading);
+        assert_eq!(server.pending_count(), 1);
+        assert!(text(&server, &handle).is_none());
+
+        assert_eq!(finish(&mut server), [handle.id()]);
+        assert_eq!(server.load_state(&handle), LoadState::Loaded);
+        assert!(server.is_loaded(&handle));
+        assert_eq!(server.pending_count(), 0);
+        assert_eq!(text(&server, &handle), Some("hello"));
+        assert_eq!(server.path(&handle), Some(Path::new("hello.txt")));
+
+        // Nothing more to do on the next frame.
+        assert!(finish(&mut server).is_empty());
+    }
+
+    #[test]
+    fn missing_undecodable_and_unknown_files_fail() {
+        let mut server = server();
+        let missing = server.load::<Text>("missing.txt");
+        let bad = server.load::<Text>("bad.txt");
+        let unknown = server.load::<Text>("hello.png");
+        // Without a loader there is nothing to wait for.
+        assert!(matches!(server.load_state(&unknown), LoadState::Failed(_)));
+
+        let mut finished = finish(&mut server);
+        finished.sort();
+        assert_eq!(finished, [missing.id(), bad.id()]);
+        for handle in [&missing, &bad, &unknown] {
+            assert!(matches!(server.load_state(handle), LoadState::Failed(_)));
+            assert!(text(&server, handle).is_none());
+        }
+        assert_eq!(server.pending_count(), 0);
+    }
+
+    #[test]
+    fn loading_a_file_twice_shares_the_asset() {
+        let mut server = server();
+        let first = server.load::<Text>("hello.txt");
+        let second = server.load::<Text>("hello.txt");
+        assert_eq!(first, second);
+        assert_eq!(first.strong_count(), 2);
+        // Only one read was started.
+        assert_eq!(finish(&mut server), [first.id()]);
+
+        // Loading again after it finished still hits the cache.
+        let third = server.load::<Text>("hello.txt");
+        assert_eq!(third, first);
+        assert!(server.is_loaded(&third));
+    }
+
+    #[test]
+    fn unused_assets_are_collected() {
+        let mut server = server();
+        let kept = server.load::<Text>("hello.txt");
+        let dropped = server.insert(Text("made in code".to_owned()));
+        let copy = dropped.clone();
+        finish(&mut server);
+
+        drop(dropped);
+        assert_eq!(
+            server.collect_unused(),
+            0,
+            "a copy of the handle is still alive"
+        );
+        drop(copy);
+        assert_eq!(server.collect_unused(), 1);
+        assert_eq!(text(&server, &kept), Some("hello"));
+
+        let id = kept.id();
+        drop(kept);
+        assert_eq!(server.collect_unused(), 1);
+        // The file is read again, under a new id.
+        let reloaded = server.load::<Text>("hello.txt");
+        assert_ne!(reloaded.id(), id);
+        assert_eq!(server.load_state(&reloaded), LoadState::Loading);
+        finish(&mut server);
+        assert_eq!(text(&server, &reloaded), Some("hello"));
+    }
+
+    #[test]
+    fn assets_collected_while_loading_are_skipped() {
+        let mut server = server();
+        let handle = server.load::<Text>("hello.txt");
+        let id = handle.id();
+        drop(handle);
+        assert_eq!(server.collect_unused(), 1);
+        assert!(finish(&mut server).is_empty());
+        assert_eq!(server.pending_count(), 0);
+        assert!(!server.entries.contains_key(&id));
+    }
+
+    #[test]
+    fn inserted_assets_are_loaded_and_editable() {
+        let mut server = server();
+        let handle = server.insert(Text("made in code".to_owned()));
+        assert!(server.is_loaded(&handle));
+        assert_eq!(server.path(&handle), None);
+        server.get_mut(&handle).unwrap().0.push('!');
+        assert_eq!(text(&server, &handle), Some("made in code!"));
+    }
+
+    #[test]
+    fn unknown_handles_are_not_loaded() {
+        let server = server();
+        let (stranger, _) = Handle::<Text>::new(AssetId(99));
+        assert_eq!(server.load_state(&stranger), LoadState::NotLoaded);
+        assert!(text(&server, &stranger).is_none());
+    }
+
+    #[test]
+    fn later_loaders_take_priority() {
+        let mut server = server();
+        server.register_loader(LoudTextLoader);
+        let handle = server.load::<Text>("hello.txt");
+        finish(&mut server);
+        assert_eq!(text(&server, &handle), Some("HELLO"));
+    }
+}
@@ -0,0 +1,214 @@
+use std::path::{Path, PathBuf}; // Asset paths and mount roots.
+
+use super::archive::{archive_path, list_files, Archive};
+
+/// A source of files that can be mounted into a `Vfs`.
+pub trait Mount: Send + Sync {
+    /// Reads the whole file at `path` (relative, `/` or platform separated).
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>>;
+
+    /// Returns true if the file at `path` exists.
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Every file in the mount, as relative paths.
+    fn files(&self) -> Vec<PathBuf>;
+}
+
+/// Mounts a directory on disk. Used during development so edited files are picked up directly.
+pub struct DirectoryMount {
+    root: PathBuf,
+}
+
+impl DirectoryMount {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    // The path on disk for an asset path, refusing paths that would leave the root.
+    fn resolve(&self, path: &Path) -> std::io::Result<PathBuf> {
+        archive_path(path)
+            .map(|relative| self.root.join(relative))
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("invalid asset path {}", path.display()),
+                )
+            })
+    }
+}
+
+impl Mount for DirectoryMount {
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        std::fs::read(self.resolve(path)?)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.resolve(path).is_ok_and(|path| path.is_file())
+    }
+
+    fn files(&self) -> Vec<PathBuf> {
+        list_files(&self.root).unwrap_or_default()
+    }
+}
+
+impl Mount for Archive {
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        Archive::read(self, path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.contains(path)
+    }
+
+    fn files(&self) -> Vec<PathBuf> {
+        self.entries()
+            .map(|(path, _)| PathBuf::from(path))
+            .collect()
+    }
+}
+
+/// A virtual file system made of mounted directories and archives.
+///
+/// Mounts are searched from the most recently added to the first, so a directory mounted after an
+/// archive can override single files (e.g. for mods or patches).
+#[derive(Default)]
+pub struct Vfs {
+    mounts: Vec<Box<dyn Mount>>,
+}
+
+impl Vfs {
+    /// Creates a file system with nothing mounted.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a file system with a single directory mounted.
+    pub fn directory(root: impl Into<PathBuf>) -> Self {
+        let mut vfs = Self::new();
+        vfs.mount(DirectoryMount::new(root));
+        vfs
+    }
+
+    /// Mounts a directory or archive file, whichever `path` points at.
+    ///
+    /// This lets the same code mount `assets/` in development and `assets.pak` in release builds.
+    pub fn mount_path(&mut self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            self.mount(DirectoryMount::new(path));
+        } else {
+            self.mount(Archive::open(path)?);
+        }
+        Ok(())
+    }
+
+    /// Mounts any file source on top of the existing mounts.
+    pub fn mount(&mut self, mount: impl Mount + 'static) {
+        self.mounts.push(Box::new(mount));
+    }
+
+    /// Reads a file from the most recent mount that has it.
+    pub fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        self.mounts
+            .iter()
+            .rev()
+            .find(|mount| mount.exists(path))
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("{} is not in any mount", path.display()),
+                )
+            })?
+            .read(path)
+    }
+
+    /// Returns true if any mount has the file.
+    pub fn exists(&self, path: &Path) -> bool {
+        self.mounts.iter().any(|mount| mount.exists(path))
+    }
+
+    /// Every file visible through the file system, sorted and without duplicates.
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<_> = self.mounts.iter().flat_map(|mount| mount.files()).collect();
+        files.sort();
+        files.dedup();
+        files
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets::ArchiveBuilder;
+
+    // A directory with an archive holding `a.txt` and `b.txt`, next to a loose `assets` folder.
+    fn setup() -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        let mut builder = ArchiveBuilder::new();
+        builder.add_file("a.txt", b"packed a".to_vec()).unwrap();
+        builder.add_file("b.txt", b"packed b".to_vec()).unwrap();
+        builder.write(root.path().join("assets.pak")).unwrap();
+
+        let loose = root.path().join("assets");
+        std::fs::create_dir_all(loose.join("sub")).unwrap();
+        std::fs::write(loose.join("a.txt"), b"loose a").unwrap();
+        std::fs::write(loose.join("sub/c.txt"), b"loose c").unwrap();
+        root
+    }
+
+    fn read(vfs: &Vfs, path: &str) -> Vec<u8> {
+        vfs.read(Path::new(path)).unwrap()
+    }
+
+    #[test]
+    fn loose_files_override_the_archive() {
+        let root = setup();
+        let mut vfs = Vfs::new();
+        vfs.mount_path(root.path().join("assets.pak")).unwrap();
+        vfs.mount_path(root.path().join("assets")).unwrap();
+
+        assert_eq!(read(&vfs, "a.txt"), b"loose a");
+        assert_eq!(read(&vfs, "b.txt"), b"packed b");
+        assert_eq!(read(&vfs, "sub/c.txt"), b"loose c");
+        assert_eq!(
+            vfs.files(),
+            [
+                Path::new("a.txt"),
+                Path::new("b.txt"),
+                Path::new("sub/c.txt")
+            ]
+        );
+    }
+
+    #[test]
+    fn later_mounts_win() {
+        let root = setup();
+        let mut vfs = Vfs::directory(root.path().join("assets"));
+        vfs.mount_path(root.path().join("assets.pak")).unwrap();
+
+        assert_eq!(read(&vfs, "a.txt"), b"packed a");
+        assert_eq!(read(&vfs, "sub/c.txt"), b"loose c");
+    }
+
+    #[test]
+    fn missing_and_escaping_paths_are_not_read() {
+        let root = setup();
+        std::fs::write(root.path().join("secret.txt"), b"secret").unwrap();
+        let vfs = Vfs::directory(root.path().join("assets"));
+
+        let error = vfs.read(Path::new("missing.txt")).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+        assert!(!vfs.exists(Path::new("../secret.txt")));
+        assert!(vfs.read(Path::new("../secret.txt")).is_err());
+    }
+
+    #[test]
+    fn unreadable_archives_are_not_mounted() {
+        let root = setup();
+        std::fs::write(root.path().join("broken.pak"), b"not an archive").unwrap();
+        let mut vfs = Vfs::new();
+        assert!(vfs.mount_path(root.path().join("broken.pak")).is_err());
+        assert!(vfs.mount_path(root.path().join("missing.pak")).is_err());
+        assert!(vfs.files().is_empty());
+    }
+}
@@ -0,0 +1,382 @@
+use std::io::Cursor; // Decoders read from the encoded bytes in memory.
+use std::sync::Arc; // Encoded bytes are shared so streams can be reopened cheaply.
+
+use super::AudioError;
+
+/// A stream of decoded, interleaved samples.
+///
+/// Music is decoded through this a chunk at a time while it plays, so long tracks never have to be
+/// decoded into memory in full.
+pub trait SampleSource: Send {
+    /// Number of interleaved channels.
+    fn channels(&self) -> u16;
+
+    /// Frames per second.
+    fn sample_rate(&self) -> u32;
+
+    /// Appends the next chunk of interleaved samples to `out`. Returns `false` at the end of the stream.
+    fn next_chunk(&mut self, out: &mut Vec<f32>) -> Result<bool, AudioError>;
+
+    /// Goes back to the start of the stream, for looping.
+    fn rewind(&mut self) -> Result<(), AudioError>;
+}
+
+/// Opens a streaming decoder for an encoded file. `extension` selects the format (`wav`, `ogg` or `flac`).
+pub fn open(
+    bytes: impl Into<Arc<[u8]>>,
+    extension: &str,
+) -> Result<Box<dyn SampleSource>, AudioError> {
+    let bytes = bytes.into();
+    match extension.to_ascii_lowercase().as_str() {
+        "wav" => Ok(Box::new(WavSource::new(bytes)?)),
+        "ogg" => Ok(Box::new(OggSource::new(bytes)?)),
+        "flac" => Ok(Box::new(FlacSource::new(bytes)?)),
+        other => Err(AudioError::UnsupportedFormat(other.to_string())),
+    }
+}
+
+/// A sound decoded in full, for short effects that are played many times.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioClip {
+    pub samples: Vec<f32>, // Interleaved samples in -1.0..=1.0.
+    pub channels: u16,     // Number of interleaved channels.
+    pub sample_rate: u32,  // Frames per second.
+}
+
+impl AudioClip {
+    /// Decodes a whole file. `extension` selects the format (`wav`, `ogg` or `flac`).
+    pub fn decode(bytes: impl Into<Arc<[u8]>>, extension: &str) -> Result<Self, AudioError> {
+        Self::from_source(open(bytes, extension)?.as_mut())
+    }
+
+    /// Decodes a sound loaded by the `AssetServer`.
+    pub fn from_sound(sound: &crate::assets::Sound) -> Result<Self, AudioError> {
+        Self::decode(sound.bytes.as_slice(), &sound.extension)
+    }
+
+    /// Reads a stream to its end.
+    pub fn from_source(source: &mut dyn SampleSource) -> Result<Self, AudioError> {
+        let mut samples = Vec::new();
+        while source.next_chunk(&mut samples)? {}
+        Ok(Self {
+            samples,
+            channels: source.channels(),
+            sample_rate: source.sample_rate(),
+        })
+    }
+
+    /// Builds a clip from samples generated in code.
+    pub fn from_samples(samples: Vec<f32>, channels: u16, sample_rate: u32) -> Self {
+        Self {
+            samples,
+            channels,
+            sample_rate,
+        }
+    }
+
+    /// Number of frames (samples per channel).
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
+    }
+
+    /// Length of the clip in seconds.
+    pub fn duration(&self) -> f32 {
+        self.frames() as f32 / self.sample_rate as f32
+    }
+
+    /// The frame at `index` as a (left, right) pair. Mono clips play on both sides.
+    pub fn frame(&self, index: usize) -> (f32, f32) {
+        let channels = self.channels as usize;
+        match self.samples.get(index * channels..(index + 1) * channels) {
+            Some([mono]) => (*mono, *mono),
+            Some([left, right, ..]) => (*left, *right),
+            _ => (0.0, 0.0),
+        }
+    }
+}
+
+// Number of frames decoded at a time from formats that do not have natural blocks.
+const CHUNK_FRAMES: usize = 4096;
+
+/// Streams a WAV file (8/16/24/32-bit integer or 32-bit float PCM).
+pub struct WavSource {
+    reader: hound::WavReader<Cursor<Arc<[u8]>>>,
+}
+
+impl WavSource {
+    pub fn new(bytes: Arc<[u8]>) -> Result<Self, AudioError> {
+        let reader = hound::WavReader::new(Cursor::new(bytes)).map_err(decode_error)?;
+        Ok(Self { reader })
+    }
+}
+
+impl SampleSource for WavSource {
+    fn channels(&self) -> u16 {
+        self.reader.spec().channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.reader.spec().sample_rate
+    }
+
+    fn next_chunk(&mut self, out: &mut Vec<f32>) -> Result<bool, AudioError> {
+        let spec = self.reader.spec();
+        let count = CHUNK_FRAMES * spec.channels as usize;
+        let before = out.len();
+        match spec.sample_format {
+            hound::SampleFormat::Float => {
+                for sample in self.reader.samples::<f32>().take(count) {
+                    out.push(sample.map_err(decode_error)?);
+                }
+            }
+            hound::SampleFormat::Int => {
+                let scale = 1.0 / (1_i64 << (spec.bits_per_sample - 1)) as f32;
+                for sample in self.reader.samples::<i32>().take(count) {
+                    out.push(sample.map_err(decode_error)? as f32 * scale);
+                }
+            }
+        }
+        Ok(out.len() - before == count)
+    }
+
+    fn rewind(&mut self) -> Result<(), AudioError> {
+        self.reader.seek(0).map_err(decode_error)
+    }
+}
+
+/// Streams an Ogg Vorbis file.
+pub struct OggSource {
+    bytes: Arc<[u8]>, // Kept to reopen the stream when rewinding.
+    reader: lewton::inside_ogg::OggStreamReader<Cursor<Arc<[u8]>>>,
+}
+
+impl OggSource {
+    pub fn new(bytes: Arc<[u8]>) -> Result<Self, AudioError> {
+        let reader = lewton::inside_ogg::OggStreamReader::new(Cursor::new(bytes.clone()))
+            .map_err(decode_error)?;
+        Ok(Self { bytes, reader })
+    }
+}
+
+impl SampleSource for OggSource {
+    fn channels(&self) -> u16 {
+        self.reader.ident_hdr.audio_channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.reader.ident_hdr.audio_sample_rate
+    }
+
+    fn next_chunk(&mut self, out: &mut Vec<f32>) -> Result<bool, AudioError> {
+        match self.reader.read_dec_packet_itl().map_err(decode_error)? {
+            Some(packet) => {
+                out.extend(packet.into_iter().map(|s| s as f32 / 32768.0));
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn rewind(&mut self) -> Result<(), AudioError> {
+        *self = Self::new(self.bytes.clone())?;
+        Ok(())
+    }
+}
+
+/// Streams a FLAC file.
+pub struct FlacSource {
+    bytes: Arc<[u8]>, // Kept to reopen the stream when rewinding.
+    reader: claxon::FlacReader<Cursor<Arc<[u8]>>>,
+    buffer: Vec<i32>, // Reused between blocks to avoid allocating.
+}
+
+impl FlacSource {
+    pub fn new(bytes: Arc<[u8]>) -> Result<Self, AudioError> {
+        let reader = claxon::FlacReader::new(Cursor::new(bytes.clone())).map_err(decode_error)?;
+        Ok(Self {
+            bytes,
+            reader,
+            buffer: Vec::new(),
+        })
+    }
+}
+
+impl SampleSource for FlacSource {
+    fn channels(&self) -> u16 {
+        self.reader.streaminfo().channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.reader.streaminfo().sample_rate
+    }
+
+    fn next_chunk(&mut self, out: &mut Vec<f32>) -> Result<bool, AudioError> {
+        let scale = 1.0 / (1_i64 << (self.reader.streaminfo().bits_per_sample - 1)) as f32;
+        let buffer = std::mem::take(&mut self.buffer);
+        let Some(block) = self
+            .reader
+            .blocks()
+            .read_next_or_eof(buffer)
+            .map_err(decode_error)?
+        else {
+            return Ok(false);
+        };
+        // Blocks store each channel separately; interleave them.
+        for frame in 0..block.duration() {
+            for channel in 0..block.channels() {
+                out.push(block.sample(channel, frame) as f32 * scale);
+            }
+        }
+        self.buffer = block.into_buffer();
+        Ok(true)
+    }
+
+    fn rewind(&mut self) -> Result<(), AudioError> {
+        *self = Self::new(self.bytes.clone())?;
+        Ok(())
+    }
+}
+
+/// Plays a clip that is already decoded as a stream, e.g. to use generated audio as music.
+pub struct ClipSource {
+    clip: Arc<AudioClip>,
+    position: usize, // Next sample to hand out.
+}
+
+impl ClipSource {
+    pub fn new(clip: Arc<AudioClip>) -> Self {
+        Self { clip, position: 0 }
+    }
+}
+
+impl SampleSource for ClipSource {
+    fn channels(&self) -> u16 {
+        self.clip.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.clip.sample_rate
+    }
+
+    fn next_chunk(&mut self, out: &mut Vec<f32>) -> Result<bool, AudioError> {
+        let end = (self.position + CHUNK_FRAMES * self.clip.channels as usize)
+            .min(self.clip.samples.len());
+        out.extend_from_slice(&self.clip.samples[self.position..end]);
+        self.position = end;
+        Ok(end < self.clip.samples.len())
+    }
+
+    fn rewind(&mut self) -> Result<(), AudioError> {
+        self.position = 0;
+        Ok(())
+    }
+}
+
+fn decode_error(error: impl std::fmt::Display) -> AudioError {
+    AudioError::Decode(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes a WAV file in memory with `hound`.
+    fn wav<S: hound::Sample + Copy>(spec: hound::WavSpec, samples: &[S]) -> Vec<u8> {
+        let mut bytes = Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+        for &sample in samples {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+        bytes.into_inner()
+    }
+
+    fn int16_stereo(samples: &[i16]) -> Vec<u8> {
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44_100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        wav(spec, samples)
+    }
+
+    #[test]
+    fn decodes_16_bit_wav_scaled_to_unit_range() {
+        let bytes = int16_stereo(&[0, 16_384, -32_768, 32_767]);
+        let clip = AudioClip::decode(bytes, "WAV").unwrap();
+        assert_eq!(clip.channels, 2);
+        assert_eq!(clip.sample_rate, 44_100);
+        assert_eq!(clip.frames(), 2);
+        assert_eq!(clip.samples[..3], [0.0, 0.5, -1.0]);
+        assert!((clip.samples[3] - 1.0).abs() < 1e-4);
+        assert_eq!(clip.frame(1), (clip.samples[2], clip.samples[3]));
+    }
+
+    #[test]
+    fn decodes_float_wav_as_is() {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 22_050,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let bytes = wav(spec, &[0.25_f32, -1.0, 1.0]);
+        let clip = AudioClip::decode(bytes, "wav").unwrap();
+        assert_eq!(clip.channels, 1);
+        assert_eq!(clip.sample_rate, 22_050);
+        assert_eq!(clip.samples, [0.25, -1.0, 1.0]);
+        assert_eq!(clip.frame(2), (1.0, 1.0));
+    }
+
+    #[test]
+    fn clip_sources_stream_in_chunks_and_rewind() {
+        // Two full chunks of stereo frames and a few more.
+        let samples: Vec<f32> = (0..(CHUNK_FRAMES * 2 + 3) * 2).map(|i| i as f32).collect();
+        let clip = Arc::new(AudioClip::from_samples(samples.clone(), 2, 48_000));
+        let mut source = ClipSource::new(clip);
+        assert_eq!((source.channels(), source.sample_rate()), (2, 48_000));
+
+        let mut out = Vec::new();
+        assert!(source.next_chunk(&mut out).unwrap());
+        assert_eq!(out.len(), CHUNK_FRAMES * 2);
+        assert!(source.next_chunk(&mut out).unwrap());
+        assert_eq!(out.len(), CHUNK_FRAMES * 4);
+        // The last chunk is short and ends the stream; reading on adds nothing.
+        assert!(!source.next_chunk(&mut out).unwrap());
+        assert_eq!(out, samples);
+        assert!(!source.next_chunk(&mut out).unwrap());
+        assert_eq!(out.len(), samples.len());
+
+        source.rewind().unwrap();
+        let mut again = Vec::new();
+        assert!(source.next_chunk(&mut again).unwrap());
+        assert_eq!(again, samples[..CHUNK_FRAMES * 2]);
+    }
+
+    #[test]
+    fn bad_input_is_an_error() {
+        assert!(matches!(
+            AudioClip::decode(int16_stereo(&[1, 2]), "mp3"),
+            Err(AudioError::UnsupportedFormat(extension)) if extension == "mp3"
+        ));
+
+        // Cut inside the header, and inside the samples.
+        let bytes = int16_stereo(&[1, 2, 3, 4, 5, 6]);
+        for length in [0, 20, bytes.len() - 3] {
+            assert!(
+                matches!(
+                    AudioClip::decode(&bytes[..length], "wav"),
+                    Err(AudioError::Decode(_))
+                ),
+                "{length} bytes decoded"
+            );
+        }
+        for extension in ["ogg", "flac"] {
+            assert!(matches!(
+                AudioClip::decode(&bytes[..20], extension),
+                Err(AudioError::Decode(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,650 @@
+use std::collections::VecDeque; // Decoded music frames waiting to be played.
+use std::sync::Arc; // Clips are shared between voices.
+use std::time::Duration; // Fade lengths.
+
+use super::decode::{AudioClip, SampleSource};
+
+/// Identifies a mixer bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BusId(usize);
+
+impl BusId {
+    pub const MASTER: BusId = BusId(0); // Everything ends up here.
+    pub const MUSIC: BusId = BusId(1); // Default bus for music.
+    pub const EFFECTS: BusId = BusId(2); // Default bus for sound effects.
+}
+
+/// A group of sounds sharing a volume, e.g. for the music and effects sliders of an options menu.
+#[derive(Debug, Clone)]
+pub struct Bus {
+    pub name: String,          // Used to look the bus up by name.
+    pub volume: f32,           // Linear gain applied to everything on the bus.
+    pub muted: bool,           // Silences the bus without losing its volume.
+    pub parent: Option<BusId>, // The bus this one feeds into; `None` only for the master bus.
+}
+
+/// Identifies a playing one-shot sound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VoiceId(u64);
+
+/// How to play a one-shot sound.
+#[derive(Debug, Clone, Copy)]
+pub struct PlaySettings {
+    pub volume: f32,   // Linear gain.
+    pub pitch: f32,    // Playback speed; 2.0 is an octave up.
+    pub pan: f32,      // -1.0 is fully left, 1.0 fully right.
+    pub bus: BusId,    // The bus the sound plays on.
+    pub looping: bool, // Whether to start over at the end instead of stopping.
+}
+
+impl Default for PlaySettings {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            pitch: 1.0,
+            pan: 0.0,
+            bus: BusId::EFFECTS,
+            looping: false,
+        }
+    }
+}
+
+/// How to play a music track.
+#[derive(Debug, Clone, Copy)]
+pub struct MusicSettings {
+    pub volume: f32,         // Linear gain.
+    pub looping: bool,       // Whether to start over at the end.
+    pub crossfade: Duration, // The new track fades in and the old one fades out over this time.
+    pub bus: BusId,          // The bus the music plays on.
+}
+
+impl Default for MusicSettings {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            looping: true,
+            crossfade: Duration::ZERO,
+            bus: BusId::MUSIC,
+        }
+    }
+}
+
+// A linear volume ramp, counted in output frames.
+#[derive(Debug, Clone, Copy)]
+struct Fade {
+    from: f32,
+    to: f32,
+    elapsed: u64,
+    length: u64,
+}
+
+impl Fade {
+    fn gain(&self) -> f32 {
+        if self.elapsed >= self.length {
+            self.to
+        } else {
+            self.from + (self.to - self.from) * self.elapsed as f32 / self.length as f32
+        }
+    }
+
+    fn advance(&mut self) {
+        self.elapsed = (self.elapsed + 1).min(self.length);
+    }
+
+    fn is_finished(&self) -> bool {
+        self.elapsed >= self.length
+    }
+}
+
+// A one-shot sound being played.
+struct Voice {
+    id: VoiceId,
+    clip: Arc<AudioClip>,
+    settings: PlaySettings,
+    position: f64, // Current frame in the clip, fractional because of resampling.
+}
+
+// A music track being streamed.
+struct MusicTrack {
+    source: Box<dyn SampleSource>,
+    frames: VecDeque<(f32, f32)>, // Decoded frames; the front one is at `floor(position)`.
+    scratch: Vec<f32>,            // Reused buffer for decoding.
+    position: f64,                // Fractional position between the first two buffered frames.
+    settings: MusicSettings,
+    fade: Fade,
+    stopping: bool, // Removed once the fade reaches silence.
+    ended: bool,    // The source has no more samples.
+}
+
+impl MusicTrack {
+    // Makes sure the two frames around the current position are decoded.
+    fn fill(&mut self) {
+        while self.frames.len() < 2 && !self.ended {
+            self.scratch.clear();
+            let more = match self.source.next_chunk(&mut self.scratch) {
+                Ok(more) => more,
+                Err(error) => {
+                    log::error!("Music stream failed: {error}");
+                    false
+                }
+            };
+            let channels = self.source.channels().max(1) as usize;
+            self.frames.extend(
+                self.scratch
+                    .chunks_exact(channels)
+                    .map(|frame| match frame {
+                        [mono] => (*mono, *mono),
+                        [left, right, ..] => (*left, *right),
+                        [] => unreachable!(),
+                    }),
+            );
+            if !more {
+                if self.settings.looping && self.source.rewind().is_ok() {
+                    // Guard against empty streams, which would otherwise loop forever.
+                    if self.scratch.is_empty() && self.frames.is_empty() {
+                        self.ended = true;
+                    }
+                } else {
+                    self.ended = true;
+                }
+            }
+        }
+    }
+}
+
+/// Mixes sound effects and music into an interleaved stereo buffer.
+///
+/// The mixer knows nothing about audio devices: an output backend (or a test) asks it to `render`
+/// as many frames as it needs.
+pub struct Mixer {
+    sample_rate: u32,       // Output frames per second.
+    buses: Vec<Bus>,        // Indexed by `BusId`.
+    voices: Vec<Voice>,     // One-shot sounds currently playing.
+    music: Vec<MusicTrack>, // The current track, plus any that are fading out.
+    next_voice: u64,        // Id given to the next voice.
+}
+
+impl Mixer {
+    /// Creates a mixer with the master, music and effects buses.
+    pub fn new(sample_rate: u32) -> Self {
+        let bus = |name: &str, parent| Bus {
+            name: name.to_string(),
+            volume: 1.0,
+            muted: false,
+            parent,
+        };
+        Self {
+            sample_rate,
+            buses: vec![
+                bus("master", None),
+                bus("music", Some(BusId::MASTER)),
+                bus("effects", Some(BusId::MASTER)),
+            ],
+            voices: Vec::new(),
+            music: Vec::new(),
+            next_voice: 0,
+        }
+    }
+
+    /// Output frames per second.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Adds a bus feeding into `parent` (e.g. "voice" under "effects").
+    pub fn add_bus(&mut self, name: &str, parent: BusId) -> BusId {
+        self.buses.push(Bus {
+            name: name.to_string(),
+            volume: 1.0,
+            muted: false,
+            parent: Some(parent),
+        });
+        BusId(self.buses.len() - 1)
+    }
+
+    /// Looks a bus up by name.
+    pub fn find_bus(&self, name: &str) -> Option<BusId> {
+        self.buses
+            .iter()
+            .position(|bus| bus.name == name)
+            .map(BusId)
+    }
+
+    pub fn bus(&self, id: BusId) -> Option<&Bus> {
+        self.buses.get(id.0)
+    }
+
+    pub fn bus_mut(&mut self, id: BusId) -> Option<&mut Bus> {
+        self.buses.get_mut(id.0)
+    }
+
+    /// Sets the volume of a bus.
+    pub fn set_bus_volume(&mut self, id: BusId, volume: f32) {
+        if let Some(bus) = self.bus_mut(id) {
+            bus.volume = volume.max(0.0);
+        }
+    }
+
+    /// Mutes or unmutes a bus.
+    pub fn set_bus_muted(&mut self, id: BusId, muted: bool) {
+        if let Some(bus) = self.bus_mut(id) {
+            bus.muted = muted;
+        }
+    }
+
+    /// The gain of a bus including every bus it feeds into.
+    ///
+    /// The chain of parents stops at the first bus seen before, so buses whose parents make a loop
+    /// only count each bus of the loop once.
+    pub fn bus_gain(&self, id: BusId) -> f32 {
+        let mut gain = 1.0;
+        let mut visited = Vec::new();
+        let mut current = Some(id);
+        while let Some(id) = current.filter(|id| !visited.contains(id)) {
+            let Some(bus) = self.bus(id) else {
+                break;
+            };
+            if bus.muted {
+                return 0.0;
+            }
+            gain *= bus.volume;
+            visited.push(id);
+            current = bus.parent;
+        }
+        gain
+    }
+
+    /// Starts playing a one-shot sound.
+    pub fn play(&mut self, clip: Arc<AudioClip>, settings: PlaySettings) -> VoiceId {
+        let id = VoiceId(self.next_voice);
+        self.next_voice += 1;
+        self.voices.push(Voice {
+            id,
+            clip,
+            settings,
+            position: 0.0,
+        });
+        id
+    }
+
+    /// Stops a one-shot sound.
+    pub fn stop(&mut self, id: VoiceId) {
+        self.voices.retain(|voice| voice.id != id);
+    }
+
+    /// Stops every one-shot sound.
+    pub fn stop_all(&mut self) {
+        self.voices.clear();
+    }
+
+    /// Returns true if the sound is still playing.
+    pub fn is_playing(&self, id: VoiceId) -> bool {
+        self.voices.iter().any(|voice| voice.id == id)
+    }
+
+    /// The settings of a playing sound, to change its volume, pitch or pan while it plays.
+    pub fn voice_settings_mut(&mut self, id: VoiceId) -> Option<&mut PlaySettings> {
+        self.voices
+            .iter_mut()
+            .find(|voice| voice.id == id)
+            .map(|voice| &mut voice.settings)
+    }
+
+    /// The number of one-shot sounds playing.
+    pub fn voice_count(&self) -> usize {
+        self.voices.len()
+    }
+
+    /// Starts a music track, cross-fading from the current one over `settings.crossfade`.
+    pub fn play_music(&mut self, source: Box<dyn SampleSource>, settings: MusicSettings) {
+        let length = self.frames_in(settings.crossfade);
+        self.fade_out_music(length);
+        self.music.push(MusicTrack {
+            source,
+            frames: VecDeque::new(),
+            scratch: Vec::new(),
+            position: 0.0,
+            settings,
+            fade: Fade {
+                from: 0.0,
+                to: 1.0,
+                elapsed: 0,
+                length,
+            },
+            stopping: false,
+            ended: false,
+        });
+    }
+
+    /// Fades the current music out over `fade` and stops it.
+    pub fn stop_music(&mut self, fade: Duration) {
+        let length = self.frames_in(fade);
+        self.fade_out_music(length);
+    }
+
+    /// Returns true if a music track is playing and not fading out.
+    pub fn is_music_playing(&self) -> bool {
+        self.music.iter().any(|track| !track.stopping)
+    }
+
+    /// Sets the volume of the current music track.
+    pub fn set_music_volume(&mut self, volume: f32) {
+        for track in self.music.iter_mut().filter(|track| !track.stopping) {
+            track.settings.volume = volume;
+        }
+    }
+
+    /// Mixes the next `output.len() / 2` frames into `output` as interleaved stereo, replacing its contents.
+    pub fn render(&mut self, output: &mut [f32]) {
+        output.fill(0.0);
+        let gains: Vec<f32> = (0..self.buses.len())
+            .map(|bus| self.bus_gain(BusId(bus)))
+            .collect();
+        let bus_gain = |bus: BusId| gains.get(bus.0).copied().unwrap_or(0.0);
+
+        // One-shot sounds.
+        let sample_rate = self.sample_rate;
+        self.voices.retain_mut(|voice| {
+            let clip = &voice.clip;
+            let frames = clip.frames();
+            if frames == 0 {
+                return false;
+            }
+            let step =
+                voice.settings.pitch.max(0.0) as f64 * clip.sample_rate as f64 / sample_rate as f64;
+            let (pan_left, pan_right) = pan_gains(voice.settings.pan, clip.channels);
+            let gain = voice.settings.volume * bus_gain(voice.settings.bus);
+
+            for out in output.chunks_exact_mut(2) {
+                if voice.position >= frames as f64 {
+                    if !voice.settings.looping {
+                        return false;
+                    }
+                    voice.position %= frames as f64;
+                }
+                let index = voice.position as usize;
+                let next = if index + 1 < frames {
+                    index + 1
+                } else if voice.settings.looping {
+                    0
+                } else {
+                    index
+                };
+                let (left, right) = lerp_frame(
+                    clip.frame(index),
+                    clip.frame(next),
+                    voice.position.fract() as f32,
+                );
+                out[0] += left * pan_left * gain;
+                out[1] += right * pan_right * gain;
+                voice.position += step;
+            }
+            voice.position < frames as f64 || voice.settings.looping
+        });
+
+        // Music.
+        for track in &mut self.music {
+            let step = track.source.sample_rate() as f64 / sample_rate as f64;
+            let gain = track.settings.volume * bus_gain(track.settings.bus);
+            for out in output.chunks_exact_mut(2) {
+                track.fill();
+                let Some(&first) = track.frames.front() else {
+                    break;
+                };
+                let second = track.frames.get(1).copied().unwrap_or(first);
+                let (left, right) = lerp_frame(first, second, track.position as f32);
+                let fade = track.fade.gain();
+                out[0] += left * gain * fade;
+                out[1] += right * gain * fade;
+                track.fade.advance();
+
+                track.position += step;
+                while track.position >= 1.0 && !track.frames.is_empty() {
+                    track.frames.pop_front();
+                    track.position -= 1.0;
+                    track.fill();
+                }
+            }
+        }
+        self.music.retain(|track| {
+            let silent = track.stopping && track.fade.is_finished();
+            let finished = track.ended && track.frames.is_empty();
+            !silent && !finished
+        });
+    }
+
+    // Starts fading every current track to silence over `length` frames.
+    fn fade_out_music(&mut self, length: u64) {
+        for track in self.music.iter_mut().filter(|track| !track.stopping) {
+            track.stopping = true;
+            track.fade = Fade {
+                from: track.fade.gain(),
+                to: 0.0,
+                elapsed: 0,
+                length,
+            };
+        }
+    }
+
+    // The number of output frames in `duration`.
+    fn frames_in(&self, duration: Duration) -> u64 {
+        (duration.as_secs_f64() * self.sample_rate as f64).round() as u64
+    }
+}
+
+/// Left and right gains for a pan position. Mono sources use an equal-power pan law so the sound is
+/// equally loud everywhere; stereo sources are balanced by turning down the opposite side.
+pub fn pan_gains(pan: f32, channels: u16) -> (f32, f32) {
+    let pan = pan.clamp(-1.0, 1.0);
+    if channels == 1 {
+        let angle = (pan + 1.0) * std::f32::consts::FRAC_PI_4;
+        (angle.cos(), angle.sin())
+    } else {
+        ((1.0 - pan).min(1.0), (1.0 + pan).min(1.0))
+    }
+}
+
+// Linear interpolation between two stereo frames.
+fn lerp_frame(a: (f32, f32), b: (f32, f32), t: f32) -> (f32, f32) {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+impl std::fmt::Debug for Mixer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Mixer")
+            .field("sample_rate", &self.sample_rate)
+            .field("buses", &self.buses)
+            .field("voices", &self.voices.len())
+            .field("music", &self.music.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::decode::ClipSource;
+    use crate::audio::Audio;
+
+    const RATE: u32 = 100; // One output frame per clip frame, and round fade lengths.
+
+    // A clip holding the same stereo frame throughout.
+    fn constant(value: f32, frames: usize) -> Arc<AudioClip> {
+        Arc::new(AudioClip::from_samples(vec![value; frames * 2], 2, RATE))
+    }
+
+    fn left(output: &[f32], frame: usize) -> f32 {
+        output[frame * 2]
+    }
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn bus_volumes_multiply_down_to_master() {
+        let audio = Audio::null(RATE);
+        audio.play(&constant(1.0, 100));
+        assert_near(left(&audio.render(1), 0), 1.0);
+
+        audio.mixer().set_bus_volume(BusId::EFFECTS, 0.5);
+        audio.mixer().set_bus_volume(BusId::MASTER, 0.5);
+        let output = audio.render(1);
+        assert_near(output[0], 0.25);
+        assert_near(output[1], 0.25);
+
+        let voice = audio.mixer().add_bus("voice", BusId::EFFECTS);
+        audio.mixer().set_bus_volume(voice, 0.5);
+        assert_near(audio.mixer().bus_gain(voice), 0.125);
+        assert_eq!(audio.mixer().find_bus("voice"), Some(voice));
+    }
+
+    #[test]
+    fn muted_buses_silence_everything_below_them() {
+        let audio = Audio::null(RATE);
+        let id = audio.play(&constant(1.0, 100));
+        let voice = audio.mixer().add_bus("voice", BusId::EFFECTS);
+
+        audio.mixer().set_bus_muted(BusId::MASTER, true);
+        assert_near(left(&audio.render(1), 0), 0.0);
+        assert_eq!(audio.mixer().bus_gain(voice), 0.0);
+        assert!(audio.mixer().is_playing(id), "muted sounds keep playing");
+
+        audio.mixer().set_bus_muted(BusId::MASTER, false);
+        audio.mixer().set_bus_muted(BusId::MUSIC, true);
+        assert_near(left(&audio.render(1), 0), 1.0);
+    }
+
+    #[test]
+    fn bus_parent_loops_end() {
+        let mut mixer = Mixer::new(RATE);
+        let a = mixer.add_bus("a", BusId::MASTER);
+        let b = mixer.add_bus("b", a);
+        mixer.bus_mut(a).unwrap().parent = Some(b);
+        mixer.set_bus_volume(a, 0.5);
+        mixer.set_bus_volume(b, 0.5);
+
+        let c = mixer.add_bus("c", b);
+        mixer.set_bus_volume(c, 0.8);
+
+        // Six buses, but the loop is only two of them, each counted once.
+        assert_eq!(mixer.buses.len(), 6);
+        assert_near(mixer.bus_gain(b), 0.25);
+        assert_near(mixer.bus_gain(a), 0.25);
+        assert_near(mixer.bus_gain(c), 0.2);
+        mixer.bus_mut(b).unwrap().parent = Some(b);
+        assert_near(mixer.bus_gain(b), 0.5);
+        assert_near(mixer.bus_gain(a), 0.25);
+    }
+
+    #[test]
+    fn pan_laws() {
+        let (left, right) = pan_gains(0.0, 1);
+        assert_near(left, std::f32::consts::FRAC_1_SQRT_2);
+        assert_near(right, std::f32::consts::FRAC_1_SQRT_2);
+        for pan in [-1.0, -0.3, 0.0, 0.6, 1.0] {
+            let (left, right) = pan_gains(pan, 1);
+            assert_near(left * left + right * right, 1.0);
+        }
+        let (left, right) = pan_gains(-1.0, 1);
+        assert_near(left, 1.0);
+        assert_near(right, 0.0);
+
+        assert_eq!(pan_gains(0.0, 2), (1.0, 1.0));
+        assert_eq!(pan_gains(0.5, 2), (0.5, 1.0));
+        assert_eq!(pan_gains(-1.0, 2), (1.0, 0.0));
+        assert_eq!(pan_gains(5.0, 2), (0.0, 1.0));
+    }
+
+    #[test]
+    fn panned_voices_play_on_one_side() {
+        let audio = Audio::null(RATE);
+        let settings = PlaySettings {
+            pan: 1.0,
+            ..Default::default()
+        };
+        audio.mixer().play(constant(1.0, 10), settings);
+        let output = audio.render(1);
+        assert_near(output[0], 0.0);
+        assert_near(output[1], 1.0);
+    }
+
+    #[test]
+    fn voices_end_unless_looping() {
+        let audio = Audio::null(RATE);
+        let once = audio.play(&constant(1.0, 4));
+        let looping = audio.mixer().play(
+            constant(0.5, 4),
+            PlaySettings {
+                looping: true,
+                ..Default::default()
+            },
+        );
+
+        let output = audio.render(10);
+        for frame in 0..4 {
+            assert_near(left(&output, frame), 1.5);
+        }
+        for frame in 4..10 {
+            assert_near(left(&output, frame), 0.5);
+        }
+        assert!(!audio.mixer().is_playing(once));
+        assert!(audio.mixer().is_playing(looping));
+        assert_eq!(audio.mixer().voice_count(), 1);
+    }
+
+    #[test]
+    fn stopped_voices_fall_silent() {
+        let audio = Audio::null(RATE);
+        let id = audio.play(&constant(1.0, 100));
+        audio.play(&constant(0.25, 100));
+        audio.render(10);
+
+        audio.mixer().stop(id);
+        assert!(!audio.mixer().is_playing(id));
+        assert_near(left(&audio.render(1), 0), 0.25);
+
+        audio.mixer().stop_all();
+        assert_eq!(audio.mixer().voice_count(), 0);
+        assert_near(left(&audio.render(1), 0), 0.0);
+    }
+
+    #[test]
+    fn music_crossfades_between_tracks() {
+        let audio = Audio::null(RATE);
+        let music = |value| Box::new(ClipSource::new(constant(value, 30)));
+        audio
+            .mixer()
+            .play_music(music(1.0), MusicSettings::default());
+        let output = audio.render(50);
+        assert_near(left(&output, 0), 1.0);
+        assert_near(left(&output, 49), 1.0); // Looped past the end of the 30 frame clip.
+
+        // Fade from the first track to the second over a second, i.e. 100 frames.
+        let settings = MusicSettings {
+            crossfade: Duration::from_secs(1),
+            ..Default::default()
+        };
+        audio.mixer().play_music(music(0.5), settings);
+        let output = audio.render(100);
+        assert_near(left(&output, 0), 1.0);
+        assert_near(left(&output, 50), 1.0 * 0.5 + 0.5 * 0.5);
+        assert_near(left(&output, 99), 1.0 * 0.01 + 0.5 * 0.99);
+
+        let output = audio.render(10);
+        assert_near(left(&output, 0), 0.5);
+        assert!(audio.mixer().is_music_playing());
+
+        audio.mixer().set_bus_volume(BusId::MUSIC, 0.5);
+        assert_near(left(&audio.render(1), 0), 0.25);
+
+        audio.mixer().stop_music(Duration::from_millis(100));
+        assert!(!audio.mixer().is_music_playing());
+        let output = audio.render(20);
+        assert_near(left(&output, 5), 0.25 * 0.5);
+        assert_near(left(&output, 15), 0.0);
+    }
+}
@@ -0,0 +1,157 @@
+//! Sound effects and music.
+//!
+//! `decode` turns WAV, Ogg Vorbis and FLAC files into samples, the `Mixer` combines playing sounds
+//! into one stereo signal, and `Audio` connects the mixer to an output device. Without the
+//! `audio-output` feature (or without a sound card) the null output is used, which only produces
+//! samples when `Audio::render` is called. `Emitter` and `Listener` place sounds in the 3D scene.
+
+pub mod decode;
+mod mixer;
+mod spatial;
+
+use std::sync::{Arc, Mutex, MutexGuard}; // The mixer is shared with the audio thread.
+
+pub use decode::{AudioClip, SampleSource};
+pub use mixer::{pan_gains, Bus, BusId, Mixer, MusicSettings, PlaySettings, VoiceId};
+pub use spatial::{Attenuation, Emitter, Listener, Spatialized, SPEED_OF_SOUND};
+
+/// Sample rate used by the null output.
+pub const DEFAULT_SAMPLE_RATE: u32 = 48_000;
+
+/// Plays audio through an output device.
+pub struct Audio {
+    mixer: Arc<Mutex<Mixer>>, // Shared with the output callback.
+    output: Output,           // Keeps the device stream alive.
+}
+
+// Where mixed samples go.
+enum Output {
+    Null, // Nowhere; samples are produced only when `render` is called.
+    #[cfg(feature = "audio-output")]
+    Device(cpal::Stream), // The default output device of the system.
+}
+
+impl Audio {
+    /// Opens the default output device, falling back to the null output if there is none.
+    pub fn new() -> Self {
+        #[cfg(feature = "audio-output")]
+        match Self::open_device() {
+            Ok(audio) => return audio,
+            Err(error) => log::warn!("No audio output, sound is disabled: {error}"),
+        }
+        Self::null(DEFAULT_SAMPLE_RATE)
+    }
+
+    /// Creates audio that is not connected to a device; call `render` to pull samples out of it.
+    pub fn null(sample_rate: u32) -> Self {
+        Self {
+            mixer: Arc::new(Mutex::new(Mixer::new(sample_rate))),
+            output: Output::Null,
+        }
+    }
+
+    /// Locks the mixer to play sounds or change volumes.
+    pub fn mixer(&self) -> MutexGuard<'_, Mixer> {
+        self.mixer.lock().expect("audio thread panicked")
+    }
+
+    /// Returns true if a real output device is playing the mixed audio.
+    pub fn has_device(&self) -> bool {
+        !matches!(self.output, Output::Null)
+    }
+
+    /// Mixes the next `frames` frames and returns them as interleaved stereo samples.
+    ///
+    /// Meant for the null output; with a device the samples would be taken away from it.
+    pub fn render(&self, frames: usize) -> Vec<f32> {
+        let mut output = vec![0.0; frames * 2];
+        self.mixer().render(&mut output);
+        output
+    }
+
+    /// Plays a one-shot sound on the effects bus with default settings.
+    pub fn play(&self, clip: &Arc<AudioClip>) -> VoiceId {
+        self.mixer().play(clip.clone(), PlaySettings::default())
+    }
+
+    #[cfg(feature = "audio-output")]
+    fn open_device() -> Result<Self, AudioError> {
+        use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+        let backend = |error: &dyn std::fmt::Display| AudioError::Backend(error.to_string());
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or_else(|| AudioError::Backend("no output device".to_string()))?;
+        let supported = device.default_output_config().map_err(|e| backend(&e))?;
+        if supported.sample_format() != cpal::SampleFormat::F32 {
+            return Err(AudioError::Backend(format!(
+                "unsupported sample format {}",
+                supported.sample_format()
+            )));
+        }
+        let config: cpal::StreamConfig = supported.into();
+        let channels = config.channels as usize;
+        let mixer = Arc::new(Mutex::new(Mixer::new(config.sample_rate.0)));
+
+        let callback_mixer = mixer.clone();
+        let mut stereo = Vec::new();
+        let stream = device
+            .build_output_stream(
+                &config,
+                move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                    // Mix in stereo, then spread over however many channels the device has.
+                    let frames = data.len() / channels;
+                    stereo.resize(frames * 2, 0.0);
+                    if let Ok(mut mixer) = callback_mixer.lock() {
+                        mixer.render(&mut stereo);
+                    }
+                    for (out, frame) in data.chunks_exact_mut(channels).zip(stereo.chunks_exact(2))
+                    {
+                        for (channel, sample) in out.iter_mut().enumerate() {
+                            *sample = match channels {
+                                1 => (frame[0] + frame[1]) * 0.5,
+                                _ => frame.get(channel).copied().unwrap_or(0.0),
+                            };
+                        }
+                    }
+                },
+                |error| log::error!("Audio stream error: {error}"),
+                None,
+            )
+            .map_err(|e| backend(&e))?;
+        stream.play().map_err(|e| backend(&e))?;
+
+        Ok(Self {
+            mixer,
+            output: Output::Device(stream),
+        })
+    }
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Errors that can occur while decoding or playing audio.
+#[derive(Debug)]
+pub enum AudioError {
+    UnsupportedFormat(String), // The file extension is not a known format.
+    Decode(String),            // The file contents were invalid.
+    Backend(String),           // The output device could not be opened.
+}
+
+impl std::fmt::Display for AudioError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AudioError::UnsupportedFormat(extension) => {
+                write!(f, "unsupported audio format \"{extension}\"")
+            }
+            AudioError::Decode(message) => write!(f, "failed to decode audio: {message}"),
+            AudioError::Backend(message) => write!(f, "audio output failed: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for AudioError {}
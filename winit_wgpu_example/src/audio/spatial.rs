@@ -0,0 +1,440 @@
+use nalgebra_glm as glm; // Positions and transforms come from the scene's matrices.
+
+use super::decode::AudioClip;
+use super::mixer::{pan_gains, Mixer, PlaySettings, VoiceId};
+
+/// Speed of sound in world units (metres) per second, used for the Doppler shift.
+pub const SPEED_OF_SOUND: f32 = 343.0;
+
+/// How the volume of a sound falls off with distance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Attenuation {
+    /// Always full volume, e.g. for sounds that should only be panned.
+    None,
+    /// Inverse distance: full volume up to `reference`, then `reference / (reference + rolloff * (d - reference))`.
+    /// This is the most natural sounding model.
+    Inverse { reference: f32, rolloff: f32 },
+    /// Linear: full volume up to `reference`, silent from `max` on.
+    Linear { reference: f32, max: f32 },
+    /// Exponential: full volume up to `reference`, then `(d / reference) ^ -rolloff`.
+    Exponential { reference: f32, rolloff: f32 },
+}
+
+impl Default for Attenuation {
+    fn default() -> Self {
+        Attenuation::Inverse {
+            reference: 1.0,
+            rolloff: 1.0,
+        }
+    }
+}
+
+impl Attenuation {
+    /// The volume at `distance` from the emitter, between 0.0 and 1.0.
+    pub fn gain(&self, distance: f32) -> f32 {
+        match *self {
+            Attenuation::None => 1.0,
+            Attenuation::Inverse { reference, rolloff } => {
+                let distance = distance.max(reference);
+                reference / (reference + rolloff * (distance - reference))
+            }
+            Attenuation::Linear { reference, max } => {
+                if distance <= reference {
+                    1.0
+                } else if distance >= max {
+                    0.0
+                } else {
+                    1.0 - (distance - reference) / (max - reference)
+                }
+            }
+            Attenuation::Exponential { reference, rolloff } => {
+                (distance.max(reference) / reference).powf(-rolloff)
+            }
+        }
+        .clamp(0.0, 1.0)
+    }
+}
+
+/// The ears of the scene, usually attached to the camera.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Listener {
+    pub position: glm::Vec3, // Where the listener is.
+    pub forward: glm::Vec3,  // The direction the listener faces.
+    pub up: glm::Vec3,       // The top of the listener's head.
+    pub velocity: glm::Vec3, // Units per second, for the Doppler shift.
+}
+
+impl Default for Listener {
+    fn default() -> Self {
+        Self {
+            position: glm::Vec3::zeros(),
+            forward: glm::Vec3::z(), // The scene uses left-handed coordinates, looking down +Z.
+            up: glm::Vec3::y(),
+            velocity: glm::Vec3::zeros(),
+        }
+    }
+}
+
+impl Listener {
+    /// A listener placed like a camera with the given view matrix (as made by `look_at_lh`).
+    pub fn from_view(view: &glm::Mat4) -> Self {
+        let camera = glm::inverse(view);
+        Self {
+            position: camera.column(3).xyz(),
+            forward: camera.column(2).xyz().normalize(),
+            up: camera.column(1).xyz().normalize(),
+            velocity: glm::Vec3::zeros(),
+        }
+    }
+
+    /// Moves the listener to a camera's new view, deriving its velocity from the distance moved.
+    pub fn follow_view(&mut self, view: &glm::Mat4, delta_time: f32) {
+        let next = Self::from_view(view);
+        if delta_time > 0.0 {
+            self.velocity = (next.position - self.position) / delta_time;
+        }
+        self.position = next.position;
+        self.forward = next.forward;
+        self.up = next.up;
+    }
+
+    /// The direction to the listener's right.
+    pub fn right(&self) -> glm::Vec3 {
+        // In left-handed coordinates, up × forward points right.
+        glm::cross(&self.up, &self.forward).normalize()
+    }
+}
+
+/// The volume, pan and pitch a sound should play with, given where it is relative to the listener.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spatialized {
+    pub gain: f32,  // Distance attenuation times the emitter's volume.
+    pub pan: f32,   // -1.0 is fully left, 1.0 fully right.
+    pub pitch: f32, // Doppler shift times the emitter's pitch.
+}
+
+impl Spatialized {
+    /// The final left and right gains for a source with `channels` channels.
+    pub fn channel_gains(&self, channels: u16) -> (f32, f32) {
+        let (left, right) = pan_gains(self.pan, channels);
+        (left * self.gain, right * self.gain)
+    }
+}
+
+/// A sound source in the scene, usually attached to an object's transform.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Emitter {
+    pub offset: glm::Vec3, // Where the sound comes from, in the object's local space.
+    pub position: glm::Vec3, // World position, updated by `follow`.
+    pub velocity: glm::Vec3, // World units per second, for the Doppler shift.
+    pub attenuation: Attenuation, // How the volume falls off with distance.
+    pub volume: f32,       // Volume before attenuation.
+    pub pitch: f32,        // Pitch before the Doppler shift.
+    pub doppler_factor: f32, // Scales the Doppler shift; 0.0 disables it.
+    pub voice: Option<VoiceId>, // The sound currently playing from this emitter.
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Self {
+            offset: glm::Vec3::zeros(),
+            position: glm::Vec3::zeros(),
+            velocity: glm::Vec3::zeros(),
+            attenuation: Attenuation::default(),
+            volume: 1.0,
+            pitch: 1.0,
+            doppler_factor: 1.0,
+            voice: None,
+        }
+    }
+}
+
+impl Emitter {
+    /// An emitter at a fixed world position.
+    pub fn at(position: glm::Vec3) -> Self {
+        Self {
+            position,
+            ..Default::default()
+        }
+    }
+
+    /// Moves the emitter with an object's model matrix, deriving its velocity from the distance moved.
+    pub fn follow(&mut self, model: &glm::Mat4, delta_time: f32) {
+        let position = (model * glm::vec4(self.offset.x, self.offset.y, self.offset.z, 1.0)).xyz();
+        if delta_time > 0.0 {
+            self.velocity = (position - self.position) / delta_time;
+        }
+        self.position = position;
+    }
+
+    /// Works out how the emitter sounds to `listener`.
+    pub fn spatialize(&self, listener: &Listener) -> Spatialized {
+        let to_emitter = self.position - listener.position;
+        let distance = to_emitter.norm();
+        let gain = self.volume * self.attenuation.gain(distance);
+        if distance <= f32::EPSILON {
+            // On top of the listener: no direction to pan towards or move along.
+            return Spatialized {
+                gain,
+                pan: 0.0,
+                pitch: self.pitch,
+            };
+        }
+
+        let direction = to_emitter / distance;
+        let pan = glm::dot(&direction, &listener.right()).clamp(-1.0, 1.0);
+
+        // Doppler shift: speeds are measured along the line from the emitter to the listener, and kept
+        // below the speed of sound so the formula stays finite.
+        let limit = SPEED_OF_SOUND * 0.9;
+        let to_listener = -direction;
+        let emitter_speed =
+            (glm::dot(&self.velocity, &to_listener) * self.doppler_factor).clamp(-limit, limit);
+        let listener_speed =
+            (glm::dot(&listener.velocity, &to_listener) * self.doppler_factor).clamp(-limit, limit);
+        let doppler = (SPEED_OF_SOUND - listener_speed) / (SPEED_OF_SOUND - emitter_speed);
+
+        Spatialized {
+            gain,
+            pan,
+            pitch: self.pitch * doppler,
+        }
+    }
+
+    /// Starts playing a clip from this emitter, replacing any sound it was already playing.
+    pub fn play(
+        &mut self,
+        mixer: &mut Mixer,
+        clip: std::sync::Arc<AudioClip>,
+        listener: &Listener,
+        looping: bool,
+    ) -> VoiceId {
+        if let Some(voice) = self.voice.take() {
+            mixer.stop(voice);
+        }
+        let spatialized = self.spatialize(listener);
+        let voice = mixer.play(
+            clip,
+            PlaySettings {
+                volume: spatialized.gain,
+                pitch: spatialized.pitch,
+                pan: spatialized.pan,
+                looping,
+                ..Default::default()
+            },
+        );
+        self.voice = Some(voice);
+        voice
+    }
+
+    /// Updates the volume, pan and pitch of the emitter's sound. Call once per frame after moving.
+    ///
+    /// Returns false (and forgets the voice) once the sound has finished.
+    pub fn apply(&mut self, listener: &Listener, mixer: &mut Mixer) -> bool {
+        let Some(voice) = self.voice else {
+            return false;
+        };
+        let spatialized = self.spatialize(listener);
+        match mixer.voice_settings_mut(voice) {
+            Some(settings) => {
+                settings.volume = spatialized.gain;
+                settings.pan = spatialized.pan;
+                settings.pitch = spatialized.pitch;
+                true
+            }
+            None => {
+                self.voice = None;
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    fn assert_gains(actual: (f32, f32), expected: (f32, f32)) {
+        assert_near(actual.0, expected.0);
+        assert_near(actual.1, expected.1);
+    }
+
+    // An emitter with no attenuation, so only the pan changes the gains.
+    fn emitter_at(x: f32, y: f32, z: f32) -> Emitter {
+        Emitter {
+            attenuation: Attenuation::None,
+            ..Emitter::at(glm::vec3(x, y, z))
+        }
+    }
+
+    #[test]
+    fn emitters_pan_towards_their_side() {
+        // The default listener stands at the origin facing +Z, so +X is to its right.
+        let listener = Listener::default();
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+
+        let left = emitter_at(-2.0, 0.0, 0.0).spatialize(&listener);
+        assert_near(left.pan, -1.0);
+        assert_gains(left.channel_gains(1), (1.0, 0.0));
+        assert_gains(left.channel_gains(2), (1.0, 0.0));
+
+        let right = emitter_at(2.0, 0.0, 0.0).spatialize(&listener);
+        assert_near(right.pan, 1.0);
+        assert_gains(right.channel_gains(1), (0.0, 1.0));
+        assert_gains(right.channel_gains(2), (0.0, 1.0));
+
+        let front = emitter_at(0.0, 0.0, 2.0).spatialize(&listener);
+        assert_near(front.pan, 0.0);
+        assert_gains(front.channel_gains(1), (half, half));
+        assert_gains(front.channel_gains(2), (1.0, 1.0));
+
+        let behind = emitter_at(0.0, 0.0, -2.0).spatialize(&listener);
+        assert_near(behind.pan, 0.0);
+
+        let on_top = emitter_at(0.0, 0.0, 0.0).spatialize(&listener);
+        assert_eq!(on_top.pan, 0.0);
+        assert_eq!(on_top.pitch, 1.0);
+    }
+
+    #[test]
+    fn distant_emitters_are_quieter() {
+        let listener = Listener::default();
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        let near = Emitter::at(glm::vec3(0.0, 0.0, 1.0)).spatialize(&listener);
+        let far = Emitter::at(glm::vec3(0.0, 0.0, 10.0)).spatialize(&listener);
+        assert_gains(near.channel_gains(1), (half, half));
+        assert_gains(far.channel_gains(1), (0.1 * half, 0.1 * half));
+
+        let quiet = Emitter {
+            volume: 0.5,
+            ..Emitter::at(glm::vec3(10.0, 0.0, 0.0))
+        };
+        assert_gains(quiet.spatialize(&listener).channel_gains(1), (0.0, 0.05));
+    }
+
+    #[test]
+    fn attenuation_models_fall_off() {
+        let none = Attenuation::None;
+        assert_eq!(none.gain(0.0), 1.0);
+        assert_eq!(none.gain(1000.0), 1.0);
+
+        let inverse = Attenuation::Inverse {
+            reference: 2.0,
+            rolloff: 1.0,
+        };
+        assert_near(inverse.gain(1.0), 1.0);
+        assert_near(inverse.gain(2.0), 1.0);
+        assert_near(inverse.gain(4.0), 0.5);
+        assert_near(inverse.gain(8.0), 0.25);
+
+        let linear = Attenuation::Linear {
+            reference: 1.0,
+            max: 5.0,
+        };
+        assert_near(linear.gain(0.5), 1.0);
+        assert_near(linear.gain(3.0), 0.5);
+        assert_near(linear.gain(5.0), 0.0);
+        assert_near(linear.gain(50.0), 0.0);
+
+        let exponential = Attenuation::Exponential {
+            reference: 1.0,
+            rolloff: 2.0,
+        };
+        assert_near(exponential.gain(0.5), 1.0);
+        assert_near(exponential.gain(2.0), 0.25);
+        assert_near(exponential.gain(4.0), 0.0625);
+
+        for model in [inverse, linear, exponential] {
+            let gains: Vec<f32> = (0..20).map(|d| model.gain(d as f32)).collect();
+            assert!(
+                gains.windows(2).all(|pair| pair[1] <= pair[0]),
+                "{model:?} gets louder with distance: {gains:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn doppler_raises_approaching_sounds_and_lowers_receding_ones() {
+        let listener = Listener::default();
+        let speed = SPEED_OF_SOUND * 0.1;
+        let moving = |velocity: glm::Vec3| Emitter {
+            velocity,
+            ..Emitter::at(glm::vec3(0.0, 0.0, 10.0))
+        };
+
+        let approaching = moving(glm::vec3(0.0, 0.0, -speed)).spatialize(&listener);
+        assert_near(approaching.pitch, 1.0 / 0.9);
+        let receding = moving(glm::vec3(0.0, 0.0, speed)).spatialize(&listener);
+        assert_near(receding.pitch, 1.0 / 1.1);
+        let passing = moving(glm::vec3(speed, 0.0, 0.0)).spatialize(&listener);
+        assert_near(passing.pitch, 1.0);
+
+        let listener_approaching = Listener {
+            velocity: glm::vec3(0.0, 0.0, speed),
+            ..Listener::default()
+        };
+        let still = moving(glm::Vec3::zeros()).spatialize(&listener_approaching);
+        assert_near(still.pitch, 1.1);
+
+        let disabled = Emitter {
+            doppler_factor: 0.0,
+            ..moving(glm::vec3(0.0, 0.0, -speed))
+        };
+        assert_near(disabled.spatialize(&listener).pitch, 1.0);
+
+        // Faster than sound is kept finite.
+        let supersonic = moving(glm::vec3(0.0, 0.0, -SPEED_OF_SOUND * 2.0)).spatialize(&listener);
+        assert!(supersonic.pitch.is_finite() && supersonic.pitch > 1.0);
+    }
+
+    #[test]
+    fn followed_emitters_update_their_voice() {
+        let listener = Listener::default();
+        let mut mixer = Mixer::new(100);
+        let clip = Arc::new(AudioClip::from_samples(vec![0.0; 100], 1, 100));
+        let mut emitter = Emitter {
+            offset: glm::vec3(0.0, 0.0, 10.0),
+            ..Default::default()
+        };
+        emitter.follow(&glm::Mat4::identity(), 0.0);
+        let voice = emitter.play(&mut mixer, clip, &listener, true);
+
+        // Move the emitter 1 unit closer in 0.1 seconds.
+        let model = glm::translation(&glm::vec3(0.0, 0.0, -1.0));
+        emitter.follow(&model, 0.1);
+        assert_near(emitter.velocity.z, -10.0);
+        assert!(emitter.apply(&listener, &mut mixer));
+
+        let settings = mixer.voice_settings_mut(voice).unwrap();
+        assert_near(settings.volume, 1.0 / 9.0);
+        assert_near(settings.pitch, SPEED_OF_SOUND / (SPEED_OF_SOUND - 10.0));
+
+        mixer.stop(voice);
+        assert!(!emitter.apply(&listener, &mut mixer));
+        assert_eq!(emitter.voice, None);
+    }
+
+    #[test]
+    fn listeners_face_like_the_camera() {
+        // The example scene's camera looks at the origin from +Z, so +X is on its left.
+        let view = glm::look_at_lh(
+            &glm::vec3(0.0, 0.0, 3.0),
+            &glm::Vec3::zeros(),
+            &glm::Vec3::y(),
+        );
+        let listener = Listener::from_view(&view);
+        assert_near(listener.position.z, 3.0);
+        assert_near(listener.forward.z, -1.0);
+        let pan = |x| emitter_at(x, 0.0, 0.0).spatialize(&listener).pan;
+        assert!(pan(1.0) < 0.0);
+        assert!(pan(-1.0) > 0.0);
+    }
+}
@@ -0,0 +1,57 @@
+//! Packs an assets folder into a single archive for shipping builds.
+//!
+//! Usage: `cargo run --bin pack_assets -- <assets dir> <output file> [--store] [--level <0-10>]`
+//!
+//! `--store` disables compression; `--level` sets the DEFLATE level (default 6). The archive is
+//! read back and verified after writing.
+
+use std::path::PathBuf;
+
+use winit_wgpu_example::assets::archive::Compression;
+use winit_wgpu_example::assets::{Archive, ArchiveBuilder};
+
+const USAGE: &str = "usage: pack_assets <assets dir> <output file> [--store] [--level <0-10>]";
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut paths = Vec::new();
+    let mut builder = ArchiveBuilder::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--store" => builder = builder.compress(false),
+            "--level" => {
+                let level = args.next().ok_or(USAGE)?.parse()?;
+                builder = builder.compression_level(level);
+            }
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    let [input, output] = <[PathBuf; 2]>::try_from(paths).map_err(|_| USAGE)?;
+
+    let count = builder.add_directory(&input)?;
+    builder.write(&output)?;
+
+    // Read the archive back so a broken archive never ships.
+    let archive = Archive::open(&output)?;
+    let failed = archive.verify();
+    if !failed.is_empty() {
+        return Err(format!("{} entries failed verification: {failed:?}", failed.len()).into());
+    }
+
+    let (mut original, mut stored, mut compressed) = (0, 0, 0);
+    for (_, entry) in archive.entries() {
+        original += entry.size;
+        stored += entry.stored_size;
+        compressed += (entry.compression == Compression::Deflate) as usize;
+    }
+    println!(
+        "Packed {count} files from {} into {} ({original} -> {stored} bytes, {compressed} compressed)",
+        input.display(),
+        output.display(),
+    );
+    Ok(())
+}
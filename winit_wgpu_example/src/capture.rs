@@ -0,0 +1,442 @@
+use std::collections::VecDeque; // Copies waiting for the GPU, oldest first.
+use std::path::{Path, PathBuf}; // File paths for the written images.
+use std::sync::mpsc::{Receiver, SyncSender, TryRecvError}; // Used to hand frames between threads.
+use std::thread::JoinHandle; // Used to wait for the PNG thread to write the last frames.
+use std::time::Duration; // Used for the recording length.
+use web_time::{SystemTime, UNIX_EPOCH}; // Used for timestamped file names.
+
+// Default length of a frame-sequence recording.
+pub const DEFAULT_RECORDING_DURATION: Duration = Duration::from_secs(5);
+
+// Frames that can wait to be encoded before the render loop waits for the encoder to catch up.
+const MAX_QUEUED_FRAMES: usize = 8;
+
+/// Copies rendered frames from the surface texture into PNG files.
+///
+/// A single screenshot or a sequence of frames (for making GIFs/videos) can be captured. Copies are
+/// read back once the GPU has finished them, without waiting, and encoded on a single background
+/// thread so the render loop only waits when that thread falls `MAX_QUEUED_FRAMES` frames behind.
+pub struct FrameCapture {
+    output_dir: PathBuf, // Directory in which screenshots and recordings are saved.
+    screenshot_requested: bool, // True if the next frame should be saved as a screenshot.
+    recording: Option<Recording>, // The frame-sequence recording in progress, if any.
+    pending: Option<PendingCapture>, // A copy that has been encoded but not yet submitted.
+    mapping: VecDeque<MappingCapture>, // Submitted copies waiting to be read back, oldest first.
+    encoder: Option<PngThread>, // Writes frames as PNGs, once started.
+}
+
+// The background thread writing PNGs, and the queue feeding it.
+struct PngThread {
+    sender: SyncSender<EncodedFrame>, // Queues frames, waiting if `MAX_QUEUED_FRAMES` are queued.
+    handle: JoinHandle<()>,           // Finishes once the sender is dropped and the queue is empty.
+}
+
+// State of a frame-sequence recording.
+struct Recording {
+    directory: PathBuf,  // Directory receiving the numbered frames.
+    remaining: Duration, // Time left before the recording stops.
+    frame: u32,          // Number of the next frame to write.
+}
+
+// A surface copy that has been recorded into a command encoder and is waiting to be read back.
+struct PendingCapture {
+    buffer: wgpu::Buffer,      // CPU-mappable buffer receiving the texture copy.
+    width: u32,                // Width of the image in pixels.
+    height: u32,               // Height of the image in pixels.
+    padded_bytes_per_row: u32, // Row pitch in the buffer, padded to `COPY_BYTES_PER_ROW_ALIGNMENT`.
+    swap_red_blue: bool,       // True if the surface is BGRA and the channels must be swapped.
+    path: PathBuf,             // Where the PNG will be written.
+}
+
+// A submitted copy whose buffer is being mapped for reading.
+struct MappingCapture {
+    capture: PendingCapture, // The copy being read back.
+    mapped: Receiver<Result<(), wgpu::BufferAsyncError>>, // Receives the result of the mapping.
+}
+
+// A frame read back from the GPU, ready to be written as a PNG.
+struct EncodedFrame {
+    path: PathBuf,   // Where the PNG will be written.
+    width: u32,      // Width of the image in pixels.
+    height: u32,     // Height of the image in pixels.
+    pixels: Vec<u8>, // RGBA8 pixels without row padding.
+}
+
+impl FrameCapture {
+    /// Creates a capture helper that saves images in `output_dir`.
+    pub fn new(output_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            output_dir: output_dir.into(),
+            screenshot_requested: false,
+            recording: None,
+            pending: None,
+            mapping: VecDeque::new(),
+            encoder: None,
+        }
+    }
+
+    // Saves the next rendered frame as a PNG.
+    pub fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+
+    // Saves every rendered frame for the given duration into a new numbered directory.
+    pub fn start_recording(&mut self, duration: Duration) {
+        let directory = self.output_dir.join(format!("capture_{}", timestamp()));
+        log::info!(
+            "Recording frames to {} for {:?}",
+            directory.display(),
+            duration
+        );
+        self.recording = Some(Recording {
+            directory,
+            remaining: duration,
+            frame: 0,
+        });
+    }
+
+    // Stops the frame-sequence recording, if one is in progress.
+    pub fn stop_recording(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            log::info!("Stopped recording after {} frames", recording.frame);
+        }
+    }
+
+    // Returns true if a frame-sequence recording is in progress.
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Drops the requested screenshot and stops the recording, if either is due, because frames
+    /// cannot be captured for the given reason.
+    pub fn cancel(&mut self, reason: &str) {
+        if std::mem::take(&mut self.screenshot_requested) || self.recording.is_some() {
+            log::warn!("Cannot capture frames: {reason}");
+            self.stop_recording();
+        }
+    }
+
+    /// Records a copy of `texture` into `encoder` if a capture is due this frame.
+    ///
+    /// The texture must have been created with `COPY_SRC` usage.
+    pub fn copy_texture(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        delta_time: Duration, // Used to count down the recording duration.
+    ) {
+        // Writing files needs a background thread and a file system, neither of which the browser
+        // has.
+        if cfg!(target_arch = "wasm32") {
+            self.cancel("not supported in the browser");
+            return;
+        }
+
+        let swap_red_blue = match texture.format() {
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            format => {
+                self.cancel(&format!("unsupported surface format {format:?}"));
+                return;
+            }
+        };
+
+        let Some(path) = self.next_path(delta_time) else {
+            return; // Nothing to capture this frame.
+        };
+
+        let (width, height) = (texture.width(), texture.height());
+        let unpadded_bytes_per_row = width * 4; // 4 bytes per pixel (RGBA8/BGRA8).
+        let padded_bytes_per_row = unpadded_bytes_per_row
+            .div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        // Create a buffer the CPU can map, large enough for the padded rows.
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Buffer"),
+            size: padded_bytes_per_row as wgpu::BufferAddress * height as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // Copy the whole texture into the buffer.
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        self.pending = Some(PendingCapture {
+            buffer,
+            width,
+            height,
+            padded_bytes_per_row,
+            swap_red_blue,
+            path,
+        });
+    }
+
+    /// Starts reading back the copy recorded this frame, and writes any earlier copies the GPU has
+    /// finished as PNGs on a background thread.
+    ///
+    /// Call after the command buffer containing the copy has been submitted.
+    pub fn finish(&mut self, device: &wgpu::Device) {
+        // Map the buffer once the GPU has finished the copy.
+        if let Some(capture) = self.pending.take() {
+            let (sender, mapped) = std::sync::mpsc::channel();
+            capture
+                .buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    let _ = sender.send(result);
+                });
+            self.mapping.push_back(MappingCapture { capture, mapped });
+        }
+        if self.mapping.is_empty() {
+            return;
+        }
+
+        // Check for finished copies without waiting for the ones still in flight. They finish in
+        // the order they were submitted, so the frames of a recording are encoded in order.
+        device.poll(wgpu::Maintain::Poll);
+        self.read_back_mapped(false);
+    }
+
+    /// Waits for every copy still in flight, writes it out, and waits for the PNG thread to finish.
+    ///
+    /// Call before exiting, or the last frames of a screenshot or recording are lost. `Drop` on
+    /// the renderer does this.
+    pub fn flush(&mut self, device: &wgpu::Device) {
+        if !self.mapping.is_empty() {
+            device.poll(wgpu::Maintain::Wait);
+            self.read_back_mapped(true);
+        }
+        if let Some(PngThread { sender, handle }) = self.encoder.take() {
+            drop(sender); // Ends the thread's loop once the queued frames are written.
+            if handle.join().is_err() {
+                log::error!("The capture thread panicked, some frames may not have been saved");
+            }
+        }
+    }
+
+    // Encodes the copies that have been read back, oldest first, stopping at the first one still
+    // in flight unless `wait` is set.
+    fn read_back_mapped(&mut self, wait: bool) {
+        while let Some(mapping) = self.mapping.front() {
+            let result = if wait {
+                matches!(mapping.mapped.recv(), Ok(Ok(())))
+            } else {
+                match mapping.mapped.try_recv() {
+                    Err(TryRecvError::Empty) => break,
+                    Ok(result) => result.is_ok(),
+                    Err(TryRecvError::Disconnected) => false,
+                }
+            };
+            let capture = self
+                .mapping
+                .pop_front()
+                .expect("mapping is not empty")
+                .capture;
+            if !result {
+                log::error!(
+                    "Failed to map capture buffer for {}",
+                    capture.path.display()
+                );
+                continue;
+            }
+            let frame = read_back(capture);
+            self.encode(frame);
+        }
+    }
+
+    // Queues a frame for the PNG thread, starting the thread if needed. Waits if the queue is full.
+    fn encode(&mut self, frame: EncodedFrame) {
+        let encoder = self.encoder.get_or_insert_with(|| {
+            let (sender, receiver) = std::sync::mpsc::sync_channel(MAX_QUEUED_FRAMES);
+            let handle = std::thread::spawn(move || {
+                for frame in receiver.iter() {
+                    let EncodedFrame {
+                        path,
+                        width,
+                        height,
+                        pixels,
+                    } = frame;
+                    match write_png(&path, width, height, &pixels) {
+                        Ok(()) => log::info!("Saved {}", path.display()),
+                        Err(error) => log::error!("Failed to save {}: {error}", path.display()),
+                    }
+                }
+            });
+            PngThread { sender, handle }
+        });
+        if encoder.sender.send(frame).is_err() {
+            log::error!("The capture thread has stopped, no more frames will be saved");
+            self.encoder = None;
+        }
+    }
+
+    // Picks the file name for this frame's capture, or `None` if nothing should be captured.
+    fn next_path(&mut self, delta_time: Duration) -> Option<PathBuf> {
+        if let Some(recording) = self.recording.as_mut() {
+            if recording.remaining.is_zero() {
+                self.stop_recording();
+            } else {
+                recording.remaining = recording.remaining.saturating_sub(delta_time);
+                let path = recording
+                    .directory
+                    .join(format!("frame_{:05}.png", recording.frame));
+                recording.frame += 1;
+                self.screenshot_requested = false; // The recorded frame doubles as the screenshot.
+                return Some(path);
+            }
+        }
+
+        if std::mem::take(&mut self.screenshot_requested) {
+            return Some(
+                self.output_dir
+                    .join(format!("screenshot_{}.png", timestamp())),
+            );
+        }
+        None
+    }
+}
+
+// Copies the pixels out of a mapped capture buffer, stripping the row padding and converting them
+// to RGBA.
+fn read_back(capture: PendingCapture) -> EncodedFrame {
+    let row_len = (capture.width * 4) as usize;
+    let mut pixels = Vec::with_capacity(row_len * capture.height as usize);
+    {
+        let data = capture.buffer.slice(..).get_mapped_range();
+        for row in data.chunks(capture.padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..row_len]);
+        }
+    }
+    capture.buffer.unmap();
+    if capture.swap_red_blue {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+    EncodedFrame {
+        path: capture.path,
+        width: capture.width,
+        height: capture.height,
+        pixels,
+    }
+}
+
+// Encodes RGBA8 pixels as a PNG file, creating parent directories if needed.
+fn write_png(
+    path: &Path,
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    Ok(())
+}
+
+// Milliseconds since the Unix epoch, used to give captures unique, sortable names.
+fn timestamp() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(10);
+
+    fn file_name(path: &Path) -> &str {
+        path.file_name().and_then(|name| name.to_str()).unwrap()
+    }
+
+    #[test]
+    fn screenshots_are_taken_once_and_timestamped() {
+        let mut capture = FrameCapture::new("shots");
+        assert_eq!(capture.next_path(FRAME), None);
+
+        capture.request_screenshot();
+        let path = capture.next_path(FRAME).expect("a screenshot is due");
+        assert_eq!(path.parent(), Some(Path::new("shots")));
+        let name = file_name(&path);
+        let stamp = name
+            .strip_prefix("screenshot_")
+            .and_then(|rest| rest.strip_suffix(".png"))
+            .expect("screenshot_<timestamp>.png");
+        assert!(stamp.parse::<u128>().is_ok(), "{name}");
+        assert_eq!(capture.next_path(FRAME), None);
+    }
+
+    #[test]
+    fn recordings_number_frames_until_their_time_is_up() {
+        let mut capture = FrameCapture::new("shots");
+        capture.start_recording(3 * FRAME);
+        assert!(capture.is_recording());
+
+        let paths: Vec<PathBuf> = (0..3).map_while(|_| capture.next_path(FRAME)).collect();
+        let names: Vec<&str> = paths.iter().map(|path| file_name(path)).collect();
+        assert_eq!(
+            names,
+            ["frame_00000.png", "frame_00001.png", "frame_00002.png"]
+        );
+        let directory = paths[0].parent().unwrap();
+        assert!(paths.iter().all(|path| path.parent() == Some(directory)));
+        assert!(file_name(directory).starts_with("capture_"));
+        assert_eq!(directory.parent(), Some(Path::new("shots")));
+
+        // The time is up, so the next frame ends the recording instead of being captured.
+        assert!(capture.is_recording());
+        assert_eq!(capture.next_path(FRAME), None);
+        assert!(!capture.is_recording());
+    }
+
+    #[test]
+    fn a_long_frame_still_counts_towards_the_recording() {
+        let mut capture = FrameCapture::new("shots");
+        capture.start_recording(3 * FRAME);
+        assert!(capture.next_path(10 * FRAME).is_some());
+        assert_eq!(capture.next_path(FRAME), None);
+        assert!(!capture.is_recording());
+    }
+
+    #[test]
+    fn a_screenshot_during_a_recording_is_the_recorded_frame() {
+        let mut capture = FrameCapture::new("shots");
+        capture.start_recording(3 * FRAME);
+        capture.request_screenshot();
+        let path = capture.next_path(FRAME).unwrap();
+        assert_eq!(file_name(&path), "frame_00000.png");
+        capture.stop_recording();
+        assert_eq!(capture.next_path(FRAME), None);
+    }
+}
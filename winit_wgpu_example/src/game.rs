@@ -0,0 +1,129 @@
+use std::path::PathBuf; // Path of the settings file.
+use std::time::Duration; // Time elapsed between frames.
+
+use winit::{event::WindowEvent, window::Window, window::WindowId};
+
+use crate::app::{App, AppEvent}; // The application handler that drives the game.
+use crate::assets::{AssetServer, Vfs}; // Loads the game's assets.
+use crate::renderer::Frame; // Passed to the game to record its draw calls.
+use crate::window_config::WindowConfig; // Settings for the main window.
+
+// Where assets are read from when `RunConfig::assets` is `None`.
+pub const DEFAULT_ASSETS_PATH: &str = "assets";
+
+/// GPU and window handles available when a game is initialised.
+pub struct GameContext<'a> {
+    pub device: &'a wgpu::Device, // For creating buffers, textures and pipelines.
+    pub queue: &'a wgpu::Queue,   // For uploading initial data.
+    pub surface_format: wgpu::TextureFormat, // The format pipelines must render to.
+    pub window: &'a Window,       // The main window.
+    pub assets: &'a mut AssetServer, // For starting to load the game's assets.
+}
+
+/// The hooks a game implements to run on top of `App` and `Renderer`.
+pub trait Game: 'static {
+    /// Creates the game once the main window and GPU are ready.
+    fn init(context: &mut GameContext) -> Self
+    where
+        Self: Sized;
+
+    /// Advances the game state by `delta_time`. Called once per frame before `render`, after assets
+    /// that finished loading have been uploaded.
+    fn update(&mut self, _delta_time: Duration) {}
+
+    /// Records the draw calls for the main window. `frame.assets` draws loaded assets and starts
+    /// loading new ones.
+    fn render(&mut self, frame: &mut Frame);
+
+    /// Records the draw calls for an extra window (e.g. a tool window). Does nothing by default.
+    fn render_window(&mut self, _window_id: WindowId, _frame: &mut Frame) {}
+
+    /// Called for every window event, before the app handles it.
+    fn on_event(&mut self, _window_id: WindowId, _event: &WindowEvent) {}
+}
+
+/// Settings passed to `run`.
+///
+/// In the browser `assets` is ignored: the `assets/` folder is packed into the binary instead.
+#[derive(Debug, Clone, Default)]
+pub struct RunConfig {
+    pub window: WindowConfig,           // Settings for the main window.
+    pub settings_path: Option<PathBuf>, // Where window settings are saved on exit, if anywhere.
+    pub assets: Option<PathBuf>, // Directory or archive assets are read from; `assets` when `None`.
+}
+
+impl From<WindowConfig> for RunConfig {
+    fn from(window: WindowConfig) -> Self {
+        Self {
+            window,
+            settings_path: None,
+            assets: None,
+        }
+    }
+}
+
+// The `assets/` folder, packed by `build.rs`. The browser has no file system to read it from.
+#[cfg(target_arch = "wasm32")]
+const EMBEDDED_ASSETS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/assets.pak"));
+
+// Mounts the directory or archive at `path`, or `DEFAULT_ASSETS_PATH` when `None`.
+//
+// A missing asset folder only fails the loads from it, but an archive that cannot be read stops the
+// game before it starts.
+#[cfg(not(target_arch = "wasm32"))]
+fn mount_assets(path: Option<PathBuf>) -> std::io::Result<Vfs> {
+    let path = path.unwrap_or_else(|| DEFAULT_ASSETS_PATH.into());
+    let mut vfs = Vfs::new();
+    if path.is_file() {
+        vfs.mount_path(&path)?;
+    } else {
+        vfs.mount(crate::assets::DirectoryMount::new(path));
+    }
+    Ok(vfs)
+}
+
+// Mounts the assets embedded in the binary; `path` cannot be read in the browser.
+#[cfg(target_arch = "wasm32")]
+fn mount_assets(path: Option<PathBuf>) -> std::io::Result<Vfs> {
+    if let Some(path) = path {
+        log::warn!(
+            "Ignoring asset path {} in the browser, using the embedded assets",
+            path.display()
+        );
+    }
+    let archive = crate::assets::Archive::from_bytes(EMBEDDED_ASSETS.to_vec())?;
+    let mut vfs = Vfs::new();
+    vfs.mount(archive);
+    Ok(vfs)
+}
+
+/// Creates the event loop and runs the game `G` until its last window is closed.
+pub fn run<G: Game>(config: impl Into<RunConfig>) -> Result<(), Box<dyn std::error::Error>> {
+    let config = config.into();
+    let event_loop = winit::event_loop::EventLoop::<AppEvent>::with_user_event().build()?;
+    event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
+
+    let assets = AssetServer::with_vfs(mount_assets(config.assets)?);
+
+    let app = App::<G>::new(
+        event_loop.create_proxy(),
+        config.window,
+        config.settings_path,
+        assets,
+    );
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let mut app = app;
+        event_loop.run_app(&mut app)?;
+    }
+
+    // In the browser the event loop is driven by `requestAnimationFrame`, so this returns immediately.
+    #[cfg(target_arch = "wasm32")]
+    {
+        use winit::platform::web::EventLoopExtWebSys;
+        event_loop.spawn_app(app);
+    }
+
+    Ok(())
+}
@@ -1,13 +1,104 @@
-// Gpu struct: Encapsulates all the GPU-related components used for rendering with wgpu.
+use std::sync::Arc; // Arc lets several windows share one GpuContext.
+
+// GpuContext struct: The parts of the GPU setup that are shared by every window.
+pub struct GpuContext {
+    pub instance: wgpu::Instance, // The wgpu instance, used to create a surface for each window.
+    pub adapter: wgpu::Adapter,   // The physical GPU that was selected.
+    pub device: wgpu::Device,     // The device represents the GPU itself.
+    pub queue: wgpu::Queue,       // The queue to which rendering commands are submitted.
+}
+
+// Gpu struct: Encapsulates all the GPU-related components used for rendering to one window with wgpu.
 pub struct Gpu<'window> {
-    pub surface: wgpu::Surface<'window>,          // Surface for rendering. This connects to the window.
-    pub device: wgpu::Device,                     // The device represents the GPU itself.
-    pub queue: wgpu::Queue,                       // The queue to which rendering commands are submitted.
+    pub context: Arc<GpuContext>, // Device and queue, shared with other windows.
+    pub surface: wgpu::Surface<'window>, // Surface for rendering. This connects to the window.
     pub surface_config: wgpu::SurfaceConfiguration, // Configuration for the surface (screen size, format, etc.).
-    pub surface_format: wgpu::TextureFormat,      // The format in which the surface (screen) will be rendered.
+    pub surface_format: wgpu::TextureFormat, // The format in which the surface (screen) will be rendered.
+}
+
+/// Errors that can occur while creating a surface for another window.
+#[derive(Debug)]
+pub enum SurfaceError {
+    Create(wgpu::CreateSurfaceError), // The window could not be given a surface.
+    Unsupported,                      // The shared adapter cannot present to the surface.
+}
+
+impl std::fmt::Display for SurfaceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SurfaceError::Create(error) => write!(f, "failed to create surface: {error}"),
+            SurfaceError::Unsupported => write!(f, "the GPU cannot present to this window"),
+        }
+    }
+}
+
+impl std::error::Error for SurfaceError {}
+
+impl GpuContext {
+    // Asynchronous method to pick an adapter and create the device and queue.
+    pub async fn new_async(
+        instance: wgpu::Instance,    // The instance the surface was created from.
+        surface: &wgpu::Surface<'_>, // The adapter must be able to present to this surface.
+    ) -> Self {
+        // Request a suitable adapter for the GPU (device that can render to our surface).
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(), // Choose a GPU based on power preferences.
+                compatible_surface: Some(surface), // The adapter must be compatible with the surface.
+                force_fallback_adapter: false, // Don't force fallback if no suitable adapter is found.
+            })
+            .await
+            .expect("Failed to request adapter!");
+
+        // Request a device and queue from the adapter.
+        let (device, queue) = {
+            log::info!("WGPU Adapter Features: {:#?}", adapter.features());
+            // Enable timestamp queries when the adapter has them, so the profiler can time GPU passes.
+            let optional_features = adapter.features() & wgpu::Features::TIMESTAMP_QUERY;
+            // WebGL2 (used in browsers without WebGPU) supports fewer resources than other backends.
+            let base_limits = if adapter.get_info().backend == wgpu::Backend::Gl {
+                wgpu::Limits::downlevel_webgl2_defaults()
+            } else {
+                wgpu::Limits::downlevel_defaults()
+            };
+            // Request the device (actual GPU) with specific configurations.
+            adapter
+                .request_device(
+                    &wgpu::DeviceDescriptor {
+                        label: Some("WGPU Device"), // Label for the device (useful for debugging).
+                        required_features: optional_features, // Only optional features the adapter supports.
+                        required_limits: wgpu::Limits {
+                            max_texture_dimension_2d: 4096, // Support larger textures.
+                            ..base_limits                   // Defaults for lower-tier devices.
+                        },
+                        memory_hints: wgpu::MemoryHints::default(), // Memory management hints.
+                    },
+                    None, // No special extensions.
+                )
+                .await
+                .expect("Failed to request a device!")
+        };
+
+        Self {
+            instance,
+            adapter,
+            device,
+            queue,
+        }
+    }
 }
 
 impl<'window> Gpu<'window> {
+    // The device, shared by all windows.
+    pub fn device(&self) -> &wgpu::Device {
+        &self.context.device
+    }
+
+    // The queue, shared by all windows.
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.context.queue
+    }
+
     // Method to calculate the aspect ratio of the surface.
     pub fn aspect_ratio(&self) -> f32 {
         // Dividing the width by height. Prevent dividing by 0 by using `.max(1)` on height.
@@ -20,26 +111,28 @@ impl<'window> Gpu<'window> {
         self.surface_config.width = width;
         self.surface_config.height = height;
         // Reconfigure the surface to apply the new size.
-        self.surface.configure(&self.device, &self.surface_config);
+        self.surface
+            .configure(&self.context.device, &self.surface_config);
     }
 
     // Creates a depth texture used for depth buffering (useful for 3D rendering).
     pub fn create_depth_texture(&self, width: u32, height: u32) -> wgpu::TextureView {
         // Create a texture with depth format (Depth32Float). This is used for depth testing.
-        let texture = self.device.create_texture(
+        let texture = self.context.device.create_texture(
             &(wgpu::TextureDescriptor {
                 label: Some("Depth Texture"), // Label for debugging purposes.
                 size: wgpu::Extent3d {
-                    width, // Width of the texture (same as window size).
-                    height, // Height of the texture (same as window size).
+                    width,                    // Width of the texture (same as window size).
+                    height,                   // Height of the texture (same as window size).
                     depth_or_array_layers: 1, // We are creating a 2D texture, so depth is 1.
                 },
                 mip_level_count: 1, // No mipmaps, we are not using them here.
-                sample_count: 1, // No anti-aliasing.
+                sample_count: 1,    // No anti-aliasing.
                 dimension: wgpu::TextureDimension::D2, // 2D texture.
                 format: wgpu::TextureFormat::Depth32Float, // Format for depth.
-                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING, // Specify the texture usage.
-                view_formats: &[], // No alternative formats for the texture.
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING, // Specify the texture usage.
+                view_formats: &[],  // No alternative formats for the texture.
             }),
         );
 
@@ -49,18 +142,18 @@ impl<'window> Gpu<'window> {
             format: Some(wgpu::TextureFormat::Depth32Float), // Depth texture format.
             dimension: Some(wgpu::TextureViewDimension::D2), // 2D texture view.
             aspect: wgpu::TextureAspect::All, // View all aspects of the texture (i.e., the whole texture).
-            base_mip_level: 0, // Start from the first mip level (no mipmaps).
-            base_array_layer: 0, // Start from the first array layer.
-            array_layer_count: None, // No array layers.
-            mip_level_count: None, // No mipmap levels.
+            base_mip_level: 0,                // Start from the first mip level (no mipmaps).
+            base_array_layer: 0,              // Start from the first array layer.
+            array_layer_count: None,          // No array layers.
+            mip_level_count: None,            // No mipmap levels.
         })
     }
 
     // Asynchronous method to create a new Gpu instance (this is where most of the GPU setup happens).
     pub async fn new_async(
         window: impl Into<wgpu::SurfaceTarget<'window>>, // Input window (surface target for rendering).
-        width: u32,   // Width of the window (and surface).
-        height: u32,  // Height of the window (and surface).
+        width: u32,                                      // Width of the window (and surface).
+        height: u32,                                     // Height of the window (and surface).
     ) -> Self {
         // Step 1: Initialize wgpu instance (this is the entry point for using the GPU).
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
@@ -72,40 +165,49 @@ impl<'window> Gpu<'window> {
         // Step 2: Create the surface from the provided window.
         let surface = instance.create_surface(window).unwrap();
 
-        // Step 3: Request a suitable adapter for the GPU (device that can render to our surface).
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(), // Choose a GPU based on power preferences.
-                compatible_surface: Some(&surface), // The adapter must be compatible with the surface.
-                force_fallback_adapter: false, // Don't force fallback if no suitable adapter is found.
-            })
-            .await
-            .expect("Failed to request adapter!");
+        // Steps 3 and 4: Request an adapter, device and queue that can render to our surface.
+        let context = Arc::new(GpuContext::new_async(instance, &surface).await);
 
-        // Step 4: Request a device and queue from the adapter.
-        let (device, queue) = {
-            log::info!("WGPU Adapter Features: {:#?}", adapter.features());
-            // Request the device (actual GPU) with specific configurations.
-            adapter
-                .request_device(
-                    &wgpu::DeviceDescriptor {
-                        label: Some("WGPU Device"), // Label for the device (useful for debugging).
-                        required_features: wgpu::Features::default(), // No special GPU features required.
-                        required_limits: wgpu::Limits {
-                            max_texture_dimension_2d: 4096, // Support larger textures.
-                            ..wgpu::Limits::downlevel_defaults() // Defaults for lower-tier devices.
-                        },
-                        memory_hints: wgpu::MemoryHints::default(), // Memory management hints.
-                    },
-                    None, // No special extensions.
-                )
-                .await
-                .expect("Failed to request a device!")
-        };
+        // Steps 5 to 8: Configure the surface.
+        Self::from_surface(context, surface, width, height)
+            .expect("The adapter was chosen to present to this surface")
+    }
 
-        // Step 5: Get the surface capabilities (e.g., formats, present modes).
-        let surface_capabilities = surface.get_capabilities(&adapter);
+    // Creates a Gpu for another window, sharing the device and queue of an existing one.
+    //
+    // The adapter was chosen for the first window, and may not be able to present to this one
+    // (e.g. a window on a display driven by another GPU), in which case an error is returned.
+    pub fn with_context(
+        context: Arc<GpuContext>, // The shared device and queue.
+        window: impl Into<wgpu::SurfaceTarget<'window>>, // Input window (surface target for rendering).
+        width: u32,                                      // Width of the window (and surface).
+        height: u32,                                     // Height of the window (and surface).
+    ) -> Result<Self, SurfaceError> {
+        // Create the surface from the shared instance, so it can be used with the shared device.
+        let surface = context
+            .instance
+            .create_surface(window)
+            .map_err(SurfaceError::Create)?;
+        Self::from_surface(context, surface, width, height)
+    }
 
+    // Chooses a format and configures a newly created surface.
+    fn from_surface(
+        context: Arc<GpuContext>,
+        surface: wgpu::Surface<'window>,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, SurfaceError> {
+        // Step 5: Get the surface capabilities (e.g., formats, present modes).
+        // They are empty for a surface the adapter cannot present to.
+        let surface_capabilities = surface.get_capabilities(&context.adapter);
+        if !context.adapter.is_surface_supported(&surface)
+            || surface_capabilities.formats.is_empty()
+            || surface_capabilities.present_modes.is_empty()
+            || surface_capabilities.alpha_modes.is_empty()
+        {
+            return Err(SurfaceError::Unsupported);
+        }
         // Step 6: Choose a surface format that is compatible with the GPU and not sRGB (for non-color-managed rendering).
         let surface_format = surface_capabilities
             .formats
@@ -115,27 +217,29 @@ impl<'window> Gpu<'window> {
             .unwrap_or(surface_capabilities.formats[0]); // Fallback to the first format if no non-sRGB format is available.
 
         // Step 7: Set the surface configuration (defines how the surface behaves).
+        // `COPY_SRC` lets screenshots copy the surface texture into a buffer, where the platform allows it.
+        let surface_usage = wgpu::TextureUsages::RENDER_ATTACHMENT
+            | (surface_capabilities.usages & wgpu::TextureUsages::COPY_SRC);
         let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT, // The surface will be used for rendering.
+            usage: surface_usage, // The surface will be used for rendering (and copying, if supported).
             format: surface_format, // The surface format (selected above).
-            width,  // Set the surface width.
-            height, // Set the surface height.
+            width,                // Set the surface width.
+            height,               // Set the surface height.
             present_mode: surface_capabilities.present_modes[0], // The presentation mode (how frames are presented to the screen).
             alpha_mode: surface_capabilities.alpha_modes[0], // How alpha blending is handled (transparent or opaque).
-            view_formats: vec![], // No additional view formats.
+            view_formats: vec![],                            // No additional view formats.
             desired_maximum_frame_latency: 2, // Max frames to keep in-flight for low latency.
         };
 
         // Step 8: Configure the surface with the device and configuration.
-        surface.configure(&device, &surface_config);
+        surface.configure(&context.device, &surface_config);
 
         // Return the new GPU struct instance with all components initialized.
-        Self {
+        Ok(Self {
+            context,
             surface,
-            device,
-            queue,
             surface_config,
             surface_format,
-        }
+        })
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,20 @@
+//! A small game framework built on winit and wgpu.
+//!
+//! Implement [`Game`] and call [`run`] to open a window and start the game loop.
+
+pub mod app;
+pub mod assets;
+pub mod audio;
+pub mod capture;
+pub mod game;
+pub mod gpu;
+pub mod profiler;
+pub mod renderer;
+pub mod scene;
+pub mod uniform_binding;
+pub mod vertex;
+pub mod window_config;
+
+pub use crate::game::{run, Game, GameContext, RunConfig};
+pub use crate::renderer::Frame;
+pub use crate::window_config::WindowConfig;
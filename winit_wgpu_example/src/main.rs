@@ -1,16 +1,83 @@
-mod app;
-mod gpu;
-mod renderer;
-mod scene;
-mod uniform_binding;
-mod vertex;
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::app::App;
+use winit::window::WindowId;
+
+use winit_wgpu_example::audio::{Attenuation, Audio, AudioClip, Emitter, Listener};
+use winit_wgpu_example::scene::Scene;
+use winit_wgpu_example::window_config::DEFAULT_SETTINGS_PATH;
+use winit_wgpu_example::{run, Frame, Game, GameContext, RunConfig, WindowConfig};
+
+// The example game: a single rotating triangle that hums from its tip.
+struct ExampleGame {
+    scene: Scene,       // The triangle, its shader and its pipeline.
+    audio: Audio,       // Plays through the sound card with the `audio-output` feature.
+    listener: Listener, // Follows the camera.
+    hum: Emitter,       // Follows the first corner of the triangle as it rotates.
+}
+
+impl Game for ExampleGame {
+    fn init(context: &mut GameContext) -> Self {
+        let audio = Audio::new();
+        let listener = Listener::from_view(&Scene::view());
+        let mut hum = Emitter {
+            offset: nalgebra_glm::vec3(1.0, -1.0, 0.0), // The red corner.
+            attenuation: Attenuation::Inverse {
+                reference: 1.0,
+                rolloff: 1.0,
+            },
+            volume: 0.2,
+            ..Default::default()
+        };
+        let scene = Scene::new(context.device, context.assets);
+        hum.follow(&scene.model, 0.0);
+        hum.play(&mut audio.mixer(), Arc::new(hum_clip()), &listener, true);
+
+        Self {
+            scene,
+            audio,
+            listener,
+            hum,
+        }
+    }
+
+    fn update(&mut self, delta_time: Duration) {
+        let delta_time = delta_time.as_secs_f32();
+        self.scene.animate(delta_time);
+
+        // Keep the sound in sync with the moved corner and the camera.
+        self.listener.follow_view(&Scene::view(), delta_time);
+        self.hum.follow(&self.scene.model, delta_time);
+        self.hum.apply(&self.listener, &mut self.audio.mixer());
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        self.scene.upload(frame.queue, frame.aspect_ratio);
+        self.scene.render(frame);
+    }
+
+    // Tool windows show the same triangle.
+    fn render_window(&mut self, _window_id: WindowId, frame: &mut Frame) {
+        self.render(frame);
+    }
+}
+
+// One second of a soft 110 Hz tone that loops seamlessly.
+fn hum_clip() -> AudioClip {
+    let sample_rate = 48_000;
+    let samples = (0..sample_rate)
+        .map(|i| (i as f32 / sample_rate as f32 * 110.0 * std::f32::consts::TAU).sin() * 0.5)
+        .collect();
+    AudioClip::from_samples(samples, 1, sample_rate)
+}
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let event_loop = winit::event_loop::EventLoop::builder().build()?;
-    event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
-    let mut app = App::default();
-    event_loop.run_app(&mut app)?;
-    Ok(())
+    // Load the window settings saved by the previous run, if any.
+    let window_config = WindowConfig::load_or_default(DEFAULT_SETTINGS_PATH);
+    run::<ExampleGame>(RunConfig {
+        window: window_config,
+        // There is no file system to save settings to in the browser.
+        settings_path: (!cfg!(target_arch = "wasm32")).then(|| DEFAULT_SETTINGS_PATH.into()),
+        assets: None,
+    })
 }
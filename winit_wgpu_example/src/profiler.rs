@@ -0,0 +1,522 @@
+use std::collections::VecDeque; // Ring buffer used for the rolling frame history.
+use std::sync::atomic::{AtomicU8, Ordering}; // Result stored by the buffer-mapping callback.
+use std::sync::Arc; // Shared ownership of the result between the profiler and the callback.
+use std::time::Duration; // Lengths of scopes and frames.
+use web_time::Instant; // CPU-side timing (`std::time::Instant` panics in the browser).
+
+// Number of frames kept in the rolling history (about 4 seconds at 60 FPS).
+pub const DEFAULT_HISTORY_LEN: usize = 240;
+
+// Number of readback slots, so the GPU can run a few frames ahead before we read its timestamps.
+const GPU_FRAMES_IN_FLIGHT: usize = 3;
+
+// Each frame writes two timestamps: one at the beginning and one at the end of the render pass.
+const QUERIES_PER_FRAME: u32 = 2;
+
+/// A named span of time recorded during a frame.
+#[derive(Debug, Clone)]
+pub struct ProfileScope {
+    pub name: &'static str, // Name shown in the trace viewer.
+    pub start: Duration,    // Start time, relative to when the profiler was created.
+    pub duration: Duration, // How long the scope took.
+    pub depth: u32,         // Nesting depth (0 for top-level scopes).
+}
+
+/// Everything recorded for a single frame.
+#[derive(Debug, Clone, Default)]
+pub struct FrameProfile {
+    pub frame_index: u64,              // Monotonic frame counter.
+    pub start: Duration,               // Frame start, relative to when the profiler was created.
+    pub cpu_time: Duration,            // Time between `begin_frame` and `end_frame`.
+    pub cpu_scopes: Vec<ProfileScope>, // CPU scopes recorded during the frame.
+    pub gpu_time: Option<Duration>, // Render pass duration on the GPU, filled in a few frames later.
+}
+
+/// Records CPU scopes and GPU pass durations, keeping a rolling history of frames.
+pub struct Profiler {
+    epoch: Instant,               // All timestamps are relative to this instant.
+    frame_index: u64,             // Index of the frame currently being recorded.
+    frame_start: Option<Instant>, // Start of the current frame, if one is in progress.
+    open_scopes: Vec<(&'static str, Instant)>, // Stack of scopes that have begun but not ended.
+    current: FrameProfile,        // The frame currently being recorded.
+    history: VecDeque<FrameProfile>, // Completed frames, oldest first.
+    history_len: usize,           // Maximum number of frames kept in `history`.
+    spike_factor: f32,            // Frames slower than `spike_factor * average` are logged.
+    gpu_timer: Option<GpuTimer>,  // `None` when the adapter does not support timestamp queries.
+}
+
+impl Profiler {
+    /// Creates a profiler. GPU timing is enabled only if the device has `TIMESTAMP_QUERY`.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let gpu_timer = if device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            Some(GpuTimer::new(device, queue))
+        } else {
+            log::info!("Timestamp queries not supported, GPU profiling disabled.");
+            None
+        };
+        Self::with_gpu_timer(gpu_timer)
+    }
+
+    // Shared with the tests, which have no GPU to time.
+    fn with_gpu_timer(gpu_timer: Option<GpuTimer>) -> Self {
+        Self {
+            epoch: Instant::now(),
+            frame_index: 0,
+            frame_start: None,
+            open_scopes: Vec::new(),
+            current: FrameProfile::default(),
+            history: VecDeque::with_capacity(DEFAULT_HISTORY_LEN),
+            history_len: DEFAULT_HISTORY_LEN,
+            spike_factor: 2.0,
+            gpu_timer,
+        }
+    }
+
+    // Returns true if GPU pass durations are being recorded.
+    pub fn gpu_timing_enabled(&self) -> bool {
+        self.gpu_timer.is_some()
+    }
+
+    // Changes how many frames are kept in the rolling history.
+    pub fn set_history_len(&mut self, history_len: usize) {
+        self.history_len = history_len.max(1);
+        while self.history.len() > self.history_len {
+            self.history.pop_front();
+        }
+    }
+
+    // Changes the multiple of the average frame time above which a frame is reported as a spike.
+    pub fn set_spike_factor(&mut self, spike_factor: f32) {
+        self.spike_factor = spike_factor;
+    }
+
+    // The completed frames, oldest first.
+    pub fn history(&self) -> &VecDeque<FrameProfile> {
+        &self.history
+    }
+
+    /// Starts recording a new frame.
+    pub fn begin_frame(&mut self) {
+        let now = Instant::now();
+        self.frame_start = Some(now);
+        self.open_scopes.clear();
+        self.current = FrameProfile {
+            frame_index: self.frame_index,
+            start: now - self.epoch,
+            ..Default::default()
+        };
+    }
+
+    /// Opens a named CPU scope. Scopes may be nested and must be closed with `end_scope`.
+    pub fn begin_scope(&mut self, name: &'static str) {
+        self.open_scopes.push((name, Instant::now()));
+    }
+
+    /// Closes the most recently opened CPU scope.
+    pub fn end_scope(&mut self) {
+        let Some((name, start)) = self.open_scopes.pop() else {
+            log::warn!("Profiler::end_scope called without a matching begin_scope.");
+            return;
+        };
+        self.current.cpu_scopes.push(ProfileScope {
+            name,
+            start: start - self.epoch,
+            duration: start.elapsed(),
+            depth: self.open_scopes.len() as u32,
+        });
+    }
+
+    /// Timestamp writes for the frame's render pass, or `None` if GPU timing is unavailable this frame.
+    pub fn render_pass_timestamp_writes(&mut self) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        let gpu_timer = self.gpu_timer.as_mut()?;
+        gpu_timer.timestamp_writes(self.frame_index)
+    }
+
+    /// Copies this frame's timestamps into a readback buffer. Call after the render pass has ended.
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(gpu_timer) = self.gpu_timer.as_mut() {
+            gpu_timer.resolve(encoder);
+        }
+    }
+
+    /// Finishes the current frame. Call after the command buffer has been submitted.
+    pub fn end_frame(&mut self, device: &wgpu::Device) {
+        self.finish_frame();
+
+        // Start mapping this frame's timestamps and collect any earlier ones that are ready.
+        if let Some(gpu_timer) = self.gpu_timer.as_mut() {
+            gpu_timer.map_pending();
+            device.poll(wgpu::Maintain::Poll);
+            for (frame_index, gpu_time) in gpu_timer.collect() {
+                if let Some(frame) = self
+                    .history
+                    .iter_mut()
+                    .find(|f| f.frame_index == frame_index)
+                {
+                    frame.gpu_time = Some(gpu_time);
+                }
+            }
+        }
+    }
+
+    // The CPU side of `end_frame`: closes the frame and moves it into the history.
+    fn finish_frame(&mut self) {
+        // Close any scopes the caller forgot about, so they still show up in the trace.
+        while !self.open_scopes.is_empty() {
+            self.end_scope();
+        }
+
+        if let Some(frame_start) = self.frame_start.take() {
+            self.current.cpu_time = frame_start.elapsed();
+        }
+
+        // Look for frame-time spikes before the new frame is added to the average.
+        if let Some(average) = self.average_cpu_time() {
+            if self.current.cpu_time.as_secs_f32() > average.as_secs_f32() * self.spike_factor {
+                log::warn!(
+                    "Frame {} took {:.2} ms (average {:.2} ms)",
+                    self.current.frame_index,
+                    self.current.cpu_time.as_secs_f64() * 1000.0,
+                    average.as_secs_f64() * 1000.0,
+                );
+            }
+        }
+
+        self.history.push_back(std::mem::take(&mut self.current));
+        while self.history.len() > self.history_len {
+            self.history.pop_front();
+        }
+        self.frame_index += 1;
+    }
+
+    // Average CPU frame time over the history, or `None` if no frames have been recorded.
+    pub fn average_cpu_time(&self) -> Option<Duration> {
+        if self.history.is_empty() {
+            return None;
+        }
+        let total: Duration = self.history.iter().map(|f| f.cpu_time).sum();
+        Some(total / self.history.len() as u32)
+    }
+
+    // Average GPU frame time over the frames that have GPU results.
+    pub fn average_gpu_time(&self) -> Option<Duration> {
+        let gpu_times: Vec<Duration> = self.history.iter().filter_map(|f| f.gpu_time).collect();
+        if gpu_times.is_empty() {
+            return None;
+        }
+        Some(gpu_times.iter().sum::<Duration>() / gpu_times.len() as u32)
+    }
+
+    // The slowest frame in the history, by CPU time.
+    pub fn slowest_frame(&self) -> Option<&FrameProfile> {
+        self.history.iter().max_by_key(|f| f.cpu_time)
+    }
+
+    /// Serializes the history in the Chrome trace event format (open with `chrome://tracing` or Perfetto).
+    ///
+    /// CPU scopes are on thread 0. GPU passes are on thread 1, aligned to the start of their frame,
+    /// since GPU and CPU clocks are not synchronised.
+    pub fn chrome_trace_json(&self) -> String {
+        let mut events = Vec::new();
+        for frame in &self.history {
+            events.push(trace_event(
+                &format!("Frame {}", frame.frame_index),
+                "frame",
+                frame.start,
+                frame.cpu_time,
+                0,
+            ));
+            for scope in &frame.cpu_scopes {
+                events.push(trace_event(
+                    scope.name,
+                    "cpu",
+                    scope.start,
+                    scope.duration,
+                    0,
+                ));
+            }
+            if let Some(gpu_time) = frame.gpu_time {
+                events.push(trace_event("Render Pass", "gpu", frame.start, gpu_time, 1));
+            }
+        }
+        format!("{{\"traceEvents\":[{}]}}", events.join(","))
+    }
+
+    /// Writes the Chrome trace JSON to a file.
+    pub fn write_chrome_trace(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.chrome_trace_json())
+    }
+}
+
+// Formats a single "complete" (`ph: X`) trace event. Times are in microseconds.
+fn trace_event(
+    name: &str,
+    category: &str,
+    start: Duration,
+    duration: Duration,
+    tid: u32,
+) -> String {
+    format!(
+        "{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\"pid\":0,\"tid\":{}}}",
+        name.replace('\\', "\\\\").replace('"', "\\\""),
+        category,
+        start.as_secs_f64() * 1_000_000.0,
+        duration.as_secs_f64() * 1_000_000.0,
+        tid,
+    )
+}
+
+// Owns the query set and buffers used to read render pass timestamps back from the GPU.
+struct GpuTimer {
+    query_set: wgpu::QuerySet, // Holds `QUERIES_PER_FRAME` timestamps per slot.
+    resolve_buffer: wgpu::Buffer, // GPU-side destination of `resolve_query_set`.
+    slots: Vec<GpuTimerSlot>,  // One readback buffer per frame in flight.
+    current_slot: usize,       // Slot used by the frame being recorded.
+    written: Option<u64>,      // Frame whose timestamps were written into the current slot.
+    timestamp_period: f32,     // Nanoseconds per timestamp tick.
+}
+
+// States of a readback buffer's `map_async` request.
+const MAP_PENDING: u8 = 0;
+const MAP_DONE: u8 = 1;
+const MAP_FAILED: u8 = 2;
+
+// A readback buffer and the frame whose timestamps it is waiting for.
+struct GpuTimerSlot {
+    readback_buffer: wgpu::Buffer, // CPU-mappable copy of the resolved timestamps.
+    frame_index: Option<u64>,      // `Some` while the slot is in use.
+    mapping: bool,                 // True once `map_async` has been requested.
+    map_state: Arc<AtomicU8>,      // One of the `MAP_` states, set by the callback.
+}
+
+impl GpuTimer {
+    const SLOT_SIZE: wgpu::BufferAddress =
+        (QUERIES_PER_FRAME * wgpu::QUERY_SIZE) as wgpu::BufferAddress;
+
+    fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Profiler Query Set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: QUERIES_PER_FRAME * GPU_FRAMES_IN_FLIGHT as u32,
+        });
+
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Profiler Resolve Buffer"),
+            size: Self::SLOT_SIZE * GPU_FRAMES_IN_FLIGHT as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let slots = (0..GPU_FRAMES_IN_FLIGHT)
+            .map(|_| GpuTimerSlot {
+                readback_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Profiler Readback Buffer"),
+                    size: Self::SLOT_SIZE,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                frame_index: None,
+                mapping: false,
+                map_state: Arc::new(AtomicU8::new(MAP_PENDING)),
+            })
+            .collect();
+
+        Self {
+            query_set,
+            resolve_buffer,
+            slots,
+            current_slot: 0,
+            written: None,
+            timestamp_period: queue.get_timestamp_period(),
+        }
+    }
+
+    fn timestamp_writes(
+        &mut self,
+        frame_index: u64,
+    ) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        // If the slot is still waiting on an old frame, skip GPU timing rather than stalling.
+        if self.slots[self.current_slot].frame_index.is_some() {
+            return None;
+        }
+        self.written = Some(frame_index);
+        let base = self.current_slot as u32 * QUERIES_PER_FRAME;
+        Some(wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(base),
+            end_of_pass_write_index: Some(base + 1),
+        })
+    }
+
+    fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if self.written.is_none() {
+            return;
+        }
+        let base = self.current_slot as u32 * QUERIES_PER_FRAME;
+        let offset = self.current_slot as wgpu::BufferAddress * Self::SLOT_SIZE;
+        encoder.resolve_query_set(
+            &self.query_set,
+            base..base + QUERIES_PER_FRAME,
+            &self.resolve_buffer,
+            offset,
+        );
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            offset,
+            &self.slots[self.current_slot].readback_buffer,
+            0,
+            Self::SLOT_SIZE,
+        );
+    }
+
+    // Requests mapping of the current slot (if it was written) and moves on to the next slot.
+    fn map_pending(&mut self) {
+        let Some(frame_index) = self.written.take() else {
+            return;
+        };
+        let slot = &mut self.slots[self.current_slot];
+        slot.frame_index = Some(frame_index);
+        slot.mapping = true;
+        let map_state = slot.map_state.clone();
+        slot.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let state = if result.is_ok() { MAP_DONE } else { MAP_FAILED };
+                map_state.store(state, Ordering::Release);
+            });
+        self.current_slot = (self.current_slot + 1) % self.slots.len();
+    }
+
+    // Reads back every slot whose mapping has completed, returning `(frame_index, gpu_time)` pairs.
+    fn collect(&mut self) -> Vec<(u64, Duration)> {
+        let mut results = Vec::new();
+        for slot in &mut self.slots {
+            if !slot.mapping {
+                continue;
+            }
+            match slot.map_state.load(Ordering::Acquire) {
+                MAP_PENDING => continue,
+                MAP_FAILED => {
+                    // Nothing to read; free the slot so later frames can still be timed.
+                    log::warn!("Failed to read back GPU timestamps.");
+                    slot.map_state.store(MAP_PENDING, Ordering::Release);
+                    slot.mapping = false;
+                    slot.frame_index = None;
+                    continue;
+                }
+                _ => {}
+            }
+            let ticks: [u64; QUERIES_PER_FRAME as usize] = {
+                let data = slot.readback_buffer.slice(..).get_mapped_range();
+                let timestamps: &[u64] = bytemuck::cast_slice(&data);
+                [timestamps[0], timestamps[1]]
+            };
+            slot.readback_buffer.unmap();
+            slot.map_state.store(MAP_PENDING, Ordering::Release);
+            slot.mapping = false;
+
+            if let Some(frame_index) = slot.frame_index.take() {
+                let nanos = ticks[1].saturating_sub(ticks[0]) as f64 * self.timestamp_period as f64;
+                results.push((frame_index, Duration::from_nanos(nanos as u64)));
+            }
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The value of a numeric field in a single trace event.
+    fn number(event: &str, field: &str) -> f64 {
+        let key = format!("\"{field}\":");
+        let start = event.find(&key).expect("the field is present") + key.len();
+        let end = event[start..]
+            .find([',', '}'])
+            .map_or(event.len(), |end| start + end);
+        event[start..end].parse().expect("the field is a number")
+    }
+
+    // The trace events, one string each, in the order they appear.
+    fn events(json: &str) -> Vec<&str> {
+        let body = json
+            .strip_prefix("{\"traceEvents\":[")
+            .and_then(|rest| rest.strip_suffix("]}"))
+            .expect("a trace event object");
+        body.split("},{").collect()
+    }
+
+    #[test]
+    fn nested_scopes_export_as_nested_complete_events() {
+        let mut profiler = Profiler::with_gpu_timer(None);
+        profiler.begin_frame();
+        profiler.begin_scope("Outer");
+        std::thread::sleep(Duration::from_millis(2));
+        profiler.begin_scope("Inner \"quoted\"");
+        std::thread::sleep(Duration::from_millis(2));
+        profiler.end_scope();
+        std::thread::sleep(Duration::from_millis(2));
+        profiler.end_scope();
+        profiler.finish_frame();
+
+        let json = profiler.chrome_trace_json();
+        let events = events(&json);
+        assert_eq!(events.len(), 3, "{json}");
+        for event in &events {
+            for field in [
+                "\"name\":",
+                "\"cat\":",
+                "\"ph\":\"X\"",
+                "\"ts\":",
+                "\"dur\":",
+            ] {
+                assert!(event.contains(field), "{field} missing from {event}");
+            }
+            assert!(event.contains("\"pid\":0") && event.contains("\"tid\":0"));
+        }
+
+        // The frame comes first, then the scopes in the order they closed.
+        let (frame, inner, outer) = (events[0], events[1], events[2]);
+        assert!(frame.contains("\"name\":\"Frame 0\"") && frame.contains("\"cat\":\"frame\""));
+        assert!(inner.contains(r#""name":"Inner \"quoted\"""#), "{inner}");
+        assert!(inner.contains("\"cat\":\"cpu\""));
+        assert!(outer.contains("\"name\":\"Outer\""));
+
+        // Each event lies inside its parent, in microseconds.
+        let span = |event: &str| {
+            let ts = number(event, "ts");
+            (ts, ts + number(event, "dur"))
+        };
+        let (frame, outer, inner) = (span(frame), span(outer), span(inner));
+        assert!(
+            frame.0 <= outer.0 && outer.1 <= frame.1,
+            "{frame:?} {outer:?}"
+        );
+        assert!(
+            outer.0 < inner.0 && inner.1 < outer.1,
+            "{outer:?} {inner:?}"
+        );
+        assert!(inner.1 - inner.0 >= 2_000.0, "the inner scope slept 2 ms");
+        assert!(outer.1 - outer.0 >= 6_000.0, "the outer scope slept 6 ms");
+    }
+
+    #[test]
+    fn gpu_passes_go_on_their_own_thread() {
+        let mut profiler = Profiler::with_gpu_timer(None);
+        profiler.begin_frame();
+        profiler.finish_frame();
+        profiler.begin_frame();
+        profiler.finish_frame();
+        profiler.history[1].gpu_time = Some(Duration::from_micros(1500));
+
+        let json = profiler.chrome_trace_json();
+        let events = events(&json);
+        assert_eq!(events.len(), 3, "{json}");
+        let gpu = events[2];
+        assert!(gpu.contains("\"name\":\"Render Pass\"") && gpu.contains("\"tid\":1"));
+        assert_eq!(number(gpu, "dur"), 1500.0);
+        assert_eq!(number(gpu, "ts"), number(events[1], "ts"));
+        assert!(number(events[0], "ts") <= number(events[1], "ts"));
+    }
+}
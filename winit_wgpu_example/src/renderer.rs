@@ -1,12 +1,26 @@
-use crate::gpu::Gpu; // Importing the Gpu struct from the gpu module.
-use crate::scene::Scene;
-pub use std::time::Duration; // Importing the Duration struct for working with time intervals. // Importing the Scene struct from the scene module.
+use crate::assets::AssetServer; // Importing the AssetServer the game draws assets from.
+use crate::capture::FrameCapture; // Importing the FrameCapture struct used for screenshots.
+use crate::gpu::{Gpu, GpuContext, SurfaceError}; // Importing the Gpu and GpuContext structs from the gpu module.
+use crate::profiler::Profiler; // Importing the Profiler used to time CPU scopes and GPU passes.
+use std::sync::Arc; // Arc is used to share the GpuContext between renderers.
+pub use std::time::Duration; // Importing the Duration struct for working with time intervals.
 
-/// The `Renderer` struct holds the GPU, depth texture view, and scene information for rendering.
+/// Everything a game needs to draw during a frame.
+pub struct Frame<'pass> {
+    pub device: &'pass wgpu::Device, // The device, for creating resources on the fly.
+    pub queue: &'pass wgpu::Queue, // The queue, for uploading uniforms before the pass is submitted.
+    pub surface_format: wgpu::TextureFormat, // The format of the colour target.
+    pub aspect_ratio: f32,         // Width divided by height of the surface.
+    pub assets: &'pass mut AssetServer, // The loaded meshes, textures and shaders; can load more.
+    pub render_pass: wgpu::RenderPass<'pass>, // The pass to record draw calls into.
+}
+
+/// The `Renderer` struct holds the GPU and depth texture view used to render into one window.
 pub struct Renderer<'window> {
     gpu: Gpu<'window>, // The GPU struct which handles GPU-related tasks.
     depth_texture_view: wgpu::TextureView, // The texture view for the depth buffer.
-    scene: Scene,      // The scene to be rendered.
+    profiler: Profiler, // Records CPU and GPU timings for each frame.
+    capture: FrameCapture, // Saves screenshots and frame sequences as PNG files.
 }
 
 impl<'window> Renderer<'window> {
@@ -21,21 +35,82 @@ impl<'window> Renderer<'window> {
     ) -> Self {
         // Initialize the GPU by creating an asynchronous GPU object.
         let gpu = Gpu::new_async(window, width, height).await;
+        Self::from_gpu(gpu, width, height)
+    }
+
+    /// Creates a renderer for another window, sharing the device and queue of an existing renderer.
+    ///
+    /// Fails if the shared GPU cannot present to the window.
+    pub fn with_context(
+        context: Arc<GpuContext>, // The shared device and queue.
+        window: impl Into<wgpu::SurfaceTarget<'window>>, // The window for rendering.
+        width: u32,               // Width of the window.
+        height: u32,              // Height of the window.
+    ) -> Result<Self, SurfaceError> {
+        let gpu = Gpu::with_context(context, window, width, height)?;
+        Ok(Self::from_gpu(gpu, width, height))
+    }
 
+    // Creates the per-window rendering state on top of a configured Gpu.
+    fn from_gpu(gpu: Gpu<'window>, width: u32, height: u32) -> Self {
         // Create a depth texture view for the renderer.
         let depth_texture_view = gpu.create_depth_texture(width, height);
 
-        // Initialize the scene to be rendered.
-        let scene = Scene::new(&gpu.device, gpu.surface_format);
+        // Create the profiler (GPU timing is enabled if the device supports timestamp queries).
+        let profiler = Profiler::new(gpu.device(), gpu.queue());
+
+        // Screenshots are saved in a `screenshots` folder in the working directory.
+        let capture = FrameCapture::new("screenshots");
 
         // Return a new `Renderer` object.
         Self {
             gpu,                // The GPU object.
             depth_texture_view, // The depth texture view.
-            scene,              // The scene object.
+            profiler,           // The profiler.
+            capture,            // The frame capture helper.
         }
     }
 
+    /// The GPU used by this renderer, e.g. to create pipelines for its surface format.
+    pub fn gpu(&self) -> &Gpu<'window> {
+        &self.gpu
+    }
+
+    /// The GPU context, which can be passed to `Renderer::with_context` to render to another window.
+    pub fn context(&self) -> &Arc<GpuContext> {
+        &self.gpu.context
+    }
+
+    /// Read-only access to the frame profiler, e.g. to export a trace.
+    pub fn profiler(&self) -> &Profiler {
+        &self.profiler
+    }
+
+    /// Mutable access to the frame profiler, e.g. to add custom CPU scopes.
+    pub fn profiler_mut(&mut self) -> &mut Profiler {
+        &mut self.profiler
+    }
+
+    /// Saves the next rendered frame as a PNG with a timestamped file name.
+    pub fn request_screenshot(&mut self) {
+        self.capture.request_screenshot();
+    }
+
+    /// Saves every rendered frame as a numbered PNG for the given duration.
+    pub fn start_recording(&mut self, duration: Duration) {
+        self.capture.start_recording(duration);
+    }
+
+    /// Stops a frame-sequence recording early.
+    pub fn stop_recording(&mut self) {
+        self.capture.stop_recording();
+    }
+
+    /// Returns true if a frame-sequence recording is in progress.
+    pub fn is_recording(&self) -> bool {
+        self.capture.is_recording()
+    }
+
     /// Function to resize the renderer and the associated depth texture view.
     pub fn resize(&mut self, width: u32, height: u32) {
         // Resizes the GPU surface configuration.
@@ -46,31 +121,33 @@ impl<'window> Renderer<'window> {
     }
 
     /// Function to render a frame, typically called once per frame.
+    ///
+    /// `render` is called with a cleared render pass to record the frame's draw calls into.
     pub fn render_frame(
         &mut self,
         delta_time: Duration, // The time that has passed since the last frame.
+        assets: &mut AssetServer, // The assets the frame can draw with and load.
+        render: impl FnOnce(&mut Frame), // Records the draw calls for this frame.
     ) {
-        // Convert delta_time into seconds as a floating-point number.
-        let delta_time = delta_time.as_secs_f32();
-
-        // Update the scene with the GPU queue, aspect ratio, and delta_time.
-        self.scene
-            .update(&self.gpu.queue, self.gpu.aspect_ratio(), delta_time);
+        // Start recording timings for this frame.
+        self.profiler.begin_frame();
 
         // Create a new command encoder for the render pass.
-        let mut encoder = self
-            .gpu
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"), // Label for debugging purposes.
-            });
+        let mut encoder =
+            self.gpu
+                .device()
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Render Encoder"), // Label for debugging purposes.
+                });
 
         // Get the current texture from the surface to render onto.
+        self.profiler.begin_scope("Acquire Surface Texture");
         let surface_texture = self
             .gpu
             .surface
             .get_current_texture()
             .expect("Failed to get surface texture!");
+        self.profiler.end_scope();
 
         // Create a texture view for the surface texture.
         let surface_texture_view =
@@ -91,8 +168,9 @@ impl<'window> Renderer<'window> {
         encoder.insert_debug_marker("Render scene");
 
         // Begin the render pass, which is responsible for rendering the scene.
+        self.profiler.begin_scope("Encode Render Pass");
         {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            let render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"), // Label for the render pass.
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                     view: &surface_texture_view, // The texture view we are rendering to.
@@ -115,18 +193,66 @@ impl<'window> Renderer<'window> {
                     }),
                     stencil_ops: None, // No stencil buffer used.
                 }),
-                timestamp_writes: None,    // No timestamp writes.
+                timestamp_writes: self.profiler.render_pass_timestamp_writes(), // GPU timing, if supported.
                 occlusion_query_set: None, // No occlusion queries.
             });
 
-            // Render the scene into the render pass.
-            self.scene.render(&mut render_pass);
+            // Let the caller render into the render pass.
+            let mut frame = Frame {
+                device: self.gpu.device(),
+                queue: self.gpu.queue(),
+                surface_format: self.gpu.surface_format,
+                aspect_ratio: self.gpu.aspect_ratio(),
+                assets,
+                render_pass,
+            };
+            render(&mut frame);
+        }
+        self.profiler.end_scope();
+
+        // Copy the surface texture into a buffer if a screenshot or recording is due.
+        if self
+            .gpu
+            .surface_config
+            .usage
+            .contains(wgpu::TextureUsages::COPY_SRC)
+        {
+            self.capture.copy_texture(
+                self.gpu.device(),
+                &mut encoder,
+                &surface_texture.texture,
+                delta_time,
+            );
+        } else {
+            self.capture.cancel("the surface does not support COPY_SRC");
         }
 
+        // Copy the render pass timestamps somewhere we can read them back from.
+        self.profiler.resolve(&mut encoder);
+
         // Submit the encoded commands to the GPU queue.
-        self.gpu.queue.submit(std::iter::once(encoder.finish()));
+        self.profiler.begin_scope("Submit");
+        self.gpu.queue().submit(std::iter::once(encoder.finish()));
+        self.profiler.end_scope();
+
+        // Read back and save the captured frame, if there is one.
+        self.profiler.begin_scope("Capture");
+        self.capture.finish(self.gpu.device());
+        self.profiler.end_scope();
 
         // Present the surface texture to the screen.
+        self.profiler.begin_scope("Present");
         surface_texture.present();
+        self.profiler.end_scope();
+
+        // Finish the frame and pick up any GPU timings that have become available.
+        self.profiler.end_frame(self.gpu.device());
+    }
+}
+
+impl Drop for Renderer<'_> {
+    // Writes out the frames still being captured, so closing the window does not lose them.
+    fn drop(&mut self) {
+        self.capture.flush(self.gpu.device());
     }
 }
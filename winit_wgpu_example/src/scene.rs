@@ -1,93 +1,104 @@
-// Import necessary modules for rendering, uniform binding, and vertex handling
-use crate::renderer::Renderer;
+// Import necessary modules for assets, rendering, uniform binding, and vertex handling
+use crate::assets::{AssetServer, Handle, Mesh, Shader};
+use crate::renderer::{Frame, Renderer};
 use crate::uniform_binding::{UniformBinding, UniformBuffer};
 use crate::vertex::Vertex;
+use std::collections::HashMap;
 
-// Define the `Scene` struct which holds the model matrix, buffers, uniform binding, and render pipeline
+// Where the scene's shader is loaded from, relative to the asset root
+pub const SHADER_PATH: &str = "shaders/scene.wgsl";
+
+// Define the `Scene` struct which holds the model matrix, assets, uniform binding, and render pipeline
 pub struct Scene {
-    pub model: nalgebra_glm::Mat4,   // Transformation matrix for the scene
-    pub vertex_buffer: wgpu::Buffer, // Vertex buffer for storing vertex data
-    pub index_buffer: wgpu::Buffer,  // Index buffer for storing indices to draw primitives
-    pub uniform: UniformBinding,     // Uniform binding to pass data to shaders
-    pub pipeline: wgpu::RenderPipeline, // The pipeline for rendering the scene
+    pub model: nalgebra_glm::Mat4, // Transformation matrix for the scene
+    pub mesh: Handle<Mesh>,        // The triangle's vertex and index buffers
+    pub shader: Handle<Shader>,    // The shader, loaded from `SHADER_PATH` in the background
+    pub uniform: UniformBinding,   // Uniform binding to pass data to shaders
+    pub pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>, // One pipeline per surface format rendered to
 }
 
 impl Scene {
     // Constructor function to create a new `Scene` instance
-    pub fn new(device: &wgpu::Device, surface_format: wgpu::TextureFormat) -> Self {
-        // Create the vertex buffer by initializing it with vertex data (using the `VERTICES` array)
-        let vertex_buffer = wgpu::util::DeviceExt::create_buffer_init(
+    //
+    // The triangle is handed to the asset server, and the shader starts loading through it
+    pub fn new(device: &wgpu::Device, assets: &mut AssetServer) -> Self {
+        // Create the vertex and index buffers from the `VERTICES` and `INDICES` arrays
+        let mesh = assets.insert(Mesh::new(
             device,
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Vertex Buffer"),              // Label for the buffer
-                contents: bytemuck::cast_slice(&VERTICES), // Vertex data to be copied into the buffer
-                usage: wgpu::BufferUsages::VERTEX,         // Usage: this is for storing vertex data
-            },
-        );
+            Some("Triangle"), // Label for the buffers
+            &VERTICES,        // Vertex data to be copied into the vertex buffer
+            &INDICES,         // Index data for rendering primitives
+        ));
 
-        // Create the index buffer by initializing it with index data (using the `INDICES` array)
-        let index_buffer = wgpu::util::DeviceExt::create_buffer_init(
-            device,
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("index Buffer"),              // Label for the buffer
-                contents: bytemuck::cast_slice(&INDICES), // Index data for rendering primitives
-                usage: wgpu::BufferUsages::INDEX,         // Usage: this is for storing indices
-            },
-        );
+        // Start loading the shader; the scene is drawn once it is ready
+        let shader = assets.load(SHADER_PATH);
 
         // Create a new uniform binding (for sending data to shaders)
         let uniform = UniformBinding::new(device);
 
-        // Create the render pipeline (which handles shader execution and drawing)
-        let pipeline = Self::create_pipeline(device, surface_format, &uniform);
-
-        // Return a new `Scene` instance with default values and the created buffers and pipeline
+        // Return a new `Scene` instance; pipelines are created once the shader has loaded
         Self {
             model: nalgebra_glm::Mat4::identity(), // Initialize the model matrix as an identity matrix
+            mesh,                                  // The triangle
+            shader,                                // The loading shader
             uniform,                               // The created uniform binding
-            pipeline,                              // The created render pipeline
-            vertex_buffer,                         // The created vertex buffer
-            index_buffer,                          // The created index buffer
+            pipelines: HashMap::new(),             // No render pipelines yet
         }
     }
 
     // Render method to draw the scene using the pipeline and bind the required buffers
-    pub fn render<'rpass>(&'rpass self, renderpass: &mut wgpu::RenderPass<'rpass>) {
+    //
+    // Windows can have different surface formats, so a pipeline is created the first time the scene
+    // is drawn into each format. Nothing is drawn until the shader has loaded
+    pub fn render(&mut self, frame: &mut Frame) {
+        let (Some(shader), Some(mesh)) =
+            (frame.assets.get(&self.shader), frame.assets.get(&self.mesh))
+        else {
+            return;
+        };
+        let uniform = &self.uniform;
+        let pipeline = self
+            .pipelines
+            .entry(frame.surface_format)
+            .or_insert_with(|| {
+                Self::create_pipeline(frame.device, frame.surface_format, &shader.module, uniform)
+            });
+        let renderpass = &mut frame.render_pass;
+
         // Set the render pipeline to be used for this render pass
-        renderpass.set_pipeline(&self.pipeline);
+        renderpass.set_pipeline(pipeline);
 
         // Bind the uniform buffer to the pipeline at binding group 0
         renderpass.set_bind_group(0, &self.uniform.bind_group, &[]);
 
-        // Set the vertex buffer for the pipeline
-        renderpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-
-        // Set the index buffer for the pipeline
-        renderpass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-
-        // Draw the indexed geometry (using the index buffer) for the defined primitive
-        renderpass.draw_indexed(0..(INDICES.len() as _), 0, 0..1);
+        // Bind the vertex and index buffers and draw the indexed geometry
+        mesh.draw(renderpass);
     }
 
     // Update method to modify the scene, including the transformation matrix and uniform data
     pub fn update(&mut self, queue: &wgpu::Queue, aspect_ratio: f32, delta_time: f32) {
-        // Create the projection matrix based on the aspect ratio and field of view
-        let projection =
-            nalgebra_glm::perspective_lh_zo(aspect_ratio, 80_f32.to_radians(), 0.1, 1000.0);
-
-        // Create the view matrix, which is a camera looking at the origin from a certain position
-        let view = nalgebra_glm::look_at_lh(
-            &nalgebra_glm::vec3(0.0, 0.0, 3.0), // Camera position (3 units away on the Z-axis)
-            &nalgebra_glm::vec3(0.0, 0.0, 0.0), // The point the camera is looking at (the origin)
-            &nalgebra_glm::Vec3::y(),           // Up direction is along the Y-axis
-        );
+        self.animate(delta_time);
+        self.upload(queue, aspect_ratio);
+    }
 
+    // Rotates the model over time, without touching the GPU
+    pub fn animate(&mut self, delta_time: f32) {
         // Rotate the model matrix by a small amount over time (to animate it)
         self.model = nalgebra_glm::rotate(
             &self.model,
             30_f32.to_radians() * delta_time, // Rotate by 30 degrees per second, scaled by delta_time
             &nalgebra_glm::Vec3::y(),         // Rotate around the Y-axis
         );
+    }
+
+    // Writes the model-view-projection matrix for the current model into the uniform buffer
+    pub fn upload(&mut self, queue: &wgpu::Queue, aspect_ratio: f32) {
+        // Create the projection matrix based on the aspect ratio and field of view
+        let projection =
+            nalgebra_glm::perspective_lh_zo(aspect_ratio, 80_f32.to_radians(), 0.1, 1000.0);
+
+        // Create the view matrix for the camera
+        let view = Self::view();
 
         // Update the uniform buffer with the new model-view-projection (MVP) matrix
         self.uniform.update_buffer(
@@ -99,18 +110,22 @@ impl Scene {
         );
     }
 
+    // The view matrix of the camera, which looks at the origin from a certain position
+    pub fn view() -> nalgebra_glm::Mat4 {
+        nalgebra_glm::look_at_lh(
+            &nalgebra_glm::vec3(0.0, 0.0, 3.0), // Camera position (3 units away on the Z-axis)
+            &nalgebra_glm::vec3(0.0, 0.0, 0.0), // The point the camera is looking at (the origin)
+            &nalgebra_glm::Vec3::y(),           // Up direction is along the Y-axis
+        )
+    }
+
     // Helper function to create the render pipeline with shaders and configuration
     fn create_pipeline(
         device: &wgpu::Device,               // The wgpu device
         surface_format: wgpu::TextureFormat, // The surface format (pixel format) for the render target
+        shader_module: &wgpu::ShaderModule,  // The vertex and fragment shaders
         uniform: &UniformBinding,            // The uniform binding for passing data to shaders
     ) -> wgpu::RenderPipeline {
-        // Create the shader module from the WGSL shader source code
-        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: None,
-            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(SHADER_SOURCE)), // The shader source code
-        });
-
         // Create the pipeline layout which defines the uniform bind groups for the pipeline
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: None,
@@ -123,7 +138,7 @@ impl Scene {
             label: None,
             layout: Some(&pipeline_layout), // Use the created pipeline layout
             vertex: wgpu::VertexState {
-                module: &shader_module,     // The vertex shader module
+                module: shader_module,      // The vertex shader module
                 entry_point: "vertex_main", // Entry point for the vertex shader
                 buffers: &[Vertex::description(&Vertex::vertex_attributes())], // Define vertex buffer layout
                 compilation_options: Default::default(),
@@ -150,7 +165,7 @@ impl Scene {
                 alpha_to_coverage_enabled: false, // Disable alpha to coverage
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shader_module,       // The fragment shader module
+                module: shader_module,        // The fragment shader module
                 entry_point: "fragment_main", // Entry point for the fragment shader
                 targets: &[Some(wgpu::ColorTargetState {
                     format: surface_format, // Use the surface format for the output color
@@ -187,35 +202,3 @@ const INDICES: [u32; 3] = [
     1, // Second vertex (index 1)
     2, // Third vertex (index 2)
 ];
-
-// Shader source code in WGSL (WebGPU Shading Language) for the vertex and fragment shaders
-const SHADER_SOURCE: &str = "
-struct Uniform {
-    mvp: mat4x4<f32>,
-};
-
-@group(0) @binding(0)
-var<uniform> ubo: Uniform;
-
-struct VertexInput {
-    @location(0) position: vec4<f32>,
-    @location(1) color: vec4<f32>,
-};
-struct VertexOutput {
-    @builtin(position) position: vec4<f32>,
-    @location(0) color: vec4<f32>,
-};
-
-@vertex
-fn vertex_main(vert: VertexInput) -> VertexOutput {
-    var out: VertexOutput;
-    out.color = vert.color;
-    out.position = ubo.mvp * vert.position;
-    return out;
-};
-
-@fragment
-fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(in.color);
-}
-";
@@ -0,0 +1,365 @@
+use std::path::{Path, PathBuf}; // Paths to the settings file and window icon.
+
+use serde::{Deserialize, Serialize}; // Lets the config be read from and written to a settings file.
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize}, // Window position and size in physical pixels.
+    event_loop::ActiveEventLoop,           // Needed to look up monitors.
+    monitor::MonitorHandle,                // A connected display.
+    window::{CursorGrabMode, Fullscreen, Icon, Window, WindowAttributes},
+};
+
+// Default location of the settings file, relative to the working directory.
+pub const DEFAULT_SETTINGS_PATH: &str = "window_settings.ron";
+
+/// How the window occupies the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DisplayMode {
+    #[default]
+    Windowed, // A normal window.
+    BorderlessFullscreen, // A borderless window covering the whole monitor.
+    ExclusiveFullscreen,  // Takes over the monitor using its best video mode.
+}
+
+/// How the cursor is constrained while the window has focus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CursorGrab {
+    #[default]
+    None, // The cursor moves freely.
+    Confined, // The cursor cannot leave the window.
+    Locked,   // The cursor is locked in place (useful for mouse-look cameras).
+}
+
+/// Settings used to create and manage the game window.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)] // Missing fields in the settings file fall back to their default values.
+pub struct WindowConfig {
+    pub title: String,                // Text in the title bar.
+    pub width: u32,                   // Inner width in physical pixels.
+    pub height: u32,                  // Inner height in physical pixels.
+    pub position: Option<(i32, i32)>, // Outer position in physical pixels, or `None` to centre on `monitor`.
+    pub resizable: bool,              // Whether the user can resize the window.
+    pub decorations: bool,            // Whether the title bar and borders are shown.
+    pub icon: Option<PathBuf>,        // Path to a PNG used as the window icon.
+    pub monitor: Option<usize>, // Index of the monitor to open on, or `None` for the primary one.
+    pub display_mode: DisplayMode, // Windowed or fullscreen.
+    pub fullscreen_mode: DisplayMode, // The fullscreen mode last used, restored by `toggle_fullscreen`.
+    pub cursor_grab: CursorGrab,      // How the cursor is constrained.
+    pub cursor_visible: bool,         // Whether the cursor is drawn over the window.
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: "Standalone Winit/Wgpu Example".to_string(),
+            width: 1280,
+            height: 720,
+            position: None,
+            resizable: true,
+            decorations: true,
+            icon: None,
+            monitor: None,
+            display_mode: DisplayMode::Windowed,
+            fullscreen_mode: DisplayMode::BorderlessFullscreen,
+            cursor_grab: CursorGrab::None,
+            cursor_visible: true,
+        }
+    }
+}
+
+impl WindowConfig {
+    /// Reads the config from a RON settings file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(ron::from_str(&text)?)
+    }
+
+    /// Reads the config from a RON settings file, falling back to defaults if it is missing or invalid.
+    pub fn load_or_default(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        match Self::load(path) {
+            Ok(config) => config,
+            Err(error) => {
+                log::info!(
+                    "Using default window settings ({}: {error})",
+                    path.display()
+                );
+                Self::default()
+            }
+        }
+    }
+
+    /// Writes the config to a RON settings file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn std::error::Error>> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+
+    /// Builds the attributes used to create the window.
+    pub fn to_attributes(&self, event_loop: &ActiveEventLoop) -> WindowAttributes {
+        let mut attributes = Window::default_attributes()
+            .with_title(self.title.clone())
+            .with_inner_size(PhysicalSize::new(self.width.max(1), self.height.max(1)))
+            .with_resizable(self.resizable)
+            .with_decorations(self.decorations)
+            .with_fullscreen(self.fullscreen(event_loop));
+
+        // Without a saved position, a window for a chosen monitor opens in the middle of it.
+        if let Some((x, y)) = self.position {
+            attributes = attributes.with_position(PhysicalPosition::new(x, y));
+        } else if let Some(monitor) = self.chosen_monitor(event_loop) {
+            attributes =
+                attributes.with_position(self.centred_on(monitor.position(), monitor.size()));
+        }
+
+        // In the browser, add the window's canvas to the page.
+        #[cfg(target_arch = "wasm32")]
+        {
+            use winit::platform::web::WindowAttributesExtWebSys;
+            attributes = attributes.with_append(true);
+        }
+
+        if let Some(icon_path) = &self.icon {
+            match load_icon(icon_path) {
+                Ok(icon) => attributes = attributes.with_window_icon(Some(icon)),
+                Err(error) => log::warn!("Failed to load icon {}: {error}", icon_path.display()),
+            }
+        }
+
+        attributes
+    }
+
+    /// Attributes for an extra window (such as a tool window) that does not use the saved settings.
+    pub fn tool_window_attributes(title: &str) -> WindowAttributes {
+        let attributes = Window::default_attributes().with_title(title);
+        #[cfg(target_arch = "wasm32")]
+        let attributes = {
+            use winit::platform::web::WindowAttributesExtWebSys;
+            attributes.with_append(true)
+        };
+        attributes
+    }
+
+    /// The winit fullscreen setting for the configured display mode and monitor.
+    pub fn fullscreen(&self, event_loop: &ActiveEventLoop) -> Option<Fullscreen> {
+        let monitor = self
+            .chosen_monitor(event_loop)
+            .or_else(|| event_loop.primary_monitor());
+        fullscreen_for(self.display_mode, monitor)
+    }
+
+    // The monitor named by `monitor`, if it is set and still connected.
+    fn chosen_monitor(&self, event_loop: &ActiveEventLoop) -> Option<MonitorHandle> {
+        self.monitor
+            .and_then(|index| event_loop.available_monitors().nth(index))
+    }
+
+    // The outer position that centres the window on a monitor, keeping its top left corner on the
+    // monitor if the window is larger.
+    fn centred_on(
+        &self,
+        monitor_position: PhysicalPosition<i32>,
+        monitor_size: PhysicalSize<u32>,
+    ) -> PhysicalPosition<i32> {
+        let centre = |start: i32, monitor: u32, window: u32| {
+            start + (monitor.saturating_sub(window) / 2) as i32
+        };
+        PhysicalPosition::new(
+            centre(monitor_position.x, monitor_size.width, self.width),
+            centre(monitor_position.y, monitor_size.height, self.height),
+        )
+    }
+
+    /// Applies the cursor grab and visibility settings to a window.
+    pub fn apply_cursor(&self, window: &Window) {
+        let result = match self.cursor_grab {
+            CursorGrab::None => window.set_cursor_grab(CursorGrabMode::None),
+            // Not every platform supports both modes, so fall back to the other one.
+            CursorGrab::Confined => window
+                .set_cursor_grab(CursorGrabMode::Confined)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Locked)),
+            CursorGrab::Locked => window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined)),
+        };
+        if let Err(error) = result {
+            log::warn!("Failed to grab cursor: {error}");
+        }
+        window.set_cursor_visible(self.cursor_visible);
+    }
+
+    /// Switches between windowed mode and fullscreen (used for Alt+Enter).
+    ///
+    /// Turning fullscreen on goes back to the fullscreen mode used last, borderless by default.
+    pub fn toggle_fullscreen(&mut self, window: &Window) {
+        self.toggle_display_mode(window.fullscreen().is_some());
+        window.set_fullscreen(fullscreen_for(self.display_mode, window.current_monitor()));
+    }
+
+    // Picks the display mode after a toggle, remembering the fullscreen mode being left.
+    fn toggle_display_mode(&mut self, fullscreen: bool) {
+        if fullscreen {
+            if self.display_mode != DisplayMode::Windowed {
+                self.fullscreen_mode = self.display_mode;
+            }
+            self.display_mode = DisplayMode::Windowed;
+        } else {
+            self.display_mode = match self.fullscreen_mode {
+                DisplayMode::Windowed => DisplayMode::BorderlessFullscreen,
+                mode => mode,
+            };
+        }
+    }
+
+    /// Records the window's current position and size so they can be restored next time.
+    ///
+    /// Only windowed geometry is stored, so leaving fullscreen restores the previous window.
+    pub fn remember_geometry(&mut self, window: &Window) {
+        if window.fullscreen().is_some() {
+            return;
+        }
+        let PhysicalSize { width, height } = window.inner_size();
+        self.width = width.max(1);
+        self.height = height.max(1);
+        if let Ok(PhysicalPosition { x, y }) = window.outer_position() {
+            self.position = Some((x, y));
+        }
+    }
+}
+
+// Converts a display mode into the winit fullscreen setting for a monitor.
+fn fullscreen_for(display_mode: DisplayMode, monitor: Option<MonitorHandle>) -> Option<Fullscreen> {
+    match display_mode {
+        DisplayMode::Windowed => None,
+        DisplayMode::BorderlessFullscreen => Some(Fullscreen::Borderless(monitor)),
+        DisplayMode::ExclusiveFullscreen => {
+            // Pick the largest resolution, then the highest refresh rate.
+            let video_mode = monitor.as_ref().and_then(|monitor| {
+                monitor.video_modes().max_by_key(|mode| {
+                    let size = mode.size();
+                    (size.width * size.height, mode.refresh_rate_millihertz())
+                })
+            });
+            match video_mode {
+                Some(video_mode) => Some(Fullscreen::Exclusive(video_mode)),
+                None => {
+                    log::warn!("No video modes available, using borderless fullscreen instead");
+                    Some(Fullscreen::Borderless(monitor))
+                }
+            }
+        }
+    }
+}
+
+// Decodes a PNG file into a window icon.
+fn load_icon(path: &Path) -> Result<Icon, Box<dyn std::error::Error>> {
+    let (rgba, width, height) = crate::assets::decode_png(&std::fs::read(path)?)?;
+    Ok(Icon::from_rgba(rgba, width, height)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_survive_a_save_and_load() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("window_settings.ron");
+        let config = WindowConfig {
+            title: "Round trip".to_string(),
+            width: 800,
+            height: 600,
+            position: Some((-20, 40)),
+            resizable: false,
+            decorations: false,
+            icon: Some(PathBuf::from("icons/game.png")),
+            monitor: Some(1),
+            display_mode: DisplayMode::ExclusiveFullscreen,
+            fullscreen_mode: DisplayMode::ExclusiveFullscreen,
+            cursor_grab: CursorGrab::Locked,
+            cursor_visible: false,
+        };
+        config.save(&path).unwrap();
+        assert_eq!(WindowConfig::load(&path).unwrap(), config);
+    }
+
+    #[test]
+    fn missing_fields_and_files_use_the_defaults() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("window_settings.ron");
+        std::fs::write(&path, "(width: 640, display_mode: BorderlessFullscreen)").unwrap();
+        let config = WindowConfig::load(&path).unwrap();
+        assert_eq!(config.width, 640);
+        assert_eq!(config.display_mode, DisplayMode::BorderlessFullscreen);
+        assert_eq!(config.height, WindowConfig::default().height);
+
+        let missing = directory.path().join("missing.ron");
+        assert_eq!(
+            WindowConfig::load_or_default(missing),
+            WindowConfig::default()
+        );
+        std::fs::write(&path, "not ron").unwrap();
+        assert_eq!(
+            WindowConfig::load_or_default(&path),
+            WindowConfig::default()
+        );
+    }
+
+    #[test]
+    fn windows_are_centred_on_their_monitor() {
+        let config = WindowConfig {
+            width: 1280,
+            height: 720,
+            ..WindowConfig::default()
+        };
+        // A monitor to the right of the primary one.
+        let position = config.centred_on(
+            PhysicalPosition::new(1920, 0),
+            PhysicalSize::new(2560, 1440),
+        );
+        assert_eq!(position, PhysicalPosition::new(1920 + 640, 360));
+
+        // One to the left, smaller than the window.
+        let position = config.centred_on(
+            PhysicalPosition::new(-1024, -100),
+            PhysicalSize::new(1024, 768),
+        );
+        assert_eq!(position, PhysicalPosition::new(-1024, -100 + 24));
+    }
+
+    #[test]
+    fn toggling_returns_to_the_last_fullscreen_mode() {
+        let mut config = WindowConfig {
+            display_mode: DisplayMode::ExclusiveFullscreen,
+            ..WindowConfig::default()
+        };
+        config.toggle_display_mode(true);
+        assert_eq!(config.display_mode, DisplayMode::Windowed);
+        config.toggle_display_mode(false);
+        assert_eq!(config.display_mode, DisplayMode::ExclusiveFullscreen);
+
+        // The preference is kept across a save and load while windowed.
+        config.toggle_display_mode(true);
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("window_settings.ron");
+        config.save(&path).unwrap();
+        let mut config = WindowConfig::load(&path).unwrap();
+        assert_eq!(config.display_mode, DisplayMode::Windowed);
+        config.toggle_display_mode(false);
+        assert_eq!(config.display_mode, DisplayMode::ExclusiveFullscreen);
+    }
+
+    #[test]
+    fn toggling_defaults_to_borderless_fullscreen() {
+        let mut config = WindowConfig::default();
+        config.toggle_display_mode(false);
+        assert_eq!(config.display_mode, DisplayMode::BorderlessFullscreen);
+        config.toggle_display_mode(true);
+        assert_eq!(config.display_mode, DisplayMode::Windowed);
+
+        // A settings file naming windowed as the fullscreen mode still goes fullscreen.
+        config.fullscreen_mode = DisplayMode::Windowed;
+        config.toggle_display_mode(false);
+        assert_eq!(config.display_mode, DisplayMode::BorderlessFullscreen);
+    }
+}